@@ -0,0 +1,173 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/*
+    Benchmarks for the Mat4 and Vector4 operations, against the code from before beagle_math::simd (copied in below).
+    The numbers from the last run are written down in beagle_math::simd.
+
+        cargo bench --bench beagle_math
+
+    Like the asset pipeline, this compiles the platform independent modules it needs straight from their files,
+    as the engine itself is Windows only. Run it in release (which cargo bench does), debug timings say nothing.
+*/
+
+#[allow(dead_code)]
+#[path = "../src/beagle_math/mod.rs"]
+mod beagle_math;
+#[allow(dead_code)]
+#[path = "../src/shared/mod.rs"]
+mod shared;
+
+use beagle_math::{simd, Mat4, Vector3, Vector4};
+
+/*
+    The code from before beagle_math::simd, copied as it was, so there's something fixed to compare against.
+    Vector4::dot is a free function here, as Vector4's own dot has changed since.
+*/
+fn previous_dot(a: &Vector4, vec: &Vector4) -> f32 {
+    a.x * vec.x + a.y * vec.y + a.z * vec.z + a.w * vec.w
+}
+
+fn previous_mul(a: &Mat4, mat: &Mat4) -> Mat4 {
+    let self_row0 = Vector4::new(a.get(0, 0), a.get(1, 0),  a.get(2, 0), a.get(3, 0));
+    let self_row1 = Vector4::new(a.get(0, 1), a.get(1, 1),  a.get(2, 1), a.get(3, 1));
+    let self_row2 = Vector4::new(a.get(0, 2), a.get(1, 2),  a.get(2, 2), a.get(3, 2));
+    let self_row3 = Vector4::new(a.get(0, 3), a.get(1, 3),  a.get(2, 3), a.get(3, 3));
+
+    let mat_column0 = Vector4::new( mat.get(0, 0), mat.get(0, 1), mat.get(0, 2), mat.get(0, 3));
+    let mat_column1 = Vector4::new( mat.get(1, 0), mat.get(1, 1), mat.get(1, 2), mat.get(1, 3));
+    let mat_column2 = Vector4::new( mat.get(2, 0), mat.get(2, 1), mat.get(2, 2), mat.get(2, 3));
+    let mat_column3 = Vector4::new( mat.get(3, 0), mat.get(3, 1), mat.get(3, 2), mat.get(3, 3));
+
+    Mat4 {
+        matrix: [
+            previous_dot(&self_row0, &mat_column0), previous_dot(&self_row0, &mat_column1), previous_dot(&self_row0, &mat_column2), previous_dot(&self_row0, &mat_column3),
+            previous_dot(&self_row1, &mat_column0), previous_dot(&self_row1, &mat_column1), previous_dot(&self_row1, &mat_column2), previous_dot(&self_row1, &mat_column3),
+            previous_dot(&self_row2, &mat_column0), previous_dot(&self_row2, &mat_column1), previous_dot(&self_row2, &mat_column2), previous_dot(&self_row2, &mat_column3),
+            previous_dot(&self_row3, &mat_column0), previous_dot(&self_row3, &mat_column1), previous_dot(&self_row3, &mat_column2), previous_dot(&self_row3, &mat_column3),
+        ]
+    }
+}
+
+fn previous_mul_row(mat: &Mat4, row: &Vector4) -> Vector4 {
+    Vector4::new(
+        row.x * mat.matrix[0] + row.y * mat.matrix[4] + row.z * mat.matrix[8] + row.w * mat.matrix[12],
+        row.x * mat.matrix[1] + row.y * mat.matrix[5] + row.z * mat.matrix[9] + row.w * mat.matrix[13],
+        row.x * mat.matrix[2] + row.y * mat.matrix[6] + row.z * mat.matrix[10] + row.w * mat.matrix[14],
+        row.x * mat.matrix[3] + row.y * mat.matrix[7] + row.z * mat.matrix[11] + row.w * mat.matrix[15])
+}
+
+fn previous_get_transposed(mat: &Mat4) -> Mat4 {
+    Mat4 {
+        matrix: [
+            mat.matrix[0], mat.matrix[4], mat.matrix[8], mat.matrix[12],
+            mat.matrix[1], mat.matrix[5], mat.matrix[9], mat.matrix[13],
+            mat.matrix[2], mat.matrix[6], mat.matrix[10], mat.matrix[14],
+            mat.matrix[3], mat.matrix[7], mat.matrix[11], mat.matrix[15]
+        ]
+    }
+}
+
+fn previous_magnitude(vector: &Vector4) -> f32 {
+    ( vector.x.powf(2.0) + vector.y.powf(2.0) + vector.z.powf(2.0) + vector.w.powf(2.0) ).sqrt()
+}
+
+fn previous_normalize(vector: &Vector4) -> Vector4 {
+    Vector4::new( vector.x / previous_magnitude(vector), vector.y / previous_magnitude(vector), vector.z / previous_magnitude(vector), vector.w / previous_magnitude(vector) )
+}
+
+/*
+    The average nanoseconds per run of the operation, as a float, since some of them take less than a nanosecond.
+    The best of 15 short rounds is taken, as other things running on the machine only ever make a round slower.
+    The rounds of the two versions take turns, so a slow moment on the machine doesn't land on only one of them.
+*/
+fn time_round(operation: &mut impl FnMut()) -> f64 {
+    let mut runs = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(40) {
+        for _ in 0..1000 {
+            operation();
+        }
+        runs += 1000;
+    }
+
+    start.elapsed().as_secs_f64() * 1.0e9 / runs as f64
+}
+
+fn compare(name: &str, mut previous: impl FnMut(), mut current: impl FnMut()) {
+    let (mut previous_time, mut current_time) = (f64::MAX, f64::MAX);
+    for _ in 0..15 {
+        previous_time = previous_time.min(time_round(&mut previous));
+        current_time = current_time.min(time_round(&mut current));
+    }
+
+    println!("{:<32} {:>10.2} ns -> {:>10.2} ns ({:.1}x)", name, previous_time, current_time, previous_time / current_time);
+}
+
+fn main() {
+    let a = Mat4::rotate_y(0.7).mul(&Mat4::translate(&Vector3::new(1.0, -2.0, 3.0)));
+    let b = Mat4::projection_reversed_z(1.0, 16.0, 9.0, 0.1, 1000.0);
+    let row = Vector4::new(1.0, 2.0, 3.0, 1.0);
+
+    println!("{:<32} {:>13}    {:>13}", "", "previous", "current");
+
+    compare("Mat4::mul",
+        || { black_box(previous_mul(black_box(&a), black_box(&b))); },
+        || { black_box(black_box(&a).mul(black_box(&b))); });
+
+    compare("Mat4::mul_row",
+        || { black_box(previous_mul_row(black_box(&a), black_box(&row))); },
+        || { black_box(black_box(&a).mul_row(black_box(&row))); });
+
+    compare("Mat4::get_transposed",
+        || { black_box(previous_get_transposed(black_box(&a))); },
+        || { black_box(black_box(&a).get_transposed()); });
+
+    // A mesh worth of points, one by one with mul_row, as it was done before, and all at once.
+    // Both start from a fresh copy of the points every run, so they don't drift off to infinity.
+    let points: Vec<Vector3> = (0..10_000).map(|index| Vector3::new(index as f32, (index % 100) as f32, -(index as f32))).collect();
+    let (mut previous_transformed, mut transformed) = (points.clone(), points.clone());
+    compare("Mat4::transform_points (10000)",
+        || {
+            previous_transformed.copy_from_slice(&points);
+            for point in previous_transformed.iter_mut() {
+                let row = previous_mul_row(black_box(&a), &Vector4::new(point.x, point.y, point.z, 1.0));
+                *point = Vector3::new(row.x, row.y, row.z);
+            }
+            black_box(&previous_transformed);
+        },
+        || {
+            transformed.copy_from_slice(&points);
+            black_box(&a).transform_points(&mut transformed);
+            black_box(&transformed);
+        });
+
+    let rows: Vec<Vector4> = points.iter().map(|point| Vector4::new(point.x, point.y, point.z, 1.0)).collect();
+    let (mut previous_rows, mut transformed_rows) = (rows.clone(), rows.clone());
+    compare("Mat4::transform_rows (10000)",
+        || {
+            previous_rows.copy_from_slice(&rows);
+            for row in previous_rows.iter_mut() {
+                *row = previous_mul_row(black_box(&a), row);
+            }
+            black_box(&previous_rows);
+        },
+        || {
+            transformed_rows.copy_from_slice(&rows);
+            black_box(&a).transform_rows(&mut transformed_rows);
+            black_box(&transformed_rows);
+        });
+
+    let vector = Vector4::new(3.0, -4.0, 12.0, 0.5);
+    compare("Vector4::normalize",
+        || { black_box(previous_normalize(black_box(&vector))); },
+        || { black_box(black_box(&vector).normalize()); });
+
+    compare("Vector4 a * 2 + b",
+        || { black_box(simd::scalar::add(&simd::scalar::mul_scalar(black_box(&vector), 2.0), black_box(&row))); },
+        || { black_box(*black_box(&vector) * 2.0 + *black_box(&row)); });
+
+    compare("Vector4::dot",
+        || { black_box(previous_dot(black_box(&vector), black_box(&row))); },
+        || { black_box(black_box(&vector).dot(black_box(&row))); });
+}
@@ -30,9 +30,10 @@ Write-Host ""
 
 Copy-Item -Path "${build_script_directory}\shaders" -Recurse -Destination "${build_script_directory}\target\debug\resources\shaders" -Force
 
-# Copy General Resources
+# Cook General Resources
 Write-Host ""
-Write-Host "**** COPYING GENERAL RESOURCES TO OUTPUT DIR ****"
+Write-Host "**** COOKING GENERAL RESOURCES INTO OUTPUT DIR ****"
 Write-Host ""
 
-Copy-Item -Path "${build_script_directory}\resources\*" -Recurse -Destination "${build_script_directory}\target\debug\resources" -Force
\ No newline at end of file
+# Converts models into the cooked format, copies everything else, and only rebuilds what changed since the last run
+cargo run --bin asset_pipeline -- --source "${build_script_directory}\resources" --output "${build_script_directory}\target\debug\resources"
\ No newline at end of file
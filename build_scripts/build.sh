@@ -0,0 +1,15 @@
+#!/bin/sh
+# Cooks the assets on machines without PowerShell (like the Linux build machines).
+# The engine itself and its shaders (fxc.exe) still need Windows, see build.ps1.
+set -e
+
+build_script_directory="$(cd "$(dirname "$0")/.." && pwd)"
+
+echo ""
+echo "**** COOKING GENERAL RESOURCES INTO OUTPUT DIR ****"
+echo ""
+
+cargo run --manifest-path "${build_script_directory}/Cargo.toml" --bin asset_pipeline -- \
+    --source "${build_script_directory}/resources" \
+    --output "${build_script_directory}/target/debug/resources" \
+    "$@"
@@ -6,6 +6,8 @@ cbuffer cbPerObject : register(b0)
 {
     float4x4 worldViewProjection;
     float4x4 modelMatrix;
+    // Transforms normals to world space, the inverse transpose of the rotation and scale in modelMatrix
+    float3x3 normalMatrix;
     float4 cameraPosition;
     float4 diffuseColor;
     float4 ambientColor;
@@ -56,7 +58,9 @@ VSOut VS(VSIn input)
     // We use this to calculate how intense the final color value should be, based on the surface's
     // angle to the incoming light direction
     // We use "max", an intrinsic HLSL function which selects whichever of x and y that are the largest
-    float lamberts_multiplier = max(dot(sun_light_direction, input.Normal), 0.0f);
+    // The normal has to be in world coordinates, like the light direction
+    float3 world_normal = normalize(mul(input.Normal, normalMatrix));
+    float lamberts_multiplier = max(dot(sun_light_direction, world_normal), 0.0f);
     float3 diffuse_calculation = light_diffuse_color * diffuseColor.xyz;
     float3 ambient_calculation = light_ambient_color * ambientColor.xyz;
     
@@ -75,7 +79,7 @@ VSOut VS(VSIn input)
     float4 surface_point_in_world_coordinates = mul(float4(input.PosL, 1.0f), modelMatrix);
 
     float3 view_vector = normalize(camera_position_truncated - surface_point_in_world_coordinates.xyz);
-    float3 reflection_vector = reflect(float3(0.0f, -1.0f, 0.0f), input.Normal);
+    float3 reflection_vector = reflect(float3(0.0f, -1.0f, 0.0f), world_normal);
     float3 specular_calculation = specular_light_color * specularColor.xyz;
     float specular_factor = pow(max(dot(reflection_vector, view_vector), 0.0f), shininess_parameter);
 
@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+use std::fs;
+use std::mem::size_of;
+use std::path::Path;
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+
+use crate::beagle_math;
+use crate::gltf2;
+use crate::shared::FromBinary;
+use crate::asset::mesh::{self, Lod, Material, Mesh, Model};
+
+/*
+    The "cooked" format is the engine's own binary format for models.
+
+    glTF is great for exchanging models between tools, but loading it means parsing JSON and decoding base64 every single startup.
+    A cooked file is made once (by the asset pipeline, or "convert_gltf") and holds the model in exactly the shape the engine
+    uses it, so loading is just reading the file into memory and copying the arrays out of it.
+
+    Everything is little endian. The file is laid out as:
+
+        Header (16 bytes)
+            magic           4 bytes, "BGLM"
+            version         u32, has to match VERSION exactly
+            section count   u32
+            file size       u32, to catch truncated files early
+
+        Section table, one entry (16 bytes) per section
+            kind            u32, see SectionKind
+            offset          u32, from the start of the file
+            length          u32, in bytes
+            count           u32, number of elements in the section
+
+        Sections, each one starting at a multiple of 16 bytes
+
+    Sections:
+        Strings     UTF-8 text of all mesh names, back to back
+        Materials   10 x f32 per material (diffuse, ambient, specular, shininess). Identical materials are only stored once.
+        Meshes      MESH_RECORD_SIZE bytes per mesh, see the end of write_model
+        Children    u16 per child, the child lists of all meshes back to back
+        Vertices    Streams of positions, normals, uvs and colors for every mesh, each stream starting at a multiple of 16 bytes
+        Indices     u32 per index, the full detail indices and LOD indices of all meshes back to back
+        Lods        first index (u32), index count (u32) and error (f32) per LOD
+
+    Sections with a kind this version doesn't know about are skipped, so adding optional data doesn't break older readers.
+    Any change to the existing layout has to bump VERSION, and old cooked files then have to be cooked again.
+*/
+
+pub const MAGIC: [u8; 4] = *b"BGLM";
+pub const VERSION: u32 = 1;
+
+const HEADER_SIZE: usize = 16;
+const SECTION_ENTRY_SIZE: usize = 16;
+const SECTION_ALIGNMENT: usize = 16;
+const MATERIAL_SIZE: usize = 10 * size_of::<f32>();
+const MESH_RECORD_SIZE: usize = 32 * size_of::<u32>();
+const LOD_RECORD_SIZE: usize = 3 * size_of::<u32>();
+
+// Bits in the attribute flags of a mesh record, telling which vertex streams the mesh has
+const HAS_NORMALS: u32 = 1;
+const HAS_UVS: u32 = 2;
+const HAS_COLORS: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SectionKind {
+    Strings = 1,
+    Materials = 2,
+    Meshes = 3,
+    Children = 4,
+    Vertices = 5,
+    Indices = 6,
+    Lods = 7
+}
+
+// The axis aligned box around all vertex positions of a mesh, in the mesh's own space
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bounds {
+    pub min: beagle_math::Vector3,
+    pub max: beagle_math::Vector3
+}
+
+pub struct CookedModel {
+    pub model: Model,
+    // One per mesh, in the same order as the meshes
+    pub bounds: Vec<Bounds>
+}
+
+impl Bounds {
+    pub fn from_positions(positions: &[beagle_math::Vector3]) -> Bounds {
+        if positions.is_empty() {
+            return Bounds::default();
+        }
+
+        let mut bounds = Bounds { min: positions[0], max: positions[0] };
+        for position in &positions[1..] {
+            bounds.min = beagle_math::Vector3::new(bounds.min.x.min(position.x), bounds.min.y.min(position.y), bounds.min.z.min(position.z));
+            bounds.max = beagle_math::Vector3::new(bounds.max.x.max(position.x), bounds.max.y.max(position.y), bounds.max.z.max(position.z));
+        }
+
+        bounds
+    }
+}
+
+pub fn load_model<P: AsRef<Path>>(path: P) -> Result<CookedModel, String> {
+    let binary_data = fs::read(&path).map_err(|err| format!("Failed to read cooked model {}: {}", path.as_ref().display(), err))?;
+    read_model(&binary_data)
+}
+
+pub fn save_model<P: AsRef<Path>>(model: &Model, path: P) -> Result<(), String> {
+    fs::write(&path, write_model(model)).map_err(|err| format!("Failed to write cooked model {}: {}", path.as_ref().display(), err))
+}
+
+// Reads a glTF file the usual way, and writes it back out as a cooked model
+pub fn convert_gltf<P: AsRef<Path>, Q: AsRef<Path>>(gltf_path: P, cooked_path: Q) -> Result<(), String> {
+    let gltf_file = gltf2::File::from(gltf_path.as_ref().to_path_buf())?;
+    let model = mesh::parse_model(&gltf_file);
+    save_model(&model, cooked_path)
+}
+
+pub fn write_model(model: &Model) -> Vec<u8> {
+    let mut strings: Vec<u8> = vec!();
+    let mut materials: Vec<u8> = vec!();
+    let mut material_lookup: HashMap<[u32; 10], u32> = HashMap::new();
+    let mut meshes: Vec<u8> = vec!();
+    let mut children: Vec<u8> = vec!();
+    let mut vertices: Vec<u8> = vec!();
+    let mut indices: Vec<u8> = vec!();
+    let mut lods: Vec<u8> = vec!();
+
+    let mut child_count = 0;
+    let mut index_count = 0;
+    let mut lod_count = 0;
+
+    for mesh in &model.meshes {
+        let name_offset = strings.len() as u32;
+        strings.extend_from_slice(mesh.name.as_bytes());
+
+        let material_values = material_to_array(&mesh.material);
+        let material_key = material_values.map(f32::to_bits);
+        let material_index = *material_lookup.entry(material_key).or_insert_with(|| {
+            write_floats(&mut materials, &material_values);
+            (materials.len() / MATERIAL_SIZE - 1) as u32
+        });
+
+        let first_child = child_count;
+        for child in &mesh.children {
+            children.write_u16::<LittleEndian>(*child).unwrap();
+        }
+        child_count += mesh.children.len() as u32;
+
+        let vertex_count = mesh.vertex_positions.len() as u32;
+        let mut attribute_flags = 0;
+        let positions_offset = write_stream(&mut vertices, mesh.vertex_positions.iter().flat_map(|position| [position.x, position.y, position.z]));
+        let normals_offset = if mesh.vertex_normals.is_empty() { 0 } else {
+            attribute_flags |= HAS_NORMALS;
+            write_stream(&mut vertices, mesh.vertex_normals.iter().flat_map(|normal| [normal.x, normal.y, normal.z]))
+        };
+        let uvs_offset = if mesh.vertex_uvs.is_empty() { 0 } else {
+            attribute_flags |= HAS_UVS;
+            write_stream(&mut vertices, mesh.vertex_uvs.iter().flat_map(|uv| [uv.x, uv.y]))
+        };
+        let colors_offset = if mesh.vertex_colors.is_empty() { 0 } else {
+            attribute_flags |= HAS_COLORS;
+            write_stream(&mut vertices, mesh.vertex_colors.iter().flat_map(|color| color.as_array()))
+        };
+
+        let first_index = index_count;
+        for index in &mesh.indices {
+            indices.write_u32::<LittleEndian>(*index).unwrap();
+        }
+        index_count += mesh.indices.len() as u32;
+
+        let first_lod = lod_count;
+        for lod in &mesh.lods {
+            lods.write_u32::<LittleEndian>(index_count).unwrap();
+            lods.write_u32::<LittleEndian>(lod.indices.len() as u32).unwrap();
+            lods.write_f32::<LittleEndian>(lod.error).unwrap();
+
+            for index in &lod.indices {
+                indices.write_u32::<LittleEndian>(*index).unwrap();
+            }
+            index_count += lod.indices.len() as u32;
+        }
+        lod_count += mesh.lods.len() as u32;
+
+        let bounds = Bounds::from_positions(&mesh.vertex_positions);
+
+        // The layout of a mesh record, 32 values of 4 bytes each
+        let record_start = meshes.len();
+        for value in [name_offset, mesh.name.len() as u32, material_index, first_child, mesh.children.len() as u32] {
+            meshes.write_u32::<LittleEndian>(value).unwrap();
+        }
+        write_floats(&mut meshes, &[
+            mesh.transform.translation.x, mesh.transform.translation.y, mesh.transform.translation.z,
+            mesh.transform.scale.x, mesh.transform.scale.y, mesh.transform.scale.z,
+            mesh.transform.rotation.w, mesh.transform.rotation.v.x, mesh.transform.rotation.v.y, mesh.transform.rotation.v.z,
+            bounds.min.x, bounds.min.y, bounds.min.z,
+            bounds.max.x, bounds.max.y, bounds.max.z
+        ]);
+        for value in [
+            vertex_count, attribute_flags, positions_offset, normals_offset, uvs_offset, colors_offset,
+            first_index, mesh.indices.len() as u32, first_lod, mesh.lods.len() as u32]
+        {
+            meshes.write_u32::<LittleEndian>(value).unwrap();
+        }
+        // Reserved, so a record stays a nice power of 2 in size
+        meshes.write_u32::<LittleEndian>(0).unwrap();
+        debug_assert_eq!(meshes.len() - record_start, MESH_RECORD_SIZE);
+    }
+
+    let sections = [
+        (SectionKind::Strings, strings.len() as u32, strings),
+        (SectionKind::Materials, material_lookup.len() as u32, materials),
+        (SectionKind::Meshes, model.meshes.len() as u32, meshes),
+        (SectionKind::Children, child_count, children),
+        (SectionKind::Vertices, vertices.len() as u32, vertices),
+        (SectionKind::Indices, index_count, indices),
+        (SectionKind::Lods, lod_count, lods)
+    ];
+
+    let table_end = HEADER_SIZE + sections.len() * SECTION_ENTRY_SIZE;
+    let mut section_data: Vec<u8> = vec![0; align(table_end) - table_end];
+    let mut section_table: Vec<u8> = vec!();
+
+    for (kind, count, data) in &sections {
+        let offset = table_end + section_data.len();
+        section_table.write_u32::<LittleEndian>(*kind as u32).unwrap();
+        section_table.write_u32::<LittleEndian>(offset as u32).unwrap();
+        section_table.write_u32::<LittleEndian>(data.len() as u32).unwrap();
+        section_table.write_u32::<LittleEndian>(*count).unwrap();
+
+        section_data.extend_from_slice(data);
+        section_data.resize(align(section_data.len() + table_end) - table_end, 0);
+    }
+
+    let mut binary_data: Vec<u8> = Vec::with_capacity(table_end + section_data.len());
+    binary_data.extend_from_slice(&MAGIC);
+    binary_data.write_u32::<LittleEndian>(VERSION).unwrap();
+    binary_data.write_u32::<LittleEndian>(sections.len() as u32).unwrap();
+    binary_data.write_u32::<LittleEndian>((table_end + section_data.len()) as u32).unwrap();
+    binary_data.extend_from_slice(&section_table);
+    binary_data.extend_from_slice(&section_data);
+
+    binary_data
+}
+
+pub fn read_model(binary_data: &[u8]) -> Result<CookedModel, String> {
+    if binary_data.len() < HEADER_SIZE || binary_data[0..4] != MAGIC {
+        return Err(String::from("Not a cooked model, the file doesn't start with the right magic bytes."));
+    }
+
+    let version = LittleEndian::read_u32(&binary_data[4..8]);
+    if version != VERSION {
+        return Err(format!("Cooked model has version {}, but only version {} is supported. The model has to be cooked again.", version, VERSION));
+    }
+
+    let file_size = LittleEndian::read_u32(&binary_data[12..16]) as usize;
+    if binary_data.len() != file_size {
+        return Err(format!("Cooked model should be {} bytes, but is {} bytes.", file_size, binary_data.len()));
+    }
+
+    let sections = Sections::read(binary_data)?;
+    let strings = sections.get(SectionKind::Strings)?;
+    let materials: Vec<f32> = sections.read_array(SectionKind::Materials, 0, sections.count(SectionKind::Materials)? * 10)?;
+    let children: Vec<u16> = sections.read_array(SectionKind::Children, 0, sections.count(SectionKind::Children)?)?;
+    let indices: Vec<u32> = sections.read_array(SectionKind::Indices, 0, sections.count(SectionKind::Indices)?)?;
+    let lod_records = sections.get(SectionKind::Lods)?;
+    let mesh_records = sections.get(SectionKind::Meshes)?;
+    let mesh_count = sections.count(SectionKind::Meshes)?;
+
+    if mesh_records.len() < mesh_count * MESH_RECORD_SIZE {
+        return Err(String::from("Cooked model is too short for its number of meshes."));
+    }
+
+    let mut meshes: Vec<Mesh> = Vec::with_capacity(mesh_count);
+    let mut bounds: Vec<Bounds> = Vec::with_capacity(mesh_count);
+
+    for record in mesh_records[..mesh_count * MESH_RECORD_SIZE].chunks_exact(MESH_RECORD_SIZE) {
+        let value = |index: usize| LittleEndian::read_u32(&record[index * 4..index * 4 + 4]) as usize;
+        let float = |index: usize| LittleEndian::read_f32(&record[index * 4..index * 4 + 4]);
+        let vector = |index: usize| beagle_math::Vector3::new(float(index), float(index + 1), float(index + 2));
+
+        let name_bytes = slice(strings, value(0), value(1))?;
+        let name = String::from_utf8(name_bytes.to_vec()).map_err(|_| String::from("Cooked model has a mesh name that isn't valid UTF-8."))?;
+
+        let material_values = slice(&materials, value(2) * 10, 10)?;
+        let mesh_children = slice(&children, value(3), value(4))?;
+
+        let vertex_count = value(21);
+        let attribute_flags = value(22) as u32;
+        let first_index = value(27);
+        let index_count = value(28);
+
+        let mut mesh = Mesh {
+            name,
+            children: mesh_children.to_vec(),
+            transform: beagle_math::Transform::new(vector(5), beagle_math::Quaternion::new(float(11), float(12), float(13), float(14)), vector(8)),
+            vertex_positions: sections.read_array(SectionKind::Vertices, value(23), vertex_count)?,
+            indices: slice(&indices, first_index, index_count)?.to_vec(),
+            material: material_from_array(material_values),
+            ..Default::default()
+        };
+
+        if attribute_flags & HAS_NORMALS != 0 {
+            mesh.vertex_normals = sections.read_array(SectionKind::Vertices, value(24), vertex_count)?;
+        }
+        if attribute_flags & HAS_UVS != 0 {
+            mesh.vertex_uvs = sections.read_array(SectionKind::Vertices, value(25), vertex_count)?;
+        }
+        if attribute_flags & HAS_COLORS != 0 {
+            mesh.vertex_colors = sections.read_array(SectionKind::Vertices, value(26), vertex_count)?;
+        }
+
+        let lod_bytes = slice(lod_records, value(29) * LOD_RECORD_SIZE, value(30) * LOD_RECORD_SIZE)?;
+        for lod_record in lod_bytes.chunks_exact(LOD_RECORD_SIZE) {
+            let lod_first_index = LittleEndian::read_u32(&lod_record[0..4]) as usize;
+            let lod_index_count = LittleEndian::read_u32(&lod_record[4..8]) as usize;
+            mesh.lods.push(Lod {
+                indices: slice(&indices, lod_first_index, lod_index_count)?.to_vec(),
+                error: LittleEndian::read_f32(&lod_record[8..12])
+            });
+        }
+
+        if mesh.indices.iter().chain(mesh.lods.iter().flat_map(|lod| lod.indices.iter())).any(|index| *index as usize >= vertex_count) {
+            return Err(format!("Mesh {} in cooked model has indices past its {} vertices.", mesh.name, vertex_count));
+        }
+
+        if let Some(child) = mesh.children.iter().find(|child| **child as usize >= mesh_count) {
+            return Err(format!("Mesh {} in cooked model has child {}, but there are only {} meshes.", mesh.name, child, mesh_count));
+        }
+
+        bounds.push(Bounds { min: vector(15), max: vector(18) });
+        meshes.push(mesh);
+    }
+
+    Ok(CookedModel { model: Model { meshes }, bounds })
+}
+
+// Where every section lives in the file, found through the section table
+struct Sections<'a> {
+    binary_data: &'a [u8],
+    entries: Vec<(u32, usize, usize, usize)>
+}
+
+impl<'a> Sections<'a> {
+    fn read(binary_data: &'a [u8]) -> Result<Sections<'a>, String> {
+        let section_count = LittleEndian::read_u32(&binary_data[8..12]) as usize;
+        let table = slice(binary_data, HEADER_SIZE, section_count * SECTION_ENTRY_SIZE)?;
+
+        let mut entries = vec!();
+        for entry in table.chunks_exact(SECTION_ENTRY_SIZE) {
+            let kind = LittleEndian::read_u32(&entry[0..4]);
+            let offset = LittleEndian::read_u32(&entry[4..8]) as usize;
+            let length = LittleEndian::read_u32(&entry[8..12]) as usize;
+            let count = LittleEndian::read_u32(&entry[12..16]) as usize;
+
+            if !offset.is_multiple_of(SECTION_ALIGNMENT) {
+                return Err(format!("Section {} of cooked model is not aligned to {} bytes.", kind, SECTION_ALIGNMENT));
+            }
+            slice(binary_data, offset, length)?;
+
+            entries.push((kind, offset, length, count));
+        }
+
+        Ok(Sections { binary_data, entries })
+    }
+
+    fn entry(&self, kind: SectionKind) -> Result<&(u32, usize, usize, usize), String> {
+        self.entries
+            .iter()
+            .find(|entry| entry.0 == kind as u32)
+            .ok_or_else(|| format!("Cooked model has no {:?} section.", kind))
+    }
+
+    fn get(&self, kind: SectionKind) -> Result<&'a [u8], String> {
+        let (_, offset, length, _) = *self.entry(kind)?;
+        Ok(&self.binary_data[offset..offset + length])
+    }
+
+    fn count(&self, kind: SectionKind) -> Result<usize, String> {
+        Ok(self.entry(kind)?.3)
+    }
+
+    // Decodes "count" elements starting "byte_offset" bytes into a section
+    fn read_array<T: FromBinary>(&self, kind: SectionKind, byte_offset: usize, count: usize) -> Result<Vec<T>, String> {
+        let bytes = slice(self.get(kind)?, byte_offset, count * size_of::<T>())?;
+        Ok(T::from_binary_collection(bytes))
+    }
+}
+
+// Bounds checked slicing, as a broken file shouldn't be able to crash the engine
+fn slice<T>(elements: &[T], start: usize, count: usize) -> Result<&[T], String> {
+    start
+        .checked_add(count)
+        .and_then(|end| elements.get(start..end))
+        .ok_or_else(|| String::from("Cooked model refers to data past the end of a section."))
+}
+
+fn align(size: usize) -> usize {
+    size.div_ceil(SECTION_ALIGNMENT) * SECTION_ALIGNMENT
+}
+
+fn write_floats(bytes: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        bytes.write_f32::<LittleEndian>(*value).unwrap();
+    }
+}
+
+// Writes a vertex stream at the next aligned offset, and returns that offset
+fn write_stream<I: Iterator<Item = f32>>(vertices: &mut Vec<u8>, values: I) -> u32 {
+    vertices.resize(align(vertices.len()), 0);
+    let offset = vertices.len() as u32;
+
+    for value in values {
+        vertices.write_f32::<LittleEndian>(value).unwrap();
+    }
+
+    offset
+}
+
+fn material_to_array(material: &Material) -> [f32; 10] {
+    [
+        material.diffuse_color.x, material.diffuse_color.y, material.diffuse_color.z,
+        material.ambient_color.x, material.ambient_color.y, material.ambient_color.z,
+        material.specular_color.x, material.specular_color.y, material.specular_color.z,
+        material.shininess_factor
+    ]
+}
+
+fn material_from_array(values: &[f32]) -> Material {
+    Material {
+        diffuse_color: beagle_math::Vector3::from_array(&values[0..3]),
+        ambient_color: beagle_math::Vector3::from_array(&values[3..6]),
+        specular_color: beagle_math::Vector3::from_array(&values[6..9]),
+        shininess_factor: values[9]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::beagle_math;
+    use crate::asset::mesh::primitives;
+    use crate::asset::mesh::simplify;
+    use crate::asset::cooked::*;
+
+    fn test_model() -> Model {
+        let mut sphere = primitives::create_uv_sphere(2.0, 16, 8);
+        sphere.name = String::from("sphere");
+        sphere.children = vec![1];
+        sphere.transform.translation = beagle_math::Vector3::new(1.0, 2.0, 3.0);
+        sphere.material.diffuse_color = beagle_math::Vector3::new(0.5, 0.25, 1.0);
+        sphere.material.shininess_factor = 5.0;
+        sphere.vertex_colors = sphere.vertex_positions.iter().map(|_| beagle_math::Vector4::new(1.0, 0.0, 0.0, 0.5)).collect();
+        sphere.lods = simplify::build_lod_chain(&sphere, &[0.5, 0.25]);
+
+        let mut cube = primitives::create_box(&beagle_math::Vector3::new(1.0, 1.0, 1.0), 1);
+        cube.name = String::from("box");
+        cube.vertex_uvs.clear();
+        cube.material = sphere.material.clone();
+
+        Model { meshes: vec![sphere, cube] }
+    }
+
+    #[test]
+    fn should_round_trip_model() {
+        let model = test_model();
+
+        let cooked = read_model(&write_model(&model)).unwrap();
+
+        assert_eq!(cooked.model.meshes.len(), 2);
+        for (original, loaded) in model.meshes.iter().zip(cooked.model.meshes.iter()) {
+            assert_eq!(original.name, loaded.name);
+            assert_eq!(original.children, loaded.children);
+            assert_eq!(original.transform, loaded.transform);
+            assert_eq!(original.indices, loaded.indices);
+            assert_eq!(original.vertex_positions.len(), loaded.vertex_positions.len());
+            assert_eq!(original.vertex_positions[3].y, loaded.vertex_positions[3].y);
+            assert_eq!(original.vertex_normals.len(), loaded.vertex_normals.len());
+            assert_eq!(original.vertex_uvs.len(), loaded.vertex_uvs.len());
+            assert_eq!(original.vertex_colors.len(), loaded.vertex_colors.len());
+            assert_eq!(original.material.diffuse_color.y, loaded.material.diffuse_color.y);
+            assert_eq!(original.material.shininess_factor, loaded.material.shininess_factor);
+            assert_eq!(original.lods.len(), loaded.lods.len());
+            for (original_lod, loaded_lod) in original.lods.iter().zip(loaded.lods.iter()) {
+                assert_eq!(original_lod.indices, loaded_lod.indices);
+                assert_eq!(original_lod.error, loaded_lod.error);
+            }
+        }
+
+        assert!(!model.meshes[0].lods.is_empty());
+        assert_eq!(cooked.model.meshes[0].vertex_colors[0].w, 0.5);
+        assert!((cooked.bounds[0].max.y - 2.0).abs() < 0.0001);
+        assert!((cooked.bounds[1].min.x + 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn should_align_sections_and_store_shared_materials_once() {
+        let binary_data = write_model(&test_model());
+        let sections = Sections::read(&binary_data).unwrap();
+
+        assert_eq!(binary_data.len() % SECTION_ALIGNMENT, 0);
+        assert!(sections.entries.iter().all(|entry| entry.1 % SECTION_ALIGNMENT == 0));
+        assert_eq!(sections.count(SectionKind::Materials).unwrap(), 1);
+    }
+
+    #[test]
+    fn should_reject_wrong_version_and_truncated_files() {
+        let binary_data = write_model(&test_model());
+
+        let mut wrong_version = binary_data.clone();
+        wrong_version[4] = 99;
+        assert!(read_model(&wrong_version).is_err());
+        assert!(read_model(&binary_data[..binary_data.len() - 16]).is_err());
+        assert!(read_model(b"glTF").is_err());
+
+        // A file claiming its mesh uses far more vertices than it has
+        let mut broken = binary_data.clone();
+        let sections = Sections::read(&binary_data).unwrap();
+        let meshes_offset = sections.entry(SectionKind::Meshes).unwrap().1;
+        broken[meshes_offset + 21 * 4..meshes_offset + 22 * 4].copy_from_slice(&1_000_000u32.to_le_bytes());
+        assert!(read_model(&broken).is_err());
+
+        // And one with a child that isn't one of its meshes
+        let mut broken = binary_data.clone();
+        let children_offset = sections.entry(SectionKind::Children).unwrap().1;
+        broken[children_offset..children_offset + 2].copy_from_slice(&99u16.to_le_bytes());
+        assert!(read_model(&broken).is_err());
+    }
+
+    #[test]
+    fn should_convert_gltf_resource() {
+        let gltf_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources").join("purple_box.gltf");
+        let cooked_path = std::env::temp_dir().join("beagle_cooked_purple_box.bglm");
+
+        convert_gltf(&gltf_path, &cooked_path).unwrap();
+        let cooked = load_model(&cooked_path).unwrap();
+        fs::remove_file(&cooked_path).unwrap();
+
+        let gltf_model = mesh::parse_model(&gltf2::File::from(gltf_path).unwrap());
+        assert_eq!(cooked.model.meshes.len(), gltf_model.meshes.len());
+        assert_eq!(cooked.model.meshes[0].indices, gltf_model.meshes[0].indices);
+    }
+}
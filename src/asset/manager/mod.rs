@@ -0,0 +1,1016 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use crate::gltf2;
+use crate::asset::cooked;
+use crate::asset::manifest::{Manifest, MANIFEST_FILE_NAME};
+use crate::asset::mesh::{self, Model};
+use crate::asset::obj;
+use crate::asset::pipeline;
+use crate::asset::ply;
+use crate::asset::shader::ShaderBytecode;
+use crate::asset::stl;
+use crate::asset::texture::Texture;
+use crate::asset::watcher::FileWatcher;
+use crate::asset::worker_pool::WorkerPool;
+
+/*
+    The asset manager owns every loaded asset, and hands out handles to them.
+
+    Assets are asked for by "logical path", relative to the asset root and always with forward slashes ("mill/mill").
+    If the asset root has a manifest (written by the asset pipeline), the path is looked up in it first, so the cooked file is used.
+    Otherwise the path is taken as a file below the asset root ("mill/mill.gltf").
+
+    Loading the same file twice gives the same handle, and the asset is only loaded once.
+    Assets are kept by the file their logical path resolves to, so "props/crate" from the manifest
+    and "props/crate.obj" (the file it points at) are the same asset.
+    Every load counts as a reference, and so does every "retain". "release" gives a reference back,
+    and when the last one is gone the asset is unloaded.
+
+    A handle is just an index and a generation. The generation changes whenever a slot is reused for another asset,
+    so a handle to an asset that was unloaded can't suddenly point at a different asset.
+
+    Assets can also be loaded in the background with "load_async", so streaming things in doesn't stall a frame.
+    The handle is returned right away, and the file is read and parsed on a worker thread (see asset::worker_pool).
+    The finished asset is sent back over a channel, but it's only put into its slot when the main thread calls "poll",
+    so the main thread never has to lock anything to read an asset, and an asset never appears halfway through a frame.
+    Until then "get" returns None, and "state" says Loading.
+
+    A load that is still running can be cancelled. The slot is freed straight away, and whatever the worker thread
+    sends back later doesn't match the slot's generation anymore, so it's thrown away.
+
+    With "watch_for_changes", the files of loaded assets are watched (see asset::watcher), and "poll" reloads the ones that changed.
+    If the manager knows where the source assets are, it watches those instead of the cooked files, and imports them
+    the same way the pipeline would. A reload goes into the slot the asset already had, so every handle stays valid.
+    The old version stays in use until the new one is done, and if the reload fails, it just stays.
+*/
+
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    asset_type: PhantomData<T>
+}
+
+// Implemented by hand, as deriving these would require T to implement them as well
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("index", &self.index)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadState {
+    // Never loaded, or unloaded because all references were released
+    Unloaded,
+    // Being loaded on a worker thread
+    Loading,
+    Loaded,
+    // Loaded, and a new version is being loaded on a worker thread
+    Reloading,
+    // A background load failed. The references stay, so the failure can be looked at and the load retried.
+    Failed(String)
+}
+
+// What "poll" reports about background loads that finished since the last poll
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadEvent {
+    Loaded { logical_path: String },
+    // A new version replaced the old one, anything built from the asset should be built again
+    Reloaded { logical_path: String },
+    // For a failed reload, the old version is still loaded
+    Failed { logical_path: String, error: String }
+}
+
+// How far along the current batch of background loads is. A new batch starts with the first load after the previous one is done.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadProgress {
+    pub requested: u32,
+    // Loaded, failed or cancelled
+    pub finished: u32
+}
+
+impl LoadProgress {
+    pub fn is_done(&self) -> bool {
+        self.finished >= self.requested
+    }
+
+    // Between 0 and 1, for a loading bar
+    pub fn fraction(&self) -> f32 {
+        if self.requested == 0 {
+            1.0
+        } else {
+            self.finished as f32 / self.requested as f32
+        }
+    }
+}
+
+// Everything the manager can load. Each asset type gets its own storage in the manager.
+// Send, because background loads create the asset on a worker thread.
+pub trait Asset: Sized + Send + 'static {
+    fn load_from_file(path: &Path) -> Result<Self, String>;
+    // Loads the asset straight from the file the asset pipeline builds it from, for hot reloading
+    fn load_from_source(source_path: &Path) -> Result<Self, String> {
+        Self::load_from_file(source_path)
+    }
+    fn storage(manager: &AssetManager) -> &AssetStorage<Self>;
+    fn storage_mut(manager: &mut AssetManager) -> &mut AssetStorage<Self>;
+}
+
+impl Asset for Model {
+    // The format is picked by the file extension
+    fn load_from_file(path: &Path) -> Result<Self, String> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("").to_lowercase();
+
+        match extension.as_str() {
+            "bglm" => cooked::load_model(path).map(|cooked_model| cooked_model.model),
+            "gltf" => Ok(mesh::parse_model(&gltf2::File::from(path.to_path_buf())?)),
+            "obj" => obj::load_model(path),
+            "stl" => stl::load_model(path),
+            "ply" => ply::load_model(path),
+            _ => Err(format!("Unsupported model format: {}", path.display()))
+        }
+    }
+
+    // Repaired and given LODs, like a cooked model. The pipeline reports the warnings when it cooks the model, so they're dropped here.
+    fn load_from_source(source_path: &Path) -> Result<Self, String> {
+        let mut warnings: Vec<String> = vec!();
+        pipeline::prepare_model(source_path, &source_path.display().to_string(), &mut warnings)
+    }
+
+    fn storage(manager: &AssetManager) -> &AssetStorage<Self> {
+        &manager.models
+    }
+
+    fn storage_mut(manager: &mut AssetManager) -> &mut AssetStorage<Self> {
+        &mut manager.models
+    }
+}
+
+impl Asset for Texture {
+    fn load_from_file(path: &Path) -> Result<Self, String> {
+        Texture::load(path)
+    }
+
+    fn storage(manager: &AssetManager) -> &AssetStorage<Self> {
+        &manager.textures
+    }
+
+    fn storage_mut(manager: &mut AssetManager) -> &mut AssetStorage<Self> {
+        &mut manager.textures
+    }
+}
+
+impl Asset for ShaderBytecode {
+    fn load_from_file(path: &Path) -> Result<Self, String> {
+        ShaderBytecode::load(path)
+    }
+
+    fn storage(manager: &AssetManager) -> &AssetStorage<Self> {
+        &manager.shaders
+    }
+
+    fn storage_mut(manager: &mut AssetManager) -> &mut AssetStorage<Self> {
+        &mut manager.shaders
+    }
+}
+
+struct Slot<T> {
+    // The one it was first loaded with, if it was loaded through several
+    logical_path: String,
+    // What the logical path resolved to, which is what identifies the asset
+    file_path: PathBuf,
+    asset: Option<T>,
+    state: LoadState,
+    // Shared with the worker thread while loading, so it can skip the work if nobody wants the asset anymore
+    cancelled: Option<Arc<AtomicBool>>,
+    // Counts the loads started for the slot. A load that finishes after a newer one was started is thrown away.
+    load_id: u32,
+    reference_count: u32,
+    generation: u32
+}
+
+// Sent back by a worker thread. The asset's type is erased to get it through the channel,
+// "complete" is the matching complete_load::<T> that puts it back together on the main thread.
+struct FinishedLoad {
+    index: u32,
+    generation: u32,
+    load_id: u32,
+    result: Result<Box<dyn Any + Send>, String>,
+    complete: fn(&mut AssetManager, FinishedLoad) -> Option<LoadEvent>
+}
+
+struct HotReload {
+    watcher: Box<dyn FileWatcher>,
+    source_directory: Option<PathBuf>,
+    // Several assets can come from the same file, like a model and a texture from the same PNG
+    watched_files: HashMap<PathBuf, Vec<WatchedAsset>>
+}
+
+struct WatchedAsset {
+    asset_type: TypeId,
+    index: u32,
+    generation: u32,
+    from_source: bool,
+    // reload_asset::<T> for the asset's type
+    reload: fn(&mut AssetManager, &WatchedAsset, PathBuf)
+}
+
+// All loaded assets of a single type
+pub struct AssetStorage<T> {
+    slots: Vec<Slot<T>>,
+    free_slots: Vec<u32>,
+    by_file_path: HashMap<PathBuf, u32>
+}
+
+impl<T> Default for AssetStorage<T> {
+    fn default() -> Self {
+        AssetStorage { slots: vec!(), free_slots: vec!(), by_file_path: HashMap::new() }
+    }
+}
+
+impl<T> AssetStorage<T> {
+    // Loaded, loading or failed, as long as the handle isn't stale
+    fn slot(&self, handle: Handle<T>) -> Option<&Slot<T>> {
+        self.slots.get(handle.index as usize).filter(|slot| slot.generation == handle.generation && slot.state != LoadState::Unloaded)
+    }
+
+    fn slot_mut(&mut self, handle: Handle<T>) -> Option<&mut Slot<T>> {
+        self.slots.get_mut(handle.index as usize).filter(|slot| slot.generation == handle.generation && slot.state != LoadState::Unloaded)
+    }
+
+    fn handle(&self, file_path: &Path) -> Option<Handle<T>> {
+        self.by_file_path.get(file_path).map(|index| self.handle_at(*index))
+    }
+
+    fn handle_at(&self, index: u32) -> Handle<T> {
+        Handle { index, generation: self.slots[index as usize].generation, asset_type: PhantomData }
+    }
+
+    // Without an asset, the slot is waiting for a background load
+    fn insert(&mut self, logical_path: &str, file_path: PathBuf, asset: Option<T>) -> Handle<T> {
+        let index = match self.free_slots.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    logical_path: String::new(),
+                    file_path: PathBuf::new(),
+                    asset: None,
+                    state: LoadState::Unloaded,
+                    cancelled: None,
+                    load_id: 0,
+                    reference_count: 0,
+                    generation: 0
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+
+        let slot = &mut self.slots[index as usize];
+        slot.logical_path = String::from(logical_path);
+        slot.file_path = file_path.clone();
+        slot.state = if asset.is_some() { LoadState::Loaded } else { LoadState::Loading };
+        slot.asset = asset;
+        slot.reference_count = 1;
+        self.by_file_path.insert(file_path, index);
+
+        Handle { index, generation: slot.generation, asset_type: PhantomData }
+    }
+
+    fn remove(&mut self, handle: Handle<T>) {
+        let slot = &mut self.slots[handle.index as usize];
+        if let Some(cancelled) = slot.cancelled.take() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+        slot.asset = None;
+        slot.state = LoadState::Unloaded;
+        slot.reference_count = 0;
+        // Every handle to the old asset is now stale
+        slot.generation = slot.generation.wrapping_add(1);
+        self.by_file_path.remove(&slot.file_path);
+        self.free_slots.push(handle.index);
+    }
+
+    // Number of assets that are loaded, loading or failed
+    pub fn len(&self) -> usize {
+        self.by_file_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_file_path.is_empty()
+    }
+}
+
+pub struct AssetManager {
+    root: PathBuf,
+    manifest: Option<Manifest>,
+    models: AssetStorage<Model>,
+    textures: AssetStorage<Texture>,
+    shaders: AssetStorage<ShaderBytecode>,
+    // Only started on the first background load, a manager that only loads synchronously doesn't need any threads
+    workers: Option<WorkerPool>,
+    finished_sender: Sender<FinishedLoad>,
+    finished_receiver: Receiver<FinishedLoad>,
+    // Events of loads that finished while waiting for something else, for the next poll
+    pending_events: Vec<LoadEvent>,
+    progress: LoadProgress,
+    hot_reload: Option<HotReload>
+}
+
+impl AssetManager {
+    // The manifest is read once here. Without one, logical paths are plain file paths below the root.
+    pub fn new<P: AsRef<Path>>(root: P) -> AssetManager {
+        let root = root.as_ref().to_path_buf();
+        let manifest_path = root.join(MANIFEST_FILE_NAME);
+        let manifest = if manifest_path.exists() {
+            match Manifest::load(&manifest_path) {
+                Ok(manifest) => Some(manifest),
+                Err(err) => {
+                    println!("Ignoring asset manifest: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let (finished_sender, finished_receiver) = mpsc::channel();
+
+        AssetManager {
+            root,
+            manifest,
+            models: AssetStorage::default(),
+            textures: AssetStorage::default(),
+            shaders: AssetStorage::default(),
+            workers: None,
+            finished_sender,
+            finished_receiver,
+            pending_events: vec!(),
+            progress: LoadProgress::default(),
+            hot_reload: None
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Loads an asset, or adds a reference to it if it's already loaded.
+    // If it's being loaded in the background, this waits for that to finish. If that failed, it's tried once more here.
+    pub fn load<T: Asset>(&mut self, logical_path: &str) -> Result<Handle<T>, String> {
+        let logical_path = normalize_logical_path(logical_path);
+        let file_path = self.resolve(&logical_path);
+
+        if let Some(handle) = T::storage(self).handle(&file_path) {
+            self.wait_for(handle);
+
+            if let LoadState::Failed(_) = self.state(handle) {
+                let asset = self.load_file::<T>(&logical_path)?;
+                let slot = T::storage_mut(self).slot_mut(handle).unwrap();
+                slot.asset = Some(asset);
+                slot.state = LoadState::Loaded;
+            }
+
+            T::storage_mut(self).slot_mut(handle).unwrap().reference_count += 1;
+            return Ok(handle);
+        }
+
+        let asset = self.load_file::<T>(&logical_path)?;
+        let handle = T::storage_mut(self).insert(&logical_path, file_path, Some(asset));
+        self.watch(handle);
+        Ok(handle)
+    }
+
+    // Starts loading an asset on a worker thread, and returns its handle straight away.
+    // "poll" tells when it's done. Adds a reference if the asset is already loaded or loading, and retries if it failed.
+    pub fn load_async<T: Asset>(&mut self, logical_path: &str) -> Handle<T> {
+        let logical_path = normalize_logical_path(logical_path);
+        let file_path = self.resolve(&logical_path);
+
+        if let Some(handle) = T::storage(self).handle(&file_path) {
+            let slot = T::storage_mut(self).slot_mut(handle).unwrap();
+            slot.reference_count += 1;
+            if let LoadState::Failed(_) = slot.state {
+                self.start_load(handle, file_path, T::load_from_file);
+            }
+            return handle;
+        }
+
+        let handle = T::storage_mut(self).insert(&logical_path, file_path.clone(), None);
+        self.start_load(handle, file_path, T::load_from_file);
+        self.watch(handle);
+        handle
+    }
+
+    pub fn load_model_async(&mut self, logical_path: &str) -> Handle<Model> {
+        self.load_async::<Model>(logical_path)
+    }
+
+    pub fn load_texture_async(&mut self, logical_path: &str) -> Handle<Texture> {
+        self.load_async::<Texture>(logical_path)
+    }
+
+    pub fn load_shader(&mut self, logical_path: &str) -> Result<Handle<ShaderBytecode>, String> {
+        self.load::<ShaderBytecode>(logical_path)
+    }
+
+    // Starts reloading changed files, and puts the assets that finished loading in the background into their slots.
+    // Meant to be called once per frame.
+    pub fn poll(&mut self) -> Vec<LoadEvent> {
+        self.reload_changed_files();
+
+        while let Ok(finished) = self.finished_receiver.try_recv() {
+            self.complete(finished);
+        }
+
+        std::mem::take(&mut self.pending_events)
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    // Stops a background load. The slot is freed, so every handle to it is stale afterwards, whoever holds it.
+    pub fn cancel<T: Asset>(&mut self, handle: Handle<T>) -> Result<(), String> {
+        match T::storage(self).slot(handle) {
+            Some(slot) if slot.state == LoadState::Loading => {
+                self.unload(handle);
+                Ok(())
+            },
+            _ => Err(format!("Can't cancel {:?}, it's not loading.", handle))
+        }
+    }
+
+    // Starts watching the files of every asset, loaded now or later. The source directory is the one the asset pipeline cooks from.
+    // Without it, or for assets that aren't in the manifest, the files that are loaded are watched.
+    pub fn watch_for_changes(&mut self, watcher: Box<dyn FileWatcher>, source_directory: Option<PathBuf>) {
+        self.hot_reload = Some(HotReload { watcher, source_directory, watched_files: HashMap::new() });
+
+        self.watch_loaded::<Model>();
+        self.watch_loaded::<Texture>();
+        self.watch_loaded::<ShaderBytecode>();
+    }
+
+    // Loads the asset again in the background, from the same file hot reloading would. "poll" tells when it's done.
+    pub fn reload<T: Asset>(&mut self, handle: Handle<T>) -> Result<(), String> {
+        let logical_path = String::from(self.logical_path(handle).ok_or_else(|| format!("Can't reload {:?}, it's not loaded.", handle))?);
+        let (file_path, from_source) = self.watched_file(&logical_path);
+        let load = if from_source { T::load_from_source } else { T::load_from_file };
+
+        self.start_load(handle, file_path, load);
+        Ok(())
+    }
+
+    pub fn load_model(&mut self, logical_path: &str) -> Result<Handle<Model>, String> {
+        self.load::<Model>(logical_path)
+    }
+
+    pub fn load_texture(&mut self, logical_path: &str) -> Result<Handle<Texture>, String> {
+        self.load::<Texture>(logical_path)
+    }
+
+    // None if the asset was unloaded, or isn't done loading
+    pub fn get<T: Asset>(&self, handle: Handle<T>) -> Option<&T> {
+        T::storage(self).slot(handle).and_then(|slot| slot.asset.as_ref())
+    }
+
+    pub fn get_mut<T: Asset>(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        T::storage_mut(self).slot_mut(handle).and_then(|slot| slot.asset.as_mut())
+    }
+
+    pub fn state<T: Asset>(&self, handle: Handle<T>) -> LoadState {
+        match T::storage(self).slot(handle) {
+            Some(slot) => slot.state.clone(),
+            None => LoadState::Unloaded
+        }
+    }
+
+    pub fn reference_count<T: Asset>(&self, handle: Handle<T>) -> u32 {
+        T::storage(self).slot(handle).map(|slot| slot.reference_count).unwrap_or(0)
+    }
+
+    // The logical path an asset was loaded with
+    pub fn logical_path<T: Asset>(&self, handle: Handle<T>) -> Option<&str> {
+        T::storage(self).slot(handle).map(|slot| slot.logical_path.as_str())
+    }
+
+    // Adds a reference, for when a handle is shared with something that will release it on its own
+    pub fn retain<T: Asset>(&mut self, handle: Handle<T>) -> Result<(), String> {
+        let slot = T::storage_mut(self).slot_mut(handle).ok_or_else(|| format!("Can't retain {:?}, it's not loaded.", handle))?;
+        slot.reference_count += 1;
+        Ok(())
+    }
+
+    // Gives back a reference. Returns true if that was the last one, and the asset got unloaded (or its load cancelled).
+    pub fn release<T: Asset>(&mut self, handle: Handle<T>) -> Result<bool, String> {
+        let slot = T::storage_mut(self).slot_mut(handle).ok_or_else(|| format!("Can't release {:?}, it's not loaded.", handle))?;
+
+        slot.reference_count -= 1;
+        if slot.reference_count > 0 {
+            return Ok(false);
+        }
+
+        self.unload(handle);
+        Ok(true)
+    }
+
+    // The file a logical path refers to: the built file from the manifest if it has one, otherwise a plain path below the root
+    pub fn resolve(&self, logical_path: &str) -> PathBuf {
+        let logical_path = normalize_logical_path(logical_path);
+        let file = self.manifest
+            .as_ref()
+            .and_then(|manifest| manifest.get(&logical_path))
+            .map(|entry| entry.file.clone())
+            .unwrap_or(logical_path);
+
+        join_path(&self.root, &file)
+    }
+
+    fn load_file<T: Asset>(&self, logical_path: &str) -> Result<T, String> {
+        T::load_from_file(&self.resolve(logical_path)).map_err(|err| format!("Failed to load asset '{}': {}", logical_path, err))
+    }
+
+    // Starting a load while another one is running for the same slot replaces that one
+    fn start_load<T: Asset>(&mut self, handle: Handle<T>, file_path: PathBuf, load: fn(&Path) -> Result<T, String>) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let slot = T::storage_mut(self).slot_mut(handle).unwrap();
+        // Only a load that is still running has a cancel flag
+        let previous_load = slot.cancelled.replace(Arc::clone(&cancelled));
+        if let Some(previous_load) = &previous_load {
+            previous_load.store(true, Ordering::Relaxed);
+        }
+        slot.state = if slot.asset.is_some() { LoadState::Reloading } else { LoadState::Loading };
+        slot.load_id = slot.load_id.wrapping_add(1);
+        let load_id = slot.load_id;
+
+        if previous_load.is_some() {
+            self.progress.finished += 1;
+        }
+        if self.progress.is_done() {
+            self.progress = LoadProgress::default();
+        }
+        self.progress.requested += 1;
+
+        let sender = self.finished_sender.clone();
+        let (index, generation) = (handle.index, handle.generation);
+        self.workers.get_or_insert_with(WorkerPool::with_default_thread_count).execute(move || {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+
+            // Some importers still panic on bad files. That shouldn't take a worker thread down, and the slot would wait forever.
+            let result = panic::catch_unwind(AssertUnwindSafe(|| load(&file_path)))
+                .unwrap_or_else(|_| Err(String::from("The importer panicked.")))
+                .map(|asset| Box::new(asset) as Box<dyn Any + Send>);
+
+            // Fails only if the manager is gone, and then nobody is waiting for the asset anyway
+            let _ = sender.send(FinishedLoad { index, generation, load_id, result, complete: complete_load::<T> });
+        });
+    }
+
+    // The file to watch for an asset, and whether it's the source file (true) or the one that is loaded (false)
+    fn watched_file(&self, logical_path: &str) -> (PathBuf, bool) {
+        let source_directory = self.hot_reload.as_ref().and_then(|hot_reload| hot_reload.source_directory.as_ref());
+        let entry = self.manifest.as_ref().and_then(|manifest| manifest.get(logical_path));
+
+        match (source_directory, entry) {
+            (Some(source_directory), Some(entry)) => (join_path(source_directory, &entry.source), true),
+            _ => (self.resolve(logical_path), false)
+        }
+    }
+
+    fn watch<T: Asset>(&mut self, handle: Handle<T>) {
+        if self.hot_reload.is_none() {
+            return;
+        }
+
+        let logical_path = String::from(self.logical_path(handle).unwrap());
+        let (file_path, from_source) = self.watched_file(&logical_path);
+        let hot_reload = self.hot_reload.as_mut().unwrap();
+
+        // Not being able to watch a file only means it won't be reloaded, the asset itself is fine
+        if let Err(err) = hot_reload.watcher.watch(&file_path) {
+            println!("Asset '{}' won't be hot reloaded: {}", logical_path, err);
+            return;
+        }
+
+        hot_reload.watched_files.entry(file_path).or_default().push(WatchedAsset {
+            asset_type: TypeId::of::<T>(),
+            index: handle.index,
+            generation: handle.generation,
+            from_source,
+            reload: reload_asset::<T>
+        });
+    }
+
+    fn watch_loaded<T: Asset>(&mut self) {
+        let handles: Vec<Handle<T>> = T::storage(self).by_file_path
+            .values()
+            .map(|index| T::storage(self).handle_at(*index))
+            .collect();
+
+        for handle in handles {
+            self.watch(handle);
+        }
+    }
+
+    fn unwatch<T: Asset>(&mut self, handle: Handle<T>) {
+        let hot_reload = match self.hot_reload.as_mut() {
+            Some(hot_reload) => hot_reload,
+            None => return
+        };
+
+        let watcher = &mut hot_reload.watcher;
+        hot_reload.watched_files.retain(|file_path, watched_assets| {
+            watched_assets.retain(|watched| {
+                !(watched.asset_type == TypeId::of::<T>() && watched.index == handle.index && watched.generation == handle.generation)
+            });
+
+            if watched_assets.is_empty() {
+                watcher.unwatch(file_path);
+            }
+            !watched_assets.is_empty()
+        });
+    }
+
+    fn reload_changed_files(&mut self) {
+        let changed_files = match self.hot_reload.as_mut() {
+            Some(hot_reload) => hot_reload.watcher.changed_files(),
+            None => return
+        };
+
+        for file_path in changed_files {
+            let watched_assets = match self.hot_reload.as_mut().and_then(|hot_reload| hot_reload.watched_files.remove(&file_path)) {
+                Some(watched_assets) => watched_assets,
+                None => continue
+            };
+
+            for watched in &watched_assets {
+                (watched.reload)(self, watched, file_path.clone());
+            }
+
+            self.hot_reload.as_mut().unwrap().watched_files.insert(file_path, watched_assets);
+        }
+    }
+
+    fn complete(&mut self, finished: FinishedLoad) {
+        let complete = finished.complete;
+        if let Some(event) = complete(self, finished) {
+            self.pending_events.push(event);
+        }
+    }
+
+    // Blocks until a background load of the asset is done, if there is one
+    fn wait_for<T: Asset>(&mut self, handle: Handle<T>) {
+        while self.state(handle) == LoadState::Loading {
+            // The manager holds a sender itself, so this can't fail
+            let finished = self.finished_receiver.recv().unwrap();
+            self.complete(finished);
+        }
+    }
+
+    fn unload<T: Asset>(&mut self, handle: Handle<T>) {
+        let was_loading = matches!(self.state(handle), LoadState::Loading | LoadState::Reloading);
+        self.unwatch(handle);
+        T::storage_mut(self).remove(handle);
+
+        // Nothing is going to come back for it, so it counts as finished
+        if was_loading {
+            self.progress.finished += 1;
+        }
+    }
+}
+
+// Runs on the main thread for every load a worker thread finished
+fn complete_load<T: Asset>(manager: &mut AssetManager, finished: FinishedLoad) -> Option<LoadEvent> {
+    let slot = match T::storage_mut(manager).slots.get_mut(finished.index as usize) {
+        Some(slot) if slot.generation == finished.generation && slot.load_id == finished.load_id
+            && matches!(slot.state, LoadState::Loading | LoadState::Reloading) => slot,
+        // Cancelled, released, or replaced by a newer load while it was loading
+        _ => return None
+    };
+
+    slot.cancelled = None;
+    let is_reload = slot.state == LoadState::Reloading;
+    let asset = finished.result.and_then(|asset| asset.downcast::<T>().map_err(|_| String::from("The loaded asset has the wrong type.")));
+    let event = match asset {
+        Ok(asset) => {
+            slot.asset = Some(*asset);
+            slot.state = LoadState::Loaded;
+            if is_reload {
+                LoadEvent::Reloaded { logical_path: slot.logical_path.clone() }
+            } else {
+                LoadEvent::Loaded { logical_path: slot.logical_path.clone() }
+            }
+        },
+        Err(err) if is_reload => {
+            // The old version keeps working
+            slot.state = LoadState::Loaded;
+            LoadEvent::Failed { logical_path: slot.logical_path.clone(), error: format!("Failed to reload asset '{}': {}", slot.logical_path, err) }
+        },
+        Err(err) => {
+            let error = format!("Failed to load asset '{}': {}", slot.logical_path, err);
+            slot.state = LoadState::Failed(error.clone());
+            LoadEvent::Failed { logical_path: slot.logical_path.clone(), error }
+        }
+    };
+
+    manager.progress.finished += 1;
+    Some(event)
+}
+
+// Runs on the main thread when a watched file changed
+fn reload_asset<T: Asset>(manager: &mut AssetManager, watched: &WatchedAsset, file_path: PathBuf) {
+    let handle: Handle<T> = Handle { index: watched.index, generation: watched.generation, asset_type: PhantomData };
+    if manager.logical_path(handle).is_none() {
+        return;
+    }
+
+    let load = if watched.from_source { T::load_from_source } else { T::load_from_file };
+    manager.start_load(handle, file_path, load);
+}
+
+// Joining the parts one by one gives the right separators on every platform
+fn join_path(base: &Path, logical_path: &str) -> PathBuf {
+    logical_path.split('/').fold(base.to_path_buf(), |path, part| path.join(part))
+}
+
+// "mill\mill", "./mill/mill" and "mill//mill" are all "mill/mill"
+fn normalize_logical_path(logical_path: &str) -> String {
+    logical_path
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::asset::manifest::{AssetKind, ManifestEntry};
+    use crate::asset::manager::*;
+    use crate::asset::watcher::PollingWatcher;
+
+    const TRIANGLE_OBJ: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+
+    fn test_root(test_name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("beagle_assets_{}_{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("props")).unwrap();
+        fs::write(root.join("props").join("triangle.obj"), TRIANGLE_OBJ).unwrap();
+        root
+    }
+
+    #[test]
+    fn should_load_each_asset_once_and_count_references() {
+        let root = test_root("references");
+        let mut assets = AssetManager::new(&root);
+
+        let first = assets.load_model("props/triangle.obj").unwrap();
+        let second = assets.load_model("props\\triangle.obj").unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(assets.models.len(), 1);
+        assert_eq!(assets.reference_count(first), 2);
+        assert_eq!(assets.get(first).unwrap().meshes[0].indices.len(), 3);
+
+        assert_eq!(assets.release(first), Ok(false));
+        assert_eq!(assets.state(first), LoadState::Loaded);
+        assert_eq!(assets.release(first), Ok(true));
+        assert_eq!(assets.state(first), LoadState::Unloaded);
+        assert!(assets.get(first).is_none());
+        assert!(assets.release(first).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn should_not_reuse_stale_handles() {
+        let root = test_root("stale");
+        fs::write(root.join("props").join("other.obj"), TRIANGLE_OBJ).unwrap();
+        let mut assets = AssetManager::new(&root);
+
+        let old = assets.load_model("props/triangle.obj").unwrap();
+        assets.release(old).unwrap();
+        let new = assets.load_model("props/other.obj").unwrap();
+
+        // The slot is reused, but the old handle doesn't see the new asset
+        assert_eq!(old.index, new.index);
+        assert!(assets.get(old).is_none());
+        assert_eq!(assets.logical_path(new), Some("props/other.obj"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn should_resolve_logical_names_through_manifest() {
+        let root = test_root("manifest");
+        let mut manifest = Manifest::default();
+        manifest.assets.insert(String::from("props/triangle"), ManifestEntry {
+            kind: AssetKind::Model,
+            file: String::from("props/triangle.obj"),
+            source: String::from("props/triangle.obj"),
+            hash: String::from("0000000000000000")
+        });
+        manifest.save(root.join(MANIFEST_FILE_NAME)).unwrap();
+        let mut assets = AssetManager::new(&root);
+
+        let by_name = assets.load_model("props/triangle").unwrap();
+        let by_file = assets.load_model("props/triangle.obj").unwrap();
+
+        assert_eq!(assets.resolve("props/triangle"), root.join("props").join("triangle.obj"));
+        assert!(assets.get(by_name).is_some());
+        assert_eq!(by_name, by_file);
+        assert_eq!(assets.models.len(), 1);
+        assert_eq!(assets.reference_count(by_name), 2);
+        assert!(assets.load_model("props/missing").is_err());
+        assert!(assets.load_texture("props/triangle.obj").is_err());
+        assert!(assets.textures.is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn poll_until_done(assets: &mut AssetManager) -> Vec<LoadEvent> {
+        let started = std::time::Instant::now();
+        let mut events: Vec<LoadEvent> = vec!();
+
+        while !assets.progress().is_done() {
+            assert!(started.elapsed().as_secs() < 10, "Background loads took too long");
+            events.extend(assets.poll());
+            std::thread::yield_now();
+        }
+
+        events
+    }
+
+    fn poll_until_event(assets: &mut AssetManager) -> Vec<LoadEvent> {
+        let started = std::time::Instant::now();
+
+        loop {
+            assert!(started.elapsed().as_secs() < 10, "Nothing was reported");
+            let events = assets.poll();
+            if !events.is_empty() {
+                return events;
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn should_load_in_background_and_report_when_done() {
+        let root = test_root("async");
+        let mut assets = AssetManager::new(&root);
+
+        let triangle = assets.load_model_async("props/triangle.obj");
+        let missing = assets.load_model_async("props/missing.obj");
+        let events = poll_until_done(&mut assets);
+
+        assert_eq!(events.len(), 2);
+        assert!(events.contains(&LoadEvent::Loaded { logical_path: String::from("props/triangle.obj") }));
+        assert_eq!(assets.state(triangle), LoadState::Loaded);
+        assert_eq!(assets.get(triangle).unwrap().meshes[0].indices.len(), 3);
+        assert_eq!(assets.progress(), LoadProgress { requested: 2, finished: 2 });
+
+        assert!(matches!(assets.state(missing), LoadState::Failed(_)));
+        assert!(assets.get(missing).is_none());
+
+        // The same asset loaded synchronously is the one that was loaded in the background
+        assert_eq!(assets.load_model("props/triangle.obj"), Ok(triangle));
+        assert_eq!(assets.reference_count(triangle), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn should_wait_for_background_load_when_loading_synchronously() {
+        let root = test_root("wait");
+        let mut assets = AssetManager::new(&root);
+
+        let in_background = assets.load_model_async("props/triangle.obj");
+        let waited_for = assets.load_model("props/triangle.obj").unwrap();
+
+        assert_eq!(in_background, waited_for);
+        assert!(assets.get(waited_for).is_some());
+        // The event isn't lost by waiting
+        assert_eq!(assets.poll(), vec![LoadEvent::Loaded { logical_path: String::from("props/triangle.obj") }]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn should_ignore_cancelled_loads() {
+        let root = test_root("cancel");
+        let mut assets = AssetManager::new(&root);
+
+        let cancelled = assets.load_model_async("props/triangle.obj");
+        assets.cancel(cancelled).unwrap();
+
+        assert_eq!(assets.state(cancelled), LoadState::Unloaded);
+        assert!(assets.progress().is_done());
+        assert!(assets.cancel(cancelled).is_err());
+
+        let reloaded = assets.load_model_async("props/triangle.obj");
+        let events = poll_until_done(&mut assets);
+
+        // Only the second load is reported, whatever happened to the first one on its worker thread
+        assert_ne!(cancelled, reloaded);
+        assert_eq!(events.len(), 1);
+        assert!(assets.get(cancelled).is_none());
+        assert!(assets.get(reloaded).is_some());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn should_hot_reload_changed_files_into_same_handle() {
+        let root = test_root("hot_reload");
+        let mut assets = AssetManager::new(&root);
+        assets.watch_for_changes(Box::new(PollingWatcher::default()), None);
+        let triangle = assets.load_model("props/triangle.obj").unwrap();
+
+        fs::write(root.join("props").join("triangle.obj"), "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        let events = poll_until_event(&mut assets);
+
+        assert_eq!(events, vec![LoadEvent::Reloaded { logical_path: String::from("props/triangle.obj") }]);
+        assert_eq!(assets.get(triangle).unwrap().meshes[0].indices.len(), 6);
+
+        // A broken file is reported, and the last version that worked stays
+        fs::write(root.join("props").join("triangle.obj"), "v 0 0 0\nf 1 2 3\n").unwrap();
+        let events = poll_until_event(&mut assets);
+
+        assert!(matches!(&events[..], [LoadEvent::Failed { .. }]));
+        assert_eq!(assets.state(triangle), LoadState::Loaded);
+        assert_eq!(assets.get(triangle).unwrap().meshes[0].indices.len(), 6);
+
+        // Unloaded assets aren't watched anymore
+        assets.release(triangle).unwrap();
+        assert!(assets.hot_reload.as_ref().unwrap().watched_files.is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn should_hot_reload_from_source_directory() {
+        let root = test_root("hot_reload_source");
+        let source_directory = root.join("source");
+        fs::create_dir_all(&source_directory).unwrap();
+        fs::write(source_directory.join("triangle.obj"), TRIANGLE_OBJ).unwrap();
+
+        let mut manifest = Manifest::default();
+        manifest.assets.insert(String::from("props/triangle"), ManifestEntry {
+            kind: AssetKind::Model,
+            file: String::from("props/triangle.obj"),
+            source: String::from("triangle.obj"),
+            hash: String::from("0000000000000000")
+        });
+        manifest.save(root.join(MANIFEST_FILE_NAME)).unwrap();
+
+        let mut assets = AssetManager::new(&root);
+        let triangle = assets.load_model("props/triangle").unwrap();
+        // Turned on after loading, so already loaded assets have to be picked up
+        assets.watch_for_changes(Box::new(PollingWatcher::default()), Some(source_directory.clone()));
+
+        fs::write(source_directory.join("triangle.obj"), "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        let events = poll_until_event(&mut assets);
+
+        assert_eq!(events, vec![LoadEvent::Reloaded { logical_path: String::from("props/triangle") }]);
+        assert_eq!(assets.get(triangle).unwrap().meshes[0].indices.len(), 6);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
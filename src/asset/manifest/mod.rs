@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+/*
+    The manifest is written by the asset pipeline next to the cooked assets, and maps the "logical" name of every asset
+    to the file that holds it. Code asks for "mill/mill", and doesn't have to know whether that came from a glTF or an OBJ file,
+    or where the cooked file ended up.
+
+    Models are named after their source path without extension ("mill/mill.gltf" is "mill/mill").
+    Files that are copied as they are keep their extension ("shaders/pixel.hlsl").
+
+    It's also what makes rebuilds incremental: every entry remembers the hash of the source it was built from.
+*/
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum AssetKind {
+    // A cooked model, see asset::cooked
+    Model,
+    // Any other file, copied from the source directory unchanged
+    File
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    pub kind: AssetKind,
+    // Path of the built file, relative to the manifest, always with forward slashes
+    pub file: String,
+    // Path of the source file, relative to the source directory, always with forward slashes
+    pub source: String,
+    // Hash of everything the built file depends on, as 16 hexadecimal digits
+    pub hash: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub version: u32,
+    // A BTreeMap keeps the names sorted, so the file doesn't change when nothing else did
+    pub assets: BTreeMap<String, ManifestEntry>
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest { version: MANIFEST_VERSION, assets: BTreeMap::new() }
+    }
+}
+
+impl Manifest {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Manifest, String> {
+        let file_content = fs::read_to_string(&path).map_err(|err| format!("Failed to read manifest {}: {}", path.as_ref().display(), err))?;
+        let manifest = serde_json::from_str::<Manifest>(&file_content).map_err(|err| format!("Failed to deserialize manifest: {}", err))?;
+
+        if manifest.version != MANIFEST_VERSION {
+            return Err(format!("Manifest has version {}, but only version {} is supported.", manifest.version, MANIFEST_VERSION));
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let file_content = serde_json::to_string_pretty(self).map_err(|err| format!("Failed to serialize manifest: {}", err))?;
+        fs::write(&path, file_content).map_err(|err| format!("Failed to write manifest {}: {}", path.as_ref().display(), err))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ManifestEntry> {
+        self.assets.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::manifest::*;
+
+    #[test]
+    fn should_round_trip_manifest_through_json() {
+        let mut manifest = Manifest::default();
+        manifest.assets.insert(String::from("mill/mill"), ManifestEntry {
+            kind: AssetKind::Model,
+            file: String::from("mill/mill.bglm"),
+            source: String::from("mill/mill.gltf"),
+            hash: String::from("0123456789abcdef")
+        });
+        let path = std::env::temp_dir().join(format!("beagle_manifest_test_{}.json", std::process::id()));
+
+        manifest.save(&path).unwrap();
+        let loaded = Manifest::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, manifest);
+        assert_eq!(loaded.get("mill/mill").unwrap().kind, AssetKind::Model);
+        assert!(loaded.get("mill").is_none());
+    }
+}
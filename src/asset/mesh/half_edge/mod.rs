@@ -0,0 +1,916 @@
+use std::collections::HashMap;
+
+use crate::beagle_math;
+use crate::asset::mesh::{self, Mesh};
+
+/*
+    A half-edge mesh stores the connectivity of a triangle mesh, so questions like "which vertices are next to this one"
+    or "where are the holes in this mesh" can be answered without searching through the whole index list.
+
+    Every edge between two vertices is split into two half-edges, one for each direction. A half-edge belongs to the face on its left
+    (as seen with clockwise winding, which is what I use for front faces), knows the vertex it points TO, its twin going the other way,
+    and the next half-edge around its face. The vertex a half-edge starts at is the vertex its twin points to.
+
+    Edges on the boundary of the mesh (holes, open borders) get a boundary half-edge on their empty side, which belongs to no face.
+    Boundary half-edges are linked to each other with "next", forming one loop per hole, so walking around a vertex
+    or along a border never runs into a dead end.
+
+    Vertices are welded by position when converting from a Mesh. Normals, uvs and colors can differ between the faces around a vertex (seams),
+    so they are stored per face corner instead: every half-edge holds the attributes of the vertex it points to, within its face.
+
+    Removed elements (after collapses) are left in place and marked with INVALID, so indices held by the caller stay valid.
+    They are skipped when converting back to a Mesh.
+
+    A half-edge mesh can only describe manifold edges: an edge shared by more than two triangles, or two neighbouring
+    triangles with opposite winding, can't be represented. "asset::mesh::validation" can find and fix the latter.
+*/
+
+pub const INVALID: u32 = u32::MAX;
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Vertex {
+    pub position: beagle_math::Vector3,
+    // One of the half-edges starting at this vertex. If the vertex is on a boundary, this is always the boundary half-edge leaving it.
+    pub half_edge: u32
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct HalfEdge {
+    // The vertex this half-edge points to
+    pub vertex: u32,
+    pub twin: u32,
+    pub next: u32,
+    // INVALID for boundary half-edges
+    pub face: u32,
+    // Attributes of "vertex" within "face"
+    pub normal: beagle_math::Vector3,
+    pub uv: beagle_math::Vector2,
+    pub color: beagle_math::Vector4
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Face {
+    pub half_edge: u32
+}
+
+#[derive(Default)]
+pub struct HalfEdgeMesh {
+    pub vertices: Vec<Vertex>,
+    pub half_edges: Vec<HalfEdge>,
+    pub faces: Vec<Face>,
+    pub has_normals: bool,
+    pub has_uvs: bool,
+    pub has_colors: bool
+}
+
+/*
+    Walks half-edges by repeatedly applying "step" until it gets back to the start.
+    Used both for walking around a face and for walking around a vertex.
+*/
+pub struct HalfEdgeCirculator<'a> {
+    mesh: &'a HalfEdgeMesh,
+    start: u32,
+    current: u32,
+    step: fn(&HalfEdgeMesh, u32) -> u32
+}
+
+impl<'a> Iterator for HalfEdgeCirculator<'a> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.current == INVALID {
+            return None;
+        }
+
+        let result = self.current;
+        let next = (self.step)(self.mesh, self.current);
+        self.current = if next == self.start { INVALID } else { next };
+
+        Some(result)
+    }
+}
+
+impl HalfEdgeMesh {
+    pub fn from_mesh(mesh: &Mesh) -> Result<HalfEdgeMesh, String> {
+        if !mesh.indices.len().is_multiple_of(3) {
+            return Err(format!("Mesh {} has {} indices, which is not a whole number of triangles.", mesh.name, mesh.indices.len()));
+        }
+
+        let mut result = HalfEdgeMesh {
+            has_normals: !mesh.vertex_normals.is_empty(),
+            has_uvs: !mesh.vertex_uvs.is_empty(),
+            has_colors: !mesh.vertex_colors.is_empty(),
+            ..Default::default()
+        };
+
+        // Weld the vertices by position
+        let mut seen_positions: HashMap<[u32; 3], u32> = HashMap::new();
+        let vertex_ids: Vec<u32> = mesh.vertex_positions.iter().map(|position| {
+            let key = [(position.x + 0.0).to_bits(), (position.y + 0.0).to_bits(), (position.z + 0.0).to_bits()];
+            *seen_positions.entry(key).or_insert_with(|| {
+                result.vertices.push(Vertex { position: *position, half_edge: INVALID });
+                (result.vertices.len() - 1) as u32
+            })
+        }).collect();
+
+        // Half-edges of the faces, keyed by (from, to)
+        let mut edge_map: HashMap<(u32, u32), u32> = HashMap::new();
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let corners = [vertex_ids[triangle[0] as usize], vertex_ids[triangle[1] as usize], vertex_ids[triangle[2] as usize]];
+
+            // Triangles which collapse into a line after welding have no edges worth keeping
+            if corners[0] == corners[1] || corners[1] == corners[2] || corners[0] == corners[2] {
+                continue;
+            }
+
+            let face = result.faces.len() as u32;
+            let first_half_edge = result.half_edges.len() as u32;
+            result.faces.push(Face { half_edge: first_half_edge });
+
+            for corner in 0..3 {
+                let from = corners[corner];
+                let to = corners[(corner + 1) % 3];
+                let half_edge = first_half_edge + corner as u32;
+
+                if edge_map.insert((from, to), half_edge).is_some() {
+                    return Err(format!("Mesh {} has an edge used twice in the same direction, either because it's shared by more than two triangles or because of inconsistent winding.", mesh.name));
+                }
+
+                let to_index = triangle[(corner + 1) % 3] as usize;
+                result.half_edges.push(HalfEdge {
+                    vertex: to,
+                    twin: INVALID,
+                    next: first_half_edge + ((corner + 1) % 3) as u32,
+                    face,
+                    normal: mesh.vertex_normals.get(to_index).copied().unwrap_or_default(),
+                    uv: mesh.vertex_uvs.get(to_index).copied().unwrap_or_default(),
+                    color: mesh.vertex_colors.get(to_index).copied().unwrap_or_default()
+                });
+
+                result.vertices[from as usize].half_edge = half_edge;
+            }
+        }
+
+        // Pair the half-edges up, creating boundary half-edges where there is no face on the other side
+        let face_half_edge_count = result.half_edges.len() as u32;
+        let mut boundary_half_edges_from: HashMap<u32, Vec<u32>> = HashMap::new();
+
+        for half_edge in 0..face_half_edge_count {
+            if result.half_edges[half_edge as usize].twin != INVALID {
+                continue;
+            }
+
+            // The twins aren't known yet, but in a triangle the half-edge two steps ahead points at the start of this one
+            let to = result.half_edges[half_edge as usize].vertex;
+            let previous = result.half_edges[result.half_edges[half_edge as usize].next as usize].next;
+            let from = result.half_edges[previous as usize].vertex;
+
+            match edge_map.get(&(to, from)) {
+                Some(twin) => {
+                    result.half_edges[half_edge as usize].twin = *twin;
+                    result.half_edges[*twin as usize].twin = half_edge;
+                },
+                None => {
+                    let boundary_half_edge = result.half_edges.len() as u32;
+                    result.half_edges.push(HalfEdge { vertex: from, twin: half_edge, next: INVALID, face: INVALID, ..Default::default() });
+                    result.half_edges[half_edge as usize].twin = boundary_half_edge;
+
+                    boundary_half_edges_from.entry(to).or_default().push(boundary_half_edge);
+                }
+            }
+        }
+
+        // Link the boundary half-edges into loops, and make boundary vertices point at their boundary half-edge
+        for boundary_half_edge in face_half_edge_count..result.half_edges.len() as u32 {
+            let to = result.half_edges[boundary_half_edge as usize].vertex;
+
+            let next = match boundary_half_edges_from.get_mut(&to).and_then(|candidates| candidates.pop()) {
+                Some(next) => next,
+                None => return Err(format!("Mesh {} has a boundary which could not be closed into a loop.", mesh.name))
+            };
+
+            result.half_edges[boundary_half_edge as usize].next = next;
+            result.vertices[to as usize].half_edge = next;
+        }
+
+        Ok(result)
+    }
+
+    /*
+        Turns the half-edge mesh back into a Mesh with a shared vertex buffer.
+        Face corners with identical position, normal, uv and color end up as the same vertex.
+        Only the geometry is filled in, so things like the name and material have to be copied over by the caller.
+    */
+    pub fn to_mesh(&self) -> Mesh {
+        let mut corners: Vec<mesh::Vertex> = vec!();
+
+        for face in 0..self.faces.len() as u32 {
+            if self.is_face_removed(face) {
+                continue;
+            }
+
+            for half_edge in self.face_half_edges(face) {
+                let half_edge = &self.half_edges[half_edge as usize];
+                corners.push(mesh::Vertex {
+                    position: self.vertices[half_edge.vertex as usize].position,
+                    normal: if self.has_normals { half_edge.normal } else { beagle_math::Vector3::zero() },
+                    uv: if self.has_uvs { half_edge.uv } else { beagle_math::Vector2::default() },
+                    color: if self.has_colors { half_edge.color } else { beagle_math::Vector4::default() }
+                });
+            }
+        }
+
+        let (vertices, indices) = mesh::weld_vertices(&corners);
+
+        Mesh {
+            vertex_positions: vertices.iter().map(|vertex| vertex.position).collect(),
+            vertex_normals: if self.has_normals { vertices.iter().map(|vertex| vertex.normal).collect() } else { vec!() },
+            vertex_uvs: if self.has_uvs { vertices.iter().map(|vertex| vertex.uv).collect() } else { vec!() },
+            vertex_colors: if self.has_colors { vertices.iter().map(|vertex| vertex.color).collect() } else { vec!() },
+            indices,
+            ..Default::default()
+        }
+    }
+
+    pub fn origin_vertex(&self, half_edge: u32) -> u32 {
+        self.half_edges[self.half_edges[half_edge as usize].twin as usize].vertex
+    }
+
+    pub fn target_vertex(&self, half_edge: u32) -> u32 {
+        self.half_edges[half_edge as usize].vertex
+    }
+
+    // The half-edge before this one in its face (or boundary loop)
+    pub fn previous(&self, half_edge: u32) -> u32 {
+        let mut current = half_edge;
+        loop {
+            let next = self.half_edges[current as usize].next;
+            if next == half_edge {
+                return current;
+            }
+            current = next;
+        }
+    }
+
+    pub fn is_boundary_half_edge(&self, half_edge: u32) -> bool {
+        self.half_edges[half_edge as usize].face == INVALID
+    }
+
+    // True if either side of the edge has no face
+    pub fn is_boundary_edge(&self, half_edge: u32) -> bool {
+        self.is_boundary_half_edge(half_edge) || self.is_boundary_half_edge(self.half_edges[half_edge as usize].twin)
+    }
+
+    pub fn is_boundary_vertex(&self, vertex: u32) -> bool {
+        let half_edge = self.vertices[vertex as usize].half_edge;
+        half_edge != INVALID && self.is_boundary_half_edge(half_edge)
+    }
+
+    pub fn is_vertex_removed(&self, vertex: u32) -> bool {
+        self.vertices[vertex as usize].half_edge == INVALID
+    }
+
+    pub fn is_face_removed(&self, face: u32) -> bool {
+        self.faces[face as usize].half_edge == INVALID
+    }
+
+    pub fn is_half_edge_removed(&self, half_edge: u32) -> bool {
+        self.half_edges[half_edge as usize].next == INVALID
+    }
+
+    pub fn face_count(&self) -> usize {
+        (0..self.faces.len() as u32).filter(|face| !self.is_face_removed(*face)).count()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        (0..self.vertices.len() as u32).filter(|vertex| !self.is_vertex_removed(*vertex)).count()
+    }
+
+    // The half-edges around a face, in winding order
+    pub fn face_half_edges(&self, face: u32) -> HalfEdgeCirculator<'_> {
+        HalfEdgeCirculator {
+            mesh: self,
+            start: self.faces[face as usize].half_edge,
+            current: self.faces[face as usize].half_edge,
+            step: |mesh, half_edge| mesh.half_edges[half_edge as usize].next
+        }
+    }
+
+    pub fn face_vertices(&self, face: u32) -> impl Iterator<Item = u32> + '_ {
+        self.face_half_edges(face).map(move |half_edge| self.half_edges[half_edge as usize].vertex)
+    }
+
+    // All half-edges starting at a vertex, including boundary half-edges
+    pub fn outgoing_half_edges(&self, vertex: u32) -> HalfEdgeCirculator<'_> {
+        let start = self.vertices[vertex as usize].half_edge;
+        HalfEdgeCirculator {
+            mesh: self,
+            start,
+            current: start,
+            // The twin of an outgoing half-edge comes back to the vertex, and the one after that leaves it again
+            step: |mesh, half_edge| mesh.half_edges[mesh.half_edges[half_edge as usize].twin as usize].next
+        }
+    }
+
+    // The vertices connected to a vertex by an edge, also known as its one-ring
+    pub fn vertex_neighbours(&self, vertex: u32) -> impl Iterator<Item = u32> + '_ {
+        self.outgoing_half_edges(vertex).map(move |half_edge| self.half_edges[half_edge as usize].vertex)
+    }
+
+    pub fn vertex_faces(&self, vertex: u32) -> impl Iterator<Item = u32> + '_ {
+        self.outgoing_half_edges(vertex)
+            .map(move |half_edge| self.half_edges[half_edge as usize].face)
+            .filter(|face| *face != INVALID)
+    }
+
+    pub fn find_half_edge(&self, from: u32, to: u32) -> Option<u32> {
+        self.outgoing_half_edges(from).find(|half_edge| self.half_edges[*half_edge as usize].vertex == to)
+    }
+
+    // Every hole or open border of the mesh, as the list of vertices going around it
+    pub fn boundary_loops(&self) -> Vec<Vec<u32>> {
+        let mut visited: Vec<bool> = vec![false; self.half_edges.len()];
+        let mut loops: Vec<Vec<u32>> = vec!();
+
+        for half_edge in 0..self.half_edges.len() as u32 {
+            if visited[half_edge as usize] || self.is_half_edge_removed(half_edge) || !self.is_boundary_half_edge(half_edge) {
+                continue;
+            }
+
+            let mut boundary_loop: Vec<u32> = vec!();
+            let mut current = half_edge;
+            while !visited[current as usize] {
+                visited[current as usize] = true;
+                boundary_loop.push(self.origin_vertex(current));
+                current = self.half_edges[current as usize].next;
+            }
+
+            loops.push(boundary_loop);
+        }
+
+        loops
+    }
+
+    /*
+        Flips the edge between two triangles, so it connects the two vertices that were opposite to it instead.
+
+        a -- c         a -- c
+        |  / |   =>    | \  |
+        | /  |         |  \ |
+        d -- b         d -- b
+
+        Returns false without changing anything if either side is a boundary, or if the new edge already exists elsewhere.
+    */
+    pub fn flip_edge(&mut self, half_edge: u32) -> bool {
+        if self.is_boundary_edge(half_edge) {
+            return false;
+        }
+
+        let h = half_edge;
+        let t = self.half_edges[h as usize].twin;
+        let h1 = self.half_edges[h as usize].next;
+        let h2 = self.half_edges[h1 as usize].next;
+        let t1 = self.half_edges[t as usize].next;
+        let t2 = self.half_edges[t1 as usize].next;
+
+        let a = self.target_vertex(t);
+        let b = self.target_vertex(h);
+        let c = self.target_vertex(h1);
+        let d = self.target_vertex(t1);
+
+        if c == d || self.find_half_edge(c, d).is_some() {
+            return false;
+        }
+
+        let face0 = self.half_edges[h as usize].face;
+        let face1 = self.half_edges[t as usize].face;
+
+        // The corner attributes of the new edge come from the half-edges which used to point at the same vertices in the same faces
+        let h1_corner = self.half_edges[h1 as usize];
+        let t1_corner = self.half_edges[t1 as usize];
+
+        // Face 0 becomes a -> d -> c, face 1 becomes d -> b -> c
+        self.half_edges[h as usize] = HalfEdge { vertex: c, next: h2, normal: h1_corner.normal, uv: h1_corner.uv, color: h1_corner.color, ..self.half_edges[h as usize] };
+        self.half_edges[t as usize] = HalfEdge { vertex: d, next: t2, normal: t1_corner.normal, uv: t1_corner.uv, color: t1_corner.color, ..self.half_edges[t as usize] };
+        self.half_edges[t1 as usize].next = h;
+        self.half_edges[h2 as usize].next = t1;
+        self.half_edges[t1 as usize].face = face0;
+        self.half_edges[h1 as usize].next = t;
+        self.half_edges[h1 as usize].face = face1;
+        self.half_edges[t2 as usize].next = h1;
+
+        self.faces[face0 as usize].half_edge = h;
+        self.faces[face1 as usize].half_edge = t;
+
+        if self.vertices[a as usize].half_edge == h {
+            self.vertices[a as usize].half_edge = t1;
+        }
+        if self.vertices[b as usize].half_edge == t {
+            self.vertices[b as usize].half_edge = h1;
+        }
+
+        true
+    }
+
+    /*
+        Splits an edge in two by adding a vertex at its middle, splitting the triangles on both sides of it in two as well.
+        The corner attributes of the new vertex are interpolated from the two ends of the edge.
+        Returns the new vertex.
+    */
+    pub fn split_edge(&mut self, half_edge: u32) -> u32 {
+        let h = half_edge;
+        let t = self.half_edges[h as usize].twin;
+        let a = self.origin_vertex(h);
+        let b = self.target_vertex(h);
+
+        let position_a = self.vertices[a as usize].position;
+        let position_b = self.vertices[b as usize].position;
+        let middle = self.vertices.len() as u32;
+        self.vertices.push(Vertex {
+            position: beagle_math::Vector3::new(
+                (position_a.x + position_b.x) * 0.5,
+                (position_a.y + position_b.y) * 0.5,
+                (position_a.z + position_b.z) * 0.5),
+            half_edge: INVALID
+        });
+
+        // Whatever came before t (in its face or boundary loop) will come before the new half-edge leaving b instead
+        let previous_of_t = self.previous(t);
+
+        // h becomes a -> middle, t becomes middle -> a, and the new pair covers middle <-> b
+        let e = self.add_half_edge(HalfEdge { vertex: b, ..self.half_edges[h as usize] });
+        let et = self.add_half_edge(HalfEdge { vertex: middle, ..self.half_edges[t as usize] });
+        self.half_edges[e as usize].twin = et;
+        self.half_edges[et as usize].twin = e;
+        self.half_edges[previous_of_t as usize].next = et;
+
+        if self.vertices[b as usize].half_edge == t {
+            self.vertices[b as usize].half_edge = et;
+        }
+
+        let middle_to_opposite = self.split_side(h, e, middle);
+        self.split_side(et, t, middle);
+
+        // Boundary vertices must point at their outgoing boundary half-edge
+        self.vertices[middle as usize].half_edge = if self.is_boundary_half_edge(t) {
+            t
+        } else if self.is_boundary_half_edge(e) {
+            e
+        } else {
+            middle_to_opposite.unwrap_or(e)
+        };
+
+        middle
+    }
+
+    fn add_half_edge(&mut self, half_edge: HalfEdge) -> u32 {
+        self.half_edges.push(half_edge);
+        (self.half_edges.len() - 1) as u32
+    }
+
+    /*
+        Finishes splitting one side of an edge. "first" is the half-edge now pointing to the middle vertex, which still has the old "next",
+        and "second" is the half-edge leaving the middle vertex along the same side.
+        For a face, the triangle is cut in two along a new edge from the middle vertex to the opposite corner.
+        Returns the half-edge from the middle vertex to the opposite corner, if there is a face.
+    */
+    fn split_side(&mut self, first: u32, second: u32, middle: u32) -> Option<u32> {
+        let old_next = self.half_edges[first as usize].next;
+        let face = self.half_edges[first as usize].face;
+
+        // first's corner attributes still belong to the vertex at the far end of the original edge
+        let far_corner = self.half_edges[first as usize];
+
+        if face == INVALID {
+            self.half_edges[first as usize].vertex = middle;
+            self.half_edges[first as usize].next = second;
+            self.half_edges[second as usize].next = old_next;
+            return None;
+        }
+
+        // first: x -> y, old_next: y -> z, after_next: z -> x. The vertex opposite the edge is z.
+        let after_next = self.half_edges[old_next as usize].next;
+        let opposite = self.half_edges[old_next as usize].vertex;
+        let near_corner = self.half_edges[after_next as usize];
+        let opposite_corner = self.half_edges[old_next as usize];
+
+        let middle_normal = lerp_vector3(&near_corner.normal, &far_corner.normal, 0.5);
+        let middle_uv = beagle_math::Vector2::new((near_corner.uv.x + far_corner.uv.x) * 0.5, (near_corner.uv.y + far_corner.uv.y) * 0.5);
+        let middle_color = beagle_math::Vector4::new(
+            (near_corner.color.x + far_corner.color.x) * 0.5,
+            (near_corner.color.y + far_corner.color.y) * 0.5,
+            (near_corner.color.z + far_corner.color.z) * 0.5,
+            (near_corner.color.w + far_corner.color.w) * 0.5);
+
+        let new_face = self.faces.len() as u32;
+        self.faces.push(Face { half_edge: second });
+
+        // Triangle x -> middle -> z keeps the old face
+        let to_opposite = self.add_half_edge(HalfEdge { vertex: opposite, twin: INVALID, next: after_next, face, normal: opposite_corner.normal, uv: opposite_corner.uv, color: opposite_corner.color });
+        self.half_edges[first as usize] = HalfEdge { vertex: middle, next: to_opposite, normal: middle_normal, uv: middle_uv, color: middle_color, ..far_corner };
+
+        // Triangle middle -> y -> z gets the new face
+        let from_opposite = self.add_half_edge(HalfEdge { vertex: middle, twin: to_opposite, next: second, face: new_face, normal: middle_normal, uv: middle_uv, color: middle_color });
+        self.half_edges[to_opposite as usize].twin = from_opposite;
+        self.half_edges[second as usize].next = old_next;
+        self.half_edges[second as usize].face = new_face;
+        self.half_edges[second as usize].normal = far_corner.normal;
+        self.half_edges[second as usize].uv = far_corner.uv;
+        self.half_edges[second as usize].color = far_corner.color;
+        self.half_edges[old_next as usize].next = from_opposite;
+        self.half_edges[old_next as usize].face = new_face;
+
+        self.faces[face as usize].half_edge = first;
+
+        Some(to_opposite)
+    }
+
+    /*
+        Collapses a half-edge, merging the vertex it starts at into the vertex it points to.
+        The triangles on both sides of the edge disappear, and the vertex keeps its position, which the caller can change afterwards.
+
+        Returns false without changing anything if the collapse would break the mesh:
+        - the two vertices share neighbours other than the ones opposite the edge (the "link condition"), which would pinch the surface
+        - both vertices are on a boundary, but the edge between them isn't, which would join two separate borders
+        - the collapse would leave two triangles on top of each other, or an edge with no triangles
+    */
+    pub fn collapse_edge(&mut self, half_edge: u32) -> bool {
+        let h = half_edge;
+        let t = self.half_edges[h as usize].twin;
+        let removed = self.origin_vertex(h);
+        let kept = self.target_vertex(h);
+
+        if self.is_boundary_vertex(removed) && self.is_boundary_vertex(kept) && !self.is_boundary_edge(h) {
+            return false;
+        }
+
+        // The vertices opposite the edge are allowed to be shared neighbours, as they will simply lose one edge
+        let mut opposite_vertices: Vec<u32> = vec!();
+        for side in [h, t] {
+            if !self.is_boundary_half_edge(side) {
+                opposite_vertices.push(self.target_vertex(self.half_edges[side as usize].next));
+            }
+        }
+
+        let kept_neighbours: Vec<u32> = self.vertex_neighbours(kept).collect();
+        let shared_neighbour_count = self.vertex_neighbours(removed).filter(|neighbour| kept_neighbours.contains(neighbour)).count();
+        if shared_neighbour_count != opposite_vertices.len() {
+            return false;
+        }
+
+        // A vertex with only three neighbours in a closed mesh would end up with two faces on top of each other
+        for opposite in &opposite_vertices {
+            if !self.is_boundary_vertex(*opposite) && self.vertex_neighbours(*opposite).count() <= 3 {
+                return false;
+            }
+        }
+
+        // If both other edges of a removed triangle are on the boundary, squashing them together would leave an edge with no faces at all
+        for side in [h, t] {
+            if !self.is_boundary_half_edge(side) {
+                let side1 = self.half_edges[side as usize].next;
+                let side2 = self.half_edges[side1 as usize].next;
+                if self.is_boundary_edge(side1) && self.is_boundary_edge(side2) {
+                    return false;
+                }
+            }
+        }
+
+        // The corner attributes of the kept vertex, taken from one of the removed faces, are used for the faces which used to have "removed" as corner
+        let kept_corner = if !self.is_boundary_half_edge(h) {
+            self.half_edges[h as usize]
+        } else {
+            let t1 = self.half_edges[t as usize].next;
+            self.half_edges[self.half_edges[t1 as usize].next as usize]
+        };
+
+        // The kept vertex needs an outgoing half-edge which survives the collapse. All half-edges leaving either vertex will leave the kept one afterwards.
+        let mut removed_half_edges: Vec<u32> = vec!(h, t);
+        for side in [h, t] {
+            if !self.is_boundary_half_edge(side) {
+                let side1 = self.half_edges[side as usize].next;
+                removed_half_edges.push(side1);
+                removed_half_edges.push(self.half_edges[side1 as usize].next);
+            }
+        }
+        let kept_half_edge = self.outgoing_half_edges(kept).chain(self.outgoing_half_edges(removed))
+            .find(|half_edge| !removed_half_edges.contains(half_edge))
+            .unwrap();
+
+        // Everything pointing at the removed vertex now points at the kept one
+        let incoming: Vec<u32> = self.outgoing_half_edges(removed).map(|outgoing| self.half_edges[outgoing as usize].twin).collect();
+        for incoming_half_edge in incoming {
+            if incoming_half_edge == t {
+                continue;
+            }
+
+            self.half_edges[incoming_half_edge as usize].vertex = kept;
+            if !self.is_boundary_half_edge(incoming_half_edge) {
+                self.half_edges[incoming_half_edge as usize].normal = kept_corner.normal;
+                self.half_edges[incoming_half_edge as usize].uv = kept_corner.uv;
+                self.half_edges[incoming_half_edge as usize].color = kept_corner.color;
+            }
+        }
+
+        for side in [h, t] {
+            if self.is_boundary_half_edge(side) {
+                // Skip over the boundary half-edge in its loop
+                let previous = self.previous(side);
+                self.half_edges[previous as usize].next = self.half_edges[side as usize].next;
+            } else {
+                self.remove_triangle(side);
+            }
+
+            self.half_edges[side as usize].next = INVALID;
+        }
+
+        self.vertices[removed as usize].half_edge = INVALID;
+        self.vertices[kept as usize].half_edge = kept_half_edge;
+
+        // Make the vertices around the collapse point at surviving half-edges, preferring boundary half-edges
+        let mut touched_vertices = opposite_vertices;
+        touched_vertices.push(kept);
+        for vertex in touched_vertices {
+            self.repair_vertex_half_edge(vertex);
+        }
+
+        true
+    }
+
+    /*
+        Removes the triangle of "side", which is one of the two half-edges of the collapsing edge.
+        The two remaining edges of the triangle get squashed into one, so their outer twins become each other's twins.
+    */
+    fn remove_triangle(&mut self, side: u32) {
+        let face = self.half_edges[side as usize].face;
+        let side1 = self.half_edges[side as usize].next;
+        let side2 = self.half_edges[side1 as usize].next;
+
+        let outer1 = self.half_edges[side1 as usize].twin;
+        let outer2 = self.half_edges[side2 as usize].twin;
+        self.half_edges[outer1 as usize].twin = outer2;
+        self.half_edges[outer2 as usize].twin = outer1;
+
+        // Vertices which pointed at the removed half-edges need to point at something that survives
+        let vertex1 = self.half_edges[side as usize].vertex;
+        let vertex2 = self.half_edges[side1 as usize].vertex;
+        if self.vertices[vertex1 as usize].half_edge == side1 {
+            self.vertices[vertex1 as usize].half_edge = outer2;
+        }
+        if self.vertices[vertex2 as usize].half_edge == side2 {
+            self.vertices[vertex2 as usize].half_edge = outer1;
+        }
+
+        self.half_edges[side1 as usize].next = INVALID;
+        self.half_edges[side2 as usize].next = INVALID;
+        self.faces[face as usize].half_edge = INVALID;
+    }
+
+    fn repair_vertex_half_edge(&mut self, vertex: u32) {
+        let current = self.vertices[vertex as usize].half_edge;
+        if current == INVALID || self.is_half_edge_removed(current) || self.origin_vertex(current) != vertex {
+            // Should not happen, as every removed half-edge is replaced above, but better to find out early
+            panic!("Vertex {} lost its half-edge during an edge collapse.", vertex);
+        }
+
+        let boundary = self.outgoing_half_edges(vertex).find(|half_edge| self.is_boundary_half_edge(*half_edge));
+        if let Some(boundary) = boundary {
+            self.vertices[vertex as usize].half_edge = boundary;
+        }
+    }
+}
+
+fn lerp_vector3(from: &beagle_math::Vector3, to: &beagle_math::Vector3, amount: f32) -> beagle_math::Vector3 {
+    beagle_math::Vector3::new(
+        from.x + (to.x - from.x) * amount,
+        from.y + (to.y - from.y) * amount,
+        from.z + (to.z - from.z) * amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math;
+    use crate::asset::mesh::*;
+    use crate::asset::mesh::half_edge::*;
+
+    fn create_grid(size: usize) -> Mesh {
+        let mut mesh = Mesh::default();
+
+        for z in 0..=size {
+            for x in 0..=size {
+                mesh.vertex_positions.push(beagle_math::Vector3::new(x as f32, 0.0, z as f32));
+                mesh.vertex_uvs.push(beagle_math::Vector2::new(x as f32 / size as f32, z as f32 / size as f32));
+            }
+        }
+
+        for z in 0..size {
+            for x in 0..size {
+                let top_left = (z * (size + 1) + x) as u32;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + (size + 1) as u32;
+                let bottom_right = bottom_left + 1;
+
+                mesh.indices.extend_from_slice(&[top_left, bottom_left, top_right]);
+                mesh.indices.extend_from_slice(&[top_right, bottom_left, bottom_right]);
+            }
+        }
+
+        mesh
+    }
+
+    // A tetrahedron, the smallest closed mesh
+    fn create_tetrahedron() -> Mesh {
+        Mesh {
+            vertex_positions: vec!(
+                beagle_math::Vector3::new(0.0, 0.0, 0.0),
+                beagle_math::Vector3::new(1.0, 0.0, 0.0),
+                beagle_math::Vector3::new(0.0, 1.0, 0.0),
+                beagle_math::Vector3::new(0.0, 0.0, 1.0)),
+            indices: vec!(0, 1, 2, 0, 3, 1, 0, 2, 3, 1, 3, 2),
+            ..Default::default()
+        }
+    }
+
+    // Checks that every link in the structure agrees with the others
+    fn assert_consistent(mesh: &HalfEdgeMesh) {
+        for half_edge in 0..mesh.half_edges.len() as u32 {
+            if mesh.is_half_edge_removed(half_edge) {
+                continue;
+            }
+
+            let twin = mesh.half_edges[half_edge as usize].twin;
+            assert_eq!(mesh.half_edges[twin as usize].twin, half_edge);
+            assert!(!mesh.is_half_edge_removed(twin));
+
+            let next = mesh.half_edges[half_edge as usize].next;
+            assert_eq!(mesh.origin_vertex(next), mesh.target_vertex(half_edge));
+            assert_eq!(mesh.half_edges[next as usize].face, mesh.half_edges[half_edge as usize].face);
+        }
+
+        for vertex in 0..mesh.vertices.len() as u32 {
+            if mesh.is_vertex_removed(vertex) {
+                continue;
+            }
+
+            assert_eq!(mesh.origin_vertex(mesh.vertices[vertex as usize].half_edge), vertex);
+        }
+
+        for face in 0..mesh.faces.len() as u32 {
+            if !mesh.is_face_removed(face) {
+                assert_eq!(mesh.face_half_edges(face).count(), 3);
+            }
+        }
+    }
+
+    #[test]
+    fn should_find_neighbours_and_boundary_of_grid() {
+        let mesh = HalfEdgeMesh::from_mesh(&create_grid(2)).unwrap();
+
+        assert_consistent(&mesh);
+        assert_eq!(mesh.vertex_count(), 9);
+        assert_eq!(mesh.face_count(), 8);
+
+        // The center vertex is connected to 6 vertices, the corner at the start only to 2
+        let mut center_neighbours: Vec<u32> = mesh.vertex_neighbours(4).collect();
+        center_neighbours.sort();
+        assert_eq!(center_neighbours, vec!(1, 2, 3, 5, 6, 7));
+        assert_eq!(mesh.vertex_neighbours(0).count(), 2);
+        assert_eq!(mesh.vertex_faces(4).count(), 6);
+
+        assert!(!mesh.is_boundary_vertex(4));
+        assert!(mesh.is_boundary_vertex(1));
+
+        let loops = mesh.boundary_loops();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].len(), 8);
+    }
+
+    #[test]
+    fn should_have_no_boundary_when_closed() {
+        let mesh = HalfEdgeMesh::from_mesh(&create_tetrahedron()).unwrap();
+
+        assert_consistent(&mesh);
+        assert!(mesh.boundary_loops().is_empty());
+        assert_eq!(mesh.half_edges.len(), 12);
+    }
+
+    #[test]
+    fn should_round_trip_through_mesh() {
+        let grid = create_grid(3);
+        let mesh = HalfEdgeMesh::from_mesh(&grid).unwrap().to_mesh();
+
+        assert_eq!(mesh.vertex_positions.len(), grid.vertex_positions.len());
+        assert_eq!(mesh.vertex_uvs.len(), grid.vertex_uvs.len());
+        assert_eq!(mesh.indices.len(), grid.indices.len());
+    }
+
+    #[test]
+    fn should_fail_on_non_manifold_edge() {
+        let mut mesh = create_grid(1);
+        mesh.vertex_positions.push(beagle_math::Vector3::new(0.0, 1.0, 0.0));
+        mesh.vertex_uvs.push(beagle_math::Vector2::new(0.0, 0.0));
+        mesh.indices.extend_from_slice(&[4, 2, 1]);
+
+        assert!(HalfEdgeMesh::from_mesh(&mesh).is_err());
+    }
+
+    #[test]
+    fn should_flip_edge() {
+        let mut mesh = HalfEdgeMesh::from_mesh(&create_grid(1)).unwrap();
+        let diagonal = mesh.find_half_edge(2, 1).unwrap();
+
+        assert!(mesh.flip_edge(diagonal));
+
+        assert_consistent(&mesh);
+        assert!(mesh.find_half_edge(2, 1).is_none());
+        assert!(mesh.find_half_edge(0, 3).is_some() || mesh.find_half_edge(3, 0).is_some());
+        assert_eq!(mesh.face_count(), 2);
+
+        // Boundary edges can't be flipped
+        let border = mesh.find_half_edge(0, 2).unwrap();
+        assert!(!mesh.flip_edge(border));
+    }
+
+    #[test]
+    fn should_split_interior_and_boundary_edges() {
+        let mut mesh = HalfEdgeMesh::from_mesh(&create_grid(1)).unwrap();
+
+        let diagonal = mesh.find_half_edge(2, 1).unwrap();
+        let middle = mesh.split_edge(diagonal);
+        assert_consistent(&mesh);
+        assert_eq!(mesh.face_count(), 4);
+        assert_eq!(mesh.vertex_neighbours(middle).count(), 4);
+        assert!(!mesh.is_boundary_vertex(middle));
+
+        let border = mesh.find_half_edge(0, 2).unwrap();
+        let border_middle = mesh.split_edge(border);
+        assert_consistent(&mesh);
+        assert_eq!(mesh.face_count(), 5);
+        assert!(mesh.is_boundary_vertex(border_middle));
+        assert_eq!(mesh.boundary_loops()[0].len(), 5);
+
+        let uv = mesh.to_mesh().vertex_uvs;
+        assert!(uv.iter().any(|uv| uv.x == 0.5 && uv.y == 0.5));
+    }
+
+    #[test]
+    fn should_collapse_edge_and_respect_link_condition() {
+        let mut mesh = HalfEdgeMesh::from_mesh(&create_grid(2)).unwrap();
+
+        // Collapse the center vertex into its right neighbour
+        let half_edge = mesh.find_half_edge(4, 5).unwrap();
+        assert!(mesh.collapse_edge(half_edge));
+
+        assert_consistent(&mesh);
+        assert!(mesh.is_vertex_removed(4));
+        assert_eq!(mesh.face_count(), 6);
+        assert_eq!(mesh.boundary_loops()[0].len(), 8);
+
+        // Collapsing an edge of the tetrahedron would leave two triangles on top of each other
+        let mut tetrahedron = HalfEdgeMesh::from_mesh(&create_tetrahedron()).unwrap();
+        let half_edge = tetrahedron.find_half_edge(0, 1).unwrap();
+        assert!(!tetrahedron.collapse_edge(half_edge));
+    }
+
+    #[test]
+    fn should_stay_consistent_when_collapsing_repeatedly() {
+        let mut mesh = HalfEdgeMesh::from_mesh(&create_grid(6)).unwrap();
+
+        let mut collapsed = true;
+        while collapsed {
+            collapsed = false;
+            for half_edge in 0..mesh.half_edges.len() as u32 {
+                if !mesh.is_half_edge_removed(half_edge) && mesh.collapse_edge(half_edge) {
+                    assert_consistent(&mesh);
+                    collapsed = true;
+                }
+            }
+        }
+
+        // The grid is flat, so whatever is left must still be a valid mesh with a single border
+        assert_eq!(mesh.boundary_loops().len(), 1);
+        assert!(crate::asset::mesh::validation::validate(&mesh.to_mesh()).is_valid());
+    }
+
+    #[test]
+    fn should_stay_consistent_when_splitting_and_flipping_repeatedly() {
+        let mut mesh = HalfEdgeMesh::from_mesh(&create_grid(3)).unwrap();
+
+        let original_half_edge_count = mesh.half_edges.len() as u32;
+        for half_edge in 0..original_half_edge_count {
+            if half_edge % 3 == 0 {
+                mesh.split_edge(half_edge);
+                assert_consistent(&mesh);
+            }
+        }
+
+        for half_edge in 0..mesh.half_edges.len() as u32 {
+            mesh.flip_edge(half_edge);
+            assert_consistent(&mesh);
+        }
+
+        assert_eq!(mesh.boundary_loops().len(), 1);
+        assert!(crate::asset::mesh::validation::validate(&mesh.to_mesh()).is_valid());
+    }
+}
@@ -1,6 +1,16 @@
+pub mod half_edge;
+pub mod optimize;
+pub mod primitives;
+pub mod simplify;
+pub mod subdivision;
+pub mod validation;
+
+use std::collections::HashMap;
+
 use crate::beagle_math;
 use crate::gltf2;
 use crate::shared;
+use crate::shared::FromBinary;
 
 #[derive(Default)]
 pub struct Model {
@@ -11,15 +21,29 @@ pub struct Model {
 pub struct Mesh {
     pub name: String,
     pub children: Vec<u16>,
-    pub translation: beagle_math::Vector3,
-    pub scale: beagle_math::Vector3,
-    pub rotation: beagle_math::Quaternion,
+    // Relative to the parent mesh, if any
+    pub transform: beagle_math::Transform,
     pub vertex_positions: Vec<beagle_math::Vector3>,
-    pub indices: Vec<u16>,
+    // Normals, uvs and colors are optional, as not every file supplies them.
+    // When present, they have exactly one element per vertex position.
+    pub vertex_normals: Vec<beagle_math::Vector3>,
+    pub vertex_uvs: Vec<beagle_math::Vector2>,
+    pub vertex_colors: Vec<beagle_math::Vector4>,
+    pub indices: Vec<u32>,
+    // Simplified versions of the mesh, from most to least detailed, all using the vertices above.
+    // The full detail mesh described by "indices" is not included.
+    pub lods: Vec<Lod>,
     pub material: Material
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
+pub struct Lod {
+    pub indices: Vec<u32>,
+    // The largest distance, in the mesh's own units, that the surface of this LOD is expected to deviate from the full detail mesh.
+    pub error: f32
+}
+
+#[derive(Default, Clone)]
 pub struct Material {
     pub diffuse_color: beagle_math::Vector3,
     pub ambient_color: beagle_math::Vector3,
@@ -27,20 +51,34 @@ pub struct Material {
     pub shininess_factor: f32
 }
 
+/*
+    A glTF node has either a matrix, or any of translation, rotation and scale.
+    Whatever is missing from the file is left at its default, which is all zeroes.
+*/
+fn node_transform(node: &gltf2::Node) -> beagle_math::Transform {
+    if let Some(matrix) = &node.matrix {
+        // glTF matrices are column major for column vectors, which is the same layout as a row major Mat4 for row vectors
+        return beagle_math::Transform::from_matrix(&beagle_math::Mat4::new(*matrix)).unwrap_or_default();
+    }
+
+    let mut transform = beagle_math::Transform::from_translation(beagle_math::Vector3::from_array(&node.translation));
+
+    if node.scale != [0.0; 3] {
+        transform.scale = beagle_math::Vector3::from_array(&node.scale);
+    }
+
+    if node.rotation != [0.0; 4] {
+        transform.rotation = beagle_math::Quaternion::from_array(&node.rotation);
+    }
+
+    transform
+}
+
 pub fn parse_model(gltf_file: &gltf2::File) -> Model {
     let mut meshes : Vec<Mesh> = vec!();
 
     for node in &gltf_file.nodes {
-        let translation = beagle_math::Vector3::from_array(&node.translation);
-
-        let mut scale = beagle_math::Vector3::from_array(&node.scale);
-        // absense of scale in file means it has a unit scale (1, 1, 1)
-        // TODO: Make easy way to compare vectors, so I could do... Vector3::zero() == node.scale
-        if scale.x == 0.0 && scale.y == 0.0 && scale.z == 0.0 {
-            scale = beagle_math::Vector3::new(1.0, 1.0, 1.0);
-        }
-
-        let rotation = beagle_math::Quaternion::from_array(&node.rotation);
+        let transform = node_transform(node);
         let child_meshes : Vec<u16> = node.children.iter().map(|x| *x as u16).collect();
 
         let root_mesh_index = node.mesh as usize;
@@ -70,12 +108,22 @@ pub fn parse_model(gltf_file: &gltf2::File) -> Model {
 
         let mut new_mesh = Mesh::default();
         new_mesh.name = mesh_name;
-        new_mesh.translation = translation;
-        new_mesh.scale = scale;
-        new_mesh.rotation = rotation;
+        new_mesh.transform = transform;
         new_mesh.children = child_meshes;
         new_mesh.vertex_positions = get_buffer_data_for_acessor::<beagle_math::Vector3>(gltf_file, mesh_primitive.attributes.position as usize);
-        new_mesh.indices = get_buffer_data_for_acessor::<u16>(gltf_file, mesh_primitive.indices as usize);
+        new_mesh.indices = get_index_data_for_accessor(gltf_file, mesh_primitive.indices as usize);
+
+        if mesh_primitive.attributes.normal != -1 {
+            new_mesh.vertex_normals = get_buffer_data_for_acessor::<beagle_math::Vector3>(gltf_file, mesh_primitive.attributes.normal as usize);
+        }
+
+        if mesh_primitive.attributes.texcoord_0 != -1 {
+            new_mesh.vertex_uvs = get_buffer_data_for_acessor::<beagle_math::Vector2>(gltf_file, mesh_primitive.attributes.texcoord_0 as usize);
+        }
+
+        if mesh_primitive.attributes.color_0 != -1 {
+            new_mesh.vertex_colors = get_color_data_for_accessor(gltf_file, mesh_primitive.attributes.color_0 as usize);
+        }
 
         // TODO: If I wanted to make the Mesh structure even more agnostic about later use, I'd probably
         // make these extra custom properties more generic. This is very shader specific.
@@ -90,8 +138,72 @@ pub fn parse_model(gltf_file: &gltf2::File) -> Model {
     Model { meshes }
 }
 
+// GLTF allows indices to be stored as unsigned bytes, shorts or ints.
+// Regardless of how they are stored in the file, I widen them to u32 so the rest of the engine only has to deal with one index type.
+fn get_index_data_for_accessor(gltf_file: &gltf2::File, accessor_index: usize) -> Vec<u32> {
+    let component_type = gltf_file.accessors[accessor_index].component_type;
+
+    match component_type {
+        // 5121 = Unsigned Byte
+        5121 => get_buffer_data_for_acessor::<u8>(gltf_file, accessor_index).iter().map(|x| *x as u32).collect(),
+        // 5123 = Unsigned Short
+        5123 => get_buffer_data_for_acessor::<u16>(gltf_file, accessor_index).iter().map(|x| *x as u32).collect(),
+        // 5125 = Unsigned Int
+        5125 => get_buffer_data_for_acessor::<u32>(gltf_file, accessor_index),
+        _ => panic!("Unsupported component type for indices: {}", component_type)
+    }
+}
+
+/*
+    GLTF colors are either RGB or RGBA, stored as floats or as unsigned bytes/shorts normalized to [0, 1].
+    They all end up as RGBA floats, with an alpha of 1 when the file has none.
+
+    Byte and short colors are often padded so every element starts on a 4 byte boundary (an RGB u8 color takes 4 bytes),
+    so the accessor's offset and the buffer view's stride are followed here, rather than reading the view as one packed array.
+*/
+fn get_color_data_for_accessor(gltf_file: &gltf2::File, accessor_index: usize) -> Vec<beagle_math::Vector4> {
+    let accessor = &gltf_file.accessors[accessor_index];
+    let buffer_view = &gltf_file.buffer_views[accessor.buffer_view as usize];
+
+    let component_count = match accessor.element_type.as_str() {
+        "VEC3" => 3,
+        "VEC4" => 4,
+        element_type => panic!("Unsupported element type for colors: {}", element_type)
+    };
+
+    let (component_size, read_component): (usize, fn(&[u8]) -> f32) = match accessor.component_type {
+        // 5121 = Unsigned Byte
+        5121 => (1, |binary| u8::from_binary(binary) as f32 / u8::MAX as f32),
+        // 5123 = Unsigned Short
+        5123 => (2, |binary| u16::from_binary(binary) as f32 / u16::MAX as f32),
+        // 5126 = Float
+        5126 => (4, f32::from_binary),
+        component_type => panic!("Unsupported component type for colors: {}", component_type)
+    };
+
+    let element_size = component_count * component_size;
+    let stride = if buffer_view.byte_stride == 0 { element_size } else { buffer_view.byte_stride as usize };
+
+    let binary_data = get_buffer_view_data(gltf_file, accessor.buffer_view as usize);
+
+    (0..accessor.count as usize)
+        .map(|element| {
+            let start_index = accessor.byte_offset as usize + element * stride;
+            let color: Vec<f32> = binary_data[start_index..start_index + element_size].chunks_exact(component_size).map(read_component).collect();
+
+            beagle_math::Vector4::new(color[0], color[1], color[2], if component_count == 4 { color[3] } else { 1.0 })
+        })
+        .collect()
+}
+
 fn get_buffer_data_for_acessor<T: shared::FromBinary + Sized>(gltf_file: &gltf2::File, accessor_index: usize) -> Vec<T> {
     let buffer_view_index = gltf_file.accessors[accessor_index].buffer_view as usize;
+
+    T::from_binary_collection(&get_buffer_view_data(gltf_file, buffer_view_index))
+}
+
+// The bytes of a buffer view, decoded from the base64 data URI of its buffer
+fn get_buffer_view_data(gltf_file: &gltf2::File, buffer_view_index: usize) -> Vec<u8> {
     let buffer_view = &gltf_file.buffer_views[buffer_view_index];
     
     let buffer = &gltf_file.buffers[buffer_view.buffer as usize];
@@ -107,5 +219,106 @@ fn get_buffer_data_for_acessor<T: shared::FromBinary + Sized>(gltf_file: &gltf2:
     let start_index = buffer_view.byte_offset as usize;
     let end_index = (buffer_view.byte_offset+buffer_view.byte_length) as usize;
 
-    T::from_binary_collection(&binary_data[start_index..end_index])
-}
\ No newline at end of file
+    binary_data[start_index..end_index].to_vec()
+}
+
+/*
+    A Vertex is the full set of attributes the GPU sees for a single vertex.
+    Two triangle corners can only share a vertex in an index buffer if ALL of these attributes are identical.
+*/
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Vertex {
+    pub position: beagle_math::Vector3,
+    pub normal: beagle_math::Vector3,
+    pub uv: beagle_math::Vector2,
+    pub color: beagle_math::Vector4
+}
+
+impl Vertex {
+    // The bit patterns of the attributes are used as a key when welding.
+    // Adding 0.0 turns -0.0 into 0.0, so the two don't end up as different vertices.
+    fn weld_key(&self) -> [u32; 12] {
+        [
+            (self.position.x + 0.0).to_bits(), (self.position.y + 0.0).to_bits(), (self.position.z + 0.0).to_bits(),
+            (self.normal.x + 0.0).to_bits(), (self.normal.y + 0.0).to_bits(), (self.normal.z + 0.0).to_bits(),
+            (self.uv.x + 0.0).to_bits(), (self.uv.y + 0.0).to_bits(),
+            (self.color.x + 0.0).to_bits(), (self.color.y + 0.0).to_bits(), (self.color.z + 0.0).to_bits(), (self.color.w + 0.0).to_bits()
+        ]
+    }
+}
+
+/*
+    Vertex welding takes a list of vertices, three per triangle, and merges every vertex that has exactly the same
+    position, normal, uv and color as a vertex seen before it.
+
+    The result is a deduplicated vertex list plus an index list which refers into it, three indices per triangle.
+    The order of the vertices is the order in which they are first seen, which keeps the result deterministic.
+*/
+pub fn weld_vertices(vertices: &[Vertex]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut unique_vertices: Vec<Vertex> = vec!();
+    let mut indices: Vec<u32> = Vec::with_capacity(vertices.len());
+    let mut seen_vertices: HashMap<[u32; 12], u32> = HashMap::new();
+
+    for vertex in vertices {
+        let index = *seen_vertices.entry(vertex.weld_key()).or_insert_with(|| {
+            unique_vertices.push(*vertex);
+            (unique_vertices.len() - 1) as u32
+        });
+
+        indices.push(index);
+    }
+
+    (unique_vertices, indices)
+}
+#[cfg(test)]
+mod tests {
+    use crate::asset::mesh::*;
+
+    #[test]
+    fn should_follow_stride_and_offset_when_reading_padded_colors() {
+        // Two RGB u8 colors padded to 4 bytes each, after 8 bytes of something else in the same buffer view
+        let binary_data: Vec<u8> = vec!(9, 9, 9, 9, 9, 9, 9, 9, 255, 0, 51, 0, 0, 255, 102, 0);
+        let gltf_json = format!(r#"{{
+            "accessors": [{{ "bufferView": 0, "byteOffset": 8, "componentType": 5121, "count": 2, "normalized": true, "type": "VEC3" }}],
+            "bufferViews": [{{ "buffer": 0, "byteLength": 16, "byteStride": 4 }}],
+            "buffers": [{{ "byteLength": 16, "uri": "data:application/octet-stream;base64,{}" }}]
+        }}"#, base64::encode(&binary_data));
+        let gltf_file: gltf2::File = serde_json::from_str(&gltf_json).unwrap();
+
+        let colors = get_color_data_for_accessor(&gltf_file, 0);
+
+        assert_eq!(colors, vec!(
+            beagle_math::Vector4::new(1.0, 0.0, 0.2, 1.0),
+            beagle_math::Vector4::new(0.0, 1.0, 0.4, 1.0)));
+    }
+
+    fn vertex_at(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex { position: beagle_math::Vector3::new(x, y, z), ..Default::default() }
+    }
+
+    #[test]
+    fn should_weld_identical_vertices_and_treat_negative_zero_as_zero() {
+        let vertices = vec!(
+            vertex_at(0.0, 0.0, 0.0), vertex_at(1.0, 0.0, 0.0), vertex_at(0.0, 1.0, 0.0),
+            vertex_at(-0.0, 0.0, -0.0), vertex_at(0.0, 1.0, 0.0), vertex_at(1.0, 1.0, 0.0));
+
+        let (unique_vertices, indices) = weld_vertices(&vertices);
+
+        assert_eq!(unique_vertices.len(), 4);
+        assert_eq!(indices, vec!(0, 1, 2, 0, 2, 3));
+    }
+
+    #[test]
+    fn should_not_weld_vertices_that_differ_in_any_attribute() {
+        // Welding is exact: even the next representable float apart is a different vertex
+        let nearly_one = f32::from_bits(1.0f32.to_bits() + 1);
+        let mut different_uv = vertex_at(0.0, 0.0, 0.0);
+        different_uv.uv = beagle_math::Vector2::new(0.5, 0.5);
+        let vertices = vec!(vertex_at(1.0, 0.0, 0.0), vertex_at(nearly_one, 0.0, 0.0), vertex_at(0.0, 0.0, 0.0), different_uv);
+
+        let (unique_vertices, indices) = weld_vertices(&vertices);
+
+        assert_eq!(unique_vertices.len(), 4);
+        assert_eq!(indices, vec!(0, 1, 2, 3));
+    }
+}
@@ -0,0 +1,622 @@
+use crate::beagle_math;
+use crate::asset::mesh::Mesh;
+
+/*
+    This module contains a CPU optimization pass for indexed triangle meshes.
+
+    The GPU keeps a small cache of recently transformed vertices (the "post-transform vertex cache").
+    If a triangle refers to a vertex that is still in that cache, the vertex shader doesn't have to run for it again.
+    So the ORDER of the triangles in the index buffer has a large influence on how many times the vertex shader runs.
+
+    The pass is split into three steps, which should be run in this order:
+
+    1. Vertex cache optimization: Reorders triangles so that triangles sharing vertices are drawn close to each other.
+    2. Overdraw optimization: Splits the cache optimized triangle order into clusters, and reorders the clusters
+       so that clusters which are likely to occlude other clusters are drawn first.
+    3. Vertex fetch optimization: Reorders the vertex buffer itself so vertices are laid out in the order they are first used,
+       which improves memory locality when the GPU fetches vertex data.
+
+    All of the steps are deterministic. Given the same input, they always give the same output.
+*/
+
+// The size of the cache simulated by the vertex cache optimization.
+// Tom Forsyth's article recommends 32, as the scoring works well for most real hardware cache sizes below that.
+const OPTIMIZER_CACHE_SIZE: usize = 32;
+
+// The size of the FIFO cache used when analyzing and when clustering for overdraw.
+// 16 is a conservative guess at the size of a typical hardware FIFO cache.
+pub const DEFAULT_ANALYZE_CACHE_SIZE: usize = 16;
+
+// The overdraw optimization is allowed to make the ACMR this much worse (5%) in exchange for less overdraw.
+pub const DEFAULT_OVERDRAW_THRESHOLD: f32 = 1.05;
+
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexCacheStatistics {
+    // The number of times a vertex had to be transformed, meaning the number of cache misses.
+    pub vertices_transformed: u32,
+
+    // ACMR = Average Cache Miss Ratio. Transformed vertices per triangle.
+    // The best possible value is around 0.5 for large, regular meshes, and the worst is 3.0.
+    pub acmr: f32,
+
+    // ATVR = Average Transformed Vertex Ratio. Transformed vertices per vertex in the vertex buffer.
+    // The best possible value is 1.0, meaning every vertex is only ever transformed once.
+    pub atvr: f32
+}
+
+/*
+    Simulates a FIFO vertex cache of the given size, and reports how many vertices had to be transformed
+    when drawing the triangles in the order given by the indices.
+*/
+pub fn analyze_vertex_cache(indices: &[u32], vertex_count: usize, cache_size: usize) -> VertexCacheStatistics {
+    let mut cache = FifoCache::new(cache_size);
+    let mut vertices_transformed: u32 = 0;
+
+    for index in indices {
+        if cache.insert(*index) {
+            vertices_transformed += 1;
+        }
+    }
+
+    let triangle_count = indices.len() / 3;
+
+    VertexCacheStatistics {
+        vertices_transformed,
+        acmr: if triangle_count == 0 { 0.0 } else { vertices_transformed as f32 / triangle_count as f32 },
+        atvr: if vertex_count == 0 { 0.0 } else { vertices_transformed as f32 / vertex_count as f32 }
+    }
+}
+
+/*
+    Reorders triangles to make good use of the post-transform vertex cache, using Tom Forsyth's
+    "Linear-Speed Vertex Cache Optimisation" (https://tomforsyth1000.github.io/papers/fast_vert_cache_opt.html).
+
+    Each vertex gets a score based on its position in a simulated LRU cache and on how many triangles still need it.
+    A triangle's score is the sum of its vertices' scores, and the best scoring triangle is always drawn next.
+    Triangles keep their original winding, only their order changes.
+*/
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    if !indices.len().is_multiple_of(3) {
+        panic!("The list of indices is not divisible by 3, which is required as this method assumes a primitive topology of triangles.")
+    }
+
+    let triangle_count = indices.len() / 3;
+    let adjacency = TriangleAdjacency::new(indices, vertex_count);
+
+    let mut remaining_valence: Vec<u32> = (0..vertex_count).map(|vertex| adjacency.triangles_of(vertex).len() as u32).collect();
+    let mut cache_position: Vec<i32> = vec![-1; vertex_count];
+    let mut vertex_scores: Vec<f32> = (0..vertex_count).map(|vertex| vertex_score(-1, remaining_valence[vertex])).collect();
+
+    let mut triangle_emitted: Vec<bool> = vec![false; triangle_count];
+
+    let mut result: Vec<u32> = Vec::with_capacity(indices.len());
+    let mut cache: Vec<u32> = vec!();
+
+    // When the cache offers no more candidates, we continue from the first triangle that has not been emitted yet.
+    let mut next_unemitted_triangle: usize = 0;
+    let mut best_triangle: Option<usize> = None;
+
+    for _ in 0..triangle_count {
+        let triangle = match best_triangle {
+            Some(triangle) => triangle,
+            None => {
+                while triangle_emitted[next_unemitted_triangle] {
+                    next_unemitted_triangle += 1;
+                }
+                next_unemitted_triangle
+            }
+        };
+
+        let triangle_vertices = &indices[triangle * 3..triangle * 3 + 3];
+        result.extend_from_slice(triangle_vertices);
+        triangle_emitted[triangle] = true;
+
+        for vertex in triangle_vertices {
+            remaining_valence[*vertex as usize] -= 1;
+        }
+
+        // Move the vertices of the emitted triangle to the front of the LRU cache.
+        let mut new_cache: Vec<u32> = triangle_vertices.to_vec();
+        new_cache.extend(cache.iter().filter(|vertex| !triangle_vertices.contains(vertex)));
+
+        // Vertices pushed out of the cache lose their cache position, and need their score updated as well.
+        for evicted_vertex in new_cache.iter().skip(OPTIMIZER_CACHE_SIZE) {
+            cache_position[*evicted_vertex as usize] = -1;
+            vertex_scores[*evicted_vertex as usize] = vertex_score(-1, remaining_valence[*evicted_vertex as usize]);
+        }
+
+        for (position, vertex) in new_cache.iter().enumerate().take(OPTIMIZER_CACHE_SIZE) {
+            cache_position[*vertex as usize] = position as i32;
+            vertex_scores[*vertex as usize] = vertex_score(position as i32, remaining_valence[*vertex as usize]);
+        }
+
+        // Only triangles touching a vertex whose score changed can have changed score themselves.
+        best_triangle = None;
+        let mut best_score = f32::MIN;
+
+        for vertex in new_cache.iter() {
+            for adjacent_triangle in adjacency.triangles_of(*vertex as usize) {
+                let adjacent_triangle = *adjacent_triangle as usize;
+                if triangle_emitted[adjacent_triangle] {
+                    continue;
+                }
+
+                let score: f32 = indices[adjacent_triangle * 3..adjacent_triangle * 3 + 3]
+                    .iter()
+                    .map(|vertex| vertex_scores[*vertex as usize])
+                    .sum();
+
+                if score > best_score {
+                    best_score = score;
+                    best_triangle = Some(adjacent_triangle);
+                }
+            }
+        }
+
+        new_cache.truncate(OPTIMIZER_CACHE_SIZE);
+        cache = new_cache;
+    }
+
+    result
+}
+
+/*
+    Reorders the triangles to reduce overdraw, while keeping most of the vertex cache efficiency of the input.
+    The indices are expected to already have been run through "optimize_vertex_cache".
+
+    This is based on the approach of Sander, Nehab and Barczak's "Fast Triangle Reordering for Vertex Locality and Reduced Overdraw":
+
+    1. The triangles are split into clusters at the points where the cache simulation shows the cache starting over anyway,
+       since reordering at those points costs nothing.
+    2. Clusters are split further wherever the ACMR of the cluster so far is within "threshold" of the ACMR of the whole mesh.
+    3. The clusters are sorted so that clusters facing away from the center of the mesh are drawn first.
+       Those are the clusters most likely to be in front of other parts of the mesh, so drawing them first lets the depth test reject more pixels.
+*/
+pub fn optimize_overdraw(indices: &[u32], vertex_positions: &[beagle_math::Vector3], threshold: f32) -> Vec<u32> {
+    if !indices.len().is_multiple_of(3) {
+        panic!("The list of indices is not divisible by 3, which is required as this method assumes a primitive topology of triangles.")
+    }
+
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return vec!();
+    }
+
+    let mesh_acmr = analyze_vertex_cache(indices, vertex_positions.len(), DEFAULT_ANALYZE_CACHE_SIZE).acmr;
+    let hard_boundaries = find_hard_cluster_boundaries(indices);
+    let cluster_starts = find_soft_cluster_boundaries(indices, &hard_boundaries, mesh_acmr * threshold);
+
+    let mesh_centroid = calculate_centroid(indices, vertex_positions);
+
+    let mut clusters: Vec<(usize, usize, f32)> = vec!();
+    for (cluster_index, cluster_start) in cluster_starts.iter().enumerate() {
+        let cluster_end = cluster_starts.get(cluster_index + 1).copied().unwrap_or(triangle_count);
+        let cluster_indices = &indices[cluster_start * 3..cluster_end * 3];
+
+        let cluster_centroid = calculate_centroid(cluster_indices, vertex_positions);
+        let cluster_normal = calculate_area_weighted_normal(cluster_indices, vertex_positions);
+
+        let centroid_offset = cluster_centroid - mesh_centroid;
+
+        clusters.push((*cluster_start, cluster_end, centroid_offset.dot(&cluster_normal)));
+    }
+
+    // A stable sort keeps clusters with equal sort keys in their original order, which keeps the result deterministic.
+    clusters.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut result: Vec<u32> = Vec::with_capacity(indices.len());
+    for (cluster_start, cluster_end, _) in clusters {
+        result.extend_from_slice(&indices[cluster_start * 3..cluster_end * 3]);
+    }
+
+    result
+}
+
+/*
+    Reorders the vertex buffer so vertices appear in the order they are first referenced by the indices.
+
+    Returns the rewritten indices, and a remap table where remap[old_vertex_index] is the new vertex index.
+    Vertices that are never referenced get the value u32::MAX in the remap table, and are dropped by "remap_vertex_buffer".
+*/
+pub fn optimize_vertex_fetch(indices: &[u32], vertex_count: usize) -> (Vec<u32>, Vec<u32>) {
+    let mut remap: Vec<u32> = vec![u32::MAX; vertex_count];
+    let mut next_vertex: u32 = 0;
+
+    let new_indices = indices.iter().map(|index| {
+        if remap[*index as usize] == u32::MAX {
+            remap[*index as usize] = next_vertex;
+            next_vertex += 1;
+        }
+
+        remap[*index as usize]
+    }).collect();
+
+    (new_indices, remap)
+}
+
+// Applies a remap table produced by "optimize_vertex_fetch" to a vertex attribute buffer.
+pub fn remap_vertex_buffer<T: Copy + Default>(vertices: &[T], remap: &[u32]) -> Vec<T> {
+    let unique_vertex_count = remap.iter().filter(|x| **x != u32::MAX).count();
+    let mut result: Vec<T> = vec![T::default(); unique_vertex_count];
+
+    for (old_index, new_index) in remap.iter().enumerate() {
+        if *new_index != u32::MAX {
+            result[*new_index as usize] = vertices[old_index];
+        }
+    }
+
+    result
+}
+
+/*
+    Runs the full optimization pass on a mesh, in the order described at the top of this module.
+    The LODs share the vertex buffers, so the vertex fetch step goes over their indices too (after the full detail ones),
+    and they're rewritten to the new vertex order.
+*/
+pub fn optimize_mesh(mesh: &mut Mesh) {
+    let vertex_count = mesh.vertex_positions.len();
+
+    let cache_optimized = optimize_vertex_cache(&mesh.indices, vertex_count);
+    let mut all_indices = optimize_overdraw(&cache_optimized, &mesh.vertex_positions, DEFAULT_OVERDRAW_THRESHOLD);
+    for lod in &mesh.lods {
+        all_indices.extend_from_slice(&lod.indices);
+    }
+
+    let (mut fetch_optimized, remap) = optimize_vertex_fetch(&all_indices, vertex_count);
+
+    // Split back up, last LOD first
+    for lod in mesh.lods.iter_mut().rev() {
+        lod.indices = fetch_optimized.split_off(fetch_optimized.len() - lod.indices.len());
+    }
+
+    mesh.indices = fetch_optimized;
+    mesh.vertex_positions = remap_vertex_buffer(&mesh.vertex_positions, &remap);
+
+    if !mesh.vertex_normals.is_empty() {
+        mesh.vertex_normals = remap_vertex_buffer(&mesh.vertex_normals, &remap);
+    }
+
+    if !mesh.vertex_uvs.is_empty() {
+        mesh.vertex_uvs = remap_vertex_buffer(&mesh.vertex_uvs, &remap);
+    }
+
+    if !mesh.vertex_colors.is_empty() {
+        mesh.vertex_colors = remap_vertex_buffer(&mesh.vertex_colors, &remap);
+    }
+}
+
+/*
+    The score of a vertex, as described in Tom Forsyth's article.
+    Vertices recently used score high, so triangles reusing them get drawn soon.
+    Vertices with few triangles left score high too, so they can get "finished off" and don't linger around.
+*/
+fn vertex_score(cache_position: i32, remaining_valence: u32) -> f32 {
+    // No triangles left that use this vertex, so it doesn't matter where it is
+    if remaining_valence == 0 {
+        return -1.0;
+    }
+
+    let mut score = 0.0;
+
+    if cache_position >= 0 {
+        if cache_position < 3 {
+            // The vertex was used by the last triangle. A fixed score is used for these,
+            // so that it doesn't matter which of the three positions it has.
+            score = LAST_TRIANGLE_SCORE;
+        } else {
+            let scaler = 1.0 / (OPTIMIZER_CACHE_SIZE - 3) as f32;
+            score = (1.0 - (cache_position - 3) as f32 * scaler).powf(CACHE_DECAY_POWER);
+        }
+    }
+
+    score + VALENCE_BOOST_SCALE * (remaining_valence as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+// Triangle indices where the FIFO cache simulation misses on all three vertices, meaning the cache effectively starts over.
+// The first triangle is always included.
+fn find_hard_cluster_boundaries(indices: &[u32]) -> Vec<usize> {
+    let mut cache = FifoCache::new(DEFAULT_ANALYZE_CACHE_SIZE);
+    let mut boundaries: Vec<usize> = vec!();
+
+    for (triangle, triangle_vertices) in indices.chunks(3).enumerate() {
+        let misses = triangle_vertices.iter().filter(|vertex| cache.insert(**vertex)).count();
+
+        // The first triangle always starts a cluster, even if it's degenerate and misses on less than three vertices,
+        // otherwise every triangle before the first full miss would belong to no cluster and get dropped.
+        if triangle == 0 || misses == 3 {
+            boundaries.push(triangle);
+        }
+    }
+
+    boundaries
+}
+
+fn find_soft_cluster_boundaries(indices: &[u32], hard_boundaries: &[usize], target_acmr: f32) -> Vec<usize> {
+    let triangle_count = indices.len() / 3;
+    let mut boundaries: Vec<usize> = vec!();
+
+    for (hard_cluster_index, hard_start) in hard_boundaries.iter().enumerate() {
+        let hard_end = hard_boundaries.get(hard_cluster_index + 1).copied().unwrap_or(triangle_count);
+
+        let mut cluster_start = *hard_start;
+        let mut cache = FifoCache::new(DEFAULT_ANALYZE_CACHE_SIZE);
+        let mut cluster_misses = 0;
+
+        boundaries.push(cluster_start);
+
+        for triangle in *hard_start..hard_end {
+            cluster_misses += indices[triangle * 3..triangle * 3 + 3].iter().filter(|vertex| cache.insert(**vertex)).count();
+
+            let cluster_triangle_count = triangle - cluster_start + 1;
+            let cluster_acmr = cluster_misses as f32 / cluster_triangle_count as f32;
+
+            // Each new cluster starts with a cold cache, so splitting is only done when the cluster so far
+            // is already as efficient as we're aiming for.
+            if cluster_acmr <= target_acmr && triangle + 1 < hard_end {
+                cluster_start = triangle + 1;
+                cluster_misses = 0;
+                cache = FifoCache::new(DEFAULT_ANALYZE_CACHE_SIZE);
+                boundaries.push(cluster_start);
+            }
+        }
+    }
+
+    boundaries
+}
+
+fn calculate_centroid(indices: &[u32], vertex_positions: &[beagle_math::Vector3]) -> beagle_math::Vector3 {
+    let mut sum = beagle_math::Vector3::zero();
+
+    for index in indices {
+        sum = sum.add(&vertex_positions[*index as usize]);
+    }
+
+    if indices.is_empty() { sum } else { sum.mul(1.0 / indices.len() as f32) }
+}
+
+// The cross product of two triangle edges has a length of twice the triangle's area,
+// so simply summing them gives a normal weighted by triangle area.
+fn calculate_area_weighted_normal(indices: &[u32], vertex_positions: &[beagle_math::Vector3]) -> beagle_math::Vector3 {
+    let mut sum = beagle_math::Vector3::zero();
+
+    for triangle in indices.chunks(3) {
+        let vert1 = vertex_positions[triangle[0] as usize];
+        let vert2 = vertex_positions[triangle[1] as usize];
+        let vert3 = vertex_positions[triangle[2] as usize];
+
+        let edge1 = vert2 - vert1;
+        let edge2 = vert3 - vert1;
+
+        sum = sum.add(&edge1.cross(&edge2));
+    }
+
+    let length = sum.length();
+    if length > 0.0 { sum.mul(1.0 / length) } else { sum }
+}
+
+/*
+    For every vertex, the list of triangles using it.
+    Stored as one flat list plus offsets into it, to avoid a separate allocation per vertex.
+*/
+struct TriangleAdjacency {
+    offsets: Vec<usize>,
+    triangles: Vec<u32>
+}
+
+impl TriangleAdjacency {
+    fn new(indices: &[u32], vertex_count: usize) -> TriangleAdjacency {
+        let mut counts: Vec<usize> = vec![0; vertex_count];
+        for index in indices {
+            counts[*index as usize] += 1;
+        }
+
+        let mut offsets: Vec<usize> = vec![0; vertex_count + 1];
+        for vertex in 0..vertex_count {
+            offsets[vertex + 1] = offsets[vertex] + counts[vertex];
+        }
+
+        let mut fill: Vec<usize> = offsets[..vertex_count].to_vec();
+        let mut triangles: Vec<u32> = vec![0; indices.len()];
+        for (position, index) in indices.iter().enumerate() {
+            triangles[fill[*index as usize]] = (position / 3) as u32;
+            fill[*index as usize] += 1;
+        }
+
+        TriangleAdjacency {
+            offsets,
+            triangles
+        }
+    }
+
+    fn triangles_of(&self, vertex: usize) -> &[u32] {
+        &self.triangles[self.offsets[vertex]..self.offsets[vertex + 1]]
+    }
+}
+
+// A simple FIFO cache simulation. Inserting a vertex that isn't in the cache counts as a miss.
+struct FifoCache {
+    entries: Vec<u32>,
+    size: usize,
+    next_slot: usize
+}
+
+impl FifoCache {
+    fn new(size: usize) -> FifoCache {
+        FifoCache {
+            entries: Vec::with_capacity(size),
+            size,
+            next_slot: 0
+        }
+    }
+
+    // Returns true when the vertex was a cache miss.
+    fn insert(&mut self, vertex: u32) -> bool {
+        if self.entries.contains(&vertex) {
+            return false;
+        }
+
+        if self.entries.len() < self.size {
+            self.entries.push(vertex);
+        } else {
+            self.entries[self.next_slot] = vertex;
+            self.next_slot = (self.next_slot + 1) % self.size;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::asset::mesh::*;
+    use crate::asset::mesh::optimize::*;
+    use crate::asset::mesh::simplify;
+    use crate::beagle_math;
+    use crate::gltf2;
+
+    fn load_resource_model(relative_path: &str) -> Model {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources");
+        for component in relative_path.split('/') {
+            path.push(component);
+        }
+
+        parse_model(&gltf2::File::from(path).unwrap())
+    }
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices.chunks(3).map(|x| [x[0], x[1], x[2]]).collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn should_keep_same_triangles_when_optimizing_vertex_cache() {
+        for model in [load_resource_model("mill/mill.gltf"), load_resource_model("terrain/terrain.gltf")] {
+            for mesh in &model.meshes {
+                let optimized = optimize_vertex_cache(&mesh.indices, mesh.vertex_positions.len());
+
+                assert_eq!(sorted_triangles(&optimized), sorted_triangles(&mesh.indices));
+            }
+        }
+    }
+
+    #[test]
+    fn should_not_increase_acmr_when_optimizing_vertex_cache() {
+        for model in [load_resource_model("mill/mill.gltf"), load_resource_model("terrain/terrain.gltf")] {
+            for mesh in &model.meshes {
+                let vertex_count = mesh.vertex_positions.len();
+                let before = analyze_vertex_cache(&mesh.indices, vertex_count, DEFAULT_ANALYZE_CACHE_SIZE);
+                let after = analyze_vertex_cache(&optimize_vertex_cache(&mesh.indices, vertex_count), vertex_count, DEFAULT_ANALYZE_CACHE_SIZE);
+
+                assert!(after.acmr <= before.acmr, "ACMR of {} went from {} to {}", mesh.name, before.acmr, after.acmr);
+            }
+        }
+    }
+
+    #[test]
+    fn should_produce_identical_results_when_optimizing_twice() {
+        let mut first = load_resource_model("terrain/terrain.gltf");
+        let mut second = load_resource_model("terrain/terrain.gltf");
+
+        optimize_mesh(&mut first.meshes[0]);
+        optimize_mesh(&mut second.meshes[0]);
+
+        assert_eq!(first.meshes[0].indices, second.meshes[0].indices);
+    }
+
+    #[test]
+    fn should_remap_lod_indices_when_optimizing() {
+        let mut model = load_resource_model("colored_sphere/no_normals.gltf");
+        let mesh = &mut model.meshes[0];
+        mesh.lods = simplify::build_lod_chain(mesh, &[0.5, 0.25]);
+
+        // A vertex only a LOD uses has to be kept as well
+        mesh.vertex_positions.push(beagle_math::Vector3::new(5.0, 5.0, 5.0));
+        let lod_only_vertex = (mesh.vertex_positions.len() - 1) as u32;
+        mesh.lods[1].indices.extend_from_slice(&[0, 1, lod_only_vertex]);
+
+        let lod_positions = |mesh: &Mesh| -> Vec<Vec<beagle_math::Vector3>> {
+            mesh.lods.iter().map(|lod| lod.indices.iter().map(|index| mesh.vertex_positions[*index as usize]).collect()).collect()
+        };
+        let before = lod_positions(mesh);
+
+        optimize_mesh(mesh);
+
+        assert_eq!(lod_positions(mesh), before);
+    }
+
+    #[test]
+    fn should_reduce_vertex_transforms_when_optimizing_indexed_icosphere() {
+        let mut model = load_resource_model("colored_sphere/no_normals.gltf");
+        let mesh = &mut model.meshes[0];
+
+        let before = analyze_vertex_cache(&mesh.indices, mesh.vertex_positions.len(), DEFAULT_ANALYZE_CACHE_SIZE);
+        optimize_mesh(mesh);
+        let after = analyze_vertex_cache(&mesh.indices, mesh.vertex_positions.len(), DEFAULT_ANALYZE_CACHE_SIZE);
+
+        assert_eq!(mesh.vertex_positions.len(), 42);
+        assert_eq!(before.vertices_transformed, 94);
+        assert_eq!(after.vertices_transformed, 54);
+    }
+
+    #[test]
+    fn should_reduce_vertex_transforms_when_optimizing_welded_terrain() {
+        let model = load_resource_model("terrain/terrain.gltf");
+        let mesh = &model.meshes[0];
+
+        // The terrain is exported with separate vertices for every quad, so it is welded by position first
+        let corners: Vec<Vertex> = mesh.indices.iter().map(|index| Vertex { position: mesh.vertex_positions[*index as usize], ..Default::default() }).collect();
+        let (welded_vertices, welded_indices) = weld_vertices(&corners);
+
+        let before = analyze_vertex_cache(&welded_indices, welded_vertices.len(), DEFAULT_ANALYZE_CACHE_SIZE);
+        let after = analyze_vertex_cache(&optimize_vertex_cache(&welded_indices, welded_vertices.len()), welded_vertices.len(), DEFAULT_ANALYZE_CACHE_SIZE);
+
+        assert_eq!(welded_vertices.len(), 81);
+        assert_eq!(before.vertices_transformed, 165);
+        assert_eq!(after.vertices_transformed, 93);
+    }
+
+    #[test]
+    fn should_keep_all_triangles_when_optimizing_overdraw_with_degenerate_first_triangle() {
+        let indices = [0, 0, 1, 0, 1, 2, 1, 3, 2];
+        let positions = [
+            beagle_math::Vector3::new(0.0, 0.0, 0.0),
+            beagle_math::Vector3::new(1.0, 0.0, 0.0),
+            beagle_math::Vector3::new(0.0, 1.0, 0.0),
+            beagle_math::Vector3::new(1.0, 1.0, 0.0)
+        ];
+
+        let optimized = optimize_overdraw(&indices, &positions, DEFAULT_OVERDRAW_THRESHOLD);
+
+        assert_eq!(sorted_triangles(&optimized), sorted_triangles(&indices));
+    }
+
+    #[test]
+    fn should_order_vertices_by_first_use_when_optimizing_vertex_fetch() {
+        let indices = [3, 1, 2, 1, 3, 0];
+
+        let (new_indices, remap) = optimize_vertex_fetch(&indices, 5);
+
+        assert_eq!(new_indices, vec![0, 1, 2, 1, 0, 3]);
+        assert_eq!(remap, vec![3, 1, 2, 0, u32::MAX]);
+        assert_eq!(remap_vertex_buffer(&[10, 11, 12, 13, 14], &remap), vec![13, 11, 12, 10]);
+    }
+
+    #[test]
+    fn should_report_worst_case_acmr_when_no_vertices_are_shared() {
+        let indices: Vec<u32> = (0..30).collect();
+
+        let statistics = analyze_vertex_cache(&indices, 30, DEFAULT_ANALYZE_CACHE_SIZE);
+
+        assert_eq!(statistics.vertices_transformed, 30);
+        assert_eq!(statistics.acmr, 3.0);
+        assert_eq!(statistics.atvr, 1.0);
+    }
+}
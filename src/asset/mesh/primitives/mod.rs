@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use crate::beagle_math;
+use crate::asset::mesh::{validation, Mesh};
+
+/*
+    Generators for simple shapes, so I don't have to make a glTF file in Blender every time I want to test something.
+
+    Every shape has positions, normals, uvs and indices, and is centered around the origin with +Y up (except for the grid, see below).
+
+    Winding: DirectX is set up with FrontCounterClockwise = 0, so front faces are wound CLOCKWISE as seen from the outside.
+    In numbers, for a triangle v0, v1, v2: cross(v1 - v0, v2 - v0) points OUT of the shape.
+
+    Uvs have (0, 0) in the top left of a texture, with u going right and v going down, as seen from the outside of the shape.
+
+    Curved shapes are "surfaces of revolution", a profile going from the top to the bottom, swept around the Y axis.
+    The vertices along the seam where the sweep starts and ends are doubled, so the uvs can go from 0 all the way to 1.
+*/
+
+pub fn create_box(size: &beagle_math::Vector3, segments: u32) -> Mesh {
+    if segments == 0 {
+        panic!("A box needs at least 1 segment per side.");
+    }
+
+    let mut mesh = new_mesh("box");
+    let half = size.mul(0.5);
+
+    // For every side: the top left corner, the "right" and the "down" direction, all as seen from outside
+    let sides = [
+        // +Z
+        (beagle_math::Vector3::new(half.x, half.y, half.z), beagle_math::Vector3::new(-size.x, 0.0, 0.0), beagle_math::Vector3::new(0.0, -size.y, 0.0)),
+        // -Z
+        (beagle_math::Vector3::new(-half.x, half.y, -half.z), beagle_math::Vector3::new(size.x, 0.0, 0.0), beagle_math::Vector3::new(0.0, -size.y, 0.0)),
+        // +X
+        (beagle_math::Vector3::new(half.x, half.y, -half.z), beagle_math::Vector3::new(0.0, 0.0, size.z), beagle_math::Vector3::new(0.0, -size.y, 0.0)),
+        // -X
+        (beagle_math::Vector3::new(-half.x, half.y, half.z), beagle_math::Vector3::new(0.0, 0.0, -size.z), beagle_math::Vector3::new(0.0, -size.y, 0.0)),
+        // +Y
+        (beagle_math::Vector3::new(-half.x, half.y, half.z), beagle_math::Vector3::new(size.x, 0.0, 0.0), beagle_math::Vector3::new(0.0, 0.0, -size.z)),
+        // -Y
+        (beagle_math::Vector3::new(-half.x, -half.y, -half.z), beagle_math::Vector3::new(size.x, 0.0, 0.0), beagle_math::Vector3::new(0.0, 0.0, size.z))
+    ];
+
+    for (top_left, right, down) in sides.iter() {
+        add_flat_patch(&mut mesh, top_left, right, down, segments, segments);
+    }
+
+    mesh
+}
+
+// A flat rectangle in the XZ plane, facing +Y
+pub fn create_plane(width: f32, depth: f32, segments_x: u32, segments_z: u32) -> Mesh {
+    if segments_x == 0 || segments_z == 0 {
+        panic!("A plane needs at least 1 segment in each direction.");
+    }
+
+    let mut mesh = new_mesh("plane");
+
+    add_flat_patch(
+        &mut mesh,
+        &beagle_math::Vector3::new(-width * 0.5, 0.0, depth * 0.5),
+        &beagle_math::Vector3::new(width, 0.0, 0.0),
+        &beagle_math::Vector3::new(0.0, 0.0, -depth),
+        segments_x,
+        segments_z);
+
+    mesh
+}
+
+/*
+    A grid of columns x rows square cells in the XZ plane, facing +Y.
+    Unlike the plane, the grid is not centered: it starts at the origin and grows along +X and +Z,
+    and the vertex at grid point (column, row) is always vertex number row * (columns + 1) + column.
+    That makes it easy to use as a base for height fields.
+*/
+pub fn create_grid(columns: u32, rows: u32, cell_size: f32) -> Mesh {
+    if columns == 0 || rows == 0 {
+        panic!("A grid needs at least 1 column and 1 row.");
+    }
+
+    let mut mesh = new_mesh("grid");
+
+    for row in 0..=rows {
+        for column in 0..=columns {
+            mesh.vertex_positions.push(beagle_math::Vector3::new(column as f32 * cell_size, 0.0, row as f32 * cell_size));
+            mesh.vertex_normals.push(beagle_math::Vector3::new(0.0, 1.0, 0.0));
+            mesh.vertex_uvs.push(beagle_math::Vector2::new(column as f32 / columns as f32, 1.0 - row as f32 / rows as f32));
+        }
+    }
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let near_left = row * (columns + 1) + column;
+            let near_right = near_left + 1;
+            let far_left = near_left + columns + 1;
+            let far_right = far_left + 1;
+
+            // Seen from above, "far" (+Z) is up and "near" is down
+            mesh.indices.extend_from_slice(&[far_left, far_right, near_left]);
+            mesh.indices.extend_from_slice(&[far_right, near_right, near_left]);
+        }
+    }
+
+    mesh
+}
+
+pub fn create_uv_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
+    if segments < 3 || rings < 2 {
+        panic!("A sphere needs at least 3 segments and 2 rings.");
+    }
+
+    let profile: Vec<ProfilePoint> = (0..=rings).map(|ring| {
+        let (sin, cos) = pole_safe_sin_cos(ring as f32 / rings as f32 * PI);
+        ProfilePoint { radius: radius * sin, height: radius * cos, normal_radius: sin, normal_height: cos }
+    }).collect();
+
+    let mut mesh = new_mesh("uv_sphere");
+    add_revolution(&mut mesh, &profile, segments);
+    mesh
+}
+
+/*
+    A sphere made by repeatedly splitting the triangles of an icosahedron in 4, and pushing the new vertices out onto the sphere.
+    The triangles are all nearly the same size, unlike the uv sphere which bunches them up at the poles.
+*/
+pub fn create_icosphere(radius: f32, subdivisions: u32) -> Mesh {
+    // The 12 corners of an icosahedron lie on three golden rectangles
+    let golden_ratio = (1.0 + 5.0f32.sqrt()) * 0.5;
+    let mut directions: Vec<beagle_math::Vector3> = [
+        (-1.0, golden_ratio, 0.0), (1.0, golden_ratio, 0.0), (-1.0, -golden_ratio, 0.0), (1.0, -golden_ratio, 0.0),
+        (0.0, -1.0, golden_ratio), (0.0, 1.0, golden_ratio), (0.0, -1.0, -golden_ratio), (0.0, 1.0, -golden_ratio),
+        (golden_ratio, 0.0, -1.0), (golden_ratio, 0.0, 1.0), (-golden_ratio, 0.0, -1.0), (-golden_ratio, 0.0, 1.0)
+    ].iter().map(|(x, y, z)| beagle_math::Vector3::new(*x, *y, *z).normalized()).collect();
+
+    // Each triangle is wound so cross(v1 - v0, v2 - v0) points outwards
+    let mut triangles: Vec<[u32; 3]> = vec!(
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1]
+    );
+
+    for _ in 0..subdivisions {
+        let mut middles: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut middle_of = |a: u32, b: u32, directions: &mut Vec<beagle_math::Vector3>| -> u32 {
+            *middles.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                directions.push(directions[a as usize].add(&directions[b as usize]).normalized());
+                (directions.len() - 1) as u32
+            })
+        };
+
+        let mut split_triangles: Vec<[u32; 3]> = Vec::with_capacity(triangles.len() * 4);
+        for [a, b, c] in triangles {
+            let ab = middle_of(a, b, &mut directions);
+            let bc = middle_of(b, c, &mut directions);
+            let ca = middle_of(c, a, &mut directions);
+
+            split_triangles.push([a, ab, ca]);
+            split_triangles.push([ab, b, bc]);
+            split_triangles.push([ca, bc, c]);
+            split_triangles.push([ab, bc, ca]);
+        }
+
+        triangles = split_triangles;
+    }
+
+    let mut mesh = new_mesh("icosphere");
+    for direction in &directions {
+        mesh.vertex_positions.push(direction.mul(radius));
+        mesh.vertex_normals.push(*direction);
+        mesh.vertex_uvs.push(spherical_uv(direction));
+    }
+
+    /*
+        Spherical uvs wrap around from u = 1 back to u = 0. Triangles crossing that line would otherwise stretch
+        across the whole texture, so their vertices on the u = 0 side get a copy with u moved past 1.
+    */
+    let mut wrapped_copies: HashMap<u32, u32> = HashMap::new();
+    for triangle in triangles.iter_mut() {
+        let us: Vec<f32> = triangle.iter().map(|vertex| mesh.vertex_uvs[*vertex as usize].x).collect();
+        let crosses_seam = us.iter().cloned().fold(f32::MIN, f32::max) - us.iter().cloned().fold(f32::MAX, f32::min) > 0.5;
+        if !crosses_seam {
+            continue;
+        }
+
+        for vertex in triangle.iter_mut() {
+            if mesh.vertex_uvs[*vertex as usize].x < 0.5 {
+                *vertex = *wrapped_copies.entry(*vertex).or_insert_with(|| {
+                    let uv = mesh.vertex_uvs[*vertex as usize];
+                    mesh.vertex_positions.push(mesh.vertex_positions[*vertex as usize]);
+                    mesh.vertex_normals.push(mesh.vertex_normals[*vertex as usize]);
+                    mesh.vertex_uvs.push(beagle_math::Vector2::new(uv.x + 1.0, uv.y));
+                    (mesh.vertex_positions.len() - 1) as u32
+                });
+            }
+        }
+    }
+
+    mesh.indices = triangles.iter().flatten().copied().collect();
+    mesh
+}
+
+pub fn create_cylinder(radius: f32, height: f32, segments: u32, height_segments: u32) -> Mesh {
+    if segments < 3 || height_segments == 0 {
+        panic!("A cylinder needs at least 3 segments and 1 height segment.");
+    }
+
+    let profile: Vec<ProfilePoint> = (0..=height_segments).map(|row| {
+        ProfilePoint { radius, height: height * (0.5 - row as f32 / height_segments as f32), normal_radius: 1.0, normal_height: 0.0 }
+    }).collect();
+
+    let mut mesh = new_mesh("cylinder");
+    add_revolution(&mut mesh, &profile, segments);
+    add_cap(&mut mesh, radius, height * 0.5, segments, true);
+    add_cap(&mut mesh, radius, -height * 0.5, segments, false);
+    mesh
+}
+
+// A cone with its tip pointing up
+pub fn create_cone(radius: f32, height: f32, segments: u32, height_segments: u32) -> Mesh {
+    if segments < 3 || height_segments == 0 {
+        panic!("A cone needs at least 3 segments and 1 height segment.");
+    }
+
+    // The normal of the sloped side leans up by the same angle as the side leans in
+    let slant_length = (radius * radius + height * height).sqrt();
+    let (normal_radius, normal_height) = (height / slant_length, radius / slant_length);
+
+    let profile: Vec<ProfilePoint> = (0..=height_segments).map(|row| {
+        let amount = row as f32 / height_segments as f32;
+        ProfilePoint { radius: radius * amount, height: height * (0.5 - amount), normal_radius, normal_height }
+    }).collect();
+
+    let mut mesh = new_mesh("cone");
+    add_revolution(&mut mesh, &profile, segments);
+    add_cap(&mut mesh, radius, -height * 0.5, segments, false);
+    mesh
+}
+
+// A cylinder with half a sphere on both ends. The height is the height of the cylinder part, so the whole capsule is height + 2 * radius high.
+pub fn create_capsule(radius: f32, height: f32, segments: u32, hemisphere_rings: u32) -> Mesh {
+    if segments < 3 || hemisphere_rings == 0 {
+        panic!("A capsule needs at least 3 segments and 1 ring per hemisphere.");
+    }
+
+    let mut profile: Vec<ProfilePoint> = vec!();
+
+    for ring in 0..=hemisphere_rings {
+        let (sin, cos) = pole_safe_sin_cos(ring as f32 / hemisphere_rings as f32 * PI * 0.5);
+        profile.push(ProfilePoint { radius: radius * sin, height: height * 0.5 + radius * cos, normal_radius: sin, normal_height: cos });
+    }
+
+    for ring in 0..=hemisphere_rings {
+        let (sin, cos) = pole_safe_sin_cos(PI * 0.5 + ring as f32 / hemisphere_rings as f32 * PI * 0.5);
+        profile.push(ProfilePoint { radius: radius * sin, height: -height * 0.5 + radius * cos, normal_radius: sin, normal_height: cos });
+    }
+
+    let mut mesh = new_mesh("capsule");
+    add_revolution(&mut mesh, &profile, segments);
+    mesh
+}
+
+/*
+    A donut lying flat in the XZ plane.
+    The major radius goes from the center to the middle of the tube, the minor radius is the radius of the tube itself.
+*/
+pub fn create_torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> Mesh {
+    if major_segments < 3 || minor_segments < 3 {
+        panic!("A torus needs at least 3 segments around both circles.");
+    }
+
+    let mut mesh = new_mesh("torus");
+
+    for minor in 0..=minor_segments {
+        // Starting at the outside, going down first, so "v" points down as seen from outside
+        let tube_angle = minor as f32 / minor_segments as f32 * PI * 2.0;
+        let (tube_sin, tube_cos) = tube_angle.sin_cos();
+
+        for major in 0..=major_segments {
+            let angle = major as f32 / major_segments as f32 * PI * 2.0;
+            let (sin, cos) = angle.sin_cos();
+
+            let normal = beagle_math::Vector3::new(tube_cos * cos, -tube_sin, tube_cos * sin);
+            let ring_center = beagle_math::Vector3::new(major_radius * cos, 0.0, major_radius * sin);
+
+            mesh.vertex_positions.push(ring_center.add(&normal.mul(minor_radius)));
+            mesh.vertex_normals.push(normal);
+            mesh.vertex_uvs.push(beagle_math::Vector2::new(major as f32 / major_segments as f32, minor as f32 / minor_segments as f32));
+        }
+    }
+
+    add_patch_indices(&mut mesh, 0, major_segments, minor_segments, &|_| false);
+    mesh
+}
+
+// A point on the profile of a surface of revolution, with the normal given in the same (radius, height) plane
+struct ProfilePoint {
+    radius: f32,
+    height: f32,
+    normal_radius: f32,
+    normal_height: f32
+}
+
+/*
+    Sweeps the profile (from top to bottom) around the Y axis.
+    At angle a, a profile point ends up at (radius * cos(a), height, radius * sin(a)), which makes u go right when seen from the outside.
+    Where the profile touches the axis (the poles of a sphere, the tip of a cone), the triangles that would have no area are left out.
+*/
+fn add_revolution(mesh: &mut Mesh, profile: &[ProfilePoint], segments: u32) {
+    let first_vertex = mesh.vertex_positions.len() as u32;
+
+    // The uvs are spread along the profile by length, so they don't bunch up
+    let mut profile_lengths: Vec<f32> = vec!(0.0);
+    for pair in profile.windows(2) {
+        let length = ((pair[1].radius - pair[0].radius).powi(2) + (pair[1].height - pair[0].height).powi(2)).sqrt();
+        profile_lengths.push(profile_lengths.last().unwrap() + length);
+    }
+    let total_length = *profile_lengths.last().unwrap();
+
+    for (point, length) in profile.iter().zip(profile_lengths.iter()) {
+        for segment in 0..=segments {
+            let angle = segment as f32 / segments as f32 * PI * 2.0;
+            let (sin, cos) = angle.sin_cos();
+
+            mesh.vertex_positions.push(beagle_math::Vector3::new(point.radius * cos, point.height, point.radius * sin));
+            mesh.vertex_normals.push(beagle_math::Vector3::new(point.normal_radius * cos, point.normal_height, point.normal_radius * sin).normalized());
+            // A point on the axis gets one vertex per segment, sitting in the middle of the segment in the uvs
+            let u = if point.radius == 0.0 { (segment as f32 + 0.5) / segments as f32 } else { segment as f32 / segments as f32 };
+            mesh.vertex_uvs.push(beagle_math::Vector2::new(u, length / total_length));
+        }
+    }
+
+    add_patch_indices(mesh, first_vertex, segments, (profile.len() - 1) as u32, &|row| profile[row as usize].radius == 0.0);
+
+    // The last vertex of a collapsed row isn't needed, see "add_patch_indices"
+    validation::remove_unreferenced_vertices(mesh);
+}
+
+/*
+    Indices for a patch of (columns + 1) x (rows + 1) vertices, stored row by row, with columns going right and rows going down as seen from the front.
+    Each cell is split into two clockwise triangles. "is_collapsed_row" tells which rows have all their vertices in the same place.
+*/
+fn add_patch_indices(mesh: &mut Mesh, first_vertex: u32, columns: u32, rows: u32, is_collapsed_row: &dyn Fn(u32) -> bool) {
+    for row in 0..rows {
+        for column in 0..columns {
+            let top_left = first_vertex + row * (columns + 1) + column;
+            let top_right = top_left + 1;
+            let bottom_left = top_left + columns + 1;
+            let bottom_right = bottom_left + 1;
+
+            // When a row is collapsed, the triangle touching it is made from the collapsed vertex in the same column,
+            // so both poles use the vertices of column 0 to columns - 1
+            if is_collapsed_row(row) {
+                mesh.indices.extend_from_slice(&[top_left, bottom_right, bottom_left]);
+            } else if is_collapsed_row(row + 1) {
+                mesh.indices.extend_from_slice(&[top_left, top_right, bottom_left]);
+            } else {
+                mesh.indices.extend_from_slice(&[top_left, top_right, bottom_left]);
+                mesh.indices.extend_from_slice(&[top_right, bottom_right, bottom_left]);
+            }
+        }
+    }
+}
+
+// A flat rectangle starting at "top_left", spanning "right" and "down" as seen from its front
+fn add_flat_patch(mesh: &mut Mesh, top_left: &beagle_math::Vector3, right: &beagle_math::Vector3, down: &beagle_math::Vector3, columns: u32, rows: u32) {
+    let first_vertex = mesh.vertex_positions.len() as u32;
+    let normal = right.cross(down).normalized();
+
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let u = column as f32 / columns as f32;
+            let v = row as f32 / rows as f32;
+
+            mesh.vertex_positions.push(top_left.add(&right.mul(u)).add(&down.mul(v)));
+            mesh.vertex_normals.push(normal);
+            mesh.vertex_uvs.push(beagle_math::Vector2::new(u, v));
+        }
+    }
+
+    add_patch_indices(mesh, first_vertex, columns, rows, &|_| false);
+}
+
+// A flat disc closing off the top or bottom of a cylinder or cone. It gets its own vertices, so the edge stays sharp.
+fn add_cap(mesh: &mut Mesh, radius: f32, height: f32, segments: u32, facing_up: bool) {
+    let center = mesh.vertex_positions.len() as u32;
+    let normal = beagle_math::Vector3::new(0.0, if facing_up { 1.0 } else { -1.0 }, 0.0);
+
+    mesh.vertex_positions.push(beagle_math::Vector3::new(0.0, height, 0.0));
+    mesh.vertex_normals.push(normal);
+    mesh.vertex_uvs.push(beagle_math::Vector2::new(0.5, 0.5));
+
+    for segment in 0..segments {
+        let angle = segment as f32 / segments as f32 * PI * 2.0;
+        let (sin, cos) = angle.sin_cos();
+
+        mesh.vertex_positions.push(beagle_math::Vector3::new(radius * cos, height, radius * sin));
+        mesh.vertex_normals.push(normal);
+        mesh.vertex_uvs.push(beagle_math::Vector2::new(0.5 + cos * 0.5, 0.5 - sin * 0.5));
+    }
+
+    for segment in 0..segments {
+        let current = center + 1 + segment;
+        let next = center + 1 + (segment + 1) % segments;
+
+        if facing_up {
+            mesh.indices.extend_from_slice(&[center, next, current]);
+        } else {
+            mesh.indices.extend_from_slice(&[center, current, next]);
+        }
+    }
+}
+
+// sin and cos, but exactly 0 at the poles (0 and PI), so the vertices of a pole end up in exactly the same place
+fn pole_safe_sin_cos(angle: f32) -> (f32, f32) {
+    if angle == 0.0 {
+        (0.0, 1.0)
+    } else if angle == PI {
+        (0.0, -1.0)
+    } else {
+        angle.sin_cos()
+    }
+}
+
+// The same mapping as the uv sphere, for a direction from the center of a sphere
+fn spherical_uv(direction: &beagle_math::Vector3) -> beagle_math::Vector2 {
+    let mut u = direction.z.atan2(direction.x) / (PI * 2.0);
+    if u < 0.0 {
+        u += 1.0;
+    }
+
+    beagle_math::Vector2::new(u, direction.y.clamp(-1.0, 1.0).acos() / PI)
+}
+
+fn new_mesh(name: &str) -> Mesh {
+    Mesh {
+        name: String::from(name),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math;
+    use crate::asset::mesh::*;
+    use crate::asset::mesh::primitives::*;
+
+    fn all_primitives() -> Vec<Mesh> {
+        vec!(
+            create_box(&beagle_math::Vector3::new(1.0, 2.0, 3.0), 2),
+            create_uv_sphere(1.0, 16, 8),
+            create_icosphere(1.0, 2),
+            create_cylinder(1.0, 2.0, 12, 2),
+            create_cone(1.0, 2.0, 12, 3),
+            create_capsule(0.5, 1.0, 12, 4),
+            create_torus(2.0, 0.5, 16, 8),
+            create_plane(2.0, 3.0, 4, 2),
+            create_grid(3, 2, 0.5)
+        )
+    }
+
+    fn face_normal(mesh: &Mesh, triangle: &[u32]) -> beagle_math::Vector3 {
+        let vert1 = mesh.vertex_positions[triangle[0] as usize];
+        let vert2 = mesh.vertex_positions[triangle[1] as usize];
+        let vert3 = mesh.vertex_positions[triangle[2] as usize];
+
+        vert2.add(&vert1.mul(-1.0)).cross(&vert3.add(&vert1.mul(-1.0)))
+    }
+
+    #[test]
+    fn should_have_valid_attributes_and_indices() {
+        for mesh in all_primitives() {
+            assert_eq!(mesh.vertex_normals.len(), mesh.vertex_positions.len(), "{}", mesh.name);
+            assert_eq!(mesh.vertex_uvs.len(), mesh.vertex_positions.len(), "{}", mesh.name);
+
+            let report = crate::asset::mesh::validation::validate(&mesh);
+            assert!(report.is_valid(), "{} has issues: {:?}", mesh.name, report.issues);
+
+            for normal in &mesh.vertex_normals {
+                assert!((normal.length() - 1.0).abs() < 0.0001, "{} has a normal of length {}", mesh.name, normal.length());
+            }
+        }
+    }
+
+    #[test]
+    fn should_wind_triangles_clockwise_seen_from_outside() {
+        for mesh in all_primitives() {
+            for triangle in mesh.indices.chunks_exact(3) {
+                // The face normal made by the winding must agree with the normals of the vertices
+                let winding_normal = face_normal(&mesh, triangle);
+                let vertex_normal = mesh.vertex_normals[triangle[0] as usize]
+                    .add(&mesh.vertex_normals[triangle[1] as usize])
+                    .add(&mesh.vertex_normals[triangle[2] as usize]);
+
+                assert!(winding_normal.dot(&vertex_normal) > 0.0, "{} has a triangle {:?} facing inwards", mesh.name, triangle);
+            }
+        }
+    }
+
+    #[test]
+    fn should_face_plane_and_grid_up() {
+        for mesh in [create_plane(1.0, 1.0, 1, 1), create_grid(1, 1, 1.0)] {
+            for triangle in mesh.indices.chunks_exact(3) {
+                assert!(face_normal(&mesh, triangle).y > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn should_place_vertices_on_sphere() {
+        for mesh in [create_uv_sphere(2.0, 10, 6), create_icosphere(2.0, 3)] {
+            for position in &mesh.vertex_positions {
+                assert!((position.length() - 2.0).abs() < 0.0001);
+            }
+        }
+
+        // 12 corners, plus one vertex per edge for every subdivision: 12 -> 42 -> 162, plus copies along the uv seam
+        let icosphere = create_icosphere(1.0, 2);
+        assert_eq!(icosphere.indices.len(), 20 * 16 * 3);
+        assert!(icosphere.vertex_positions.len() >= 162);
+    }
+
+    #[test]
+    fn should_not_stretch_uvs_across_icosphere_seam() {
+        let mesh = create_icosphere(1.0, 2);
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let us: Vec<f32> = triangle.iter().map(|vertex| mesh.vertex_uvs[*vertex as usize].x).collect();
+            let span = us.iter().cloned().fold(f32::MIN, f32::max) - us.iter().cloned().fold(f32::MAX, f32::min);
+            assert!(span <= 0.5);
+        }
+    }
+}
@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+
+use crate::beagle_math;
+use crate::asset::mesh::{Mesh, Lod};
+
+/*
+    Mesh simplification using the Quadric Error Metric from Garland and Heckbert's
+    "Surface Simplification Using Quadric Error Metrics" (https://www.cs.cmu.edu/~./garland/Papers/quadrics.pdf).
+
+    Every vertex gets a "quadric", which is a compact way of storing the sum of squared distances to the planes of all the triangles around it.
+    Collapsing an edge (u -> v) moves vertex u onto vertex v, and the cost of doing that is how far v is from all the planes u and v used to lie on.
+    Edges are collapsed cheapest first, until the target triangle count is reached or the next collapse would cost more than the allowed error.
+
+    I only ever collapse a vertex onto one of its existing neighbours (a "half-edge collapse").
+    That means no new vertices are created, and the simplified index list refers directly into the original vertex buffers.
+
+    To preserve the shape and look of the mesh, some vertices are never moved:
+
+    - Border vertices: Vertices on an edge that only has a triangle on one side. Moving them would change the outline of the mesh,
+      and for chunked meshes (like terrain) it would open up cracks to the neighbouring chunk.
+    - Seam vertices: Vertices where several vertices share the same position but have different uvs or colors.
+      Moving only one side of the seam would tear the mesh apart.
+    - Non-manifold vertices: Vertices on an edge shared by more than two triangles.
+
+    Normals are NOT considered, since they are expected to be recalculated after simplification (which the flat shaded renderer does anyway).
+*/
+
+// How many passes to make at most. Each pass collapses a set of independent edges.
+const MAX_PASSES: usize = 100;
+
+/*
+    Simplifies the mesh down to (at most) the target number of indices, without collapsing any edge with an error larger than target_error.
+    The error is measured in the mesh's own units, as the RMS distance to the original triangle planes around a collapsed vertex.
+*/
+pub fn simplify(mesh: &Mesh, target_index_count: usize, target_error: f32) -> Lod {
+    let mut quadrics = build_quadrics(mesh);
+    simplify_indices(mesh, &mesh.indices, &mut quadrics, target_index_count, target_error)
+}
+
+/*
+    Builds a chain of LODs for the mesh. Each entry of triangle_ratios is the fraction of the original triangle count the LOD should have,
+    and should be given in decreasing order, like [0.5, 0.25, 0.125].
+
+    Each LOD is simplified from the one before it, which is faster and keeps the LODs consistent with each other.
+    The quadrics are built once from the original triangles and carried along the chain, so every collapse is measured
+    against the original surface rather than against the previous LOD.
+    The error of a LOD is the largest error of any collapse made on the way to it, so errors never decrease along the chain.
+*/
+pub fn build_lod_chain(mesh: &Mesh, triangle_ratios: &[f32]) -> Vec<Lod> {
+    let original_triangle_count = mesh.indices.len() / 3;
+
+    let mut lods: Vec<Lod> = vec!();
+    let mut previous_indices = mesh.indices.clone();
+    let mut previous_error: f32 = 0.0;
+    let mut quadrics = build_quadrics(mesh);
+
+    for triangle_ratio in triangle_ratios {
+        let target_index_count = ((original_triangle_count as f32 * triangle_ratio) as usize) * 3;
+        let lod = simplify_indices(mesh, &previous_indices, &mut quadrics, target_index_count, f32::MAX);
+
+        // If nothing more could be collapsed, there's no point in keeping an identical LOD around.
+        if lod.indices.len() == previous_indices.len() {
+            break;
+        }
+
+        previous_error = previous_error.max(lod.error);
+        previous_indices = lod.indices.clone();
+
+        lods.push(Lod {
+            indices: lod.indices,
+            error: previous_error
+        });
+    }
+
+    lods
+}
+
+/*
+    The quadric of every representative vertex, from the triangles of the original mesh.
+    Collapsing a vertex adds its quadric onto the vertex it's moved to, so the quadrics keep describing the original surface.
+*/
+fn build_quadrics(mesh: &Mesh) -> Vec<Quadric> {
+    let representatives = find_representatives(mesh);
+    let indices: Vec<u32> = mesh.indices.iter().map(|index| representatives[*index as usize]).collect();
+
+    let mut quadrics: Vec<Quadric> = vec![Quadric::default(); mesh.vertex_positions.len()];
+    for triangle in remove_degenerate_triangles(&indices).chunks(3) {
+        let quadric = Quadric::from_triangle(
+            &mesh.vertex_positions[triangle[0] as usize],
+            &mesh.vertex_positions[triangle[1] as usize],
+            &mesh.vertex_positions[triangle[2] as usize]);
+
+        for vertex in triangle {
+            quadrics[*vertex as usize] = quadrics[*vertex as usize].add(&quadric);
+        }
+    }
+
+    quadrics
+}
+
+fn simplify_indices(mesh: &Mesh, indices: &[u32], quadrics: &mut [Quadric], target_index_count: usize, target_error: f32) -> Lod {
+    if !indices.len().is_multiple_of(3) {
+        panic!("The list of indices is not divisible by 3, which is required as this method assumes a primitive topology of triangles.")
+    }
+
+    let vertex_count = mesh.vertex_positions.len();
+    let representatives = find_representatives(mesh);
+    let locked = find_locked_vertices(mesh, &representatives, indices);
+
+    // All the work is done on representative vertices, so every index is mapped to its representative up front.
+    let mut current_indices: Vec<u32> = indices.iter().map(|index| representatives[*index as usize]).collect();
+    current_indices = remove_degenerate_triangles(&current_indices);
+
+    let mut result_error: f32 = 0.0;
+    let max_cost = if target_error == f32::MAX { f64::MAX } else { (target_error as f64) * (target_error as f64) };
+
+    for _ in 0..MAX_PASSES {
+        if current_indices.len() <= target_index_count {
+            break;
+        }
+
+        let vertex_triangles = build_vertex_triangles(&current_indices, vertex_count);
+        let candidates = find_collapse_candidates(mesh, &current_indices, &locked, quadrics);
+
+        let mut collapsed_into: Vec<u32> = (0..vertex_count as u32).collect();
+        let mut touched: Vec<bool> = vec![false; vertex_count];
+        let mut triangles_to_remove = (current_indices.len() - target_index_count) / 3;
+        let mut collapses = 0;
+
+        for candidate in candidates {
+            if triangles_to_remove == 0 || candidate.cost > max_cost {
+                break;
+            }
+
+            let from = candidate.from as usize;
+            let to = candidate.to as usize;
+
+            // Collapses in the same pass must not share vertices, as each collapse changes the neighbourhood of its vertices.
+            if touched[from] || touched[to] {
+                continue;
+            }
+
+            if collapse_flips_triangle(mesh, &current_indices, &vertex_triangles[from], candidate.from, candidate.to) {
+                continue;
+            }
+
+            let mut removed_triangles = 0;
+            for triangle in &vertex_triangles[from] {
+                let triangle_vertices = &current_indices[*triangle as usize * 3..*triangle as usize * 3 + 3];
+                for vertex in triangle_vertices {
+                    touched[*vertex as usize] = true;
+                }
+
+                if triangle_vertices.contains(&candidate.to) {
+                    removed_triangles += 1;
+                }
+            }
+
+            collapsed_into[from] = candidate.to;
+            quadrics[to] = quadrics[to].add(&quadrics[from]);
+
+            triangles_to_remove = triangles_to_remove.saturating_sub(removed_triangles);
+            result_error = result_error.max(candidate.error());
+            collapses += 1;
+        }
+
+        if collapses == 0 {
+            break;
+        }
+
+        let collapsed_indices: Vec<u32> = current_indices.iter().map(|index| collapsed_into[*index as usize]).collect();
+        current_indices = remove_degenerate_triangles(&collapsed_indices);
+    }
+
+    Lod {
+        indices: current_indices,
+        error: result_error
+    }
+}
+
+/*
+    For every vertex, the first vertex with the same position, uv and color.
+    Vertices only differing in normals are treated as one vertex by the simplifier.
+*/
+fn find_representatives(mesh: &Mesh) -> Vec<u32> {
+    let mut first_seen: HashMap<[u32; 9], u32> = HashMap::new();
+
+    mesh.vertex_positions.iter().enumerate().map(|(vertex, position)| {
+        let uv = mesh.vertex_uvs.get(vertex).copied().unwrap_or_default();
+        let color = mesh.vertex_colors.get(vertex).copied().unwrap_or_default();
+        let key = [
+            (position.x + 0.0).to_bits(), (position.y + 0.0).to_bits(), (position.z + 0.0).to_bits(),
+            (uv.x + 0.0).to_bits(), (uv.y + 0.0).to_bits(),
+            (color.x + 0.0).to_bits(), (color.y + 0.0).to_bits(), (color.z + 0.0).to_bits(), (color.w + 0.0).to_bits()
+        ];
+
+        *first_seen.entry(key).or_insert(vertex as u32)
+    }).collect()
+}
+
+// Finds border, seam and non-manifold vertices, as described at the top of this module.
+fn find_locked_vertices(mesh: &Mesh, representatives: &[u32], indices: &[u32]) -> Vec<bool> {
+    let vertex_count = mesh.vertex_positions.len();
+    let mut locked: Vec<bool> = vec![false; vertex_count];
+
+    // Seams: Positions shared by more than one representative vertex.
+    let mut representatives_at_position: HashMap<[u32; 3], Vec<u32>> = HashMap::new();
+    for (vertex, position) in mesh.vertex_positions.iter().enumerate() {
+        if representatives[vertex] != vertex as u32 {
+            continue;
+        }
+
+        representatives_at_position
+            .entry([(position.x + 0.0).to_bits(), (position.y + 0.0).to_bits(), (position.z + 0.0).to_bits()])
+            .or_default()
+            .push(vertex as u32);
+    }
+
+    let mut position_ids: Vec<u32> = (0..vertex_count as u32).collect();
+    for vertices_at_position in representatives_at_position.values() {
+        for vertex in vertices_at_position {
+            position_ids[*vertex as usize] = vertices_at_position[0];
+
+            if vertices_at_position.len() > 1 {
+                locked[*vertex as usize] = true;
+            }
+        }
+    }
+
+    // Borders and non-manifold edges: Edges (by position) used by a number of triangles other than two.
+    let mut edge_use_count: HashMap<(u32, u32), u32> = HashMap::new();
+    for triangle in indices.chunks(3) {
+        for corner in 0..3 {
+            let a = position_ids[representatives[triangle[corner] as usize] as usize];
+            let b = position_ids[representatives[triangle[(corner + 1) % 3] as usize] as usize];
+
+            if a != b {
+                *edge_use_count.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut position_locked: Vec<bool> = vec![false; vertex_count];
+    for ((a, b), use_count) in edge_use_count {
+        if use_count != 2 {
+            position_locked[a as usize] = true;
+            position_locked[b as usize] = true;
+        }
+    }
+
+    for vertex in 0..vertex_count {
+        if position_locked[position_ids[representatives[vertex] as usize] as usize] {
+            locked[representatives[vertex] as usize] = true;
+        }
+    }
+
+    locked
+}
+
+fn build_vertex_triangles(indices: &[u32], vertex_count: usize) -> Vec<Vec<u32>> {
+    let mut vertex_triangles: Vec<Vec<u32>> = vec![vec!(); vertex_count];
+
+    for (position, index) in indices.iter().enumerate() {
+        vertex_triangles[*index as usize].push((position / 3) as u32);
+    }
+
+    vertex_triangles
+}
+
+struct CollapseCandidate {
+    from: u32,
+    to: u32,
+    // The mean squared distance to the planes, the square of the error.
+    // Candidates are sorted and cut off by this, so the result doesn't depend on the size of the triangles.
+    cost: f64
+}
+
+impl CollapseCandidate {
+    fn error(&self) -> f32 {
+        self.cost.sqrt() as f32
+    }
+}
+
+// Returns every possible collapse, cheapest first.
+fn find_collapse_candidates(mesh: &Mesh, indices: &[u32], locked: &[bool], quadrics: &[Quadric]) -> Vec<CollapseCandidate> {
+    let mut candidates: Vec<CollapseCandidate> = vec!();
+
+    for triangle in indices.chunks(3) {
+        for corner in 0..3 {
+            let from = triangle[corner];
+            let to = triangle[(corner + 1) % 3];
+
+            // Both directions of every edge are visited through the triangles on either side,
+            // except on borders, where both ends are locked anyway.
+            for (from, to) in [(from, to), (to, from)] {
+                if locked[from as usize] {
+                    continue;
+                }
+
+                let combined = quadrics[from as usize].add(&quadrics[to as usize]);
+
+                // The quadric error is area weighted, so it's divided by the total area to get back to a squared distance.
+                let cost = if combined.weight > 0.0 { (combined.error(&mesh.vertex_positions[to as usize]) / combined.weight).max(0.0) } else { 0.0 };
+
+                candidates.push(CollapseCandidate { from, to, cost });
+            }
+        }
+    }
+
+    // Sorting on the vertex indices as well keeps the order deterministic when costs are equal.
+    candidates.sort_by(|a, b| {
+        a.cost.partial_cmp(&b.cost).unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.from.cmp(&b.from))
+            .then(a.to.cmp(&b.to))
+    });
+    candidates.dedup_by(|a, b| a.from == b.from && a.to == b.to);
+
+    candidates
+}
+
+// A collapse is rejected if any of the remaining triangles around the moved vertex would turn around to face the other way.
+fn collapse_flips_triangle(mesh: &Mesh, indices: &[u32], triangles_around_from: &[u32], from: u32, to: u32) -> bool {
+    for triangle in triangles_around_from {
+        let triangle_vertices = &indices[*triangle as usize * 3..*triangle as usize * 3 + 3];
+
+        // These triangles disappear with the collapse
+        if triangle_vertices.contains(&to) {
+            continue;
+        }
+
+        let positions_before: Vec<beagle_math::Vector3> = triangle_vertices.iter().map(|x| mesh.vertex_positions[*x as usize]).collect();
+        let positions_after: Vec<beagle_math::Vector3> = triangle_vertices.iter().map(|x| mesh.vertex_positions[if *x == from { to } else { *x } as usize]).collect();
+
+        let normal_before = triangle_normal(&positions_before[0], &positions_before[1], &positions_before[2]);
+        let normal_after = triangle_normal(&positions_after[0], &positions_after[1], &positions_after[2]);
+
+        if normal_before.dot(&normal_after) <= 0.0 {
+            return true;
+        }
+    }
+
+    false
+}
+
+fn triangle_normal(vert1: &beagle_math::Vector3, vert2: &beagle_math::Vector3, vert3: &beagle_math::Vector3) -> beagle_math::Vector3 {
+    let edge1 = *vert2 - *vert1;
+    let edge2 = *vert3 - *vert1;
+
+    edge1.cross(&edge2)
+}
+
+fn remove_degenerate_triangles(indices: &[u32]) -> Vec<u32> {
+    indices
+        .chunks(3)
+        .filter(|triangle| triangle[0] != triangle[1] && triangle[1] != triangle[2] && triangle[0] != triangle[2])
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/*
+    A quadric is the symmetric 4x4 matrix (n, d)(n, d)^T built from the plane equation n.p + d = 0 of a triangle.
+    For a point p, [p 1] Q [p 1]^T is the squared distance from p to the plane, and adding quadrics together gives
+    the sum of squared distances to all their planes.
+
+    Since the matrix is symmetric, only 10 of its 16 values need storing.
+    Doubles are used as the values get large and precision matters when subtracting them.
+*/
+#[derive(Default, Clone, Copy)]
+struct Quadric {
+    a2: f64, ab: f64, ac: f64, ad: f64,
+    b2: f64, bc: f64, bd: f64,
+    c2: f64, cd: f64,
+    d2: f64,
+    // The total triangle area the quadric was built from
+    weight: f64
+}
+
+impl Quadric {
+    fn from_triangle(vert1: &beagle_math::Vector3, vert2: &beagle_math::Vector3, vert3: &beagle_math::Vector3) -> Quadric {
+        let normal = triangle_normal(vert1, vert2, vert3);
+        let length = normal.length();
+
+        if length <= 0.0 {
+            return Quadric::default();
+        }
+
+        // The cross product has a length of twice the triangle's area, which is used to weigh the quadric,
+        // so large triangles count for more than small ones.
+        let area = (length * 0.5) as f64;
+
+        let a = (normal.x / length) as f64;
+        let b = (normal.y / length) as f64;
+        let c = (normal.z / length) as f64;
+        let d = -(a * vert1.x as f64 + b * vert1.y as f64 + c * vert1.z as f64);
+
+        Quadric {
+            a2: a * a * area, ab: a * b * area, ac: a * c * area, ad: a * d * area,
+            b2: b * b * area, bc: b * c * area, bd: b * d * area,
+            c2: c * c * area, cd: c * d * area,
+            d2: d * d * area,
+            weight: area
+        }
+    }
+
+    fn add(&self, other: &Quadric) -> Quadric {
+        Quadric {
+            a2: self.a2 + other.a2, ab: self.ab + other.ab, ac: self.ac + other.ac, ad: self.ad + other.ad,
+            b2: self.b2 + other.b2, bc: self.bc + other.bc, bd: self.bd + other.bd,
+            c2: self.c2 + other.c2, cd: self.cd + other.cd,
+            d2: self.d2 + other.d2,
+            weight: self.weight + other.weight
+        }
+    }
+
+    fn error(&self, point: &beagle_math::Vector3) -> f64 {
+        let x = point.x as f64;
+        let y = point.y as f64;
+        let z = point.z as f64;
+
+        x * x * self.a2 + 2.0 * x * y * self.ab + 2.0 * x * z * self.ac + 2.0 * x * self.ad
+            + y * y * self.b2 + 2.0 * y * z * self.bc + 2.0 * y * self.bd
+            + z * z * self.c2 + 2.0 * z * self.cd
+            + self.d2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math;
+    use crate::asset::mesh::*;
+    use crate::asset::mesh::simplify::*;
+
+    // A flat grid of (size x size) quads in the XZ plane, with shared vertices.
+    fn create_grid(size: u32) -> Mesh {
+        let mut mesh = Mesh::default();
+
+        for z in 0..=size {
+            for x in 0..=size {
+                mesh.vertex_positions.push(beagle_math::Vector3::new(x as f32, 0.0, z as f32));
+            }
+        }
+
+        for z in 0..size {
+            for x in 0..size {
+                let top_left = z * (size + 1) + x;
+                let bottom_left = top_left + size + 1;
+                mesh.indices.extend_from_slice(&[top_left, bottom_left, top_left + 1, top_left + 1, bottom_left, bottom_left + 1]);
+            }
+        }
+
+        mesh
+    }
+
+    #[test]
+    fn should_collapse_flat_interior_without_error() {
+        let mesh = create_grid(8);
+
+        let lod = simplify(&mesh, 0, 0.001);
+
+        assert!(lod.indices.len() < mesh.indices.len());
+        assert_eq!(lod.error, 0.0);
+    }
+
+    #[test]
+    fn should_keep_border_vertices_when_simplifying() {
+        let mesh = create_grid(8);
+
+        let lod = simplify(&mesh, 0, f32::MAX);
+
+        for vertex in 0..mesh.vertex_positions.len() as u32 {
+            let position = mesh.vertex_positions[vertex as usize];
+            let is_border = position.x == 0.0 || position.x == 8.0 || position.z == 0.0 || position.z == 8.0;
+
+            if is_border {
+                assert!(lod.indices.contains(&vertex), "Border vertex {} was removed", vertex);
+            }
+        }
+    }
+
+    #[test]
+    fn should_not_simplify_when_error_is_not_allowed() {
+        let mut mesh = create_grid(4);
+
+        // A bump in the middle of the grid can't be removed without error
+        mesh.vertex_positions[12].y = 1.0;
+
+        let lod = simplify(&mesh, 0, 0.0);
+        let bump_kept = lod.indices.contains(&12);
+
+        assert!(bump_kept);
+    }
+
+    #[test]
+    fn should_stop_at_target_error_regardless_of_scale() {
+        let mut curved = create_grid(12);
+        for position in curved.vertex_positions.iter_mut() {
+            position.y = (position.x * 0.4).sin() * (position.z * 0.3).cos();
+        }
+
+        // Scaling by a power of two keeps every calculation exact, so the same collapses have to be made
+        let mut scaled = create_grid(12);
+        for (position, curved_position) in scaled.vertex_positions.iter_mut().zip(curved.vertex_positions.iter()) {
+            *position = *curved_position * 4.0;
+        }
+
+        let target_error = 0.02;
+        let lod = simplify(&curved, 0, target_error);
+        let scaled_lod = simplify(&scaled, 0, target_error * 4.0);
+
+        assert!(lod.indices.len() < curved.indices.len());
+        assert!(lod.indices.len() > simplify(&curved, 0, f32::MAX).indices.len());
+        assert!(lod.error <= target_error);
+        assert_eq!(lod.indices, scaled_lod.indices);
+    }
+
+    #[test]
+    fn should_build_lod_chain_with_decreasing_triangle_counts() {
+        let mut mesh = create_grid(16);
+        for (vertex, position) in mesh.vertex_positions.iter_mut().enumerate() {
+            position.y = ((vertex as f32) * 0.37).sin() * 0.1;
+        }
+
+        let lods = build_lod_chain(&mesh, &[0.5, 0.25]);
+
+        assert_eq!(lods.len(), 2);
+        assert!(lods[0].indices.len() <= mesh.indices.len() / 2);
+        assert!(lods[1].indices.len() < lods[0].indices.len());
+        assert!(lods[1].error >= lods[0].error);
+    }
+}
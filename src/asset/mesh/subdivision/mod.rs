@@ -0,0 +1,705 @@
+use std::collections::HashMap;
+
+use crate::beagle_math;
+use crate::asset::mesh::{self, Mesh};
+
+/*
+    Subdivision surfaces: refining a coarse mesh (a "cage") into a smooth, denser one.
+
+    Each iteration splits every face into smaller faces, and moves every vertex towards a weighted average of its neighbours,
+    so the mesh converges to a smooth surface. Two schemes are supported:
+
+    - Loop: For triangle meshes. Every triangle is split into 4, by adding a vertex on every edge.
+    - Catmull-Clark: For quad meshes. Every face with n corners is split into n quads, by adding a vertex on every edge and one in the face.
+
+    Meshes only hold triangles, so before Catmull-Clark runs, triangles are paired back up into the quads they most likely came from.
+    Triangles without a fitting partner stay triangles, which Catmull-Clark handles fine (they just turn into 3 quads).
+    After the last iteration the quads are split into triangles again.
+
+    Creases keep edges sharp. An edge with sharpness s stays fully sharp for the first floor(s) iterations, and is blended
+    between sharp and smooth when s is between 0 and 1. Every iteration the sharpness of the edges drops by 1, which makes
+    a crease with a sharpness of 2.5 rounder than one with 10. Open borders (and edges with more than 2 faces) are always sharp,
+    so an open mesh keeps its outline instead of shrinking away from it.
+
+    Positions are smoothed, but uvs, colors and normals are interpolated LINEARLY inside every face ("face-varying").
+    Smoothing the uvs as well would make textures swim, and corners on different sides of a uv seam could never be smoothed consistently.
+    Normals are renormalized after interpolation, but won't follow the new, smoother shape exactly.
+
+    The algorithms are described here:
+    - Loop: https://www.microsoft.com/en-us/research/wp-content/uploads/2016/02/thesis-10.pdf
+    - Catmull-Clark and creases: "Subdivision Surfaces in Character Animation", DeRose, Kass & Truong
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scheme {
+    Loop,
+    CatmullClark
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Crease {
+    // Indices of the vertices at both ends of the edge, in the vertex list of the mesh being subdivided
+    pub vertices: (u32, u32),
+    // f32::INFINITY keeps the edge sharp forever
+    pub sharpness: f32
+}
+
+#[derive(Debug, Clone)]
+pub struct SubdivisionOptions {
+    pub scheme: Scheme,
+    pub iterations: u32,
+    pub creases: Vec<Crease>
+}
+
+// The attributes of a single corner of a face
+#[derive(Default, Clone, Copy)]
+struct Attributes {
+    normal: beagle_math::Vector3,
+    uv: beagle_math::Vector2,
+    color: beagle_math::Vector4
+}
+
+#[derive(Clone, Copy)]
+struct Corner {
+    vertex: u32,
+    attributes: Attributes
+}
+
+// The mesh while it's being subdivided. Vertices are welded by position, and faces can have any number of corners.
+struct Level {
+    positions: Vec<beagle_math::Vector3>,
+    faces: Vec<Vec<Corner>>,
+    // Sharpness of creased edges, keyed by (smallest vertex, largest vertex)
+    sharpness: HashMap<(u32, u32), f32>
+}
+
+struct Edge {
+    vertices: (u32, u32),
+    // The faces using this edge, along with the position of the edge's first corner in that face
+    faces: Vec<(usize, usize)>,
+    sharpness: f32
+}
+
+struct Topology {
+    edges: Vec<Edge>,
+    edge_lookup: HashMap<(u32, u32), usize>,
+    vertex_edges: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>
+}
+
+pub fn subdivide(mesh: &Mesh, options: &SubdivisionOptions) -> Result<Mesh, String> {
+    let mut level = Level::from_mesh(mesh, &options.creases)?;
+
+    if options.scheme == Scheme::CatmullClark {
+        level = pair_triangles_into_quads(level);
+    }
+
+    for _ in 0..options.iterations {
+        level = match options.scheme {
+            Scheme::Loop => loop_subdivide(&level),
+            Scheme::CatmullClark => catmull_clark_subdivide(&level)
+        };
+    }
+
+    let mut result = level.to_mesh(!mesh.vertex_normals.is_empty(), !mesh.vertex_uvs.is_empty(), !mesh.vertex_colors.is_empty());
+    result.name = mesh.name.clone();
+    result.children = mesh.children.clone();
+    result.transform = mesh.transform;
+    result.material = mesh.material.clone();
+
+    Ok(result)
+}
+
+impl Level {
+    fn from_mesh(mesh: &Mesh, creases: &[Crease]) -> Result<Level, String> {
+        let vertex_count = mesh.vertex_positions.len();
+        if let Some(crease) = creases.iter().find(|crease| crease.vertices.0 as usize >= vertex_count || crease.vertices.1 as usize >= vertex_count) {
+            return Err(format!("Crease ({}, {}) uses a vertex outside of the {} vertices of the mesh.", crease.vertices.0, crease.vertices.1, vertex_count));
+        }
+
+        let mut positions: Vec<beagle_math::Vector3> = vec!();
+        let mut seen_positions: HashMap<[u32; 3], u32> = HashMap::new();
+
+        let vertex_ids: Vec<u32> = mesh.vertex_positions.iter().map(|position| {
+            let key = [(position.x + 0.0).to_bits(), (position.y + 0.0).to_bits(), (position.z + 0.0).to_bits()];
+            *seen_positions.entry(key).or_insert_with(|| {
+                positions.push(*position);
+                (positions.len() - 1) as u32
+            })
+        }).collect();
+
+        let faces: Vec<Vec<Corner>> = mesh.indices.chunks_exact(3).map(|triangle| {
+            triangle.iter().map(|index| Corner {
+                vertex: vertex_ids[*index as usize],
+                attributes: Attributes {
+                    normal: mesh.vertex_normals.get(*index as usize).copied().unwrap_or_default(),
+                    uv: mesh.vertex_uvs.get(*index as usize).copied().unwrap_or_default(),
+                    color: mesh.vertex_colors.get(*index as usize).copied().unwrap_or_default()
+                }
+            }).collect()
+        }).collect();
+
+        let sharpness: HashMap<(u32, u32), f32> = creases.iter()
+            .filter(|crease| crease.sharpness > 0.0)
+            .map(|crease| (edge_key(vertex_ids[crease.vertices.0 as usize], vertex_ids[crease.vertices.1 as usize]), crease.sharpness))
+            .collect();
+
+        Ok(Level { positions, faces, sharpness })
+    }
+
+    // Splits every face into a fan of triangles, then welds the corners into a Mesh
+    fn to_mesh(&self, has_normals: bool, has_uvs: bool, has_colors: bool) -> Mesh {
+        let mut corners: Vec<mesh::Vertex> = vec!();
+
+        for face in &self.faces {
+            for corner in 1..face.len() - 1 {
+                for face_corner in [&face[0], &face[corner], &face[corner + 1]] {
+                    let attributes = &face_corner.attributes;
+                    let normal_length = attributes.normal.length();
+
+                    corners.push(mesh::Vertex {
+                        position: self.positions[face_corner.vertex as usize],
+                        normal: if has_normals && normal_length > 0.0 { attributes.normal.mul(1.0 / normal_length) } else { beagle_math::Vector3::zero() },
+                        uv: if has_uvs { attributes.uv } else { beagle_math::Vector2::default() },
+                        color: if has_colors { attributes.color } else { beagle_math::Vector4::default() }
+                    });
+                }
+            }
+        }
+
+        let (vertices, indices) = mesh::weld_vertices(&corners);
+
+        Mesh {
+            vertex_positions: vertices.iter().map(|vertex| vertex.position).collect(),
+            vertex_normals: if has_normals { vertices.iter().map(|vertex| vertex.normal).collect() } else { vec!() },
+            vertex_uvs: if has_uvs { vertices.iter().map(|vertex| vertex.uv).collect() } else { vec!() },
+            vertex_colors: if has_colors { vertices.iter().map(|vertex| vertex.color).collect() } else { vec!() },
+            indices,
+            ..Default::default()
+        }
+    }
+
+    fn build_topology(&self) -> Topology {
+        let mut topology = Topology {
+            edges: vec!(),
+            edge_lookup: HashMap::new(),
+            vertex_edges: vec![vec!(); self.positions.len()],
+            vertex_faces: vec![vec!(); self.positions.len()]
+        };
+
+        for (face_index, face) in self.faces.iter().enumerate() {
+            for corner in 0..face.len() {
+                let a = face[corner].vertex;
+                let b = face[(corner + 1) % face.len()].vertex;
+                let key = edge_key(a, b);
+
+                let edge_index = match topology.edge_lookup.get(&key) {
+                    Some(edge_index) => *edge_index,
+                    None => {
+                        topology.edges.push(Edge { vertices: key, faces: vec!(), sharpness: 0.0 });
+                        let edge_index = topology.edges.len() - 1;
+                        topology.edge_lookup.insert(key, edge_index);
+                        topology.vertex_edges[a as usize].push(edge_index);
+                        topology.vertex_edges[b as usize].push(edge_index);
+                        edge_index
+                    }
+                };
+
+                topology.edges[edge_index].faces.push((face_index, corner));
+                topology.vertex_faces[a as usize].push(face_index);
+            }
+        }
+
+        for edge in topology.edges.iter_mut() {
+            // Borders and non-manifold edges have no proper smooth rule, so they are always sharp
+            edge.sharpness = if edge.faces.len() != 2 {
+                f32::INFINITY
+            } else {
+                self.sharpness.get(&edge.vertices).copied().unwrap_or(0.0)
+            };
+        }
+
+        topology
+    }
+
+    // Sharpness for the two halves of every creased edge after it has been split by "edge_vertex"
+    fn child_sharpness(&self, topology: &Topology, edge_vertex_offset: u32) -> HashMap<(u32, u32), f32> {
+        let mut result: HashMap<(u32, u32), f32> = HashMap::new();
+
+        for (edge_index, edge) in topology.edges.iter().enumerate() {
+            if edge.faces.len() != 2 {
+                continue;
+            }
+
+            let sharpness = edge.sharpness - 1.0;
+            if sharpness > 0.0 {
+                let edge_vertex = edge_vertex_offset + edge_index as u32;
+                result.insert(edge_key(edge.vertices.0, edge_vertex), sharpness);
+                result.insert(edge_key(edge_vertex, edge.vertices.1), sharpness);
+            }
+        }
+
+        result
+    }
+}
+
+/*
+    Loop subdivision, one iteration.
+
+    New vertex on an edge between a and b, with c and d opposite the edge in the two triangles:
+        3/8 * (a + b) + 1/8 * (c + d)
+    Existing vertex with n neighbours:
+        (1 - n * beta) * vertex + beta * sum(neighbours), beta = 3/16 for n = 3 and 3/(8n) otherwise (Warren's simplification)
+*/
+fn loop_subdivide(level: &Level) -> Level {
+    for face in &level.faces {
+        if face.len() != 3 {
+            panic!("Loop subdivision only works on triangles, found a face with {} corners.", face.len());
+        }
+    }
+
+    let topology = level.build_topology();
+    let mut positions: Vec<beagle_math::Vector3> = Vec::with_capacity(level.positions.len() + topology.edges.len());
+
+    for vertex in 0..level.positions.len() {
+        let position = level.positions[vertex];
+        let neighbours: Vec<beagle_math::Vector3> = topology.vertex_edges[vertex].iter()
+            .map(|edge_index| level.positions[other_end(&topology.edges[*edge_index], vertex as u32) as usize])
+            .collect();
+
+        let smooth = if neighbours.is_empty() {
+            position
+        } else {
+            let n = neighbours.len() as f32;
+            let beta = if neighbours.len() == 3 { 3.0 / 16.0 } else { 3.0 / (8.0 * n) };
+            position.mul(1.0 - n * beta).add(&sum(&neighbours).mul(beta))
+        };
+
+        positions.push(apply_vertex_crease_rule(level, &topology, vertex as u32, &smooth));
+    }
+
+    for edge in &topology.edges {
+        let a = level.positions[edge.vertices.0 as usize];
+        let b = level.positions[edge.vertices.1 as usize];
+        let middle = a.add(&b).mul(0.5);
+
+        let smooth = if edge.faces.len() == 2 {
+            let opposite: Vec<beagle_math::Vector3> = edge.faces.iter()
+                .map(|(face, corner)| level.positions[level.faces[*face][(corner + 2) % 3].vertex as usize])
+                .collect();
+
+            a.add(&b).mul(3.0 / 8.0).add(&opposite[0].add(&opposite[1]).mul(1.0 / 8.0))
+        } else {
+            middle
+        };
+
+        positions.push(lerp(&smooth, &middle, edge.sharpness.min(1.0)));
+    }
+
+    let edge_vertex_offset = level.positions.len() as u32;
+    let mut faces: Vec<Vec<Corner>> = Vec::with_capacity(level.faces.len() * 4);
+
+    for face in &level.faces {
+        // The corner on the middle of the edge starting at each corner of the triangle
+        let edge_corners: Vec<Corner> = (0..3).map(|corner| {
+            let next = (corner + 1) % 3;
+            Corner {
+                vertex: edge_vertex_offset + topology.edge_lookup[&edge_key(face[corner].vertex, face[next].vertex)] as u32,
+                attributes: average_attributes(&[face[corner].attributes, face[next].attributes])
+            }
+        }).collect();
+
+        // Three corner triangles and one in the middle, all wound the same way as the original
+        faces.push(vec!(face[0], edge_corners[0], edge_corners[2]));
+        faces.push(vec!(edge_corners[0], face[1], edge_corners[1]));
+        faces.push(vec!(edge_corners[2], edge_corners[1], face[2]));
+        faces.push(vec!(edge_corners[0], edge_corners[1], edge_corners[2]));
+    }
+
+    Level {
+        positions,
+        faces,
+        sharpness: level.child_sharpness(&topology, edge_vertex_offset)
+    }
+}
+
+/*
+    Catmull-Clark subdivision, one iteration.
+
+    New vertex in a face: the average of its corners.
+    New vertex on an edge: the average of both ends and the new vertices of both faces next to it.
+    Existing vertex with n edges: (F + 2R + (n - 3) * vertex) / n,
+    where F is the average of the new face vertices around it and R the average of the middles of its edges.
+*/
+fn catmull_clark_subdivide(level: &Level) -> Level {
+    let topology = level.build_topology();
+
+    let face_points: Vec<beagle_math::Vector3> = level.faces.iter().map(|face| {
+        let corners: Vec<beagle_math::Vector3> = face.iter().map(|corner| level.positions[corner.vertex as usize]).collect();
+        sum(&corners).mul(1.0 / corners.len() as f32)
+    }).collect();
+
+    let mut positions: Vec<beagle_math::Vector3> = Vec::with_capacity(level.positions.len() + topology.edges.len() + level.faces.len());
+
+    for vertex in 0..level.positions.len() {
+        let position = level.positions[vertex];
+        let vertex_edges = &topology.vertex_edges[vertex];
+        let vertex_faces = &topology.vertex_faces[vertex];
+
+        let smooth = if vertex_edges.is_empty() || vertex_faces.is_empty() {
+            position
+        } else {
+            let n = vertex_edges.len() as f32;
+            let face_average = sum(&vertex_faces.iter().map(|face| face_points[*face]).collect::<Vec<_>>()).mul(1.0 / vertex_faces.len() as f32);
+            let edge_middles: Vec<beagle_math::Vector3> = vertex_edges.iter()
+                .map(|edge_index| {
+                    let edge = &topology.edges[*edge_index];
+                    level.positions[edge.vertices.0 as usize].add(&level.positions[edge.vertices.1 as usize]).mul(0.5)
+                })
+                .collect();
+            let edge_average = sum(&edge_middles).mul(1.0 / n);
+
+            face_average.add(&edge_average.mul(2.0)).add(&position.mul(n - 3.0)).mul(1.0 / n)
+        };
+
+        positions.push(apply_vertex_crease_rule(level, &topology, vertex as u32, &smooth));
+    }
+
+    for edge in &topology.edges {
+        let a = level.positions[edge.vertices.0 as usize];
+        let b = level.positions[edge.vertices.1 as usize];
+        let middle = a.add(&b).mul(0.5);
+
+        let smooth = if edge.faces.len() == 2 {
+            a.add(&b).add(&face_points[edge.faces[0].0]).add(&face_points[edge.faces[1].0]).mul(0.25)
+        } else {
+            middle
+        };
+
+        positions.push(lerp(&smooth, &middle, edge.sharpness.min(1.0)));
+    }
+
+    let edge_vertex_offset = level.positions.len() as u32;
+    let face_vertex_offset = edge_vertex_offset + topology.edges.len() as u32;
+    positions.extend_from_slice(&face_points);
+
+    let mut faces: Vec<Vec<Corner>> = vec!();
+
+    for (face_index, face) in level.faces.iter().enumerate() {
+        let corner_count = face.len();
+        let face_corner = Corner {
+            vertex: face_vertex_offset + face_index as u32,
+            attributes: average_attributes(&face.iter().map(|corner| corner.attributes).collect::<Vec<_>>())
+        };
+
+        let edge_corners: Vec<Corner> = (0..corner_count).map(|corner| {
+            let next = (corner + 1) % corner_count;
+            Corner {
+                vertex: edge_vertex_offset + topology.edge_lookup[&edge_key(face[corner].vertex, face[next].vertex)] as u32,
+                attributes: average_attributes(&[face[corner].attributes, face[next].attributes])
+            }
+        }).collect();
+
+        // One quad per corner: corner -> middle of the next edge -> face center -> middle of the previous edge
+        for corner in 0..corner_count {
+            let previous = (corner + corner_count - 1) % corner_count;
+            faces.push(vec!(face[corner], edge_corners[corner], face_corner, edge_corners[previous]));
+        }
+    }
+
+    Level {
+        positions,
+        faces,
+        sharpness: level.child_sharpness(&topology, edge_vertex_offset)
+    }
+}
+
+/*
+    Vertices with creased edges follow different rules than smooth ones:
+    - With 2 sharp edges the vertex lies on a crease, and only follows the crease: 3/4 * vertex + 1/8 * (a + b)
+    - With more than 2 sharp edges the vertex is a corner, and doesn't move at all (just like the corners of an open border)
+    When the sharp edges have a sharpness below 1, the result is blended with the smooth position.
+*/
+fn apply_vertex_crease_rule(level: &Level, topology: &Topology, vertex: u32, smooth: &beagle_math::Vector3) -> beagle_math::Vector3 {
+    let sharp_edges: Vec<&Edge> = topology.vertex_edges[vertex as usize].iter()
+        .map(|edge_index| &topology.edges[*edge_index])
+        .filter(|edge| edge.sharpness > 0.0)
+        .collect();
+
+    if sharp_edges.len() < 2 {
+        return *smooth;
+    }
+
+    let position = level.positions[vertex as usize];
+
+    // A vertex with just two edges, both sharp, is the corner of an open border (like the corners of a plane) and stays put
+    let is_border_corner = topology.vertex_edges[vertex as usize].len() == 2;
+
+    let sharp = if sharp_edges.len() == 2 && !is_border_corner {
+        let a = level.positions[other_end(sharp_edges[0], vertex) as usize];
+        let b = level.positions[other_end(sharp_edges[1], vertex) as usize];
+        position.mul(0.75).add(&a.add(&b).mul(0.125))
+    } else {
+        position
+    };
+
+    let sharpness = sharp_edges.iter().map(|edge| edge.sharpness).sum::<f32>() / sharp_edges.len() as f32;
+
+    lerp(smooth, &sharp, sharpness.min(1.0))
+}
+
+/*
+    Catmull-Clark works best on quads, but meshes only store triangles. Most quad meshes were triangulated by splitting every quad
+    along one of its diagonals, so pairs of neighbouring triangles are merged back into quads where that looks likely:
+    - The shared edge is the longest edge of both triangles, like a diagonal would be
+    - The triangles face (nearly) the same direction
+    The best pairs (the flattest) are merged first.
+*/
+fn pair_triangles_into_quads(level: Level) -> Level {
+    let topology = level.build_topology();
+
+    let face_normals: Vec<beagle_math::Vector3> = level.faces.iter().map(|face| {
+        let a = level.positions[face[0].vertex as usize];
+        let b = level.positions[face[1].vertex as usize];
+        let c = level.positions[face[2].vertex as usize];
+        let normal = b.add(&a.mul(-1.0)).cross(&c.add(&a.mul(-1.0)));
+        let length = normal.length();
+        if length > 0.0 { normal.mul(1.0 / length) } else { normal }
+    }).collect();
+
+    // Cosine of the largest angle allowed between the two triangles of a quad (about 30 degrees)
+    const MIN_NORMAL_SIMILARITY: f32 = 0.866;
+
+    let mut candidates: Vec<(f32, usize)> = vec!();
+    for (edge_index, edge) in topology.edges.iter().enumerate() {
+        if edge.faces.len() != 2 || edge.sharpness.is_infinite() {
+            continue;
+        }
+
+        let (first, first_corner) = edge.faces[0];
+        let (second, second_corner) = edge.faces[1];
+        if !is_longest_edge(&level, first, first_corner) || !is_longest_edge(&level, second, second_corner) {
+            continue;
+        }
+
+        let similarity = face_normals[first].dot(&face_normals[second]);
+        if similarity >= MIN_NORMAL_SIMILARITY {
+            candidates.push((similarity, edge_index));
+        }
+    }
+
+    // Sort by similarity, best first. The edge index breaks ties, so the result doesn't depend on the sort implementation.
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut merged: Vec<bool> = vec![false; level.faces.len()];
+    let mut faces: Vec<Vec<Corner>> = vec!();
+
+    for (_, edge_index) in candidates {
+        let edge = &topology.edges[edge_index];
+        let (first, first_corner) = edge.faces[0];
+        let (second, second_corner) = edge.faces[1];
+        if merged[first] || merged[second] {
+            continue;
+        }
+
+        merged[first] = true;
+        merged[second] = true;
+
+        // The first triangle is a -> b -> c along the shared edge a-b, and the second b -> a -> d, so the quad is a -> d -> b -> c
+        let first_face = &level.faces[first];
+        let second_face = &level.faces[second];
+        faces.push(vec!(
+            first_face[first_corner],
+            second_face[(second_corner + 2) % 3],
+            first_face[(first_corner + 1) % 3],
+            first_face[(first_corner + 2) % 3]));
+    }
+
+    for (face_index, face) in level.faces.iter().enumerate() {
+        if !merged[face_index] {
+            faces.push(face.clone());
+        }
+    }
+
+    Level {
+        positions: level.positions,
+        faces,
+        sharpness: level.sharpness
+    }
+}
+
+fn is_longest_edge(level: &Level, face: usize, corner: usize) -> bool {
+    let corners = &level.faces[face];
+    let edge_length = |start: usize| {
+        let a = level.positions[corners[start].vertex as usize];
+        let b = level.positions[corners[(start + 1) % 3].vertex as usize];
+        b.add(&a.mul(-1.0)).length()
+    };
+
+    let length = edge_length(corner);
+    length >= edge_length((corner + 1) % 3) && length >= edge_length((corner + 2) % 3)
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+fn other_end(edge: &Edge, vertex: u32) -> u32 {
+    if edge.vertices.0 == vertex { edge.vertices.1 } else { edge.vertices.0 }
+}
+
+fn sum(vectors: &[beagle_math::Vector3]) -> beagle_math::Vector3 {
+    vectors.iter().fold(beagle_math::Vector3::zero(), |total, vector| total.add(vector))
+}
+
+fn lerp(from: &beagle_math::Vector3, to: &beagle_math::Vector3, amount: f32) -> beagle_math::Vector3 {
+    from.mul(1.0 - amount).add(&to.mul(amount))
+}
+
+fn average_attributes(attributes: &[Attributes]) -> Attributes {
+    let weight = 1.0 / attributes.len() as f32;
+    let mut result = Attributes::default();
+
+    for attribute in attributes {
+        result.normal = result.normal.add(&attribute.normal.mul(weight));
+        result.uv.x += attribute.uv.x * weight;
+        result.uv.y += attribute.uv.y * weight;
+        result.color.x += attribute.color.x * weight;
+        result.color.y += attribute.color.y * weight;
+        result.color.z += attribute.color.z * weight;
+        result.color.w += attribute.color.w * weight;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math;
+    use crate::asset::mesh::*;
+    use crate::asset::mesh::subdivision::*;
+
+    // A cube of 2x2x2 around the origin, each side made of 2 triangles, wound clockwise seen from outside
+    fn create_cube() -> Mesh {
+        let mut mesh = Mesh::default();
+        for z in [-1.0, 1.0] {
+            for y in [-1.0, 1.0] {
+                for x in [-1.0, 1.0] {
+                    mesh.vertex_positions.push(beagle_math::Vector3::new(x, y, z));
+                }
+            }
+        }
+
+        // Vertex index = x + 2y + 4z, with 0 for -1 and 1 for 1
+        let quads: [[u32; 4]; 6] = [
+            [0, 2, 3, 1], [4, 5, 7, 6],
+            [0, 1, 5, 4], [2, 6, 7, 3],
+            [0, 4, 6, 2], [1, 3, 7, 5]
+        ];
+
+        for quad in quads {
+            mesh.indices.extend_from_slice(&[quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+        }
+
+        mesh
+    }
+
+    fn options(scheme: Scheme, iterations: u32) -> SubdivisionOptions {
+        SubdivisionOptions { scheme, iterations, creases: vec!() }
+    }
+
+    fn distance_from_origin(position: &beagle_math::Vector3) -> f32 {
+        position.length()
+    }
+
+    #[test]
+    fn should_split_every_triangle_in_four_with_loop() {
+        let cube = create_cube();
+
+        let result = subdivide(&cube, &options(Scheme::Loop, 2)).unwrap();
+
+        assert_eq!(result.indices.len(), cube.indices.len() * 16);
+        assert!(crate::asset::mesh::validation::validate(&result).is_valid());
+    }
+
+    #[test]
+    fn should_pair_triangles_into_quads_for_catmull_clark() {
+        let cube = create_cube();
+
+        let result = subdivide(&cube, &options(Scheme::CatmullClark, 1)).unwrap();
+
+        // 6 quads become 24 quads, which are 48 triangles using 6 + 12 + 8 = 26 vertices
+        assert_eq!(result.indices.len(), 48 * 3);
+        assert_eq!(result.vertex_positions.len(), 26);
+        assert!(crate::asset::mesh::validation::validate(&result).is_valid());
+    }
+
+    #[test]
+    fn should_round_cube_towards_sphere() {
+        let result = subdivide(&create_cube(), &options(Scheme::CatmullClark, 3)).unwrap();
+
+        let distances: Vec<f32> = result.vertex_positions.iter().map(distance_from_origin).collect();
+        let min = distances.iter().cloned().fold(f32::MAX, f32::min);
+        let max = distances.iter().cloned().fold(0.0, f32::max);
+
+        // The corners of the cube are sqrt(3) = 1.73 away and the face centers 1.0, the limit surface is a lot rounder
+        assert!(max / min < 1.15, "Ratio was {}", max / min);
+    }
+
+    #[test]
+    fn should_keep_infinitely_sharp_creases_straight() {
+        let mut cube_options = options(Scheme::CatmullClark, 2);
+
+        // All 4 edges around the top (+z) face of the cube
+        cube_options.creases = [(4, 5), (5, 7), (7, 6), (6, 4)].iter()
+            .map(|vertices| Crease { vertices: *vertices, sharpness: f32::INFINITY })
+            .collect();
+
+        let result = subdivide(&create_cube(), &cube_options).unwrap();
+
+        // The top face stays completely flat
+        for position in &result.vertex_positions {
+            if position.z > 0.99 {
+                assert!((position.z - 1.0).abs() < 0.0001, "Vertex at {:?} left the top face", position);
+            }
+        }
+        assert!(result.vertex_positions.iter().filter(|position| (position.z - 1.0).abs() < 0.0001).count() > 9);
+    }
+
+    #[test]
+    fn should_reject_creases_outside_of_mesh() {
+        let mut cube_options = options(Scheme::CatmullClark, 1);
+        cube_options.creases = vec!(Crease { vertices: (4, 8), sharpness: f32::INFINITY });
+
+        assert!(subdivide(&create_cube(), &cube_options).is_err());
+    }
+
+    #[test]
+    fn should_interpolate_uvs_linearly_and_keep_open_borders() {
+        let quad = Mesh {
+            vertex_positions: vec!(
+                beagle_math::Vector3::new(0.0, 0.0, 0.0),
+                beagle_math::Vector3::new(0.0, 1.0, 0.0),
+                beagle_math::Vector3::new(1.0, 1.0, 0.0),
+                beagle_math::Vector3::new(1.0, 0.0, 0.0)),
+            vertex_uvs: vec!(
+                beagle_math::Vector2::new(0.0, 1.0),
+                beagle_math::Vector2::new(0.0, 0.0),
+                beagle_math::Vector2::new(1.0, 0.0),
+                beagle_math::Vector2::new(1.0, 1.0)),
+            indices: vec!(0, 1, 2, 0, 2, 3),
+            ..Default::default()
+        };
+
+        let result = subdivide(&quad, &options(Scheme::CatmullClark, 1)).unwrap();
+
+        // A flat quad is split into four flat quads, with uvs following the positions exactly
+        assert_eq!(result.vertex_positions.len(), 9);
+        for (position, uv) in result.vertex_positions.iter().zip(result.vertex_uvs.iter()) {
+            assert!((uv.x - position.x).abs() < 0.0001 && (uv.y - (1.0 - position.y)).abs() < 0.0001);
+        }
+
+        // The corners stay where they are, as open borders are sharp
+        assert!(result.vertex_positions.iter().any(|position| position.x == 1.0 && position.y == 1.0));
+    }
+}
@@ -0,0 +1,536 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::beagle_math;
+use crate::asset::mesh::Mesh;
+
+/*
+    Validation and repair of meshes.
+
+    Meshes coming from files (or from my own code...) can contain all sorts of problems that either break the renderer outright,
+    like indices pointing outside of the vertex buffer, or that cause subtle issues later, like degenerate triangles producing NaN normals.
+
+    "validate" finds the problems and reports them, without changing anything.
+    The "remove_..." and "fix_..." functions each repair one kind of problem, and "repair" runs all of them in a sensible order.
+    Non-manifold edges are only reported, as there is no single right way of fixing them.
+
+    Edges are compared by vertex POSITION rather than by vertex index. Meshes often have several vertices at the same position
+    (for example where uvs or normals differ), and those should still count as being connected.
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshIssue {
+    IndexCountNotDivisibleByThree { index_count: usize },
+    AttributeCountMismatch { attribute: &'static str, count: usize, expected: usize },
+    IndexOutOfRange { triangle: usize, index: u32 },
+    NonFinitePosition { vertex: u32 },
+    // A triangle using the same vertex more than once
+    DegenerateTriangle { triangle: usize },
+    // A triangle with three different vertices, but with no area because they lie on a line (or on top of each other)
+    ZeroAreaTriangle { triangle: usize },
+    DuplicateTriangle { triangle: usize, duplicate_of: usize },
+    UnreferencedVertex { vertex: u32 },
+    // Two neighbouring triangles whose shared edge runs the same direction in both, meaning one of them is facing the wrong way
+    InconsistentWinding { triangle: usize, neighbour: usize },
+    // An edge shared by more than two triangles
+    NonManifoldEdge { vertex_a: u32, vertex_b: u32, triangle_count: usize }
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<MeshIssue>
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RepairReport {
+    pub removed_triangles: usize,
+    pub flipped_triangles: usize,
+    pub removed_vertices: usize
+}
+
+pub fn validate(mesh: &Mesh) -> ValidationReport {
+    let mut issues: Vec<MeshIssue> = vec!();
+    let vertex_count = mesh.vertex_positions.len();
+
+    if !mesh.indices.len().is_multiple_of(3) {
+        issues.push(MeshIssue::IndexCountNotDivisibleByThree { index_count: mesh.indices.len() });
+    }
+
+    if !mesh.vertex_normals.is_empty() && mesh.vertex_normals.len() != vertex_count {
+        issues.push(MeshIssue::AttributeCountMismatch { attribute: "normal", count: mesh.vertex_normals.len(), expected: vertex_count });
+    }
+
+    if !mesh.vertex_uvs.is_empty() && mesh.vertex_uvs.len() != vertex_count {
+        issues.push(MeshIssue::AttributeCountMismatch { attribute: "uv", count: mesh.vertex_uvs.len(), expected: vertex_count });
+    }
+
+    if !mesh.vertex_colors.is_empty() && mesh.vertex_colors.len() != vertex_count {
+        issues.push(MeshIssue::AttributeCountMismatch { attribute: "color", count: mesh.vertex_colors.len(), expected: vertex_count });
+    }
+
+    for (vertex, position) in mesh.vertex_positions.iter().enumerate() {
+        if !is_finite(position) {
+            issues.push(MeshIssue::NonFinitePosition { vertex: vertex as u32 });
+        }
+    }
+
+    let mut referenced: Vec<bool> = vec![false; vertex_count];
+    let mut valid_triangles: Vec<usize> = vec!();
+
+    for (triangle, triangle_vertices) in mesh.indices.chunks_exact(3).enumerate() {
+        let mut in_range = true;
+        for index in triangle_vertices {
+            if *index as usize >= vertex_count {
+                issues.push(MeshIssue::IndexOutOfRange { triangle, index: *index });
+                in_range = false;
+            } else {
+                referenced[*index as usize] = true;
+            }
+        }
+
+        if !in_range {
+            continue;
+        }
+
+        if is_index_degenerate(triangle_vertices) {
+            issues.push(MeshIssue::DegenerateTriangle { triangle });
+        } else if is_zero_area(mesh, triangle_vertices) {
+            issues.push(MeshIssue::ZeroAreaTriangle { triangle });
+        } else {
+            valid_triangles.push(triangle);
+        }
+    }
+
+    let mut seen_triangles: HashMap<[u32; 3], usize> = HashMap::new();
+    for triangle in &valid_triangles {
+        let key = canonical_triangle(&mesh.indices[triangle * 3..triangle * 3 + 3]);
+
+        match seen_triangles.get(&key) {
+            Some(duplicate_of) => issues.push(MeshIssue::DuplicateTriangle { triangle: *triangle, duplicate_of: *duplicate_of }),
+            None => { seen_triangles.insert(key, *triangle); }
+        }
+    }
+
+    for (vertex, is_referenced) in referenced.iter().enumerate() {
+        if !is_referenced {
+            issues.push(MeshIssue::UnreferencedVertex { vertex: vertex as u32 });
+        }
+    }
+
+    let position_ids = find_position_ids(mesh);
+    let edges = build_edge_map(mesh, &position_ids, &valid_triangles);
+
+    let mut edge_keys: Vec<&(u32, u32)> = edges.keys().collect();
+    edge_keys.sort();
+
+    for edge in edge_keys {
+        let edge_triangles = &edges[edge];
+
+        if edge_triangles.len() > 2 {
+            issues.push(MeshIssue::NonManifoldEdge { vertex_a: edge.0, vertex_b: edge.1, triangle_count: edge_triangles.len() });
+        } else if edge_triangles.len() == 2 {
+            // If the edge runs the same direction in both triangles, they disagree on which side is the front.
+            let (first_triangle, first_direction) = edge_triangles[0];
+            let (second_triangle, second_direction) = edge_triangles[1];
+
+            if first_direction == second_direction {
+                issues.push(MeshIssue::InconsistentWinding { triangle: first_triangle.min(second_triangle), neighbour: first_triangle.max(second_triangle) });
+            }
+        }
+    }
+
+    ValidationReport {
+        issues
+    }
+}
+
+// Runs every repair, in an order where later repairs can't bring back problems fixed by earlier ones.
+pub fn repair(mesh: &mut Mesh) -> RepairReport {
+    let mut report = RepairReport::default();
+
+    // Trailing indices that don't make up a whole triangle are useless
+    let whole_triangle_index_count = mesh.indices.len() - mesh.indices.len() % 3;
+    mesh.indices.truncate(whole_triangle_index_count);
+
+    report.removed_triangles += remove_out_of_range_triangles(mesh);
+    report.removed_triangles += remove_non_finite_triangles(mesh);
+    report.removed_triangles += remove_degenerate_triangles(mesh);
+    report.removed_triangles += remove_duplicate_triangles(mesh);
+    report.flipped_triangles += fix_winding(mesh);
+    report.removed_vertices += remove_unreferenced_vertices(mesh);
+
+    report
+}
+
+pub fn remove_out_of_range_triangles(mesh: &mut Mesh) -> usize {
+    let vertex_count = mesh.vertex_positions.len();
+    retain_triangles(mesh, |triangle| triangle.iter().all(|index| (*index as usize) < vertex_count))
+}
+
+// Removes triangles using a vertex with a NaN or infinite position. The vertices themselves are left for "remove_unreferenced_vertices".
+pub fn remove_non_finite_triangles(mesh: &mut Mesh) -> usize {
+    let vertex_positions = mesh.vertex_positions.clone();
+    retain_triangles(mesh, |triangle| triangle.iter().all(|index| is_finite(&vertex_positions[*index as usize])))
+}
+
+// Removes both triangles using a vertex more than once and triangles with zero area.
+pub fn remove_degenerate_triangles(mesh: &mut Mesh) -> usize {
+    let mesh_positions_only = Mesh {
+        vertex_positions: mesh.vertex_positions.clone(),
+        ..Default::default()
+    };
+
+    retain_triangles(mesh, |triangle| !is_index_degenerate(triangle) && !is_zero_area(&mesh_positions_only, triangle))
+}
+
+// Removes every triangle using the same three vertices, with the same winding, as a triangle before it.
+pub fn remove_duplicate_triangles(mesh: &mut Mesh) -> usize {
+    let mut seen_triangles: HashSet<[u32; 3]> = HashSet::new();
+    retain_triangles(mesh, |triangle| seen_triangles.insert(canonical_triangle(triangle)))
+}
+
+/*
+    Makes the winding of every triangle agree with its neighbours, by flipping triangles that disagree.
+
+    Starting from the first triangle of each connected part of the mesh, the agreement is spread across shared edges (a breadth first search).
+    The first triangle of each part decides the winding for the whole part, so the result is consistent, but not necessarily facing outwards.
+
+    Only edges shared by exactly two triangles are followed, as the winding across a non-manifold edge has no clear answer.
+    Returns the number of flipped triangles.
+*/
+pub fn fix_winding(mesh: &mut Mesh) -> usize {
+    let triangle_count = mesh.indices.len() / 3;
+    let all_triangles: Vec<usize> = (0..triangle_count).collect();
+    let position_ids = find_position_ids(mesh);
+    let edges = build_edge_map(mesh, &position_ids, &all_triangles);
+
+    let mut triangle_edges: Vec<Vec<(u32, u32)>> = vec![vec!(); triangle_count];
+    for (edge, edge_triangles) in &edges {
+        if edge_triangles.len() == 2 {
+            for (triangle, _) in edge_triangles {
+                triangle_edges[*triangle].push(*edge);
+            }
+        }
+    }
+
+    // Sort the edges so the search visits triangles in the same order every time
+    for edges_of_triangle in triangle_edges.iter_mut() {
+        edges_of_triangle.sort();
+    }
+
+    let mut visited: Vec<bool> = vec![false; triangle_count];
+    let mut flipped: Vec<bool> = vec![false; triangle_count];
+
+    for seed in 0..triangle_count {
+        if visited[seed] {
+            continue;
+        }
+
+        visited[seed] = true;
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        queue.push_back(seed);
+
+        while let Some(triangle) = queue.pop_front() {
+            for edge in &triangle_edges[triangle] {
+                let edge_triangles = &edges[edge];
+                let (this_direction, (neighbour, neighbour_direction)) = if edge_triangles[0].0 == triangle {
+                    (edge_triangles[0].1, edge_triangles[1])
+                } else {
+                    (edge_triangles[1].1, edge_triangles[0])
+                };
+
+                if visited[neighbour] {
+                    continue;
+                }
+
+                // The directions are recorded for the ORIGINAL winding, so flips made so far have to be taken into account.
+                // Consistent neighbours have their shared edge running in opposite directions.
+                let this_direction = this_direction != flipped[triangle];
+                let should_flip = neighbour_direction == this_direction;
+
+                flipped[neighbour] = should_flip;
+                visited[neighbour] = true;
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    for (triangle, should_flip) in flipped.iter().enumerate() {
+        if *should_flip {
+            mesh.indices.swap(triangle * 3 + 1, triangle * 3 + 2);
+        }
+    }
+
+    let flipped_count = flipped.iter().filter(|x| **x).count();
+
+    // LODs no longer match the mesh when it changes, so they have to be regenerated
+    if flipped_count > 0 {
+        mesh.lods.clear();
+    }
+
+    flipped_count
+}
+
+// Removes vertices no triangle uses, compacting all vertex attributes and remapping the indices.
+// Triangles with an index out of range can't be remapped, so they are removed first.
+pub fn remove_unreferenced_vertices(mesh: &mut Mesh) -> usize {
+    let out_of_range_count = remove_out_of_range_triangles(mesh);
+    if out_of_range_count > 0 {
+        println!("Removed {} triangles with indices outside of the {} vertices of mesh '{}'", out_of_range_count, mesh.vertex_positions.len(), mesh.name);
+    }
+
+    let vertex_count = mesh.vertex_positions.len();
+    let mut referenced: Vec<bool> = vec![false; vertex_count];
+    for index in &mesh.indices {
+        referenced[*index as usize] = true;
+    }
+
+    // Vertices only used by a LOD are kept too, or the LOD would lose those triangles
+    for lod in &mesh.lods {
+        for index in &lod.indices {
+            if let Some(is_referenced) = referenced.get_mut(*index as usize) {
+                *is_referenced = true;
+            }
+        }
+    }
+
+    let mut remap: Vec<u32> = vec![u32::MAX; vertex_count];
+    let mut next_vertex: u32 = 0;
+    for vertex in 0..vertex_count {
+        if referenced[vertex] {
+            remap[vertex] = next_vertex;
+            next_vertex += 1;
+        }
+    }
+
+    mesh.vertex_positions = keep_referenced(&mesh.vertex_positions, &referenced);
+    if mesh.vertex_normals.len() == vertex_count {
+        mesh.vertex_normals = keep_referenced(&mesh.vertex_normals, &referenced);
+    }
+    if mesh.vertex_uvs.len() == vertex_count {
+        mesh.vertex_uvs = keep_referenced(&mesh.vertex_uvs, &referenced);
+    }
+    if mesh.vertex_colors.len() == vertex_count {
+        mesh.vertex_colors = keep_referenced(&mesh.vertex_colors, &referenced);
+    }
+
+    for index in mesh.indices.iter_mut() {
+        *index = remap[*index as usize];
+    }
+
+    // Whole triangles are dropped, so a LOD index out of range doesn't shift all the triangles after it
+    for lod in mesh.lods.iter_mut() {
+        lod.indices = lod.indices
+            .chunks_exact(3)
+            .filter(|triangle| triangle.iter().all(|index| (*index as usize) < vertex_count))
+            .flatten()
+            .map(|index| remap[*index as usize])
+            .collect();
+    }
+
+    vertex_count - next_vertex as usize
+}
+
+fn keep_referenced<T: Copy>(attribute: &[T], referenced: &[bool]) -> Vec<T> {
+    attribute.iter().zip(referenced.iter()).filter(|(_, is_referenced)| **is_referenced).map(|(value, _)| *value).collect()
+}
+
+// Keeps the triangles the predicate returns true for, and returns the number of triangles removed.
+// As the triangles of the mesh change, any LODs are dropped, since they would no longer match.
+fn retain_triangles<F: FnMut(&[u32]) -> bool>(mesh: &mut Mesh, mut predicate: F) -> usize {
+    let triangle_count = mesh.indices.len() / 3;
+    let mut kept_indices: Vec<u32> = Vec::with_capacity(mesh.indices.len());
+
+    for triangle_vertices in mesh.indices.chunks_exact(3) {
+        if predicate(triangle_vertices) {
+            kept_indices.extend_from_slice(triangle_vertices);
+        }
+    }
+
+    let removed = triangle_count - kept_indices.len() / 3;
+    mesh.indices = kept_indices;
+
+    if removed > 0 {
+        mesh.lods.clear();
+    }
+
+    removed
+}
+
+fn is_finite(position: &beagle_math::Vector3) -> bool {
+    position.x.is_finite() && position.y.is_finite() && position.z.is_finite()
+}
+
+fn is_index_degenerate(triangle: &[u32]) -> bool {
+    triangle[0] == triangle[1] || triangle[1] == triangle[2] || triangle[0] == triangle[2]
+}
+
+/*
+    The length of the cross product of two edges is twice the area of the triangle.
+    The area is compared relative to the longest edge, so the check works the same for tiny and huge meshes.
+*/
+fn is_zero_area(mesh: &Mesh, triangle: &[u32]) -> bool {
+    let vert1 = mesh.vertex_positions[triangle[0] as usize];
+    let vert2 = mesh.vertex_positions[triangle[1] as usize];
+    let vert3 = mesh.vertex_positions[triangle[2] as usize];
+
+    let edge1 = vert2 - vert1;
+    let edge2 = vert3 - vert1;
+    let edge3 = vert3 - vert2;
+
+    let longest_edge = edge1.length().max(edge2.length()).max(edge3.length());
+    let doubled_area = edge1.cross(&edge2).length();
+
+    doubled_area <= f32::EPSILON * longest_edge * longest_edge
+}
+
+// Rotates the triangle so its smallest index comes first. Rotating keeps the winding, so (0, 1, 2) and (1, 2, 0) are the same triangle,
+// while (0, 2, 1) is a different, opposite facing, one.
+fn canonical_triangle(triangle: &[u32]) -> [u32; 3] {
+    if triangle[0] <= triangle[1] && triangle[0] <= triangle[2] {
+        [triangle[0], triangle[1], triangle[2]]
+    } else if triangle[1] <= triangle[2] {
+        [triangle[1], triangle[2], triangle[0]]
+    } else {
+        [triangle[2], triangle[0], triangle[1]]
+    }
+}
+
+// For every vertex, the first vertex with the same position.
+fn find_position_ids(mesh: &Mesh) -> Vec<u32> {
+    let mut first_seen: HashMap<[u32; 3], u32> = HashMap::new();
+
+    mesh.vertex_positions.iter().enumerate().map(|(vertex, position)| {
+        let key = [(position.x + 0.0).to_bits(), (position.y + 0.0).to_bits(), (position.z + 0.0).to_bits()];
+        *first_seen.entry(key).or_insert(vertex as u32)
+    }).collect()
+}
+
+/*
+    Maps every edge (by position, smallest id first) to the triangles using it.
+    Along with each triangle is stored whether the triangle's winding runs along the edge from the smaller to the larger id.
+*/
+fn build_edge_map(mesh: &Mesh, position_ids: &[u32], triangles: &[usize]) -> HashMap<(u32, u32), Vec<(usize, bool)>> {
+    let mut edges: HashMap<(u32, u32), Vec<(usize, bool)>> = HashMap::new();
+
+    for triangle in triangles {
+        for corner in 0..3 {
+            let a = position_ids[mesh.indices[triangle * 3 + corner] as usize];
+            let b = position_ids[mesh.indices[triangle * 3 + (corner + 1) % 3] as usize];
+
+            if a == b {
+                continue;
+            }
+
+            edges.entry((a.min(b), a.max(b))).or_default().push((*triangle, a < b));
+        }
+    }
+
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math;
+    use crate::asset::mesh::*;
+    use crate::asset::mesh::validation::*;
+
+    // Two triangles making up a unit quad in the XY plane
+    fn create_quad() -> Mesh {
+        Mesh {
+            vertex_positions: vec!(
+                beagle_math::Vector3::new(0.0, 0.0, 0.0),
+                beagle_math::Vector3::new(0.0, 1.0, 0.0),
+                beagle_math::Vector3::new(1.0, 1.0, 0.0),
+                beagle_math::Vector3::new(1.0, 0.0, 0.0)),
+            indices: vec!(0, 1, 2, 0, 2, 3),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_report_no_issues_for_valid_mesh() {
+        let mesh = create_quad();
+
+        assert!(validate(&mesh).is_valid());
+    }
+
+    #[test]
+    fn should_report_and_remove_broken_triangles() {
+        let mut mesh = create_quad();
+        mesh.vertex_positions.push(beagle_math::Vector3::new(f32::NAN, 0.0, 0.0));
+        mesh.vertex_positions.push(beagle_math::Vector3::new(2.0, 0.0, 0.0));
+        mesh.indices.extend_from_slice(&[0, 1, 9]);   // out of range
+        mesh.indices.extend_from_slice(&[0, 0, 1]);   // degenerate
+        mesh.indices.extend_from_slice(&[0, 3, 5]);   // zero area, all on the x axis
+        mesh.indices.extend_from_slice(&[2, 0, 1]);   // duplicate of the first triangle
+        mesh.indices.extend_from_slice(&[0, 1, 4]);   // uses a NaN position
+
+        let issues = validate(&mesh).issues;
+
+        assert!(issues.contains(&MeshIssue::IndexOutOfRange { triangle: 2, index: 9 }));
+        assert!(issues.contains(&MeshIssue::DegenerateTriangle { triangle: 3 }));
+        assert!(issues.contains(&MeshIssue::ZeroAreaTriangle { triangle: 4 }));
+        assert!(issues.contains(&MeshIssue::DuplicateTriangle { triangle: 5, duplicate_of: 0 }));
+        assert!(issues.contains(&MeshIssue::NonFinitePosition { vertex: 4 }));
+
+        let report = repair(&mut mesh);
+
+        assert_eq!(report.removed_triangles, 5);
+        assert_eq!(report.removed_vertices, 2);
+        assert_eq!(mesh.indices, vec!(0, 1, 2, 0, 2, 3));
+        assert!(validate(&mesh).is_valid());
+    }
+
+    #[test]
+    fn should_fix_inconsistent_winding() {
+        let mut mesh = create_quad();
+        mesh.indices = vec!(0, 1, 2, 0, 3, 2);
+
+        assert!(validate(&mesh).issues.contains(&MeshIssue::InconsistentWinding { triangle: 0, neighbour: 1 }));
+
+        assert_eq!(fix_winding(&mut mesh), 1);
+        assert_eq!(mesh.indices, vec!(0, 1, 2, 0, 2, 3));
+        assert!(validate(&mesh).is_valid());
+    }
+
+    #[test]
+    fn should_keep_lod_triangles_when_removing_unreferenced_vertices() {
+        let mut mesh = create_quad();
+        mesh.vertex_positions.insert(0, beagle_math::Vector3::new(5.0, 5.0, 5.0));
+        mesh.vertex_positions.push(beagle_math::Vector3::new(2.0, 0.0, 0.0));
+        mesh.indices = vec!(1, 2, 3, 1, 3, 4);
+        mesh.lods = vec!(Lod { indices: vec!(1, 2, 5, 1, 2, 9, 1, 2, 3), error: 0.5 });
+
+        assert_eq!(remove_unreferenced_vertices(&mut mesh), 1);
+        assert_eq!(mesh.indices, vec!(0, 1, 2, 0, 2, 3));
+        assert_eq!(mesh.lods[0].indices, vec!(0, 1, 4, 0, 1, 2));
+    }
+
+    #[test]
+    fn should_skip_out_of_range_triangles_when_removing_unreferenced_vertices() {
+        let mut mesh = create_quad();
+        mesh.vertex_positions.push(beagle_math::Vector3::new(2.0, 0.0, 0.0));
+        mesh.indices.extend_from_slice(&[0, 4, 7]);
+
+        assert_eq!(remove_unreferenced_vertices(&mut mesh), 1);
+        assert_eq!(mesh.indices, vec!(0, 1, 2, 0, 2, 3));
+        assert_eq!(mesh.vertex_positions.len(), 4);
+    }
+
+    #[test]
+    fn should_report_non_manifold_edge() {
+        let mut mesh = create_quad();
+        mesh.vertex_positions.push(beagle_math::Vector3::new(0.5, 0.5, 1.0));
+        mesh.indices.extend_from_slice(&[0, 4, 2]);
+
+        let issues = validate(&mesh).issues;
+
+        assert!(issues.contains(&MeshIssue::NonManifoldEdge { vertex_a: 0, vertex_b: 2, triangle_count: 3 }));
+    }
+}
@@ -1 +1,12 @@
-pub mod mesh;
\ No newline at end of file
+pub mod cooked;
+pub mod manager;
+pub mod manifest;
+pub mod mesh;
+pub mod obj;
+pub mod pipeline;
+pub mod ply;
+pub mod shader;
+pub mod stl;
+pub mod texture;
+pub mod watcher;
+pub mod worker_pool;
@@ -0,0 +1,685 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::beagle_math;
+use crate::asset::mesh::{self, Material, Mesh, Model};
+
+/*
+    Importer for Wavefront OBJ files and the MTL material libraries they refer to.
+
+    OBJ is a text format, one statement per line:
+        v x y z [r g b]     a position (some exporters add a vertex color after it)
+        vt u v              a texture coordinate
+        vn x y z            a normal
+        f 1/1/1 2/2/2 ...   a polygon, each corner being position/uv/normal indices (uv and normal are optional)
+        o name, g name      start a new object or group
+        s 1, s off          smoothing group, used to generate normals when the file has none
+        mtllib a.mtl b.mtl  load materials from one or more library files
+        usemtl name         use a material for the faces that follow (the default material if the name isn't in any library)
+
+    Indices start at 1, and negative indices count back from the last element read so far (-1 is the latest one).
+
+    A Mesh can only have a single material, so every object/group gets one Mesh per material it uses.
+    Everything else is kept the way parse_model does it for glTF: positions and winding exactly as in the file
+    (OBJ, like glTF, has counter clockwise front faces in a right handed space, which is what the renderer expects),
+    and uvs with (0, 0) in the top left. OBJ has (0, 0) in the bottom left, so v gets flipped.
+*/
+
+pub fn load_model<P: AsRef<Path>>(path: P) -> Result<Model, String> {
+    let path = path.as_ref();
+    let obj_source = fs::read_to_string(path).map_err(|err| format!("Failed to read OBJ file {}: {}", path.display(), err))?;
+
+    // Material libraries are relative to the OBJ file
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_model(&obj_source, |library_name| {
+        let library_path = directory.join(library_name);
+        fs::read_to_string(&library_path).map_err(|err| format!("Failed to read MTL file {}: {}", library_path.display(), err))
+    })
+}
+
+/*
+    Parses the content of an OBJ file. "read_material_library" is handed the name after every "mtllib" statement,
+    and returns the content of that MTL file. That keeps parsing away from the file system, which makes testing easier.
+*/
+pub fn parse_model<F: FnMut(&str) -> Result<String, String>>(obj_source: &str, mut read_material_library: F) -> Result<Model, String> {
+    let mut positions: Vec<beagle_math::Vector3> = vec!();
+    let mut colors: Vec<Option<beagle_math::Vector4>> = vec!();
+    let mut uvs: Vec<beagle_math::Vector2> = vec!();
+    let mut normals: Vec<beagle_math::Vector3> = vec!();
+    let mut materials: HashMap<String, Material> = HashMap::new();
+
+    let mut builders: Vec<MeshBuilder> = vec!();
+    let mut builder_lookup: HashMap<(String, String), usize> = HashMap::new();
+
+    let mut object_name = String::from("default");
+    let mut material_name = String::new();
+    let mut smoothing_group: u32 = 0;
+
+    for (line_number, line) in join_continued_lines(obj_source) {
+        let line = line.split('#').next().unwrap().trim();
+        let mut parts = line.split_whitespace();
+
+        let keyword = match parts.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let arguments: Vec<&str> = parts.collect();
+
+        match keyword {
+            "v" => {
+                let values = parse_floats(&arguments, line_number)?;
+                match values.len() {
+                    3 | 4 => colors.push(None),
+                    6 | 7 => colors.push(Some(beagle_math::Vector4::new(values[3], values[4], values[5], 1.0))),
+                    _ => return Err(format!("Line {}: A position needs 3 values, or 6 with a color, but has {}.", line_number, values.len()))
+                }
+                positions.push(beagle_math::Vector3::new(values[0], values[1], values[2]));
+            },
+            "vt" => {
+                let values = parse_floats(&arguments, line_number)?;
+                if values.is_empty() || values.len() > 3 {
+                    return Err(format!("Line {}: A texture coordinate needs 1 to 3 values, but has {}.", line_number, values.len()));
+                }
+                let v = if values.len() > 1 { values[1] } else { 0.0 };
+                uvs.push(beagle_math::Vector2::new(values[0], 1.0 - v));
+            },
+            "vn" => {
+                let values = parse_floats(&arguments, line_number)?;
+                if values.len() != 3 {
+                    return Err(format!("Line {}: A normal needs 3 values, but has {}.", line_number, values.len()));
+                }
+                normals.push(beagle_math::Vector3::new(values[0], values[1], values[2]));
+            },
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(format!("Line {}: A face needs at least 3 corners, but has {}.", line_number, arguments.len()));
+                }
+
+                let mut corners: Vec<Corner> = Vec::with_capacity(arguments.len());
+                for argument in &arguments {
+                    corners.push(parse_corner(argument, positions.len(), uvs.len(), normals.len(), line_number)?);
+                }
+
+                let key = (object_name.clone(), material_name.clone());
+                let builder_index = *builder_lookup.entry(key).or_insert_with(|| {
+                    let material = materials.get(&material_name).cloned().unwrap_or_default();
+                    builders.push(MeshBuilder::new(&object_name, &material_name, material));
+                    builders.len() - 1
+                });
+
+                builders[builder_index].faces.push(Face { corners, smoothing_group });
+            },
+            "o" | "g" => {
+                // A group can have several names, which I simply join together
+                object_name = if arguments.is_empty() { String::from("default") } else { arguments.join(" ") };
+            },
+            "s" => {
+                smoothing_group = match arguments.first() {
+                    None | Some(&"off") => 0,
+                    Some(group) => group.parse::<u32>().map_err(|_| format!("Line {}: Invalid smoothing group '{}'.", line_number, group))?
+                };
+            },
+            "mtllib" => {
+                // Several libraries can be given on one line, separated by whitespace.
+                // One that can't be read only means its materials fall back to the default, which usemtl takes care of.
+                for library_name in &arguments {
+                    match read_material_library(library_name) {
+                        Ok(library_source) => materials.extend(parse_material_library(&library_source)?),
+                        Err(err) => println!("Line {}: Using the default material for materials from '{}': {}", line_number, library_name, err)
+                    }
+                }
+            },
+            "usemtl" => {
+                // A material missing from the libraries (or a library that couldn't be found) isn't worth failing the whole model over,
+                // the faces simply get the default material. Only a usemtl without a name is an error.
+                if arguments.is_empty() {
+                    return Err(format!("Line {}: usemtl needs a material name.", line_number));
+                }
+                material_name = arguments.join(" ");
+            },
+            // Lines, points, curves and the like don't end up in a triangle mesh
+            _ => ()
+        }
+    }
+
+    let meshes = builders
+        .iter()
+        .map(|builder| builder.build(&positions, &colors, &uvs, &normals, builders.iter().filter(|other| other.object_name == builder.object_name).count() > 1))
+        .collect();
+
+    Ok(Model { meshes })
+}
+
+/*
+    Parses an MTL file into materials by name. Only the colors and shininess that Material has room for are read:
+        Kd r g b    diffuse color
+        Ka r g b    ambient color
+        Ks r g b    specular color
+        Ns value    specular exponent (shininess)
+*/
+pub fn parse_material_library(mtl_source: &str) -> Result<HashMap<String, Material>, String> {
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    let mut current_material: Option<String> = None;
+
+    for (line_number, line) in join_continued_lines(mtl_source) {
+        let line = line.split('#').next().unwrap().trim();
+        let mut parts = line.split_whitespace();
+
+        let keyword = match parts.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let arguments: Vec<&str> = parts.collect();
+
+        if keyword == "newmtl" {
+            let name = arguments.join(" ");
+            materials.insert(name.clone(), Material::default());
+            current_material = Some(name);
+            continue;
+        }
+
+        let material = match keyword {
+            "Kd" | "Ka" | "Ks" | "Ns" => match &current_material {
+                Some(name) => materials.get_mut(name).unwrap(),
+                None => return Err(format!("Line {}: '{}' comes before any 'newmtl'.", line_number, keyword))
+            },
+            // Textures, transparency, illumination models and so on aren't supported by Material (yet)
+            _ => continue
+        };
+
+        let values = parse_floats(&arguments, line_number)?;
+        if keyword == "Ns" {
+            if values.len() != 1 {
+                return Err(format!("Line {}: Ns needs a single value, but has {}.", line_number, values.len()));
+            }
+            material.shininess_factor = values[0];
+            continue;
+        }
+
+        // A single value is a shorthand for a gray color
+        let color = match values.len() {
+            1 => beagle_math::Vector3::new(values[0], values[0], values[0]),
+            3 => beagle_math::Vector3::from_array(&values),
+            _ => return Err(format!("Line {}: A color needs 1 or 3 values, but has {}.", line_number, values.len()))
+        };
+
+        match keyword {
+            "Kd" => material.diffuse_color = color,
+            "Ka" => material.ambient_color = color,
+            _ => material.specular_color = color
+        }
+    }
+
+    Ok(materials)
+}
+
+// Indices into the position, uv and normal lists, already made absolute and zero based
+#[derive(Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>
+}
+
+struct Face {
+    corners: Vec<Corner>,
+    smoothing_group: u32
+}
+
+// Collects the faces of a single object/group and material, until the whole file is read and the Mesh can be built
+struct MeshBuilder {
+    object_name: String,
+    material_name: String,
+    material: Material,
+    faces: Vec<Face>
+}
+
+impl MeshBuilder {
+    fn new(object_name: &str, material_name: &str, material: Material) -> MeshBuilder {
+        MeshBuilder { object_name: String::from(object_name), material_name: String::from(material_name), material, faces: vec!() }
+    }
+
+    fn build(
+        &self,
+        positions: &[beagle_math::Vector3],
+        colors: &[Option<beagle_math::Vector4>],
+        uvs: &[beagle_math::Vector2],
+        normals: &[beagle_math::Vector3],
+        name_with_material: bool) -> Mesh
+    {
+        let has_uvs = self.faces.iter().any(|face| face.corners.iter().any(|corner| corner.uv.is_some()));
+        let has_colors = self.faces.iter().any(|face| face.corners.iter().any(|corner| colors[corner.position].is_some()));
+
+        /*
+            Faces without normals get generated ones. Faces in smoothing group 0 are flat shaded, and get their face normal.
+            Other faces share normals at positions they have in common with faces of the same smoothing group, so those
+            normals are the sum of all those face normals. The face normals aren't normalized first, so big faces count more.
+        */
+        let face_normals: Vec<beagle_math::Vector3> = self.faces.iter().map(|face| polygon_normal(&face.corners, positions)).collect();
+        let mut smooth_normals: HashMap<(usize, u32), beagle_math::Vector3> = HashMap::new();
+
+        for (face, face_normal) in self.faces.iter().zip(face_normals.iter()) {
+            if face.smoothing_group == 0 {
+                continue;
+            }
+
+            for corner in face.corners.iter().filter(|corner| corner.normal.is_none()) {
+                let normal = smooth_normals.entry((corner.position, face.smoothing_group)).or_insert_with(beagle_math::Vector3::zero);
+                *normal = normal.add(face_normal);
+            }
+        }
+
+        let mut vertices: Vec<mesh::Vertex> = vec!();
+
+        for (face, face_normal) in self.faces.iter().zip(face_normals.iter()) {
+            let corner_vertices: Vec<mesh::Vertex> = face.corners.iter().map(|corner| {
+                let normal = match corner.normal {
+                    Some(normal) => normals[normal],
+                    None if face.smoothing_group == 0 => *face_normal,
+                    None => smooth_normals[&(corner.position, face.smoothing_group)]
+                };
+
+                mesh::Vertex {
+                    position: positions[corner.position],
+                    normal: normalize_or_zero(&normal),
+                    uv: corner.uv.map(|uv| uvs[uv]).unwrap_or_default(),
+                    color: colors[corner.position].unwrap_or_else(|| beagle_math::Vector4::new(1.0, 1.0, 1.0, 1.0))
+                }
+            }).collect();
+
+            for triangle in triangulate_polygon(&face.corners, positions, face_normal) {
+                vertices.extend(triangle.iter().map(|corner| corner_vertices[*corner]));
+            }
+        }
+
+        let (unique_vertices, indices) = mesh::weld_vertices(&vertices);
+
+        let name = if name_with_material && !self.material_name.is_empty() {
+            format!("{}_{}", self.object_name, self.material_name)
+        } else {
+            self.object_name.clone()
+        };
+
+        Mesh {
+            name,
+            vertex_positions: unique_vertices.iter().map(|vertex| vertex.position).collect(),
+            vertex_normals: unique_vertices.iter().map(|vertex| vertex.normal).collect(),
+            vertex_uvs: if has_uvs { unique_vertices.iter().map(|vertex| vertex.uv).collect() } else { vec!() },
+            vertex_colors: if has_colors { unique_vertices.iter().map(|vertex| vertex.color).collect() } else { vec!() },
+            indices,
+            material: self.material.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+// Long statements can be spread over several lines by ending a line with a backslash.
+// Every joined statement keeps the number of the line it starts on, so error messages point at the right line.
+fn join_continued_lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = vec!();
+    let mut continued = false;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let (content, continues) = match line.trim_end().strip_suffix('\\') {
+            Some(content) => (content, true),
+            None => (line, false)
+        };
+
+        if continued {
+            let last = &mut lines.last_mut().unwrap().1;
+            last.push(' ');
+            last.push_str(content);
+        } else {
+            lines.push((line_index + 1, String::from(content)));
+        }
+
+        continued = continues;
+    }
+
+    lines
+}
+
+fn parse_floats(arguments: &[&str], line_number: usize) -> Result<Vec<f32>, String> {
+    arguments
+        .iter()
+        .map(|argument| argument.parse::<f32>().map_err(|_| format!("Line {}: '{}' is not a number.", line_number, argument)))
+        .collect()
+}
+
+// Parses "v", "v/vt", "v//vn" or "v/vt/vn"
+fn parse_corner(argument: &str, position_count: usize, uv_count: usize, normal_count: usize, line_number: usize) -> Result<Corner, String> {
+    let mut indices = argument.split('/');
+
+    let position = resolve_index(indices.next(), position_count, "position", line_number)?
+        .ok_or_else(|| format!("Line {}: Face corner '{}' has no position.", line_number, argument))?;
+    let uv = resolve_index(indices.next(), uv_count, "texture coordinate", line_number)?;
+    let normal = resolve_index(indices.next(), normal_count, "normal", line_number)?;
+
+    if indices.next().is_some() {
+        return Err(format!("Line {}: Face corner '{}' has too many indices.", line_number, argument));
+    }
+
+    Ok(Corner { position, uv, normal })
+}
+
+// Turns a 1 based or negative (relative) index into a 0 based one
+fn resolve_index(index: Option<&str>, count: usize, kind: &str, line_number: usize) -> Result<Option<usize>, String> {
+    let index = match index {
+        None | Some("") => return Ok(None),
+        Some(index) => index.parse::<i64>().map_err(|_| format!("Line {}: '{}' is not a valid {} index.", line_number, index, kind))?
+    };
+
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("Line {}: The {} index {} is out of range, only {} have been defined.", line_number, kind, index, count));
+    }
+
+    Ok(Some(resolved as usize))
+}
+
+/*
+    Newell's method: sums up the cross products along the polygon's outline.
+    It works for any polygon, even if it's concave or not completely flat, and the length is twice the polygon's area.
+    With counter clockwise corners in a right handed space, the same as cross(v1 - v0, v2 - v0) for a triangle.
+*/
+fn polygon_normal(corners: &[Corner], positions: &[beagle_math::Vector3]) -> beagle_math::Vector3 {
+    let mut normal = beagle_math::Vector3::zero();
+
+    for (index, corner) in corners.iter().enumerate() {
+        let current = positions[corner.position];
+        let next = positions[corners[(index + 1) % corners.len()].position];
+
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    normal
+}
+
+fn normalize_or_zero(vector: &beagle_math::Vector3) -> beagle_math::Vector3 {
+    let length = vector.length();
+    if length > 0.0 && length.is_finite() {
+        vector.mul(1.0 / length)
+    } else {
+        beagle_math::Vector3::zero()
+    }
+}
+
+/*
+    Splits a polygon into triangles, returned as indices into its corners.
+
+    Triangles and convex polygons could just be split into a fan from the first corner, but a fan goes outside
+    of concave polygons (an L shape for example). So this uses "ear clipping": keep cutting off a corner whose triangle
+    bends the right way and has no other corner inside it, until only one triangle is left.
+    If the polygon is too broken for that (twisted, or all corners on a line), the rest becomes a fan.
+*/
+fn triangulate_polygon(corners: &[Corner], positions: &[beagle_math::Vector3], normal: &beagle_math::Vector3) -> Vec<[usize; 3]> {
+    let mut triangles: Vec<[usize; 3]> = vec!();
+    let mut remaining: Vec<usize> = (0..corners.len()).collect();
+    let position = |corner: usize| positions[corners[corner].position];
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+
+        let ear = (0..count).find(|index| {
+            let previous = remaining[(index + count - 1) % count];
+            let current = remaining[*index];
+            let next = remaining[(index + 1) % count];
+            let (a, b, c) = (position(previous), position(current), position(next));
+
+            // A convex corner turns the same way as the whole polygon
+            let turn = b.add(&a.mul(-1.0)).cross(&c.add(&a.mul(-1.0)));
+            if turn.dot(normal) <= 0.0 {
+                return false;
+            }
+
+            !remaining
+                .iter()
+                .filter(|other| **other != previous && **other != current && **other != next)
+                .any(|other| is_point_in_triangle(&position(*other), &a, &b, &c, normal))
+        });
+
+        match ear {
+            Some(index) => {
+                triangles.push([remaining[(index + count - 1) % count], remaining[index], remaining[(index + 1) % count]]);
+                remaining.remove(index);
+            },
+            None => break
+        }
+    }
+
+    for index in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[index], remaining[index + 1]]);
+    }
+
+    triangles
+}
+
+fn is_point_in_triangle(
+    point: &beagle_math::Vector3,
+    a: &beagle_math::Vector3,
+    b: &beagle_math::Vector3,
+    c: &beagle_math::Vector3,
+    normal: &beagle_math::Vector3) -> bool
+{
+    // The point is inside if it's on the inner side of all three edges
+    let edges = [(a, b), (b, c), (c, a)];
+    edges.iter().all(|(start, end)| {
+        let edge = end.add(&start.mul(-1.0));
+        let to_point = point.add(&start.mul(-1.0));
+        edge.cross(&to_point).dot(normal) >= 0.0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::mesh::validation;
+    use crate::asset::obj::*;
+
+    fn no_libraries(name: &str) -> Result<String, String> {
+        Err(format!("Unexpected material library {}", name))
+    }
+
+    #[test]
+    fn should_parse_triangle_with_uvs_and_normals() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 0 1
+            vn 0 0 1
+            f 1/1/1 2/2/1 3/3/1
+        ";
+
+        let model = parse_model(source, no_libraries).unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(mesh.vertex_positions[1].x, 1.0);
+        // v is flipped, so the top of the texture is at v = 0
+        assert_eq!(mesh.vertex_uvs[0].y, 1.0);
+        assert_eq!(mesh.vertex_uvs[2].y, 0.0);
+        assert_eq!(mesh.vertex_normals[0].z, 1.0);
+        assert!(mesh.vertex_colors.is_empty());
+    }
+
+    #[test]
+    fn should_resolve_negative_indices() {
+        let source = "
+            v 5 5 5
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f -3 -2 -1
+        ";
+
+        let model = parse_model(source, no_libraries).unwrap();
+        let mesh = &model.meshes[0];
+
+        assert_eq!(mesh.vertex_positions.len(), 3);
+        assert_eq!(mesh.vertex_positions[0].x, 0.0);
+        assert!(parse_model("v 0 0 0\nf 1 2 3", no_libraries).is_err());
+        assert!(parse_model("v 0 0 0\nf 0 1 1", no_libraries).is_err());
+    }
+
+    #[test]
+    fn should_triangulate_concave_polygon_without_leaving_it() {
+        // An L shape in the XY plane, counter clockwise seen from +Z. A fan from the first corner would cover the missing corner.
+        let source = "
+            v 0 0 0
+            v 2 0 0
+            v 2 1 0
+            v 1 1 0
+            v 1 2 0
+            v 0 2 0
+            f 2 3 4 5 6 1
+        ";
+
+        let model = parse_model(source, no_libraries).unwrap();
+        let mesh = &model.meshes[0];
+
+        assert_eq!(mesh.indices.len(), 4 * 3);
+        let mut area = 0.0;
+        for triangle in mesh.indices.chunks_exact(3) {
+            let a = mesh.vertex_positions[triangle[0] as usize];
+            let b = mesh.vertex_positions[triangle[1] as usize];
+            let c = mesh.vertex_positions[triangle[2] as usize];
+            let normal = b.add(&a.mul(-1.0)).cross(&c.add(&a.mul(-1.0)));
+            // Every triangle keeps the winding of the polygon
+            assert!(normal.z > 0.0);
+            area += normal.z * 0.5;
+        }
+        assert!((area - 3.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn should_split_groups_and_materials_into_meshes() {
+        let source = "
+            mtllib shapes.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            v 1 1 0
+            o first
+            usemtl red
+            f 1 2 3
+            usemtl blue
+            f 2 4 3
+            g second
+            f 1 2 4
+        ";
+        let library = "
+            newmtl red
+            Kd 1 0 0
+            Ka 0.1 0.1 0.1
+            Ks 0.5
+            Ns 32
+            newmtl blue
+            Kd 0 0 1
+        ";
+
+        let model = parse_model(source, |name| {
+            assert_eq!(name, "shapes.mtl");
+            Ok(String::from(library))
+        }).unwrap();
+
+        let names: Vec<&str> = model.meshes.iter().map(|mesh| mesh.name.as_str()).collect();
+        assert_eq!(names, vec!["first_red", "first_blue", "second"]);
+
+        let red = &model.meshes[0].material;
+        assert_eq!((red.diffuse_color.x, red.diffuse_color.z), (1.0, 0.0));
+        assert_eq!(red.ambient_color.y, 0.1);
+        assert_eq!(red.specular_color.z, 0.5);
+        assert_eq!(red.shininess_factor, 32.0);
+        assert_eq!(model.meshes[2].material.diffuse_color.z, 1.0);
+
+        let fallback = parse_model("v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl missing\nf 1 2 3", no_libraries).unwrap();
+        assert_eq!(fallback.meshes[0].material.diffuse_color, Material::default().diffuse_color);
+        assert!(parse_model("usemtl", no_libraries).is_err());
+    }
+
+    #[test]
+    fn should_load_every_library_and_skip_missing_ones() {
+        let source = "
+            mtllib missing.mtl red.mtl
+            mtllib blue.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            usemtl red
+            f 1 2 3
+            usemtl blue
+            f 1 2 3
+        ";
+
+        let mut requested: Vec<String> = vec!();
+        let model = parse_model(source, |name| {
+            requested.push(String::from(name));
+            match name {
+                "red.mtl" => Ok(String::from("newmtl red\nKd 1 0 0")),
+                "blue.mtl" => Ok(String::from("newmtl blue\nKd 0 0 1")),
+                _ => Err(format!("{} not found", name))
+            }
+        }).unwrap();
+
+        assert_eq!(requested, vec!["missing.mtl", "red.mtl", "blue.mtl"]);
+        assert_eq!(model.meshes[0].material.diffuse_color.x, 1.0);
+        assert_eq!(model.meshes[1].material.diffuse_color.z, 1.0);
+    }
+
+    #[test]
+    fn should_generate_normals_from_smoothing_groups() {
+        // Two faces of a box meeting at the edge from (0, 0, 0) to (0, 1, 0)
+        let faces = "
+            v 0 0 0
+            v 0 1 0
+            v 1 0 0
+            v 1 1 0
+            v 0 0 1
+            v 0 1 1
+            f 1 2 4 3
+            f 5 6 2 1
+        ";
+
+        let smooth = parse_model(&format!("s 1\n{}", faces), no_libraries).unwrap();
+        let flat = parse_model(&format!("s off\n{}", faces), no_libraries).unwrap();
+
+        // Smooth faces share the vertices along the edge, flat faces each have their own
+        assert_eq!(smooth.meshes[0].vertex_positions.len(), 6);
+        assert_eq!(flat.meshes[0].vertex_positions.len(), 8);
+
+        for mesh in [&smooth.meshes[0], &flat.meshes[0]] {
+            assert!(validation::validate(mesh).is_valid());
+            assert!(mesh.vertex_normals.iter().all(|normal| (normal.length() - 1.0).abs() < 0.0001));
+        }
+
+        let origin = smooth.meshes[0].vertex_positions.iter().position(|position| position.length() == 0.0).unwrap();
+        let edge_normal = smooth.meshes[0].vertex_normals[origin];
+        assert!((edge_normal.x - edge_normal.z).abs() < 0.0001);
+        assert!(edge_normal.x != 0.0);
+    }
+
+    #[test]
+    fn should_read_vertex_colors_and_continued_lines() {
+        let source = "
+            v 0 0 0 1 0 0
+            v 1 0 0 0 1 0
+            v 0 1 0 \\
+              0 0 1
+            f 1 2 \\
+              3
+        ";
+
+        let model = parse_model(source, no_libraries).unwrap();
+        let mesh = &model.meshes[0];
+
+        assert_eq!(mesh.vertex_colors.len(), 3);
+        assert_eq!(mesh.vertex_colors[2].z, 1.0);
+        assert_eq!(mesh.vertex_colors[0].w, 1.0);
+    }
+}
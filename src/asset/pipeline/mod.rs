@@ -0,0 +1,367 @@
+use std::collections::HashSet;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+
+use crate::gltf2;
+use crate::asset::cooked;
+use crate::asset::manifest::{AssetKind, Manifest, ManifestEntry, MANIFEST_FILE_NAME};
+use crate::asset::mesh::{self, simplify, validation, Model};
+use crate::asset::obj;
+
+/*
+    The asset pipeline turns the source assets (what artists and tools produce) into the assets the engine loads.
+
+    It walks the source directory, and for every file:
+    - Models (glTF and OBJ) are loaded, validated (and repaired if needed), given LODs, and written in the cooked format.
+    - Files that are only inputs for other files (MTL libraries, Blender files) are skipped.
+    - Everything else is copied as it is.
+
+    Every built file is listed in a manifest in the output directory, together with a hash of everything it was built from.
+    When the pipeline runs again, files whose hash didn't change are skipped, and files whose source is gone are removed.
+*/
+
+// Bump this whenever the pipeline starts producing different output for the same input, so everything is rebuilt
+const PIPELINE_VERSION: u32 = 1;
+
+const COOKED_EXTENSION: &str = "bglm";
+const MODEL_EXTENSIONS: [&str; 2] = ["gltf", "obj"];
+const SOURCE_ONLY_EXTENSIONS: [&str; 3] = ["mtl", "blend", "blend1"];
+
+// The same ratios the engine used when it built LODs at startup
+const LOD_TRIANGLE_RATIOS: [f32; 3] = [0.5, 0.25, 0.125];
+
+pub struct PipelineOptions {
+    pub source_directory: PathBuf,
+    pub output_directory: PathBuf,
+    // Rebuild everything, even if nothing changed
+    pub force: bool
+}
+
+// What happened to every asset, by logical name
+#[derive(Debug, Default)]
+pub struct PipelineReport {
+    pub cooked: Vec<String>,
+    pub copied: Vec<String>,
+    pub up_to_date: Vec<String>,
+    pub removed: Vec<String>,
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>
+}
+
+impl PipelineReport {
+    pub fn is_success(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/*
+    Runs the pipeline. Problems with a single asset end up in the report's errors, and the other assets are still built.
+    Only problems that stop the whole pipeline (like an unreadable source directory) are returned as an Err.
+*/
+pub fn run(options: &PipelineOptions) -> Result<PipelineReport, String> {
+    let mut report = PipelineReport::default();
+    let manifest_path = options.output_directory.join(MANIFEST_FILE_NAME);
+
+    // A missing or broken manifest just means everything gets built again
+    let previous_manifest = if options.force { Manifest::default() } else { Manifest::load(&manifest_path).unwrap_or_default() };
+    let mut manifest = Manifest::default();
+
+    let mut source_files: Vec<PathBuf> = vec!();
+    find_files(&options.source_directory, &mut source_files)?;
+
+    for source_path in &source_files {
+        let relative_source = relative_path(&options.source_directory, source_path);
+        let extension = source_path.extension().and_then(|extension| extension.to_str()).unwrap_or("").to_lowercase();
+
+        if SOURCE_ONLY_EXTENSIONS.contains(&extension.as_str()) {
+            continue;
+        }
+
+        let is_model = MODEL_EXTENSIONS.contains(&extension.as_str());
+        let (name, kind, relative_output) = if is_model {
+            let name = relative_source[..relative_source.len() - extension.len() - 1].to_string();
+            let output = format!("{}.{}", name, COOKED_EXTENSION);
+            (name, AssetKind::Model, output)
+        } else {
+            (relative_source.clone(), AssetKind::File, relative_source.clone())
+        };
+
+        if let Some(existing) = manifest.assets.get(&name) {
+            report.errors.push(format!("{}: Both {} and {} would become asset '{}'.", relative_source, existing.source, relative_source, name));
+            continue;
+        }
+
+        let hash = match hash_source(source_path, is_model) {
+            Ok(hash) => format!("{:016x}", hash),
+            Err(err) => {
+                report.errors.push(format!("{}: {}", relative_source, err));
+                continue;
+            }
+        };
+
+        let entry = ManifestEntry { kind, file: relative_output.clone(), source: relative_source.clone(), hash };
+        let output_path = options.output_directory.join(&relative_output);
+
+        if previous_manifest.get(&name) == Some(&entry) && output_path.exists() {
+            report.up_to_date.push(name.clone());
+            manifest.assets.insert(name, entry);
+            continue;
+        }
+
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).map_err(|err| format!("Failed to create directory {}: {}", parent.display(), err))?;
+        }
+
+        let result = if is_model {
+            cook_model(source_path, &relative_source, &output_path, &mut report.warnings)
+        } else {
+            fs::copy(source_path, &output_path).map(|_| ()).map_err(|err| format!("Failed to copy: {}", err))
+        };
+
+        match result {
+            Ok(()) => {
+                if is_model { report.cooked.push(name.clone()) } else { report.copied.push(name.clone()) }
+                manifest.assets.insert(name, entry);
+            },
+            Err(err) => report.errors.push(format!("{}: {}", relative_source, err))
+        }
+    }
+
+    // Built files whose source is gone are removed, so stale assets can't be loaded by accident
+    let current_files: HashSet<String> = manifest.assets.values().map(|entry| entry.file.clone()).collect();
+    for (name, entry) in &previous_manifest.assets {
+        if manifest.assets.contains_key(name) || current_files.contains(&entry.file) {
+            continue;
+        }
+
+        // Failed assets stay in the output, so the last good version can still be used
+        if source_files.iter().any(|source_path| relative_path(&options.source_directory, source_path) == entry.source) {
+            manifest.assets.insert(name.clone(), entry.clone());
+            continue;
+        }
+
+        let output_path = options.output_directory.join(&entry.file);
+        if output_path.exists() {
+            fs::remove_file(&output_path).map_err(|err| format!("Failed to remove {}: {}", output_path.display(), err))?;
+        }
+        report.removed.push(name.clone());
+    }
+
+    fs::create_dir_all(&options.output_directory)
+        .map_err(|err| format!("Failed to create directory {}: {}", options.output_directory.display(), err))?;
+    manifest.save(&manifest_path)?;
+
+    Ok(report)
+}
+
+/*
+    FNV-1a, a tiny and fast hash. It's not meant to stand up to someone trying to make two files with the same hash,
+    only to notice when a file changed, which it does very well.
+*/
+pub fn fnv1a_64(bytes: &[u8]) -> u64 {
+    fnv1a_64_continue(0xcbf2_9ce4_8422_2325, bytes)
+}
+
+// Continues a hash with more bytes, so several files can be hashed as if they were one
+fn fnv1a_64_continue(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
+
+// Everything the output depends on: the pipeline and cooked format versions, the file itself, and for OBJs its material libraries
+fn hash_source(source_path: &Path, is_model: bool) -> Result<u64, String> {
+    let content = fs::read(source_path).map_err(|err| format!("Failed to read: {}", err))?;
+
+    let mut hash = fnv1a_64(&PIPELINE_VERSION.to_le_bytes());
+    if is_model {
+        hash = fnv1a_64_continue(hash, &cooked::VERSION.to_le_bytes());
+    }
+    hash = fnv1a_64_continue(hash, &content);
+
+    if is_model && source_path.extension().map(|extension| extension.eq_ignore_ascii_case("obj")) == Some(true) {
+        let directory = source_path.parent().unwrap_or_else(|| Path::new(""));
+        for line in String::from_utf8_lossy(&content).lines() {
+            if let Some(library_name) = line.trim().strip_prefix("mtllib ") {
+                // A missing library is reported when cooking, so it just doesn't count towards the hash here
+                let library = fs::read(directory.join(library_name.trim())).unwrap_or_default();
+                hash = fnv1a_64_continue(hash, &library);
+            }
+        }
+    }
+
+    Ok(hash)
+}
+
+fn cook_model(source_path: &Path, relative_source: &str, output_path: &Path, warnings: &mut Vec<String>) -> Result<(), String> {
+    let model = prepare_model(source_path, relative_source, warnings)?;
+    cooked::save_model(&model, output_path)
+}
+
+// Loads a source model, and does everything to it that cooking does, except saving it.
+// Also used by the asset manager to hot reload models straight from their source.
+pub fn prepare_model(source_path: &Path, relative_source: &str, warnings: &mut Vec<String>) -> Result<Model, String> {
+    // The glTF parsing still panics on files it doesn't support. A single bad file shouldn't stop the whole pipeline though.
+    let loaded = panic::catch_unwind(|| load_source_model(source_path))
+        .map_err(|_| String::from("Loading the model panicked, see the message above."))?;
+    let mut model = loaded?;
+
+    for mesh in model.meshes.iter_mut() {
+        let validation_report = validation::validate(mesh);
+        if !validation_report.is_valid() {
+            warnings.push(format!("{}: Mesh {} has {} issues: {:?}", relative_source, mesh.name, validation_report.issues.len(), validation_report.issues));
+            let repair_report = validation::repair(mesh);
+            warnings.push(format!("{}: Repaired mesh {}: {:?}", relative_source, mesh.name, repair_report));
+
+            let repaired_report = validation::validate(mesh);
+            if !repaired_report.is_valid() {
+                return Err(format!("Mesh {} still has issues after repairing: {:?}", mesh.name, repaired_report.issues));
+            }
+        }
+
+        mesh.lods = simplify::build_lod_chain(mesh, &LOD_TRIANGLE_RATIOS);
+    }
+
+    Ok(model)
+}
+
+fn load_source_model(source_path: &Path) -> Result<Model, String> {
+    match source_path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()).as_deref() {
+        Some("gltf") => Ok(mesh::parse_model(&gltf2::File::from(source_path.to_path_buf())?)),
+        Some("obj") => obj::load_model(source_path),
+        _ => Err(String::from("Unsupported model format."))
+    }
+}
+
+// All files below a directory, sorted so the pipeline always does things in the same order. Hidden files are skipped.
+fn find_files(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(directory).map_err(|err| format!("Failed to read directory {}: {}", directory.display(), err))?;
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| !path.file_name().and_then(|name| name.to_str()).unwrap_or("").starts_with('.'))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            find_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// Manifests use forward slashes on every platform, so they can be shared between Windows and Linux machines
+fn relative_path(base: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Keeps the report readable: assets by name, per outcome
+pub fn summarize(report: &PipelineReport) -> String {
+    let sections = [
+        ("Cooked", &report.cooked),
+        ("Copied", &report.copied),
+        ("Removed", &report.removed),
+        ("Warnings", &report.warnings),
+        ("Errors", &report.errors)
+    ];
+
+    let mut summary = String::new();
+    for (title, items) in sections.iter().filter(|(_, items)| !items.is_empty()) {
+        summary.push_str(&format!("{}:\n", title));
+        for item in items.iter() {
+            summary.push_str(&format!("    {}\n", item));
+        }
+    }
+    summary.push_str(&format!(
+        "{} cooked, {} copied, {} up to date, {} removed, {} errors\n",
+        report.cooked.len(), report.copied.len(), report.up_to_date.len(), report.removed.len(), report.errors.len()));
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::pipeline::*;
+
+    const TRIANGLE_OBJ: &str = "mtllib triangle.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n";
+
+    fn test_directories(test_name: &str) -> PipelineOptions {
+        let root = std::env::temp_dir().join(format!("beagle_pipeline_{}_{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("source").join("props")).unwrap();
+
+        PipelineOptions { source_directory: root.join("source"), output_directory: root.join("output"), force: false }
+    }
+
+    #[test]
+    fn should_hash_like_reference_fnv1a() {
+        assert_eq!(fnv1a_64(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a_64(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a_64(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn should_cook_copy_and_rebuild_only_changed_assets() {
+        let options = test_directories("incremental");
+        let source = &options.source_directory;
+        fs::write(source.join("props").join("triangle.obj"), TRIANGLE_OBJ).unwrap();
+        fs::write(source.join("props").join("triangle.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        fs::write(source.join("readme.txt"), "hello").unwrap();
+
+        let first = run(&options).unwrap();
+        assert!(first.is_success(), "{:?}", first.errors);
+        assert_eq!(first.cooked, vec!["props/triangle"]);
+        assert_eq!(first.copied, vec!["readme.txt"]);
+
+        let manifest = Manifest::load(options.output_directory.join(MANIFEST_FILE_NAME)).unwrap();
+        let entry = manifest.get("props/triangle").unwrap();
+        assert_eq!(entry.file, "props/triangle.bglm");
+        let cooked = cooked::load_model(options.output_directory.join(&entry.file)).unwrap();
+        assert_eq!(cooked.model.meshes[0].material.diffuse_color.x, 1.0);
+
+        // Nothing changed, nothing is built
+        let second = run(&options).unwrap();
+        assert!(second.cooked.is_empty() && second.copied.is_empty());
+        assert_eq!(second.up_to_date.len(), 2);
+
+        // Changing the material library rebuilds the model that uses it, deleting a source removes its output
+        fs::write(source.join("props").join("triangle.mtl"), "newmtl red\nKd 0 1 0\n").unwrap();
+        fs::remove_file(source.join("readme.txt")).unwrap();
+        let third = run(&options).unwrap();
+        assert_eq!(third.cooked, vec!["props/triangle"]);
+        assert_eq!(third.removed, vec!["readme.txt"]);
+        assert!(!options.output_directory.join("readme.txt").exists());
+
+        fs::remove_dir_all(source.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn should_report_broken_assets_and_keep_going() {
+        let options = test_directories("broken");
+        let source = &options.source_directory;
+        fs::write(source.join("broken.obj"), "v 0 0 0\nf 1 2 3\n").unwrap();
+        fs::write(source.join("props").join("triangle.obj"), TRIANGLE_OBJ.replace("mtllib triangle.mtl\n", "").replace("usemtl red\n", "")).unwrap();
+
+        let report = run(&options).unwrap();
+
+        assert!(!report.is_success());
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.errors[0].starts_with("broken.obj"));
+        assert_eq!(report.cooked, vec!["props/triangle"]);
+
+        fs::remove_dir_all(source.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,525 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::beagle_math;
+use crate::asset::mesh::{Mesh, Model};
+
+/*
+    Importer and exporter for PLY (Polygon File Format) files, which most 3D scanning software writes.
+
+    A PLY file starts with a text header describing "elements" and their "properties", followed by the data itself,
+    either as text or as binary in little or big endian:
+
+        ply
+        format binary_little_endian 1.0
+        element vertex 8
+        property float x
+        property float y
+        property float z
+        property uchar red
+        property uchar green
+        property uchar blue
+        element face 6
+        property list uchar int vertex_indices
+        end_header
+        <data>
+
+    Every element is a table: "count" rows, each with a value for all of the properties, in order.
+    List properties first store how many items they have, then the items themselves.
+
+    Only the "vertex" and "face" elements end up in the mesh. From the vertices I read positions (x, y, z),
+    normals (nx, ny, nz), uvs (u, v or s, t) and colors (red, green, blue, alpha). Colors stored as integers are normalized to 0..1.
+    Polygon faces are split into a fan of triangles, and everything else is skipped.
+
+    Like OBJ, PLY puts uv (0, 0) in the bottom left, so v is flipped both ways.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64
+}
+
+enum PropertyType {
+    Scalar(ScalarType),
+    // The type of the item count, then the type of the items
+    List(ScalarType, ScalarType)
+}
+
+struct Property {
+    name: String,
+    property_type: PropertyType
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>
+}
+
+pub fn load_model<P: AsRef<Path>>(path: P) -> Result<Model, String> {
+    let binary_data = fs::read(&path).map_err(|err| format!("Failed to read PLY file {}: {}", path.as_ref().display(), err))?;
+    parse_model(&binary_data)
+}
+
+pub fn parse_model(binary_data: &[u8]) -> Result<Model, String> {
+    let (format, elements, header_length) = parse_header(binary_data)?;
+    let mut reader = ValueReader::new(format, &binary_data[header_length..])?;
+
+    let mut mesh = Mesh {
+        name: String::from("ply"),
+        ..Default::default()
+    };
+
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut reader, element, &mut mesh)?,
+            "face" => read_faces(&mut reader, element, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        read_property(&mut reader, property)?;
+                    }
+                }
+            }
+        }
+    }
+
+    let vertex_count = mesh.vertex_positions.len() as u32;
+    if let Some(index) = mesh.indices.iter().find(|index| **index >= vertex_count) {
+        return Err(format!("Face uses vertex {}, but there are only {} vertices.", index, vertex_count));
+    }
+
+    Ok(Model { meshes: vec![mesh] })
+}
+
+fn parse_header(binary_data: &[u8]) -> Result<(Format, Vec<Element>, usize), String> {
+    // The header is text, but the data after it might not be, so only the header is turned into a string
+    let end_marker = b"end_header";
+    let end_position = binary_data
+        .windows(end_marker.len())
+        .position(|window| window == end_marker)
+        .ok_or_else(|| String::from("PLY file has no 'end_header'."))?;
+    let header_length = match binary_data[end_position..].iter().position(|byte| *byte == b'\n') {
+        Some(newline) => end_position + newline + 1,
+        None => binary_data.len()
+    };
+
+    let header = std::str::from_utf8(&binary_data[..end_position]).map_err(|_| String::from("PLY header is not valid text."))?;
+    let mut lines = header.lines();
+
+    if lines.next().map(|line| line.trim()) != Some("ply") {
+        return Err(String::from("File does not start with 'ply'."));
+    }
+
+    let mut format: Option<Format> = None;
+    let mut elements: Vec<Element> = vec!();
+
+    for line in lines {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        match parts.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("Unknown PLY format '{}'.", name))
+                });
+            },
+            ["element", name, count] => {
+                let count = count.parse::<usize>().map_err(|_| format!("Invalid element count '{}'.", count))?;
+                elements.push(Element { name: String::from(*name), count, properties: vec!() });
+            },
+            ["property", "list", count_type, item_type, name] => {
+                let property_type = PropertyType::List(parse_scalar_type(count_type)?, parse_scalar_type(item_type)?);
+                add_property(&mut elements, name, property_type)?;
+            },
+            ["property", scalar_type, name] => {
+                let property_type = PropertyType::Scalar(parse_scalar_type(scalar_type)?);
+                add_property(&mut elements, name, property_type)?;
+            },
+            ["comment", ..] | ["obj_info", ..] | [] => (),
+            _ => return Err(format!("Invalid PLY header line '{}'.", line))
+        }
+    }
+
+    let format = format.ok_or_else(|| String::from("PLY header has no 'format' line."))?;
+    Ok((format, elements, header_length))
+}
+
+fn add_property(elements: &mut [Element], name: &str, property_type: PropertyType) -> Result<(), String> {
+    let element = elements.last_mut().ok_or_else(|| format!("Property '{}' comes before any element.", name))?;
+    element.properties.push(Property { name: String::from(name), property_type });
+    Ok(())
+}
+
+// PLY has two names for most types
+fn parse_scalar_type(name: &str) -> Result<ScalarType, String> {
+    match name {
+        "char" | "int8" => Ok(ScalarType::Int8),
+        "uchar" | "uint8" => Ok(ScalarType::UInt8),
+        "short" | "int16" => Ok(ScalarType::Int16),
+        "ushort" | "uint16" => Ok(ScalarType::UInt16),
+        "int" | "int32" => Ok(ScalarType::Int32),
+        "uint" | "uint32" => Ok(ScalarType::UInt32),
+        "float" | "float32" => Ok(ScalarType::Float32),
+        "double" | "float64" => Ok(ScalarType::Float64),
+        _ => Err(format!("Unknown PLY property type '{}'.", name))
+    }
+}
+
+/*
+    Reads the values of the data section one at a time, whether they're text or binary.
+    Values are returned as f64, which can hold every type PLY has without losing anything.
+*/
+struct ValueReader<'a> {
+    format: Format,
+    cursor: Cursor<&'a [u8]>,
+    tokens: std::str::SplitAsciiWhitespace<'a>
+}
+
+impl<'a> ValueReader<'a> {
+    fn new(format: Format, data: &'a [u8]) -> Result<ValueReader<'a>, String> {
+        let text = if format == Format::Ascii {
+            std::str::from_utf8(data).map_err(|_| String::from("ASCII PLY data is not valid text."))?
+        } else {
+            ""
+        };
+
+        Ok(ValueReader { format, cursor: Cursor::new(data), tokens: text.split_ascii_whitespace() })
+    }
+
+    fn read(&mut self, scalar_type: ScalarType) -> Result<f64, String> {
+        let value = match self.format {
+            Format::Ascii => {
+                let token = self.tokens.next().ok_or_else(|| String::from("PLY data ends too early."))?;
+                return token.parse::<f64>().map_err(|_| format!("'{}' is not a number.", token));
+            },
+            Format::BinaryLittleEndian => read_binary::<LittleEndian>(&mut self.cursor, scalar_type),
+            Format::BinaryBigEndian => read_binary::<BigEndian>(&mut self.cursor, scalar_type)
+        };
+
+        value.map_err(|_| String::from("PLY data ends too early."))
+    }
+}
+
+fn read_binary<B: byteorder::ByteOrder>(cursor: &mut Cursor<&[u8]>, scalar_type: ScalarType) -> std::io::Result<f64> {
+    Ok(match scalar_type {
+        ScalarType::Int8 => cursor.read_i8()? as f64,
+        ScalarType::UInt8 => cursor.read_u8()? as f64,
+        ScalarType::Int16 => cursor.read_i16::<B>()? as f64,
+        ScalarType::UInt16 => cursor.read_u16::<B>()? as f64,
+        ScalarType::Int32 => cursor.read_i32::<B>()? as f64,
+        ScalarType::UInt32 => cursor.read_u32::<B>()? as f64,
+        ScalarType::Float32 => cursor.read_f32::<B>()? as f64,
+        ScalarType::Float64 => cursor.read_f64::<B>()?
+    })
+}
+
+fn read_property(reader: &mut ValueReader, property: &Property) -> Result<Vec<f64>, String> {
+    match property.property_type {
+        PropertyType::Scalar(scalar_type) => Ok(vec![reader.read(scalar_type)?]),
+        PropertyType::List(count_type, item_type) => {
+            let count = reader.read(count_type)? as usize;
+            (0..count).map(|_| reader.read(item_type)).collect()
+        }
+    }
+}
+
+fn read_vertices(reader: &mut ValueReader, element: &Element, mesh: &mut Mesh) -> Result<(), String> {
+    let find = |names: &[&str]| element.properties.iter().position(|property| names.contains(&property.name.as_str()));
+
+    let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+    let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+    let uv = [find(&["u", "s", "texture_u", "texture_s"]), find(&["v", "t", "texture_v", "texture_t"])];
+    let color = [find(&["red", "diffuse_red"]), find(&["green", "diffuse_green"]), find(&["blue", "diffuse_blue"]), find(&["alpha"])];
+
+    if position.iter().any(|index| index.is_none()) {
+        return Err(String::from("PLY vertices need x, y and z properties."));
+    }
+    let has_normals = normal.iter().all(|index| index.is_some());
+    let has_uvs = uv.iter().all(|index| index.is_some());
+    let has_colors = color[..3].iter().all(|index| index.is_some());
+
+    // Integer colors go from 0 to the largest value of their type, float colors are already 0 to 1
+    let color_scale = |index: usize| match element.properties[index].property_type {
+        PropertyType::Scalar(ScalarType::UInt8) | PropertyType::Scalar(ScalarType::Int8) => 1.0 / u8::MAX as f32,
+        PropertyType::Scalar(ScalarType::UInt16) | PropertyType::Scalar(ScalarType::Int16) => 1.0 / u16::MAX as f32,
+        _ => 1.0
+    };
+
+    for _ in 0..element.count {
+        let mut values: Vec<f32> = Vec::with_capacity(element.properties.len());
+        for property in &element.properties {
+            // Lists on vertices aren't used for anything, but still have to be read past
+            values.push(read_property(reader, property)?.first().cloned().unwrap_or(0.0) as f32);
+        }
+
+        let value = |index: Option<usize>| values[index.unwrap()];
+
+        mesh.vertex_positions.push(beagle_math::Vector3::new(value(position[0]), value(position[1]), value(position[2])));
+
+        if has_normals {
+            mesh.vertex_normals.push(beagle_math::Vector3::new(value(normal[0]), value(normal[1]), value(normal[2])));
+        }
+
+        if has_uvs {
+            mesh.vertex_uvs.push(beagle_math::Vector2::new(value(uv[0]), 1.0 - value(uv[1])));
+        }
+
+        if has_colors {
+            let channel = |index: Option<usize>| value(index) * color_scale(index.unwrap());
+            let alpha = if color[3].is_some() { channel(color[3]) } else { 1.0 };
+            mesh.vertex_colors.push(beagle_math::Vector4::new(channel(color[0]), channel(color[1]), channel(color[2]), alpha));
+        }
+    }
+
+    Ok(())
+}
+
+fn read_faces(reader: &mut ValueReader, element: &Element, mesh: &mut Mesh) -> Result<(), String> {
+    let indices_property = element.properties
+        .iter()
+        .position(|property| property.name == "vertex_indices" || property.name == "vertex_index")
+        .ok_or_else(|| String::from("PLY faces need a vertex_indices property."))?;
+
+    for _ in 0..element.count {
+        for (property_index, property) in element.properties.iter().enumerate() {
+            let values = read_property(reader, property)?;
+            if property_index != indices_property {
+                continue;
+            }
+
+            if values.iter().any(|index| *index < 0.0) {
+                return Err(String::from("PLY face has a negative vertex index."));
+            }
+
+            for corner in 1..values.len().saturating_sub(1) {
+                mesh.indices.extend_from_slice(&[values[0] as u32, values[corner] as u32, values[corner + 1] as u32]);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/*
+    Exports all meshes into a single PLY file, as PLY only holds one mesh. Positions are written in each mesh's own space,
+    without its translation, rotation and scale. Normals, uvs and colors are only written if every mesh has them.
+*/
+pub fn write_model(model: &Model, format: Format) -> Vec<u8> {
+    let has_normals = model.meshes.iter().all(|mesh| !mesh.vertex_normals.is_empty());
+    let has_uvs = model.meshes.iter().all(|mesh| !mesh.vertex_uvs.is_empty());
+    let has_colors = model.meshes.iter().all(|mesh| !mesh.vertex_colors.is_empty());
+    let vertex_count: usize = model.meshes.iter().map(|mesh| mesh.vertex_positions.len()).sum();
+    let face_count: usize = model.meshes.iter().map(|mesh| mesh.indices.len() / 3).sum();
+
+    let format_name = match format {
+        Format::Ascii => "ascii",
+        Format::BinaryLittleEndian => "binary_little_endian",
+        Format::BinaryBigEndian => "binary_big_endian"
+    };
+
+    let mut header = format!("ply\nformat {} 1.0\nelement vertex {}\nproperty float x\nproperty float y\nproperty float z\n", format_name, vertex_count);
+    if has_normals {
+        header.push_str("property float nx\nproperty float ny\nproperty float nz\n");
+    }
+    if has_uvs {
+        header.push_str("property float u\nproperty float v\n");
+    }
+    if has_colors {
+        header.push_str("property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha\n");
+    }
+    header.push_str(&format!("element face {}\nproperty list uchar uint vertex_indices\nend_header\n", face_count));
+
+    let mut writer = ValueWriter { format, data: header.into_bytes() };
+    let to_byte = |channel: f32| (channel.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8;
+
+    for mesh in &model.meshes {
+        for vertex in 0..mesh.vertex_positions.len() {
+            let position = mesh.vertex_positions[vertex];
+            writer.write_floats(&[position.x, position.y, position.z]);
+
+            if has_normals {
+                let normal = mesh.vertex_normals[vertex];
+                writer.write_floats(&[normal.x, normal.y, normal.z]);
+            }
+
+            if has_uvs {
+                let uv = mesh.vertex_uvs[vertex];
+                writer.write_floats(&[uv.x, 1.0 - uv.y]);
+            }
+
+            if has_colors {
+                let color = mesh.vertex_colors[vertex];
+                writer.write_bytes(&[to_byte(color.x), to_byte(color.y), to_byte(color.z), to_byte(color.w)]);
+            }
+
+            writer.end_line();
+        }
+    }
+
+    // Indices of later meshes come after the vertices of the earlier ones
+    let mut first_vertex = 0;
+    for mesh in &model.meshes {
+        for triangle in mesh.indices.chunks_exact(3) {
+            writer.write_bytes(&[3]);
+            writer.write_indices(&[first_vertex + triangle[0], first_vertex + triangle[1], first_vertex + triangle[2]]);
+            writer.end_line();
+        }
+        first_vertex += mesh.vertex_positions.len() as u32;
+    }
+
+    writer.data
+}
+
+pub fn save_model<P: AsRef<Path>>(model: &Model, format: Format, path: P) -> Result<(), String> {
+    fs::write(&path, write_model(model, format)).map_err(|err| format!("Failed to write PLY file {}: {}", path.as_ref().display(), err))
+}
+
+struct ValueWriter {
+    format: Format,
+    data: Vec<u8>
+}
+
+impl ValueWriter {
+    fn write_floats(&mut self, values: &[f32]) {
+        for value in values {
+            match self.format {
+                Format::Ascii => self.data.extend_from_slice(format!("{} ", value).as_bytes()),
+                Format::BinaryLittleEndian => self.data.write_f32::<LittleEndian>(*value).unwrap(),
+                Format::BinaryBigEndian => self.data.write_f32::<BigEndian>(*value).unwrap()
+            }
+        }
+    }
+
+    fn write_indices(&mut self, values: &[u32]) {
+        for value in values {
+            match self.format {
+                Format::Ascii => self.data.extend_from_slice(format!("{} ", value).as_bytes()),
+                Format::BinaryLittleEndian => self.data.write_u32::<LittleEndian>(*value).unwrap(),
+                Format::BinaryBigEndian => self.data.write_u32::<BigEndian>(*value).unwrap()
+            }
+        }
+    }
+
+    fn write_bytes(&mut self, values: &[u8]) {
+        for value in values {
+            match self.format {
+                Format::Ascii => self.data.extend_from_slice(format!("{} ", value).as_bytes()),
+                _ => self.data.push(*value)
+            }
+        }
+    }
+
+    // Text files have every row on its own line, binary files don't have lines at all
+    fn end_line(&mut self) {
+        if self.format == Format::Ascii {
+            self.data.pop();
+            self.data.push(b'\n');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math;
+    use crate::asset::mesh::primitives;
+    use crate::asset::mesh::validation;
+    use crate::asset::ply::*;
+
+    fn colored_sphere() -> Model {
+        let mut mesh = primitives::create_uv_sphere(1.0, 8, 6);
+        mesh.vertex_colors = mesh.vertex_positions
+            .iter()
+            .map(|position| beagle_math::Vector4::new(position.x * 0.5 + 0.5, position.y * 0.5 + 0.5, 0.0, 1.0))
+            .collect();
+        Model { meshes: vec![mesh] }
+    }
+
+    #[test]
+    fn should_round_trip_every_format() {
+        let model = colored_sphere();
+        let original = &model.meshes[0];
+
+        for format in [Format::Ascii, Format::BinaryLittleEndian, Format::BinaryBigEndian] {
+            let loaded = parse_model(&write_model(&model, format)).unwrap();
+            let mesh = &loaded.meshes[0];
+
+            assert_eq!(mesh.indices, original.indices);
+            assert!(validation::validate(mesh).is_valid());
+            for vertex in 0..original.vertex_positions.len() {
+                assert!(mesh.vertex_positions[vertex].add(&original.vertex_positions[vertex].mul(-1.0)).length() < 0.0001);
+                assert!(mesh.vertex_normals[vertex].add(&original.vertex_normals[vertex].mul(-1.0)).length() < 0.0001);
+                assert!((mesh.vertex_uvs[vertex].x - original.vertex_uvs[vertex].x).abs() < 0.0001);
+                assert!((mesh.vertex_uvs[vertex].y - original.vertex_uvs[vertex].y).abs() < 0.0001);
+                // Colors are stored as bytes, so they're only as precise as 1 / 255
+                assert!((mesh.vertex_colors[vertex].x - original.vertex_colors[vertex].x).abs() < 0.5 / 255.0 + 0.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn should_read_ascii_ply_with_quads_and_extra_elements() {
+        let text = "ply
+format ascii 1.0
+comment made by hand
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+property uchar flags
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3 7
+0 1
+";
+
+        let model = parse_model(text.as_bytes()).unwrap();
+        let mesh = &model.meshes[0];
+
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.vertex_colors[1].y, 1.0);
+        assert_eq!(mesh.vertex_colors[1].x, 0.0);
+        assert!(mesh.vertex_normals.is_empty());
+        assert!(mesh.vertex_uvs.is_empty());
+    }
+
+    #[test]
+    fn should_reject_broken_ply() {
+        let missing_data = "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nend_header\n0 0 0\n";
+        let bad_index = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n3 0 0 5\n";
+
+        assert!(parse_model(missing_data.as_bytes()).is_err());
+        assert!(parse_model(bad_index.as_bytes()).is_err());
+        assert!(parse_model(b"not a ply file").is_err());
+    }
+}
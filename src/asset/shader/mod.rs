@@ -0,0 +1,25 @@
+use std::fs;
+use std::path::Path;
+
+/*
+    A compiled shader, as fxc writes it (see build_scripts/build.ps1).
+
+    The bytes are only handed to the GPU as they are, but loading them through the asset manager means they can be
+    hot reloaded like everything else: recompile the shader while the game runs, and the new version gets picked up.
+*/
+
+pub struct ShaderBytecode {
+    pub bytes: Vec<u8>
+}
+
+impl ShaderBytecode {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ShaderBytecode, String> {
+        let bytes = fs::read(&path).map_err(|err| format!("Failed to read shader {}: {}", path.as_ref().display(), err))?;
+        if bytes.is_empty() {
+            // fxc truncates the file before writing it, a reload can catch it in between
+            return Err(format!("Shader {} is empty.", path.as_ref().display()));
+        }
+
+        Ok(ShaderBytecode { bytes })
+    }
+}
@@ -0,0 +1,356 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::beagle_math;
+use crate::asset::mesh::{self, Mesh, Model};
+
+/*
+    Importer and exporter for STL files, which is what most CAD and 3D printing tools speak.
+
+    STL is about as simple as a mesh format gets: a list of triangles, each with a facet normal and three positions.
+    There are no shared vertices, uvs, colors or materials. Two flavours exist:
+
+    ASCII:
+        solid name
+          facet normal nx ny nz
+            outer loop
+              vertex x y z
+              vertex x y z
+              vertex x y z
+            endloop
+          endfacet
+        endsolid name
+
+    Binary (always little endian):
+        80 byte header, u32 triangle count, then per triangle: normal (3 x f32), 3 positions (9 x f32) and a u16 "attribute byte count".
+
+    Triangles are counter clockwise seen from outside in a right handed space, the same as glTF, so they're kept as they are.
+    Every triangle is flat, so the facet normal becomes the normal of all three corners, and welding only merges corners
+    of neighbouring triangles that lie in the same plane.
+*/
+
+pub fn load_model<P: AsRef<Path>>(path: P) -> Result<Model, String> {
+    let binary_data = fs::read(&path).map_err(|err| format!("Failed to read STL file {}: {}", path.as_ref().display(), err))?;
+    parse_model(&binary_data)
+}
+
+pub fn parse_model(binary_data: &[u8]) -> Result<Model, String> {
+    if is_binary(binary_data) {
+        parse_binary(binary_data)
+    } else {
+        parse_ascii(binary_data)
+    }
+}
+
+/*
+    Binary files are supposed to not start with "solid", but plenty of exporters write "solid" in the header anyway.
+    The size of a binary file follows from its triangle count though, so if that matches, the file is binary.
+*/
+fn is_binary(binary_data: &[u8]) -> bool {
+    if binary_data.len() >= 84 {
+        let triangle_count = LittleEndian::read_u32(&binary_data[80..84]) as usize;
+        if binary_data.len() == 84 + triangle_count * 50 {
+            return true;
+        }
+    }
+
+    !binary_data.trim_ascii_start().starts_with(b"solid")
+}
+
+fn parse_binary(binary_data: &[u8]) -> Result<Model, String> {
+    if binary_data.len() < 84 {
+        return Err(format!("A binary STL file needs at least 84 bytes, but has {}.", binary_data.len()));
+    }
+
+    let mut cursor = Cursor::new(&binary_data[80..]);
+    let triangle_count = cursor.read_u32::<LittleEndian>().unwrap() as usize;
+    if binary_data.len() < 84 + triangle_count * 50 {
+        return Err(format!("Binary STL file should have {} triangles, but is too short for that.", triangle_count));
+    }
+
+    let mut triangles: Vec<[beagle_math::Vector3; 4]> = Vec::with_capacity(triangle_count);
+    for _ in 0..triangle_count {
+        let mut triangle = [beagle_math::Vector3::zero(); 4];
+        for vector in triangle.iter_mut() {
+            let x = cursor.read_f32::<LittleEndian>().unwrap();
+            let y = cursor.read_f32::<LittleEndian>().unwrap();
+            let z = cursor.read_f32::<LittleEndian>().unwrap();
+            *vector = beagle_math::Vector3::new(x, y, z);
+        }
+        // Attribute byte count, which nobody agrees on the meaning of
+        cursor.read_u16::<LittleEndian>().unwrap();
+
+        triangles.push(triangle);
+    }
+
+    Ok(Model { meshes: vec![build_mesh("stl", &triangles)] })
+}
+
+// ASCII files can contain several solids, every solid becomes a Mesh
+fn parse_ascii(binary_data: &[u8]) -> Result<Model, String> {
+    let source = std::str::from_utf8(binary_data).map_err(|_| String::from("ASCII STL file is not valid text."))?;
+
+    let mut meshes: Vec<Mesh> = vec!();
+    let mut solid_name: Option<String> = None;
+    let mut triangles: Vec<[beagle_math::Vector3; 4]> = vec!();
+    let mut normal = beagle_math::Vector3::zero();
+    let mut loop_positions: Vec<beagle_math::Vector3> = vec!();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let mut parts = line.split_whitespace();
+
+        match parts.next() {
+            Some("solid") => {
+                let name: Vec<&str> = parts.collect();
+                solid_name = Some(if name.is_empty() { String::from("stl") } else { name.join(" ") });
+            },
+            Some("facet") => {
+                let values: Vec<&str> = parts.collect();
+                if values.len() != 4 || values[0] != "normal" {
+                    return Err(format!("Line {}: Expected 'facet normal nx ny nz'.", line_number));
+                }
+                normal = parse_vector(&values[1..], line_number)?;
+            },
+            Some("outer") => loop_positions.clear(),
+            Some("vertex") => {
+                let values: Vec<&str> = parts.collect();
+                loop_positions.push(parse_vector(&values, line_number)?);
+            },
+            Some("endloop") if loop_positions.len() != 3 => {
+                return Err(format!("Line {}: A facet needs 3 vertices, but has {}.", line_number, loop_positions.len()));
+            },
+            Some("endloop") => (),
+            Some("endfacet") => {
+                if solid_name.is_none() {
+                    return Err(format!("Line {}: Facet outside of a solid.", line_number));
+                }
+                // Also catches a facet with no loop at all
+                if loop_positions.len() != 3 {
+                    return Err(format!("Line {}: A facet needs 3 vertices, but has {}.", line_number, loop_positions.len()));
+                }
+                triangles.push([normal, loop_positions[0], loop_positions[1], loop_positions[2]]);
+                loop_positions.clear();
+            },
+            Some("endsolid") => {
+                let name = solid_name.take().ok_or_else(|| format!("Line {}: 'endsolid' without 'solid'.", line_number))?;
+                meshes.push(build_mesh(&name, &triangles));
+                triangles.clear();
+            },
+            Some(keyword) => return Err(format!("Line {}: Unknown keyword '{}'.", line_number, keyword)),
+            None => ()
+        }
+    }
+
+    if solid_name.is_some() {
+        return Err(String::from("ASCII STL file ends in the middle of a solid."));
+    }
+
+    Ok(Model { meshes })
+}
+
+fn parse_vector(values: &[&str], line_number: usize) -> Result<beagle_math::Vector3, String> {
+    if values.len() != 3 {
+        return Err(format!("Line {}: Expected 3 numbers, but found {}.", line_number, values.len()));
+    }
+
+    let mut numbers = [0.0; 3];
+    for (number, value) in numbers.iter_mut().zip(values.iter()) {
+        *number = value.parse::<f32>().map_err(|_| format!("Line {}: '{}' is not a number.", line_number, value))?;
+    }
+
+    Ok(beagle_math::Vector3::from_array(&numbers))
+}
+
+// Every triangle is the facet normal followed by the three positions
+fn build_mesh(name: &str, triangles: &[[beagle_math::Vector3; 4]]) -> Mesh {
+    let mut vertices: Vec<mesh::Vertex> = Vec::with_capacity(triangles.len() * 3);
+
+    for triangle in triangles {
+        // Lots of exporters just write a zero normal, so it's calculated from the positions when it's missing
+        let normal = if triangle[0].length() > 0.0 {
+            triangle[0].normalized()
+        } else {
+            face_normal(&triangle[1], &triangle[2], &triangle[3])
+        };
+
+        for position in &triangle[1..] {
+            vertices.push(mesh::Vertex { position: *position, normal, ..Default::default() });
+        }
+    }
+
+    let (unique_vertices, indices) = mesh::weld_vertices(&vertices);
+
+    Mesh {
+        name: String::from(name),
+        vertex_positions: unique_vertices.iter().map(|vertex| vertex.position).collect(),
+        vertex_normals: unique_vertices.iter().map(|vertex| vertex.normal).collect(),
+        indices,
+        ..Default::default()
+    }
+}
+
+// The normalized normal of a triangle, or zero if the triangle has no area
+fn face_normal(a: &beagle_math::Vector3, b: &beagle_math::Vector3, c: &beagle_math::Vector3) -> beagle_math::Vector3 {
+    let normal = b.add(&a.mul(-1.0)).cross(&c.add(&a.mul(-1.0)));
+    let length = normal.length();
+
+    if length > 0.0 && length.is_finite() {
+        normal.mul(1.0 / length)
+    } else {
+        beagle_math::Vector3::zero()
+    }
+}
+
+/*
+    Exporting writes the triangles of every mesh in the mesh's own space: the translation, rotation and scale
+    of the meshes are not applied. Facet normals are calculated from the positions, as STL has no smooth normals.
+*/
+pub fn write_binary(model: &Model) -> Vec<u8> {
+    let triangle_count: usize = model.meshes.iter().map(|mesh| mesh.indices.len() / 3).sum();
+
+    let mut binary_data: Vec<u8> = Vec::with_capacity(84 + triangle_count * 50);
+    let mut header = [0u8; 80];
+    let name = b"binary STL";
+    header[..name.len()].copy_from_slice(name);
+    binary_data.extend_from_slice(&header);
+    binary_data.write_u32::<LittleEndian>(triangle_count as u32).unwrap();
+
+    for mesh in &model.meshes {
+        for triangle in mesh.indices.chunks_exact(3) {
+            let a = mesh.vertex_positions[triangle[0] as usize];
+            let b = mesh.vertex_positions[triangle[1] as usize];
+            let c = mesh.vertex_positions[triangle[2] as usize];
+
+            for vector in [face_normal(&a, &b, &c), a, b, c] {
+                binary_data.write_f32::<LittleEndian>(vector.x).unwrap();
+                binary_data.write_f32::<LittleEndian>(vector.y).unwrap();
+                binary_data.write_f32::<LittleEndian>(vector.z).unwrap();
+            }
+            binary_data.write_u16::<LittleEndian>(0).unwrap();
+        }
+    }
+
+    binary_data
+}
+
+// Every mesh becomes its own solid
+pub fn write_ascii(model: &Model) -> String {
+    let mut text = String::new();
+
+    for mesh in &model.meshes {
+        // Solid names end at the line, and can't be empty
+        let name = if mesh.name.trim().is_empty() { String::from("mesh") } else { mesh.name.replace(['\r', '\n'], " ") };
+        text.push_str(&format!("solid {}\n", name));
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let a = mesh.vertex_positions[triangle[0] as usize];
+            let b = mesh.vertex_positions[triangle[1] as usize];
+            let c = mesh.vertex_positions[triangle[2] as usize];
+            let normal = face_normal(&a, &b, &c);
+
+            text.push_str(&format!("  facet normal {:e} {:e} {:e}\n    outer loop\n", normal.x, normal.y, normal.z));
+            for position in [a, b, c] {
+                text.push_str(&format!("      vertex {:e} {:e} {:e}\n", position.x, position.y, position.z));
+            }
+            text.push_str("    endloop\n  endfacet\n");
+        }
+
+        text.push_str(&format!("endsolid {}\n", name));
+    }
+
+    text
+}
+
+pub fn save_binary<P: AsRef<Path>>(model: &Model, path: P) -> Result<(), String> {
+    fs::write(&path, write_binary(model)).map_err(|err| format!("Failed to write STL file {}: {}", path.as_ref().display(), err))
+}
+
+pub fn save_ascii<P: AsRef<Path>>(model: &Model, path: P) -> Result<(), String> {
+    fs::write(&path, write_ascii(model)).map_err(|err| format!("Failed to write STL file {}: {}", path.as_ref().display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math;
+    use crate::asset::mesh::primitives;
+    use crate::asset::mesh::validation;
+    use crate::asset::mesh::Model;
+    use crate::asset::stl::*;
+
+    fn box_model() -> Model {
+        let mut mesh = primitives::create_box(&beagle_math::Vector3::new(1.0, 2.0, 3.0), 2);
+        mesh.name = String::from("my box");
+        Model { meshes: vec![mesh] }
+    }
+
+    fn assert_same_triangles(original: &Mesh, loaded: &Mesh) {
+        assert_eq!(original.indices.len(), loaded.indices.len());
+
+        for (original_triangle, loaded_triangle) in original.indices.chunks_exact(3).zip(loaded.indices.chunks_exact(3)) {
+            for (original_index, loaded_index) in original_triangle.iter().zip(loaded_triangle.iter()) {
+                let original_position = original.vertex_positions[*original_index as usize];
+                let loaded_position = loaded.vertex_positions[*loaded_index as usize];
+                assert!(original_position.add(&loaded_position.mul(-1.0)).length() < 0.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn should_round_trip_binary_stl() {
+        let model = box_model();
+
+        let binary_data = write_binary(&model);
+        let loaded = parse_model(&binary_data).unwrap();
+
+        assert_eq!(binary_data.len(), 84 + 50 * model.meshes[0].indices.len() / 3);
+        assert_eq!(loaded.meshes.len(), 1);
+        assert_same_triangles(&model.meshes[0], &loaded.meshes[0]);
+        // The flat sides of a box weld back into 6 separate sides of 3x3 vertices
+        assert_eq!(loaded.meshes[0].vertex_positions.len(), 6 * 9);
+        assert!(validation::validate(&loaded.meshes[0]).is_valid());
+    }
+
+    #[test]
+    fn should_round_trip_ascii_stl() {
+        let model = box_model();
+
+        let text = write_ascii(&model);
+        let loaded = parse_model(text.as_bytes()).unwrap();
+
+        assert_eq!(loaded.meshes[0].name, "my box");
+        assert_same_triangles(&model.meshes[0], &loaded.meshes[0]);
+    }
+
+    #[test]
+    fn should_read_binary_stl_with_solid_in_header_and_missing_normals() {
+        let mut binary_data: Vec<u8> = vec!();
+        let mut header = [b' '; 80];
+        header[..5].copy_from_slice(b"solid");
+        binary_data.extend_from_slice(&header);
+        binary_data.write_u32::<LittleEndian>(1).unwrap();
+        for value in [0.0f32, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            binary_data.write_f32::<LittleEndian>(value).unwrap();
+        }
+        binary_data.write_u16::<LittleEndian>(0).unwrap();
+
+        let model = parse_model(&binary_data).unwrap();
+
+        assert_eq!(model.meshes[0].indices, vec![0, 1, 2]);
+        assert_eq!(model.meshes[0].vertex_normals[0].z, 1.0);
+    }
+
+    #[test]
+    fn should_reject_broken_ascii_stl() {
+        let text = "solid broken\n  facet normal 0 0 1\n    outer loop\n      vertex 0 0 0\n      vertex 1 0 0\n    endloop\n  endfacet\nendsolid broken\n";
+
+        assert!(parse_model(text.as_bytes()).is_err());
+        assert!(parse_model(b"solid unfinished\n").is_err());
+
+        let without_loop = "solid broken\n  facet normal 0 0 1\n  endfacet\nendsolid broken\n";
+        assert_eq!(parse_model(without_loop.as_bytes()).err(), Some(String::from("Line 3: A facet needs 3 vertices, but has 0.")));
+    }
+}
@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::Path;
+
+/*
+    A texture as it sits in memory before it's handed to the GPU: width x height pixels, row by row from the top,
+    4 bytes per pixel (red, green, blue, alpha).
+
+    Everything is turned into that one layout when loading, whatever the file had (grayscale, palette, 16 bits),
+    so the renderer only ever has to deal with DXGI_FORMAT_R8G8B8A8_UNORM.
+*/
+
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+impl Texture {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Texture, String> {
+        let binary_data = fs::read(&path).map_err(|err| format!("Failed to read texture {}: {}", path.as_ref().display(), err))?;
+        Texture::from_png(&binary_data)
+    }
+
+    pub fn from_png(binary_data: &[u8]) -> Result<Texture, String> {
+        let mut decoder = png::Decoder::new(binary_data);
+        // Turns palettes into colors, and 16 bit or less than 8 bit samples into 8 bit ones
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder.read_info().map_err(|err| format!("Could not read PNG texture: {}", err))?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buffer).map_err(|err| format!("Could not decode PNG texture: {}", err))?;
+
+        let mut pixels: Vec<u8> = Vec::with_capacity((frame.width * frame.height * 4) as usize);
+        for line in buffer[..frame.buffer_size()].chunks_exact(frame.line_size) {
+            match frame.color_type {
+                png::ColorType::Grayscale => line[..frame.width as usize]
+                    .iter()
+                    .for_each(|gray| pixels.extend_from_slice(&[*gray, *gray, *gray, u8::MAX])),
+                png::ColorType::GrayscaleAlpha => line[..frame.width as usize * 2]
+                    .chunks_exact(2)
+                    .for_each(|pixel| pixels.extend_from_slice(&[pixel[0], pixel[0], pixel[0], pixel[1]])),
+                png::ColorType::Rgb => line[..frame.width as usize * 3]
+                    .chunks_exact(3)
+                    .for_each(|pixel| pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], u8::MAX])),
+                png::ColorType::Rgba => pixels.extend_from_slice(&line[..frame.width as usize * 4]),
+                png::ColorType::Indexed => return Err(String::from("PNG texture still has a palette after expanding it."))
+            }
+        }
+
+        Ok(Texture { width: frame.width, height: frame.height, pixels })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::texture::*;
+
+    #[test]
+    fn should_expand_rgb_png_to_rgba() {
+        let mut png_data: Vec<u8> = vec!();
+        {
+            let mut encoder = png::Encoder::new(&mut png_data, 2, 1);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 0, 0, 0, 128, 255]).unwrap();
+        }
+
+        let texture = Texture::from_png(&png_data).unwrap();
+
+        assert_eq!((texture.width, texture.height), (2, 1));
+        assert_eq!(texture.pixels, vec![255, 0, 0, 255, 0, 128, 255, 255]);
+        assert!(Texture::from_png(b"not a png").is_err());
+    }
+}
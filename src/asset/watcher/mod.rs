@@ -0,0 +1,286 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/*
+    Tells which files changed on disk, so assets can be reloaded while the game is running.
+
+    There's one implementation per way of finding out:
+        - InotifyWatcher asks the Linux kernel to tell about changes, so nothing is done as long as nothing changes.
+        - PollingWatcher looks at the modification time and size of every file, at most every POLL_INTERVAL. It works everywhere,
+          and is what's used on platforms that don't have a watcher of their own yet (Windows would get one around ReadDirectoryChangesW).
+          The asset manager asks every frame, and looking at every file that often is a waste, so asking in between gives nothing.
+
+    Both only report changes when asked, from the thread that asks, so no other thread is ever involved.
+    A file that changed several times since the last call is only reported once.
+*/
+
+pub trait FileWatcher {
+    // Watching a file that is already watched does nothing
+    fn watch(&mut self, path: &Path) -> Result<(), String>;
+    fn unwatch(&mut self, path: &Path);
+    // Files that changed since the last call, sorted
+    fn changed_files(&mut self) -> Vec<PathBuf>;
+}
+
+// The best watcher there is for the platform
+pub fn create_watcher() -> Box<dyn FileWatcher> {
+    #[cfg(target_os = "linux")]
+    {
+        match InotifyWatcher::new() {
+            Ok(watcher) => return Box::new(watcher),
+            Err(err) => println!("Falling back to polling for file changes: {}", err)
+        }
+    }
+
+    Box::new(PollingWatcher::default())
+}
+
+// How often PollingWatcher looks at the files. Saving a file and seeing it in the game a fraction of a second later is plenty fast.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// None if the file doesn't exist (at the moment)
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+pub struct PollingWatcher {
+    stamps: HashMap<PathBuf, Option<(SystemTime, u64)>>,
+    interval: Duration,
+    last_poll: Option<Instant>
+}
+
+impl Default for PollingWatcher {
+    fn default() -> Self {
+        PollingWatcher::with_interval(POLL_INTERVAL)
+    }
+}
+
+impl PollingWatcher {
+    // A zero interval looks at the files every time it's asked
+    pub fn with_interval(interval: Duration) -> PollingWatcher {
+        PollingWatcher { stamps: HashMap::new(), interval, last_poll: None }
+    }
+}
+
+impl FileWatcher for PollingWatcher {
+    fn watch(&mut self, path: &Path) -> Result<(), String> {
+        self.stamps.entry(path.to_path_buf()).or_insert_with(|| file_stamp(path));
+        Ok(())
+    }
+
+    fn unwatch(&mut self, path: &Path) {
+        self.stamps.remove(path);
+    }
+
+    fn changed_files(&mut self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = vec!();
+
+        // Changes in between aren't lost, they're found the next time the files are looked at
+        if self.last_poll.is_some_and(|last_poll| last_poll.elapsed() < self.interval) {
+            return changed;
+        }
+        self.last_poll = Some(Instant::now());
+
+        for (path, stamp) in self.stamps.iter_mut() {
+            let current_stamp = file_stamp(path);
+            if current_stamp != *stamp {
+                *stamp = current_stamp;
+                // A file that is deleted is gone, not changed. Saving in some editors deletes the file for a moment.
+                if current_stamp.is_some() {
+                    changed.push(path.clone());
+                }
+            }
+        }
+
+        changed.sort();
+        changed
+    }
+}
+
+/*
+    inotify watches directories rather than the files themselves.
+    A lot of editors and exporters save by writing a new file and renaming it over the old one,
+    and a watch on the old file would be gone after that.
+
+    The events I'm interested in:
+        - IN_CLOSE_WRITE: a file that was opened for writing got closed, so it's done being written
+        - IN_MOVED_TO: a file was renamed into the directory
+*/
+#[cfg(target_os = "linux")]
+pub struct InotifyWatcher {
+    file_descriptor: i32,
+    // Watch descriptor -> directory
+    directories: HashMap<i32, PathBuf>,
+    files: HashSet<PathBuf>
+}
+
+#[cfg(target_os = "linux")]
+impl InotifyWatcher {
+    pub fn new() -> Result<InotifyWatcher, String> {
+        // Non blocking, so reading events returns right away when there are none
+        let file_descriptor = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if file_descriptor < 0 {
+            return Err(format!("Failed to initialize inotify: {}", std::io::Error::last_os_error()));
+        }
+
+        Ok(InotifyWatcher { file_descriptor, directories: HashMap::new(), files: HashSet::new() })
+    }
+
+    // Reads everything that is waiting, and turns it into the full paths of the files the events were about
+    fn read_events(&mut self) -> BTreeSet<PathBuf> {
+        let mut paths: BTreeSet<PathBuf> = BTreeSet::new();
+        // u64s so the buffer is aligned well enough for the event structs in it
+        let mut buffer = [0u64; 1024];
+        let header_size = std::mem::size_of::<libc::inotify_event>();
+
+        loop {
+            let read = unsafe {
+                libc::read(self.file_descriptor, buffer.as_mut_ptr() as *mut libc::c_void, std::mem::size_of_val(&buffer))
+            };
+            // Either no more events (EAGAIN), or something went wrong. Both mean there's nothing to read right now.
+            if read <= 0 {
+                break;
+            }
+
+            let bytes = unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, read as usize) };
+            let mut offset = 0;
+            while offset + header_size <= bytes.len() {
+                let event = unsafe { std::ptr::read_unaligned(bytes[offset..].as_ptr() as *const libc::inotify_event) };
+                let name_bytes = &bytes[offset + header_size..offset + header_size + event.len as usize];
+                offset += header_size + event.len as usize;
+
+                // The name is padded with zeroes
+                let name_length = name_bytes.iter().position(|byte| *byte == 0).unwrap_or(name_bytes.len());
+                let name = String::from_utf8_lossy(&name_bytes[..name_length]).into_owned();
+
+                if let Some(directory) = self.directories.get(&event.wd) {
+                    paths.insert(directory.join(name));
+                }
+            }
+        }
+
+        paths
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl FileWatcher for InotifyWatcher {
+    fn watch(&mut self, path: &Path) -> Result<(), String> {
+        if self.files.contains(path) {
+            return Ok(());
+        }
+
+        let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        if !self.directories.values().any(|watched| watched == directory) {
+            use std::os::unix::ffi::OsStrExt;
+            let directory_name = std::ffi::CString::new(directory.as_os_str().as_bytes())
+                .map_err(|_| format!("Can't watch {}, its path has a zero byte in it.", directory.display()))?;
+
+            let watch_descriptor = unsafe {
+                libc::inotify_add_watch(self.file_descriptor, directory_name.as_ptr(), libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO)
+            };
+            if watch_descriptor < 0 {
+                return Err(format!("Failed to watch {}: {}", directory.display(), std::io::Error::last_os_error()));
+            }
+
+            self.directories.insert(watch_descriptor, directory.to_path_buf());
+        }
+
+        self.files.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    // The directory stays watched, events for files in it that aren't watched are just ignored
+    fn unwatch(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+
+    fn changed_files(&mut self) -> Vec<PathBuf> {
+        let changed = self.read_events();
+        changed.into_iter().filter(|path| self.files.contains(path)).collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for InotifyWatcher {
+    // Closing it removes all the watches as well
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.file_descriptor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::watcher::*;
+
+    fn test_directory(test_name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("beagle_watcher_{}_{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("watched.txt"), "first").unwrap();
+        fs::write(directory.join("other.txt"), "first").unwrap();
+        directory
+    }
+
+    fn should_report_changed_watched_files(watcher: &mut dyn FileWatcher, directory: &Path) {
+        let watched = directory.join("watched.txt");
+        watcher.watch(&watched).unwrap();
+        watcher.watch(&watched).unwrap();
+
+        assert!(watcher.changed_files().is_empty());
+
+        // Different size, as the modification time may not have changed on file systems with a coarse clock
+        fs::write(&watched, "second version").unwrap();
+        fs::write(directory.join("other.txt"), "second version").unwrap();
+        assert_eq!(watcher.changed_files(), vec![watched.clone()]);
+        assert!(watcher.changed_files().is_empty());
+
+        // Saved by writing another file and renaming it over the watched one
+        fs::write(directory.join("watched.tmp"), "third").unwrap();
+        fs::rename(directory.join("watched.tmp"), &watched).unwrap();
+        assert_eq!(watcher.changed_files(), vec![watched.clone()]);
+
+        watcher.unwatch(&watched);
+        fs::write(&watched, "fourth version!").unwrap();
+        assert!(watcher.changed_files().is_empty());
+    }
+
+    #[test]
+    fn should_report_changed_files_when_polling() {
+        let directory = test_directory("polling");
+        should_report_changed_watched_files(&mut PollingWatcher::with_interval(Duration::ZERO), &directory);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn should_only_look_at_files_once_per_interval_when_polling() {
+        let directory = test_directory("polling_interval");
+        let watched = directory.join("watched.txt");
+        let mut watcher = PollingWatcher::with_interval(Duration::from_secs(3600));
+        watcher.watch(&watched).unwrap();
+
+        // The first call looks, and starts the interval
+        assert!(watcher.changed_files().is_empty());
+        fs::write(&watched, "second version").unwrap();
+        assert!(watcher.changed_files().is_empty());
+
+        // Once the interval is over, the change is still there to be found
+        watcher.interval = Duration::ZERO;
+        assert_eq!(watcher.changed_files(), vec![watched]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn should_report_changed_files_with_inotify() {
+        let directory = test_directory("inotify");
+        should_report_changed_watched_files(&mut InotifyWatcher::new().unwrap(), &directory);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/*
+    A fixed number of threads that run jobs in the order they were handed in.
+
+    Jobs go into a single channel, and every thread takes the next job out as soon as it's done with the previous one.
+    The receiving end of a channel can only be used by one thread at a time, so it sits behind a Mutex.
+
+    Dropping the pool closes the channel. The threads finish the jobs that are still queued, notice there are
+    no more coming, and stop. Drop waits for that, so no thread outlives the pool.
+*/
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+pub struct WorkerPool {
+    sender: Option<Sender<Job>>,
+    threads: Vec<JoinHandle<()>>
+}
+
+impl WorkerPool {
+    pub fn new(thread_count: usize) -> WorkerPool {
+        if thread_count == 0 {
+            panic!("A worker pool needs at least 1 thread.");
+        }
+
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let threads = (0..thread_count)
+            .map(|index| {
+                let receiver = Arc::clone(&receiver);
+                thread::Builder::new()
+                    .name(format!("asset worker {}", index))
+                    .spawn(move || run_worker(&receiver))
+                    .expect("Failed to start asset worker thread")
+            })
+            .collect();
+
+        WorkerPool { sender: Some(sender), threads }
+    }
+
+    // One thread less than the machine has cores, so the main thread keeps one to itself
+    pub fn with_default_thread_count() -> WorkerPool {
+        let cores = thread::available_parallelism().map(|cores| cores.get()).unwrap_or(2);
+        WorkerPool::new(cores.saturating_sub(1).max(1))
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.threads.len()
+    }
+
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) {
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(Box::new(job))
+            .expect("All asset worker threads have stopped");
+    }
+}
+
+fn run_worker(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        // The lock is only held while taking a job, not while running it
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return
+        };
+
+        match job {
+            Ok(job) => job(),
+            // The pool was dropped
+            Err(_) => return
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.sender = None;
+
+        for thread in self.threads.drain(..) {
+            // A job that panicked already printed its message, there's nothing more to do with it here
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::asset::worker_pool::*;
+
+    #[test]
+    fn should_run_every_job_before_dropping() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let pool = WorkerPool::new(3);
+
+        for _ in 0..100 {
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        drop(pool);
+
+        assert_eq!(counter.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn should_run_jobs_on_several_threads() {
+        let pool = WorkerPool::new(2);
+        let (sender, receiver) = mpsc::channel();
+
+        // Both jobs wait for each other, which only works if they run at the same time
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+        for _ in 0..2 {
+            let barrier = Arc::clone(&barrier);
+            let sender = sender.clone();
+            pool.execute(move || {
+                barrier.wait();
+                sender.send(thread::current().name().unwrap().to_string()).unwrap();
+            });
+        }
+
+        let mut names: Vec<String> = receiver.iter().take(2).collect();
+        names.sort();
+        assert_eq!(names, vec!["asset worker 0", "asset worker 1"]);
+    }
+}
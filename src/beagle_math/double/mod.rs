@@ -0,0 +1,388 @@
+use std::ops;
+
+use crate::beagle_math::{Mat3, Mat4, Quaternion, Vector2, Vector3, Vector4};
+
+/*
+    Double precision (f64) versions of the vectors, Mat4 and Quaternion, for world positions and physics.
+
+    An f32 has 24 bits of precision. 10 km from the origin, that's only enough to tell positions apart every millimeter,
+    and 100 km out, every 8 millimeters. Far enough out, models start to shake as the camera moves.
+    An f64 has 53 bits, which is still better than a micrometer at the other side of the earth.
+
+    The GPU works in f32 though. The way around that is camera-relative rendering:
+    the camera's position is subtracted from everything in f64, where nothing is lost, and only the difference is turned into f32.
+    What's close to the camera, which is where precision matters, ends up with small coordinates, and f32 is plenty for those.
+    The camera itself ends up at the origin, so its view matrix only rotates (see camera_relative_view).
+
+    The D types work the same way as their f32 counterparts, with the same conventions (row vectors, rows stored one after the other).
+    f32 to f64 is lossless, so it's a From (DVector3::from(vector), or vector.into()).
+    f64 to f32 rounds, so it has to be asked for with to_f32.
+*/
+
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct DVector2 {
+    pub x: f64,
+    pub y: f64
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct DVector3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct DVector4 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64
+}
+
+impl_vector_arithmetic!(DVector2: f64 { x, y });
+impl_vector_arithmetic!(DVector3: f64 { x, y, z });
+impl_vector_arithmetic!(DVector4: f64 { x, y, z, w });
+
+impl_vector!(DVector2: f64 { x, y });
+impl_vector!(DVector3: f64 { x, y, z });
+impl_vector!(DVector4: f64 { x, y, z, w });
+
+// From the f32 vector (lossless), and back with to_f32 (rounding every component to the nearest f32)
+macro_rules! impl_vector_conversions {
+    ($double:ident, $single:ident { $($component:ident),+ }) => {
+        impl From<$single> for $double {
+            fn from(vector: $single) -> $double {
+                $double { $($component: f64::from(vector.$component)),+ }
+            }
+        }
+
+        impl $double {
+            pub fn to_f32(self) -> $single {
+                $single { $($component: self.$component as f32),+ }
+            }
+        }
+    };
+}
+
+impl_vector_conversions!(DVector2, Vector2 { x, y });
+impl_vector_conversions!(DVector3, Vector3 { x, y, z });
+impl_vector_conversions!(DVector4, Vector4 { x, y, z, w });
+
+impl DVector2 {
+    pub fn new(x: f64, y: f64) -> DVector2 {
+        DVector2 { x, y }
+    }
+}
+
+impl DVector3 {
+    pub fn zero() -> DVector3 {
+        DVector3::default()
+    }
+
+    pub fn new(x: f64, y: f64, z: f64) -> DVector3 {
+        DVector3 { x, y, z }
+    }
+
+    pub fn cross(&self, vec: &DVector3) -> DVector3 {
+        DVector3::new(
+            self.y * vec.z - self.z * vec.y,
+            self.z * vec.x - self.x * vec.z,
+            self.x * vec.y - self.y * vec.x)
+    }
+
+    // Where this position is seen from origin (usually the camera), in f32. The subtraction is done in f64, so only the result is rounded.
+    pub fn relative_to(&self, origin: &DVector3) -> Vector3 {
+        (*self - *origin).to_f32()
+    }
+}
+
+impl DVector4 {
+    pub fn new(x: f64, y: f64, z: f64, w: f64) -> DVector4 {
+        DVector4 { x, y, z, w }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DMat4 {
+    pub matrix: [f64; 16]
+}
+
+impl Default for DMat4 {
+    fn default() -> Self { DMat4::identity() }
+}
+
+impl From<Mat4> for DMat4 {
+    fn from(mat: Mat4) -> DMat4 {
+        DMat4 { matrix: mat.matrix.map(f64::from) }
+    }
+}
+
+impl DMat4 {
+    pub fn new(matrix: [f64; 16]) -> DMat4 {
+        DMat4 { matrix }
+    }
+
+    pub fn identity() -> DMat4 {
+        DMat4::new([
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    // Column x of row y, like Mat4::get
+    pub fn get(&self, x: usize, y: usize) -> f64 {
+        self.matrix[x + 4 * y]
+    }
+
+    pub fn translate(pos: &DVector3) -> DMat4 {
+        DMat4::new([
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            pos.x, pos.y, pos.z, 1.0
+        ])
+    }
+
+    pub fn scale(scale: &DVector3) -> DMat4 {
+        DMat4::new([
+            scale.x, 0.0, 0.0, 0.0,
+            0.0, scale.y, 0.0, 0.0,
+            0.0, 0.0, scale.z, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    // The translation row, which is where the origin of the matrix's space ends up
+    pub fn translation(&self) -> DVector3 {
+        DVector3::new(self.matrix[12], self.matrix[13], self.matrix[14])
+    }
+
+    pub fn mul(&self, mat: &DMat4) -> DMat4 {
+        let mut matrix = [0.0; 16];
+        for row in 0..4 {
+            let result = mat.mul_row(&DVector4::new(self.matrix[row * 4], self.matrix[row * 4 + 1], self.matrix[row * 4 + 2], self.matrix[row * 4 + 3]));
+            matrix[row * 4..row * 4 + 4].copy_from_slice(&[result.x, result.y, result.z, result.w]);
+        }
+
+        DMat4 { matrix }
+    }
+
+    pub fn mul_row(&self, row: &DVector4) -> DVector4 {
+        let m = &self.matrix;
+        DVector4::new(
+            row.x * m[0] + row.y * m[4] + row.z * m[8] + row.w * m[12],
+            row.x * m[1] + row.y * m[5] + row.z * m[9] + row.w * m[13],
+            row.x * m[2] + row.y * m[6] + row.z * m[10] + row.w * m[14],
+            row.x * m[3] + row.y * m[7] + row.z * m[11] + row.w * m[15])
+    }
+
+    // As a row vector with w = 1, without dividing by the resulting w, so for affine transforms only
+    pub fn transform_point(&self, point: &DVector3) -> DVector3 {
+        let row = self.mul_row(&DVector4::new(point.x, point.y, point.z, 1.0));
+        DVector3::new(row.x, row.y, row.z)
+    }
+
+    pub fn get_transposed(&self) -> DMat4 {
+        DMat4::new(std::array::from_fn(|index| self.matrix[(index % 4) * 4 + index / 4]))
+    }
+
+    pub fn to_f32(self) -> Mat4 {
+        Mat4::new(self.matrix.map(|value| value as f32))
+    }
+
+    /*
+        The matrix as seen from origin (usually the camera), in f32, for an affine matrix (like a model's world matrix).
+        Only the translation row depends on where the origin is, so that's the only part that's moved, in f64, before rounding.
+        Multiplied by the view matrix from camera_relative_view, this is the same transform as before, without the large numbers.
+    */
+    pub fn relative_to(&self, origin: &DVector3) -> Mat4 {
+        let mut relative = *self;
+        relative.matrix[12] -= origin.x;
+        relative.matrix[13] -= origin.y;
+        relative.matrix[14] -= origin.z;
+
+        relative.to_f32()
+    }
+}
+
+impl ops::Mul for DMat4 {
+    type Output = DMat4;
+
+    fn mul(self, other: DMat4) -> DMat4 {
+        DMat4::mul(&self, &other)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DQuaternion {
+    pub w: f64,
+    pub v: DVector3
+}
+
+impl Default for DQuaternion {
+    fn default() -> Self { DQuaternion::identity() }
+}
+
+impl From<Quaternion> for DQuaternion {
+    fn from(quaternion: Quaternion) -> DQuaternion {
+        DQuaternion { w: f64::from(quaternion.w), v: DVector3::from(quaternion.v) }
+    }
+}
+
+impl DQuaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> DQuaternion {
+        DQuaternion { w, v: DVector3::new(x, y, z) }
+    }
+
+    pub fn identity() -> DQuaternion {
+        DQuaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    pub fn from_axis_angle(axis: &DVector3, angle_in_radians: f64) -> DQuaternion {
+        let (sin, cos) = (angle_in_radians / 2.0).sin_cos();
+        DQuaternion { w: cos, v: axis.normalized() * sin }
+    }
+
+    pub fn dot(&self, other: &DQuaternion) -> f64 {
+        self.w * other.w + self.v.dot(&other.v)
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    // The identity for a zero quaternion, as it has no rotation to keep
+    pub fn normalized(&self) -> DQuaternion {
+        let length = self.length();
+        if length < f64::MIN_POSITIVE {
+            return DQuaternion::identity();
+        }
+
+        DQuaternion { w: self.w / length, v: self.v / length }
+    }
+
+    pub fn conjugate(&self) -> DQuaternion {
+        DQuaternion { w: self.w, v: -self.v }
+    }
+
+    // The same as Quaternion::rotate, for a unit quaternion
+    pub fn rotate(&self, vec: &DVector3) -> DVector3 {
+        let t = self.v.cross(vec) * 2.0;
+        *vec + t * self.w + self.v.cross(&t)
+    }
+
+    // The same matrix as Quaternion::to_matrix
+    pub fn to_matrix(self) -> DMat4 {
+        let (x, y, z, w) = (self.v.x, self.v.y, self.v.z, self.w);
+
+        DMat4::new([
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + z * w), 2.0 * (x * z - y * w), 0.0,
+            2.0 * (x * y - z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + x * w), 0.0,
+            2.0 * (x * z + y * w), 2.0 * (y * z - x * w), 1.0 - 2.0 * (x * x + y * y), 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    pub fn to_f32(self) -> Quaternion {
+        Quaternion { w: self.w as f32, v: self.v.to_f32() }
+    }
+}
+
+// The same as Quaternion's: other's rotation first, then self's
+impl ops::Mul for DQuaternion {
+    type Output = DQuaternion;
+
+    fn mul(self, other: DQuaternion) -> DQuaternion {
+        DQuaternion {
+            w: self.w * other.w - self.v.dot(&other.v),
+            v: other.v * self.w + self.v * other.w + self.v.cross(&other.v)
+        }
+    }
+}
+
+/*
+    The view matrix for drawing things made camera relative with relative_to.
+    The camera is at the origin then, so only the rotation of its view matrix is left, and the translation is dropped.
+*/
+pub fn camera_relative_view(view: &Mat4) -> Mat4 {
+    Mat3::from_mat4(view).to_mat4()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math::double::*;
+
+    fn assert_matrices_near(actual: &Mat4, expected: &Mat4, tolerance: f32) {
+        for (value, expected_value) in actual.matrix.iter().zip(expected.matrix.iter()) {
+            assert!((value - expected_value).abs() < tolerance, "{:?}\n{:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn should_convert_between_f32_and_f64() {
+        let vector = Vector3::new(0.1, -2.5, 1.0e30);
+        let double = DVector3::from(vector);
+
+        // Every f32 fits in an f64 exactly, so there and back again changes nothing
+        assert_eq!(double.x, 0.1f32 as f64);
+        assert_eq!(double.to_f32(), vector);
+        assert_eq!(DVector4::from(Vector4::new(1.0, 2.0, 3.0, 4.0)).to_f32(), Vector4::new(1.0, 2.0, 3.0, 4.0));
+        assert_eq!(DMat4::from(Mat4::rotate_y(0.3)).to_f32(), Mat4::rotate_y(0.3));
+
+        // The other way rounds to the nearest f32
+        assert_eq!(DVector2::new(0.1, 16_777_217.0).to_f32(), Vector2::new(0.1, 16_777_216.0));
+
+        let quaternion = Quaternion::from_axis_angle(&Vector3::new(1.0, 2.0, 3.0), 0.7);
+        assert_eq!(DQuaternion::from(quaternion).to_f32(), quaternion);
+    }
+
+    #[test]
+    fn should_keep_precision_far_from_origin_when_relative_to_camera() {
+        // 10 000 km out, where the f32s next to each other are a whole meter apart
+        let camera = DVector3::new(1.0e7, 0.0, -1.0e7);
+        let position = camera + DVector3::new(0.25, 1.5, -0.125);
+
+        assert_eq!(position.relative_to(&camera), Vector3::new(0.25, 1.5, -0.125));
+        assert_ne!(position.to_f32() - camera.to_f32(), Vector3::new(0.25, 1.5, -0.125));
+
+        // A model rotated and placed out there, and drawn from the camera, ends up where it would have close to the origin
+        let local_to_world = DQuaternion::from_axis_angle(&DVector3::new(0.0, 1.0, 0.0), 0.5).to_matrix().mul(&DMat4::translate(&position));
+        let relative = local_to_world.relative_to(&camera);
+
+        let expected = Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 0.5).to_matrix().mul(&Mat4::translate(&Vector3::new(0.25, 1.5, -0.125)));
+        assert_matrices_near(&relative, &expected, 1e-6);
+    }
+
+    #[test]
+    fn should_match_f32_types() {
+        let a = Mat4::rotate_x(0.4).mul(&Mat4::translate(&Vector3::new(1.0, 2.0, 3.0)));
+        let b = Mat4::rotate_z(-1.1).mul(&Mat4::scale(&Vector3::new(2.0, 1.0, 0.5)));
+        assert_matrices_near(&DMat4::from(a).mul(&DMat4::from(b)).to_f32(), &a.mul(&b), 1e-5);
+        assert_eq!(DMat4::from(a).get_transposed().to_f32(), a.get_transposed());
+        assert_eq!(DMat4::translate(&DVector3::new(1.0, 2.0, 3.0)).transform_point(&DVector3::new(1.0, 1.0, 1.0)), DVector3::new(2.0, 3.0, 4.0));
+
+        let axis = Vector3::new(1.0, -1.0, 0.5);
+        let (q1, q2) = (Quaternion::from_axis_angle(&axis, 0.8), Quaternion::from_axis_angle(&Vector3::new(0.0, 0.0, 1.0), 2.0));
+        let (d1, d2) = (DQuaternion::from_axis_angle(&DVector3::from(axis), 0.8), DQuaternion::from_axis_angle(&DVector3::new(0.0, 0.0, 1.0), 2.0));
+
+        let rotated = (d1 * d2).rotate(&DVector3::new(3.0, 0.0, 1.0)).to_f32();
+        assert!((rotated - (q1 * q2).rotate(&Vector3::new(3.0, 0.0, 1.0))).length() < 1e-5);
+        assert!((d1.conjugate() * d1).normalized().v.length() < 1e-12);
+    }
+
+    #[test]
+    fn should_draw_the_same_with_camera_relative_view() {
+        let view = Mat4::look_at(&Vector3::new(10.0, 5.0, -20.0), &Vector3::new(0.0, 1.0, 0.0), &Vector3::new(0.0, 1.0, 0.0)).unwrap();
+        let world = Mat4::translate(&Vector3::new(3.0, 1.0, 2.0));
+        let camera = DVector3::new(10.0, 5.0, -20.0);
+
+        let relative = DMat4::from(world).relative_to(&camera).mul(&camera_relative_view(&view));
+        assert_matrices_near(&relative, &world.mul(&view), 1e-4);
+    }
+}
@@ -0,0 +1,875 @@
+use crate::beagle_math::{Mat4, Vector3, Vector4};
+
+/*
+    Shapes for picking, culling and collision, and the tests between them.
+
+    All of them live in whatever space their points are given in. Nothing here knows about world or view space,
+    so to test shapes from different spaces against each other, one of them has to be transformed first.
+
+    Tests that can tell where something was hit give back the distance along the ray (t),
+    in multiples of the ray's direction, so with a normalized direction it's the actual distance.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3,
+    pub direction: Vector3
+}
+
+impl Ray {
+    pub fn new(origin: Vector3, direction: Vector3) -> Ray {
+        Ray { origin, direction }
+    }
+
+    pub fn point_at(&self, t: f32) -> Vector3 {
+        self.origin + self.direction * t
+    }
+
+    /*
+        Möller–Trumbore: the hit point is written as a + u * (b - a) + v * (c - a), which together with origin + t * direction
+        makes three equations for t, u and v. Solving them with Cramer's rule is a handful of cross and dot products.
+        The hit is inside the triangle when u, v and u + v are all between 0 and 1.
+        Both sides of the triangle can be hit.
+    */
+    pub fn intersect_triangle(&self, triangle: &Triangle) -> Option<f32> {
+        let edge1 = triangle.b - triangle.a;
+        let edge2 = triangle.c - triangle.a;
+
+        let p = self.direction.cross(&edge2);
+        let determinant = edge1.dot(&p);
+        // The ray runs along the plane of the triangle (or the triangle has no area)
+        if determinant.abs() < 1e-8 {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let to_origin = self.origin - triangle.a;
+
+        let u = to_origin.dot(&p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = to_origin.cross(&edge1);
+        let v = self.direction.dot(&q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(&q) * inverse_determinant;
+        if t < 0.0 {
+            return None;
+        }
+
+        Some(t)
+    }
+
+    /*
+        The slab test: the box is where the three slabs between min and max along each axis overlap.
+        The ray enters the box when it has entered all three slabs, and leaves it when it leaves the first one.
+        Starting inside the box is a hit at 0.
+    */
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut t_enter = 0.0f32;
+        let mut t_exit = f32::INFINITY;
+
+        for axis in 0..3 {
+            let origin = self.origin[axis];
+            let direction = self.direction[axis];
+
+            if direction.abs() < f32::MIN_POSITIVE {
+                // Parallel to the slab, so either always in it or never
+                if origin < aabb.min[axis] || origin > aabb.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let inverse_direction = 1.0 / direction;
+            let t1 = (aabb.min[axis] - origin) * inverse_direction;
+            let t2 = (aabb.max[axis] - origin) * inverse_direction;
+
+            t_enter = t_enter.max(t1.min(t2));
+            t_exit = t_exit.min(t1.max(t2));
+            if t_enter > t_exit {
+                return None;
+            }
+        }
+
+        Some(t_enter)
+    }
+
+    // The same slab test, along the axes of the box rather than X, Y and Z
+    pub fn intersect_obb(&self, obb: &Obb) -> Option<f32> {
+        let to_origin = self.origin - obb.center;
+        let local_ray = Ray::new(
+            Vector3::new(to_origin.dot(&obb.axes[0]), to_origin.dot(&obb.axes[1]), to_origin.dot(&obb.axes[2])),
+            Vector3::new(self.direction.dot(&obb.axes[0]), self.direction.dot(&obb.axes[1]), self.direction.dot(&obb.axes[2])));
+
+        local_ray.intersect_aabb(&Aabb::new(-obb.half_extents, obb.half_extents))
+    }
+
+    // Starting inside the sphere is a hit at 0
+    pub fn intersect_sphere(&self, sphere: &Sphere) -> Option<f32> {
+        let to_origin = self.origin - sphere.center;
+        let c = to_origin.length_squared() - sphere.radius * sphere.radius;
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+
+        // Solving |origin + t * direction - center|^2 = radius^2 for t
+        let a = self.direction.length_squared();
+        let b = to_origin.dot(&self.direction);
+        // Outside and pointing away
+        if b > 0.0 || a < f32::MIN_POSITIVE {
+            return None;
+        }
+
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        Some((-b - discriminant.sqrt()) / a)
+    }
+
+    // Either side of the plane can be hit
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let denominator = plane.normal.dot(&self.direction);
+        if denominator.abs() < f32::MIN_POSITIVE {
+            return None;
+        }
+
+        let t = -plane.signed_distance(&self.origin) / denominator;
+        if t < 0.0 { None } else { Some(t) }
+    }
+}
+
+/*
+    All the points p where normal.dot(p) + distance == 0.
+    With a normalized normal, distance is how far the plane is from the origin (against the normal),
+    and signed_distance is the actual distance, positive on the side the normal points to.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3,
+    pub distance: f32
+}
+
+impl Plane {
+    pub fn new(normal: Vector3, distance: f32) -> Plane {
+        Plane { normal, distance }
+    }
+
+    pub fn from_point_and_normal(point: &Vector3, normal: &Vector3) -> Plane {
+        Plane { normal: *normal, distance: -normal.dot(point) }
+    }
+
+    // The normal points to the side a, b and c look clockwise from, the front of a triangle in this engine. None if they're on a line.
+    pub fn from_points(a: &Vector3, b: &Vector3, c: &Vector3) -> Option<Plane> {
+        let normal = (*b - *a).cross(&(*c - *a)).try_normalize()?;
+        Some(Plane::from_point_and_normal(a, &normal))
+    }
+
+    // From the four numbers of a plane, as they come out of a matrix, (a, b, c) being the normal and d the distance
+    pub fn from_vector4(plane: &Vector4) -> Plane {
+        Plane { normal: Vector3::new(plane.x, plane.y, plane.z), distance: plane.w }
+    }
+
+    // Scales the normal to a length of 1, so signed_distance gives actual distances. None if the normal has no length.
+    pub fn normalized(&self) -> Option<Plane> {
+        let length = self.normal.length();
+        if length < f32::MIN_POSITIVE {
+            return None;
+        }
+
+        Some(Plane { normal: self.normal / length, distance: self.distance / length })
+    }
+
+    pub fn signed_distance(&self, point: &Vector3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+
+    pub fn closest_point(&self, point: &Vector3) -> Vector3 {
+        *point - self.normal * (self.signed_distance(point) / self.normal.length_squared())
+    }
+}
+
+// A box lined up with the axes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3
+}
+
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    // The smallest box around all the points. None if there are no points.
+    pub fn from_points(points: &[Vector3]) -> Option<Aabb> {
+        let first = points.first()?;
+        Some(points[1..].iter().fold(Aabb::new(*first, *first), |aabb, point| aabb.expanded_to(point)))
+    }
+
+    pub fn center(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vector3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn expanded_to(&self, point: &Vector3) -> Aabb {
+        Aabb::new(self.min.min(point), self.max.max(point))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(&other.min), self.max.max(&other.max))
+    }
+
+    /*
+        The box around this box after transforming it, which is usually larger than the box itself, as it's turned.
+        Rather than transforming all eight corners, each element of the matrix adds its smallest and largest
+        contribution to the new min and max (Arvo's method).
+    */
+    pub fn transformed(&self, mat: &Mat4) -> Aabb {
+        let mut min = Vector3::new(mat.get(0, 3), mat.get(1, 3), mat.get(2, 3));
+        let mut max = min;
+
+        for row in 0..3 {
+            for column in 0..3 {
+                let element = mat.get(column as i32, row as i32);
+                let a = element * self.min[row];
+                let b = element * self.max[row];
+                min[column] += a.min(b);
+                max[column] += a.max(b);
+            }
+        }
+
+        Aabb::new(min, max)
+    }
+
+    pub fn contains_point(&self, point: &Vector3) -> bool {
+        (0..3).all(|axis| point[axis] >= self.min[axis] && point[axis] <= self.max[axis])
+    }
+
+    pub fn closest_point(&self, point: &Vector3) -> Vector3 {
+        point.clamp(&self.min, &self.max)
+    }
+
+    // Touching counts as intersecting
+    pub fn intersects_aabb(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && self.max[axis] >= other.min[axis])
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        (self.closest_point(&sphere.center) - sphere.center).length_squared() <= sphere.radius * sphere.radius
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: Vector3,
+    pub radius: f32
+}
+
+impl Sphere {
+    pub fn new(center: Vector3, radius: f32) -> Sphere {
+        Sphere { center, radius }
+    }
+
+    /*
+        A sphere around all the points, not necessarily the smallest one, but usually close (Ritter's algorithm).
+        It starts with a sphere around two points that are far apart, and grows it just enough for every point that's outside.
+        None if there are no points.
+    */
+    pub fn from_points(points: &[Vector3]) -> Option<Sphere> {
+        let first = points.first()?;
+
+        let farthest_from = |from: &Vector3| *points
+            .iter()
+            .max_by(|a, b| (**a - *from).length_squared().total_cmp(&(**b - *from).length_squared()))
+            .unwrap();
+        let a = farthest_from(first);
+        let b = farthest_from(&a);
+
+        let mut sphere = Sphere::new((a + b) * 0.5, (b - a).length() * 0.5);
+        for point in points {
+            let distance = (*point - sphere.center).length();
+            if distance > sphere.radius {
+                // Move the center towards the point, just far enough for the far side of the sphere to stay where it is
+                let radius = (sphere.radius + distance) * 0.5;
+                sphere.center = sphere.center + (*point - sphere.center) * ((radius - sphere.radius) / distance);
+                sphere.radius = radius;
+            }
+        }
+
+        Some(sphere)
+    }
+
+    // The sphere around this sphere after transforming it, which is this sphere when the matrix scales the same along all axes
+    pub fn transformed(&self, mat: &Mat4) -> Sphere {
+        let center = mat.mul_row(&Vector4::new(self.center.x, self.center.y, self.center.z, 1.0));
+        let largest_scale = (0..3)
+            .map(|row| Vector3::new(mat.get(0, row), mat.get(1, row), mat.get(2, row)).length())
+            .fold(0.0, f32::max);
+
+        Sphere::new(Vector3::new(center.x, center.y, center.z), self.radius * largest_scale)
+    }
+
+    pub fn contains_point(&self, point: &Vector3) -> bool {
+        (*point - self.center).length_squared() <= self.radius * self.radius
+    }
+
+    pub fn intersects_sphere(&self, other: &Sphere) -> bool {
+        let radii = self.radius + other.radius;
+        (other.center - self.center).length_squared() <= radii * radii
+    }
+}
+
+// A box turned any which way. The axes have a length of 1 and are perpendicular to each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obb {
+    pub center: Vector3,
+    pub axes: [Vector3; 3],
+    pub half_extents: Vector3
+}
+
+impl Obb {
+    pub fn new(center: Vector3, axes: [Vector3; 3], half_extents: Vector3) -> Obb {
+        Obb { center, axes, half_extents }
+    }
+
+    /*
+        The box after transforming it, which stays a box as long as the matrix doesn't shear.
+        None if the matrix squashes the box flat along an axis.
+    */
+    pub fn from_aabb(aabb: &Aabb, mat: &Mat4) -> Option<Obb> {
+        let center = aabb.center();
+        let center = mat.mul_row(&Vector4::new(center.x, center.y, center.z, 1.0));
+        let half_extents = aabb.half_extents();
+
+        let mut axes = [Vector3::zero(); 3];
+        let mut scaled_half_extents = Vector3::zero();
+        for (row, axis) in axes.iter_mut().enumerate() {
+            let scaled_axis = Vector3::new(mat.get(0, row as i32), mat.get(1, row as i32), mat.get(2, row as i32));
+            *axis = scaled_axis.try_normalize()?;
+            scaled_half_extents[row] = half_extents[row] * scaled_axis.length();
+        }
+
+        Some(Obb::new(Vector3::new(center.x, center.y, center.z), axes, scaled_half_extents))
+    }
+
+    // Clamps the point to the box along each of its axes
+    pub fn closest_point(&self, point: &Vector3) -> Vector3 {
+        let to_point = *point - self.center;
+        (0..3).fold(self.center, |closest, axis| {
+            let distance = to_point.dot(&self.axes[axis]).clamp(-self.half_extents[axis], self.half_extents[axis]);
+            closest + self.axes[axis] * distance
+        })
+    }
+
+    pub fn contains_point(&self, point: &Vector3) -> bool {
+        let to_point = *point - self.center;
+        (0..3).all(|axis| to_point.dot(&self.axes[axis]).abs() <= self.half_extents[axis])
+    }
+}
+
+// The corners go clockwise around the front, like the triangles of a mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Triangle {
+    pub a: Vector3,
+    pub b: Vector3,
+    pub c: Vector3
+}
+
+impl Triangle {
+    pub fn new(a: Vector3, b: Vector3, c: Vector3) -> Triangle {
+        Triangle { a, b, c }
+    }
+
+    // Not normalized, its length is twice the area
+    pub fn normal(&self) -> Vector3 {
+        (self.b - self.a).cross(&(self.c - self.a))
+    }
+
+    pub fn area(&self) -> f32 {
+        self.normal().length() * 0.5
+    }
+
+    /*
+        Works out which of the seven regions around the triangle the point is in (three corners, three edges or the inside),
+        from the barycentric coordinates of the point, and only projects onto that one (Ericson, Real-Time Collision Detection 5.1.5).
+    */
+    pub fn closest_point(&self, point: &Vector3) -> Vector3 {
+        let (a, b, c) = (self.a, self.b, self.c);
+        let ab = b - a;
+        let ac = c - a;
+
+        let ap = *point - a;
+        let d1 = ab.dot(&ap);
+        let d2 = ac.dot(&ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return a;
+        }
+
+        let bp = *point - b;
+        let d3 = ab.dot(&bp);
+        let d4 = ac.dot(&bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return b;
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return a + ab * (d1 / (d1 - d3));
+        }
+
+        let cp = *point - c;
+        let d5 = ab.dot(&cp);
+        let d6 = ac.dot(&cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return c;
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return a + ac * (d2 / (d2 - d6));
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+            return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+        }
+
+        let denominator = 1.0 / (va + vb + vc);
+        a + ab * (vb * denominator) + ac * (vc * denominator)
+    }
+}
+
+pub fn closest_point_on_segment(point: &Vector3, start: &Vector3, end: &Vector3) -> Vector3 {
+    let segment = *end - *start;
+    let length_squared = segment.length_squared();
+    if length_squared < f32::MIN_POSITIVE {
+        return *start;
+    }
+
+    let t = ((*point - *start).dot(&segment) / length_squared).clamp(0.0, 1.0);
+    *start + segment * t
+}
+
+/*
+    The closest points between two segments, one on each (Ericson, Real-Time Collision Detection 5.1.9).
+    The infinite lines through them are closest where the line between the points is perpendicular to both,
+    and when that's past the end of a segment, the point is clamped to the end and the other one worked out again.
+*/
+pub fn closest_points_between_segments(start1: &Vector3, end1: &Vector3, start2: &Vector3, end2: &Vector3) -> (Vector3, Vector3) {
+    let d1 = *end1 - *start1;
+    let d2 = *end2 - *start2;
+    let r = *start1 - *start2;
+    let a = d1.length_squared();
+    let e = d2.length_squared();
+    let f = d2.dot(&r);
+
+    let (s, t) = if a < f32::MIN_POSITIVE && e < f32::MIN_POSITIVE {
+        // Both are points
+        (0.0, 0.0)
+    } else if a < f32::MIN_POSITIVE {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(&r);
+        if e < f32::MIN_POSITIVE {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let b = d1.dot(&d2);
+            let denominator = a * e - b * b;
+
+            // Parallel segments have no single closest pair, any s will do
+            let s = if denominator > 0.0 { ((b * f - c * e) / denominator).clamp(0.0, 1.0) } else { 0.0 };
+            let t = (b * s + f) / e;
+
+            if t < 0.0 {
+                ((-c / a).clamp(0.0, 1.0), 0.0)
+            } else if t > 1.0 {
+                (((b - c) / a).clamp(0.0, 1.0), 1.0)
+            } else {
+                (s, t)
+            }
+        }
+    };
+
+    (*start1 + d1 * s, *start2 + d2 * t)
+}
+
+// All the points within radius of the segment between start and end, a cylinder with a half sphere on each end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub start: Vector3,
+    pub end: Vector3,
+    pub radius: f32
+}
+
+impl Capsule {
+    pub fn new(start: Vector3, end: Vector3, radius: f32) -> Capsule {
+        Capsule { start, end, radius }
+    }
+
+    pub fn contains_point(&self, point: &Vector3) -> bool {
+        (closest_point_on_segment(point, &self.start, &self.end) - *point).length_squared() <= self.radius * self.radius
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        let closest = closest_point_on_segment(&sphere.center, &self.start, &self.end);
+        Sphere::new(closest, self.radius).intersects_sphere(sphere)
+    }
+
+    pub fn intersects_capsule(&self, other: &Capsule) -> bool {
+        let (closest, other_closest) = closest_points_between_segments(&self.start, &self.end, &other.start, &other.end);
+        Sphere::new(closest, self.radius).intersects_sphere(&Sphere::new(other_closest, other.radius))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside
+}
+
+/*
+    The space between six planes, with their normals pointing inwards.
+    Nothing here requires the planes to be a view frustum, any convex space with six sides works.
+
+    The tests are conservative: something that is reported outside is always outside, but something close to
+    a corner of the frustum can be reported as intersecting while it's actually just outside.
+    For culling, that only means drawing a little more than needed.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    // Left, right, bottom, top, near and far, for a view frustum
+    pub planes: [Plane; 6]
+}
+
+impl Frustum {
+    pub fn new(planes: [Plane; 6]) -> Frustum {
+        Frustum { planes }
+    }
+
+    /*
+        The frustum of a (view) projection matrix, in the space the matrix starts from (world space for a view projection).
+
+        A point p is visible when its clip space position c = p * matrix has -c.w <= c.x <= c.w, the same for y, and 0 <= c.z <= c.w
+        (Direct3D's depth range). Every component of c is p dotted with a column of the matrix,
+        so every one of those inequalities is a plane made of one or two columns (Gribb and Hartmann).
+        This works for all the projections in Mat4, reversed depth included, since it's still between 0 and w.
+
+        An infinite projection has no far plane (or near plane, with reversed depth), that plane lets everything through.
+    */
+    pub fn from_view_projection(view_projection: &Mat4) -> Frustum {
+        let column = |x: i32| Vector4::new(view_projection.get(x, 0), view_projection.get(x, 1), view_projection.get(x, 2), view_projection.get(x, 3));
+        let (x, y, z, w) = (column(0), column(1), column(2), column(3));
+
+        let plane = |plane: Vector4| Plane::from_vector4(&plane)
+            .normalized()
+            .unwrap_or(Plane::new(Vector3::zero(), f32::INFINITY));
+
+        Frustum::new([
+            plane(w + x),
+            plane(w - x),
+            plane(w + y),
+            plane(w - y),
+            plane(z),
+            plane(w - z)
+        ])
+    }
+
+    pub fn contains_point(&self, point: &Vector3) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    // The planes have to be normalized for this, so the signed distances are actual distances
+    pub fn classify_sphere(&self, sphere: &Sphere) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in &self.planes {
+            let distance = plane.signed_distance(&sphere.center);
+            if distance < -sphere.radius {
+                return Containment::Outside;
+            }
+            if distance < sphere.radius {
+                containment = Containment::Intersecting;
+            }
+        }
+
+        containment
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.classify_sphere(sphere) != Containment::Outside
+    }
+
+    /*
+        For each plane, only two corners of the box matter: the one the furthest along the normal (the positive vertex),
+        and the one furthest against it (the negative vertex).
+        If the positive vertex is behind a plane, the whole box is. If the negative vertex is in front of all planes, the whole box is inside.
+    */
+    pub fn classify_aabb(&self, aabb: &Aabb) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in &self.planes {
+            let mut positive = aabb.min;
+            let mut negative = aabb.max;
+            for axis in 0..3 {
+                if plane.normal[axis] >= 0.0 {
+                    positive[axis] = aabb.max[axis];
+                    negative[axis] = aabb.min[axis];
+                }
+            }
+
+            if plane.signed_distance(&positive) < 0.0 {
+                return Containment::Outside;
+            }
+            if plane.signed_distance(&negative) < 0.0 {
+                containment = Containment::Intersecting;
+            }
+        }
+
+        containment
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.classify_aabb(aabb) != Containment::Outside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math::geometry::*;
+
+    fn assert_vectors_near(actual: &Vector3, expected: &Vector3, tolerance: f32) {
+        assert!((*actual - *expected).length() <= tolerance, "Expected {:?} but got {:?}", expected, actual);
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0))
+    }
+
+    // The box from -10 to 10 along every axis, as a frustum
+    fn box_frustum() -> Frustum {
+        let plane = |x: f32, y: f32, z: f32| Plane::new(Vector3::new(x, y, z), 10.0);
+        Frustum::new([
+            plane(1.0, 0.0, 0.0), plane(-1.0, 0.0, 0.0),
+            plane(0.0, 1.0, 0.0), plane(0.0, -1.0, 0.0),
+            plane(0.0, 0.0, 1.0), plane(0.0, 0.0, -1.0)
+        ])
+    }
+
+    #[test]
+    fn should_hit_triangle_only_inside_its_edges() {
+        let triangle = Triangle::new(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 2.0, 5.0), Vector3::new(2.0, 0.0, 5.0));
+
+        let hit = Ray::new(Vector3::new(0.5, 0.5, 0.0), Vector3::new(0.0, 0.0, 1.0)).intersect_triangle(&triangle);
+        assert_eq!(hit, Some(5.0));
+        // From behind works too
+        let hit = Ray::new(Vector3::new(0.5, 0.5, 10.0), Vector3::new(0.0, 0.0, -2.0)).intersect_triangle(&triangle);
+        assert_eq!(hit, Some(2.5));
+
+        assert!(Ray::new(Vector3::new(1.5, 1.5, 0.0), Vector3::new(0.0, 0.0, 1.0)).intersect_triangle(&triangle).is_none());
+        assert!(Ray::new(Vector3::new(0.5, 0.5, 0.0), Vector3::new(0.0, 0.0, -1.0)).intersect_triangle(&triangle).is_none());
+        assert!(Ray::new(Vector3::new(0.5, 0.5, 0.0), Vector3::new(1.0, 0.0, 0.0)).intersect_triangle(&triangle).is_none());
+    }
+
+    #[test]
+    fn should_hit_boxes_spheres_and_planes_from_outside_and_inside() {
+        let ray = Ray::new(Vector3::new(-5.0, 0.5, 0.0), Vector3::new(1.0, 0.0, 0.0));
+
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(4.0));
+        assert_eq!(Ray::new(Vector3::zero(), Vector3::new(0.0, 1.0, 0.0)).intersect_aabb(&unit_box()), Some(0.0));
+        assert!(Ray::new(Vector3::new(-5.0, 2.0, 0.0), Vector3::new(1.0, 0.0, 0.0)).intersect_aabb(&unit_box()).is_none());
+        assert!(Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0)).intersect_aabb(&unit_box()).is_none());
+
+        let sphere = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0);
+        assert_eq!(Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0)).intersect_sphere(&sphere), Some(2.0));
+        assert_eq!(Ray::new(Vector3::new(0.5, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)).intersect_sphere(&sphere), Some(0.0));
+        assert!(Ray::new(Vector3::new(-5.0, 1.5, 0.0), Vector3::new(1.0, 0.0, 0.0)).intersect_sphere(&sphere).is_none());
+        assert!(Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0)).intersect_sphere(&sphere).is_none());
+
+        let ground = Plane::from_point_and_normal(&Vector3::new(0.0, -2.0, 0.0), &Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(Ray::new(Vector3::new(3.0, 2.0, 1.0), Vector3::new(0.0, -1.0, 0.0)).intersect_plane(&ground), Some(4.0));
+        assert!(Ray::new(Vector3::new(3.0, 2.0, 1.0), Vector3::new(0.0, 1.0, 0.0)).intersect_plane(&ground).is_none());
+        assert!(Ray::new(Vector3::new(3.0, 2.0, 1.0), Vector3::new(1.0, 0.0, 0.0)).intersect_plane(&ground).is_none());
+    }
+
+    #[test]
+    fn should_hit_turned_box_where_transformed_box_is() {
+        let transform = Mat4::uniform_scale(2.0).mul(&Mat4::rotate_y(std::f32::consts::FRAC_PI_4)).mul(&Mat4::translate(&Vector3::new(10.0, 0.0, 0.0)));
+        let obb = Obb::from_aabb(&unit_box(), &transform).unwrap();
+
+        // The corner of the box points at the ray, at 2 * sqrt(2) from the center
+        let hit = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)).intersect_obb(&obb).unwrap();
+        assert!((hit - (10.0 - 2.0 * 2.0f32.sqrt())).abs() < 1e-4, "{}", hit);
+        assert!(Ray::new(Vector3::new(0.0, 2.5, 0.0), Vector3::new(1.0, 0.0, 0.0)).intersect_obb(&obb).is_none());
+
+        assert!(obb.contains_point(&Vector3::new(11.0, 1.5, 0.5)));
+        assert!(!obb.contains_point(&Vector3::new(13.0, 0.0, 0.0)));
+        assert_vectors_near(&obb.closest_point(&Vector3::new(20.0, 0.0, 0.0)), &Vector3::new(10.0 + 2.0 * 2.0f32.sqrt(), 0.0, 0.0), 1e-4);
+    }
+
+    #[test]
+    fn should_build_bounds_around_all_points() {
+        let points = vec![
+            Vector3::new(1.0, 2.0, 3.0), Vector3::new(-4.0, 0.0, 1.0), Vector3::new(2.0, 5.0, -3.0),
+            Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.5, 0.5, 0.5)
+        ];
+
+        let aabb = Aabb::from_points(&points).unwrap();
+        assert_eq!(aabb, Aabb::new(Vector3::new(-4.0, -1.0, -3.0), Vector3::new(2.0, 5.0, 3.0)));
+
+        let sphere = Sphere::from_points(&points).unwrap();
+        for point in &points {
+            assert!((*point - sphere.center).length() <= sphere.radius + 1e-4);
+        }
+        // Not much bigger than the box
+        assert!(sphere.radius <= aabb.half_extents().length() * 1.1);
+
+        assert!(Aabb::from_points(&[]).is_none());
+        assert!(Sphere::from_points(&[]).is_none());
+    }
+
+    #[test]
+    fn should_contain_transformed_corners_when_transforming_bounds() {
+        let aabb = Aabb::new(Vector3::new(-1.0, 0.0, 2.0), Vector3::new(3.0, 1.0, 4.0));
+        let transform = Mat4::scale(&Vector3::new(2.0, 1.0, 0.5)).mul(&Mat4::rotate_x(0.7)).mul(&Mat4::translate(&Vector3::new(5.0, -2.0, 1.0)));
+
+        let transformed = aabb.transformed(&transform);
+        let sphere = Sphere::from_points(&[aabb.min, aabb.max]).unwrap().transformed(&transform);
+
+        let mut corners: Vec<Vector3> = vec!();
+        for i in 0..8 {
+            let corner = Vector3::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z });
+            let corner = transform.mul_row(&Vector4::new(corner.x, corner.y, corner.z, 1.0));
+            corners.push(Vector3::new(corner.x, corner.y, corner.z));
+        }
+
+        // The same as the box around the transformed corners
+        let expected = Aabb::from_points(&corners).unwrap();
+        assert_vectors_near(&transformed.min, &expected.min, 1e-5);
+        assert_vectors_near(&transformed.max, &expected.max, 1e-5);
+
+        for corner in &corners {
+            assert!((*corner - sphere.center).length() <= sphere.radius + 1e-4);
+        }
+    }
+
+    #[test]
+    fn should_overlap_when_boxes_and_spheres_touch() {
+        let other = Aabb::new(Vector3::new(1.0, 0.5, -3.0), Vector3::new(2.0, 2.0, 3.0));
+        assert!(unit_box().intersects_aabb(&other));
+        assert!(!unit_box().intersects_aabb(&Aabb::new(Vector3::new(1.1, 0.0, 0.0), Vector3::new(2.0, 1.0, 1.0))));
+
+        // Close to the corner, which a test against the sides of the box only would get wrong
+        assert!(!unit_box().intersects_sphere(&Sphere::new(Vector3::new(1.5, 1.5, 0.0), 0.6)));
+        assert!(unit_box().intersects_sphere(&Sphere::new(Vector3::new(1.5, 1.5, 0.0), 0.75)));
+
+        assert!(Sphere::new(Vector3::zero(), 1.0).intersects_sphere(&Sphere::new(Vector3::new(0.0, 2.5, 0.0), 1.5)));
+        assert!(!Sphere::new(Vector3::zero(), 1.0).intersects_sphere(&Sphere::new(Vector3::new(0.0, 2.5, 0.0), 1.4)));
+    }
+
+    #[test]
+    fn should_find_closest_point_in_every_region_of_triangle() {
+        let triangle = Triangle::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 4.0), Vector3::new(4.0, 0.0, 0.0));
+
+        // Above the inside, beyond each corner, and beyond each edge
+        assert_vectors_near(&triangle.closest_point(&Vector3::new(1.0, 3.0, 1.0)), &Vector3::new(1.0, 0.0, 1.0), 1e-6);
+        assert_vectors_near(&triangle.closest_point(&Vector3::new(-1.0, 1.0, -1.0)), &triangle.a, 1e-6);
+        assert_vectors_near(&triangle.closest_point(&Vector3::new(-1.0, 0.0, 6.0)), &triangle.b, 1e-6);
+        assert_vectors_near(&triangle.closest_point(&Vector3::new(6.0, 0.0, -1.0)), &triangle.c, 1e-6);
+        assert_vectors_near(&triangle.closest_point(&Vector3::new(-2.0, 0.0, 2.0)), &Vector3::new(0.0, 0.0, 2.0), 1e-6);
+        assert_vectors_near(&triangle.closest_point(&Vector3::new(2.0, 0.0, -2.0)), &Vector3::new(2.0, 0.0, 0.0), 1e-6);
+        assert_vectors_near(&triangle.closest_point(&Vector3::new(3.0, 5.0, 3.0)), &Vector3::new(2.0, 0.0, 2.0), 1e-5);
+
+        assert_eq!(triangle.area(), 8.0);
+        assert_eq!(Plane::from_points(&triangle.a, &triangle.b, &triangle.c).unwrap().normal, Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn should_find_closest_points_on_segments_and_capsules() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let end = Vector3::new(10.0, 0.0, 0.0);
+
+        assert_eq!(closest_point_on_segment(&Vector3::new(3.0, 4.0, 0.0), &start, &end), Vector3::new(3.0, 0.0, 0.0));
+        assert_eq!(closest_point_on_segment(&Vector3::new(-3.0, 4.0, 0.0), &start, &end), start);
+        assert_eq!(closest_point_on_segment(&Vector3::new(13.0, 4.0, 0.0), &start, &end), end);
+
+        // Crossing over each other
+        let (a, b) = closest_points_between_segments(&start, &end, &Vector3::new(4.0, 2.0, -5.0), &Vector3::new(4.0, 2.0, 5.0));
+        assert_vectors_near(&a, &Vector3::new(4.0, 0.0, 0.0), 1e-6);
+        assert_vectors_near(&b, &Vector3::new(4.0, 2.0, 0.0), 1e-6);
+        // Parallel
+        let (a, b) = closest_points_between_segments(&start, &end, &Vector3::new(12.0, 1.0, 0.0), &Vector3::new(20.0, 1.0, 0.0));
+        assert_vectors_near(&a, &end, 1e-6);
+        assert_vectors_near(&b, &Vector3::new(12.0, 1.0, 0.0), 1e-6);
+
+        let capsule = Capsule::new(start, end, 1.0);
+        assert!(capsule.contains_point(&Vector3::new(5.0, 0.9, 0.0)));
+        assert!(!capsule.contains_point(&Vector3::new(10.8, 0.8, 0.0)));
+        assert!(capsule.intersects_sphere(&Sphere::new(Vector3::new(-1.5, 0.0, 0.0), 0.6)));
+        assert!(!capsule.intersects_sphere(&Sphere::new(Vector3::new(5.0, 2.0, 0.0), 0.9)));
+        assert!(capsule.intersects_capsule(&Capsule::new(Vector3::new(4.0, 1.8, -5.0), Vector3::new(4.0, 1.8, 5.0), 1.0)));
+        assert!(!capsule.intersects_capsule(&Capsule::new(Vector3::new(4.0, 2.2, -5.0), Vector3::new(4.0, 2.2, 5.0), 1.0)));
+    }
+
+    #[test]
+    fn should_classify_shapes_against_frustum() {
+        let frustum = box_frustum();
+
+        assert!(frustum.contains_point(&Vector3::new(9.0, -9.0, 0.0)));
+        assert!(!frustum.contains_point(&Vector3::new(11.0, 0.0, 0.0)));
+
+        assert_eq!(frustum.classify_sphere(&Sphere::new(Vector3::zero(), 5.0)), Containment::Inside);
+        assert_eq!(frustum.classify_sphere(&Sphere::new(Vector3::new(0.0, 0.0, 12.0), 5.0)), Containment::Intersecting);
+        assert_eq!(frustum.classify_sphere(&Sphere::new(Vector3::new(0.0, 0.0, 16.0), 5.0)), Containment::Outside);
+        assert!(!frustum.intersects_sphere(&Sphere::new(Vector3::new(0.0, -20.0, 0.0), 5.0)));
+
+        assert_eq!(frustum.classify_aabb(&unit_box()), Containment::Inside);
+        assert_eq!(frustum.classify_aabb(&Aabb::new(Vector3::new(9.0, 0.0, 0.0), Vector3::new(11.0, 1.0, 1.0))), Containment::Intersecting);
+        assert_eq!(frustum.classify_aabb(&Aabb::new(Vector3::new(10.5, 0.0, 0.0), Vector3::new(11.0, 1.0, 1.0))), Containment::Outside);
+        assert!(frustum.intersects_aabb(&Aabb::new(Vector3::new(-20.0, -20.0, -20.0), Vector3::new(20.0, 20.0, 20.0))));
+    }
+
+    #[test]
+    fn should_extract_view_frustum_from_view_projection() {
+        let view = Mat4::look_at(&Vector3::new(0.0, 0.0, -10.0), &Vector3::zero(), &Vector3::new(0.0, 1.0, 0.0)).unwrap();
+        let fov = std::f32::consts::FRAC_PI_2;
+
+        let projections = [
+            Mat4::projection(fov, 1.0, 1.0, 1.0, 100.0),
+            Mat4::projection_reversed_z(fov, 1.0, 1.0, 1.0, 100.0)
+        ];
+        for projection in projections {
+            let frustum = Frustum::from_view_projection(&view.mul(&projection));
+
+            // The eye is at z = -10, so the near plane is at z = -9, the far plane at 90, and the sides at 45 degrees
+            assert!(frustum.contains_point(&Vector3::new(0.0, 0.0, 0.0)));
+            assert!(frustum.contains_point(&Vector3::new(9.9, -9.9, 0.0)));
+            assert!(!frustum.contains_point(&Vector3::new(10.1, 0.0, 0.0)));
+            assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, -9.5)));
+            assert!(frustum.contains_point(&Vector3::new(0.0, 0.0, 89.0)));
+            assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, 91.0)));
+
+            // The planes are normalized, so sphere radii are actual distances
+            assert_eq!(frustum.classify_sphere(&Sphere::new(Vector3::new(0.0, 0.0, 95.0), 4.0)), Containment::Outside);
+            assert_eq!(frustum.classify_sphere(&Sphere::new(Vector3::new(0.0, 0.0, 95.0), 6.0)), Containment::Intersecting);
+            assert_eq!(frustum.classify_aabb(&unit_box()), Containment::Inside);
+        }
+
+        // Nothing is too far away without a far plane
+        let frustum = Frustum::from_view_projection(&view.mul(&Mat4::projection_reversed_z_infinite(fov, 1.0, 1.0, 1.0)));
+        assert!(frustum.contains_point(&Vector3::new(0.0, 0.0, 1.0e6)));
+        assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, -9.5)));
+        assert_eq!(frustum.classify_aabb(&Aabb::new(Vector3::new(-1.0, -1.0, 1.0e5), Vector3::new(1.0, 1.0, 1.0e5 + 2.0))), Containment::Inside);
+    }
+}
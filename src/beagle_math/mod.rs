@@ -1,5 +1,9 @@
+pub mod geometry;
+pub mod simd;
+
 use std::fmt::{self};
 use std::mem::{size_of};
+use std::ops;
 use byteorder::{LittleEndian, ByteOrder};
 
 // The "marker" module contains primitive traits and types representing basic properties of types.
@@ -18,7 +22,289 @@ use crate::shared;
     -- Type of Left-Handedness used: +X (right), +Y (Up), +Z (Into Screen - Away from Viewer)
 */
 
-#[derive(Default, Clone, Copy)]
+/*
+    Operators and common methods for the vector types.
+
+    Vector2, Vector3 and Vector4 all work the same way, component by component, they just have a different number of components.
+    Instead of writing every impl block three times, this macro writes them for a vector type and a list of its components.
+    "$(...),+" repeats whatever is inside it once for every component, so for Vector3, "$(self.$component + other.$component),+"
+    becomes "self.x + other.x, self.y + other.y, self.z + other.z".
+
+    The std::ops traits are what makes "a + b", "a * 2.0", "-a", "a += b" and "a[0]" work.
+    They take their operands by value, which is cheap as the vectors are Copy.
+
+    The arithmetic itself (+, -, *, /, unary - and dot) is in impl_vector_arithmetic, so Vector4 can do it with SIMD instead (see simd).
+    Everything in impl_vector is built on top of it.
+
+    The type of the components is passed in as well, so the same macros also write the f64 vectors (see double).
+*/
+macro_rules! impl_vector_arithmetic {
+    ($vector:ident: $scalar:ty { $($component:ident),+ }) => {
+        impl ops::Add for $vector {
+            type Output = $vector;
+
+            fn add(self, other: $vector) -> $vector {
+                $vector { $($component: self.$component + other.$component),+ }
+            }
+        }
+
+        impl ops::Sub for $vector {
+            type Output = $vector;
+
+            fn sub(self, other: $vector) -> $vector {
+                $vector { $($component: self.$component - other.$component),+ }
+            }
+        }
+
+        impl ops::Mul<$scalar> for $vector {
+            type Output = $vector;
+
+            fn mul(self, scalar: $scalar) -> $vector {
+                $vector { $($component: self.$component * scalar),+ }
+            }
+        }
+
+        // Component by component, not a dot or cross product
+        impl ops::Mul for $vector {
+            type Output = $vector;
+
+            fn mul(self, other: $vector) -> $vector {
+                $vector { $($component: self.$component * other.$component),+ }
+            }
+        }
+
+        impl ops::Div<$scalar> for $vector {
+            type Output = $vector;
+
+            fn div(self, scalar: $scalar) -> $vector {
+                $vector { $($component: self.$component / scalar),+ }
+            }
+        }
+
+        impl ops::Neg for $vector {
+            type Output = $vector;
+
+            fn neg(self) -> $vector {
+                $vector { $($component: -self.$component),+ }
+            }
+        }
+
+        impl $vector {
+            pub fn dot(&self, other: &$vector) -> $scalar {
+                0.0 $(+ self.$component * other.$component)+
+            }
+        }
+    };
+}
+
+macro_rules! impl_vector {
+    ($vector:ident: $scalar:ty { $($component:ident),+ }) => {
+        // So "2.0 * a" works as well as "a * 2.0"
+        impl ops::Mul<$vector> for $scalar {
+            type Output = $vector;
+
+            fn mul(self, vector: $vector) -> $vector {
+                vector * self
+            }
+        }
+
+        impl ops::AddAssign for $vector {
+            fn add_assign(&mut self, other: $vector) {
+                *self = *self + other;
+            }
+        }
+
+        impl ops::SubAssign for $vector {
+            fn sub_assign(&mut self, other: $vector) {
+                *self = *self - other;
+            }
+        }
+
+        impl ops::MulAssign<$scalar> for $vector {
+            fn mul_assign(&mut self, scalar: $scalar) {
+                *self = *self * scalar;
+            }
+        }
+
+        impl ops::DivAssign<$scalar> for $vector {
+            fn div_assign(&mut self, scalar: $scalar) {
+                *self = *self / scalar;
+            }
+        }
+
+        // a[0] is x, a[1] is y, and so on
+        impl ops::Index<usize> for $vector {
+            type Output = $scalar;
+
+            fn index(&self, index: usize) -> &$scalar {
+                let components = [$(&self.$component),+];
+                match components.get(index) {
+                    Some(component) => component,
+                    None => panic!("{} has no component {}.", stringify!($vector), index)
+                }
+            }
+        }
+
+        impl ops::IndexMut<usize> for $vector {
+            fn index_mut(&mut self, index: usize) -> &mut $scalar {
+                let components = [$(&mut self.$component),+];
+                match components.into_iter().nth(index) {
+                    Some(component) => component,
+                    None => panic!("{} has no component {}.", stringify!($vector), index)
+                }
+            }
+        }
+
+        impl $vector {
+            // Cheaper than length, as there's no square root. Good enough for comparing lengths.
+            pub fn length_squared(&self) -> $scalar {
+                self.dot(self)
+            }
+
+            pub fn length(&self) -> $scalar {
+                self.length_squared().sqrt()
+            }
+
+            pub fn distance(&self, other: &$vector) -> $scalar {
+                (*other - *self).length()
+            }
+
+            // Divides by zero for a zero vector, giving NaNs. Use try_normalize when the vector could be (close to) zero.
+            pub fn normalized(&self) -> $vector {
+                *self / self.length()
+            }
+
+            // None if the vector is too short to have a direction
+            pub fn try_normalize(&self) -> Option<$vector> {
+                let length = self.length();
+                // Below MIN_POSITIVE, 1 / length would overflow to infinity
+                if length >= <$scalar>::MIN_POSITIVE && length.is_finite() {
+                    Some(*self * (1.0 / length))
+                } else {
+                    None
+                }
+            }
+
+            // t = 0 gives self, t = 1 gives other. Values outside of 0..1 extrapolate.
+            pub fn lerp(&self, other: &$vector, t: $scalar) -> $vector {
+                *self + (*other - *self) * t
+            }
+
+            pub fn min(&self, other: &$vector) -> $vector {
+                $vector { $($component: self.$component.min(other.$component)),+ }
+            }
+
+            pub fn max(&self, other: &$vector) -> $vector {
+                $vector { $($component: self.$component.max(other.$component)),+ }
+            }
+
+            pub fn clamp(&self, min: &$vector, max: &$vector) -> $vector {
+                self.max(min).min(max)
+            }
+
+            pub fn abs(&self) -> $vector {
+                $vector { $($component: self.$component.abs()),+ }
+            }
+
+            // The part of this vector that points along "onto". Zero if "onto" has no direction.
+            pub fn project(&self, onto: &$vector) -> $vector {
+                let length_squared = onto.length_squared();
+                if length_squared < <$scalar>::MIN_POSITIVE {
+                    return $vector::default();
+                }
+
+                *onto * (self.dot(onto) / length_squared)
+            }
+
+            // The part of this vector that is perpendicular to "from", so project + reject gives the vector back
+            pub fn reject(&self, from: &$vector) -> $vector {
+                *self - self.project(from)
+            }
+
+            // Bounces the vector off a surface with the given (normalized) normal, like light off a mirror
+            pub fn reflect(&self, normal: &$vector) -> $vector {
+                *self - *normal * (2.0 * self.dot(normal))
+            }
+
+            /*
+                Bends a (normalized) direction going through a surface with the given (normalized) normal, like light going into water.
+                eta is the ratio of refractive indices, the one the direction comes from over the one it goes into (1.0 / 1.33 for air into water).
+                Same formula as HLSL's refract, except that total internal reflection gives None instead of a zero vector.
+            */
+            pub fn refract(&self, normal: &$vector, eta: $scalar) -> Option<$vector> {
+                let cos_incident = self.dot(normal);
+                let k = 1.0 - eta * eta * (1.0 - cos_incident * cos_incident);
+                if k < 0.0 {
+                    return None;
+                }
+
+                Some(*self * eta - *normal * (eta * cos_incident + k.sqrt()))
+            }
+        }
+    };
+}
+
+impl_vector_arithmetic!(Vector2: f32 { x, y });
+impl_vector_arithmetic!(Vector3: f32 { x, y, z });
+
+impl ops::Add for Vector4 {
+    type Output = Vector4;
+
+    fn add(self, other: Vector4) -> Vector4 {
+        simd::add(&self, &other)
+    }
+}
+
+impl ops::Sub for Vector4 {
+    type Output = Vector4;
+
+    fn sub(self, other: Vector4) -> Vector4 {
+        simd::sub(&self, &other)
+    }
+}
+
+impl ops::Mul<f32> for Vector4 {
+    type Output = Vector4;
+
+    fn mul(self, scalar: f32) -> Vector4 {
+        simd::mul_scalar(&self, scalar)
+    }
+}
+
+// Component by component, not a dot product
+impl ops::Mul for Vector4 {
+    type Output = Vector4;
+
+    fn mul(self, other: Vector4) -> Vector4 {
+        simd::mul_components(&self, &other)
+    }
+}
+
+impl ops::Div<f32> for Vector4 {
+    type Output = Vector4;
+
+    fn div(self, scalar: f32) -> Vector4 {
+        simd::div_scalar(&self, scalar)
+    }
+}
+
+impl ops::Neg for Vector4 {
+    type Output = Vector4;
+
+    fn neg(self) -> Vector4 {
+        simd::neg(&self)
+    }
+}
+
+impl_vector!(Vector2: f32 { x, y });
+impl_vector!(Vector3: f32 { x, y, z });
+impl_vector!(Vector4: f32 { x, y, z, w });
+
+// Declared after the macros, so the f64 types in it can use them as well
+pub mod double;
+
+#[repr(C)]
+#[derive(Default, Clone, Copy, PartialEq)]
 pub struct Vector2
 {
     pub x: f32,
@@ -34,6 +320,33 @@ impl fmt::Debug for Vector2 {
     }
 }
 
+impl shared::FromBinary for Vector2 {
+    fn from_binary(binary_data: &[u8]) -> Self {
+        let size_of_vector_in_bytes = size_of::<Vector2>();
+
+        if binary_data.len() != size_of_vector_in_bytes {
+            panic!("Binary data does not have the size of a single vector2 in bytes, which is {}", size_of_vector_in_bytes);
+        }
+
+        Vector2::new(
+            LittleEndian::read_f32(&binary_data[0..4]),
+            LittleEndian::read_f32(&binary_data[4..8]))
+    }
+
+    fn from_binary_collection(binary_data: &[u8]) -> Vec<Self> {
+        let size_of_vector_in_bytes = size_of::<Vector2>();
+
+        if binary_data.len() % size_of_vector_in_bytes != 0 {
+            panic!("Binary vector data is not divisible by size of a vector2 in bytes, which is {}", size_of_vector_in_bytes);
+        }
+
+        binary_data
+            .chunks(size_of_vector_in_bytes)
+            .map(Vector2::from_binary)
+            .collect()
+    }
+}
+
 impl Vector2 {
     pub fn new(x: f32, y: f32) -> Vector2 {
         Vector2 {
@@ -43,10 +356,9 @@ impl Vector2 {
     }
 }
 
-// TODO: ADD SUBTRACT METHOD
 // TODO: Add helper builders for common axes, like "up", "right", "forward"
 #[repr(C)]
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone, Copy, PartialEq)]
 pub struct Vector3
 {
     pub x: f32,
@@ -150,26 +462,14 @@ impl Vector3 {
             self.x * vec.y - self.y * vec.x)
     }
 
-    pub fn dot(&self, vec: &Vector3) -> f32 {
-        self.x * vec.x + self.y * vec.y + self.z * vec.z
-    }
-
     pub fn mul(&self, scalar: f32) -> Vector3 {
         Vector3::new(self.x * scalar, self.y * scalar, self.z * scalar)
     }
-
-    pub fn length(&self) -> f32 {
-        ((self.x * self.x) + (self.y * self.y) + (self.z * self.z)).sqrt()
-    }
-
-    pub fn normalized(&self) -> Vector3 {
-        let magnitude = self.length();
-        Vector3::new(self.x / magnitude, self.y / magnitude, self.z / magnitude)
-    }
 }
 
-#[repr(C)]
-#[derive(Default, Clone, Copy)]
+// Aligned to 16 bytes like a float4 in HLSL, which is also what the SIMD loads want (see simd)
+#[repr(C, align(16))]
+#[derive(Default, Clone, Copy, PartialEq)]
 pub struct Vector4 {
     pub x: f32,
     pub y: f32,
@@ -188,6 +488,35 @@ impl fmt::Debug for Vector4 {
     }
 }
 
+impl shared::FromBinary for Vector4 {
+    fn from_binary(binary_data: &[u8]) -> Self {
+        let size_of_vector_in_bytes = size_of::<Vector4>();
+
+        if binary_data.len() != size_of_vector_in_bytes {
+            panic!("Binary data does not have the size of a single vector4 in bytes, which is {}", size_of_vector_in_bytes);
+        }
+
+        Vector4::new(
+            LittleEndian::read_f32(&binary_data[0..4]),
+            LittleEndian::read_f32(&binary_data[4..8]),
+            LittleEndian::read_f32(&binary_data[8..12]),
+            LittleEndian::read_f32(&binary_data[12..16]))
+    }
+
+    fn from_binary_collection(binary_data: &[u8]) -> Vec<Self> {
+        let size_of_vector_in_bytes = size_of::<Vector4>();
+
+        if binary_data.len() % size_of_vector_in_bytes != 0 {
+            panic!("Binary vector data is not divisible by size of a vector4 in bytes, which is {}", size_of_vector_in_bytes);
+        }
+
+        binary_data
+            .chunks(size_of_vector_in_bytes)
+            .map(Vector4::from_binary)
+            .collect()
+    }
+}
+
 impl Vector4 {
     pub fn new(x: f32, y: f32, z: f32, w: f32) -> Vector4 {
         Vector4 {
@@ -198,16 +527,16 @@ impl Vector4 {
         }
     }
 
-    pub fn dot(&self, vec: &Vector4) -> f32 {
-        self.x * vec.x + self.y * vec.y + self.z * vec.z + self.w * vec.w
-    }
-
     pub fn magnitude(&self) -> f32 {
-        ( self.x.powf(2.0) + self.y.powf(2.0) + self.z.powf(2.0) + self.w.powf(2.0) ).sqrt()
+        self.length()
     }
 
     pub fn normalize(&self) -> Vector4 {
-        Vector4::new( self.x / self.magnitude(), self.y / self.magnitude(), self.z / self.magnitude(), self.w / self.magnitude() )
+        self.normalized()
+    }
+
+    pub fn dot(&self, other: &Vector4) -> f32 {
+        simd::dot(self, other)
     }
 
     pub fn as_array(&self) -> [f32; 4] {
@@ -224,7 +553,9 @@ impl Vector4 {
     In general, matrices represent a linear and/or affine transformation. In the case of the linear transformation, multiplying a vector by a matrix creates a linear displacement.
 */
 
-// TODO: Implement copy trait!
+// Aligned to 16 bytes, so every row can be loaded straight into a SIMD register, and copied straight into a float4x4 in a constant buffer
+#[repr(C, align(16))]
+#[derive(Clone, Copy, PartialEq)]
 pub struct Mat4
 {
     pub matrix: [f32; 16]
@@ -260,10 +591,174 @@ impl Mat4
         self.matrix[index]
     }
 
-    pub fn determinant(mat: &Mat4) -> f32 {
-        return
-            mat.get(0, 0) * ( mat.get(1, 1) * ( mat.get(2, 2) * mat.get(3, 3) - mat.get(2, 3) * mat.get(3, 2) ) )
-            
+    /*
+        The determinant, and the inverse below, use Laplace expansion: the determinant of a 4x4 matrix is a sum over the
+        determinants of 3x3 matrices, which are sums over the determinants of 2x2 matrices.
+        Lots of those 2x2 determinants are the same, so I calculate the twelve different ones from the two top rows (s0..s5)
+        and the two bottom rows (c0..c5) once, and combine them.
+
+        Still works as Mat4::determinant(&mat), which is how it used to be called.
+    */
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.sub_determinants();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    fn sub_determinants(&self) -> ([f32; 6], [f32; 6]) {
+        let m = &self.matrix;
+
+        let s = [
+            m[0] * m[5] - m[4] * m[1],
+            m[0] * m[6] - m[4] * m[2],
+            m[0] * m[7] - m[4] * m[3],
+            m[1] * m[6] - m[5] * m[2],
+            m[1] * m[7] - m[5] * m[3],
+            m[2] * m[7] - m[6] * m[3]
+        ];
+
+        let c = [
+            m[8] * m[13] - m[12] * m[9],
+            m[8] * m[14] - m[12] * m[10],
+            m[8] * m[15] - m[12] * m[11],
+            m[9] * m[14] - m[13] * m[10],
+            m[9] * m[15] - m[13] * m[11],
+            m[10] * m[15] - m[14] * m[11]
+        ];
+
+        (s, c)
+    }
+
+    /*
+        The inverse undoes the matrix: m * m.inverse() is the identity.
+
+        Each value of the inverse is a cofactor (the determinant of the 3x3 matrix left after removing a row and a column,
+        with a sign depending on the position), transposed, and divided by the determinant.
+        A matrix with a determinant of zero squashes space flat (like a scale of zero), which can't be undone, so that gives None.
+
+        For transforms that are only scale, rotation and translation, inverse_affine and inverse_rigid are cheaper.
+    */
+    pub fn inverse(&self) -> Option<Mat4> {
+        let m = &self.matrix;
+        let (s, c) = self.sub_determinants();
+
+        let determinant = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+
+        let inverse = Mat4::new([
+            ( m[5] * c[5] - m[6] * c[4] + m[7] * c[3]) * inverse_determinant,
+            (-m[1] * c[5] + m[2] * c[4] - m[3] * c[3]) * inverse_determinant,
+            ( m[13] * s[5] - m[14] * s[4] + m[15] * s[3]) * inverse_determinant,
+            (-m[9] * s[5] + m[10] * s[4] - m[11] * s[3]) * inverse_determinant,
+
+            (-m[4] * c[5] + m[6] * c[2] - m[7] * c[1]) * inverse_determinant,
+            ( m[0] * c[5] - m[2] * c[2] + m[3] * c[1]) * inverse_determinant,
+            (-m[12] * s[5] + m[14] * s[2] - m[15] * s[1]) * inverse_determinant,
+            ( m[8] * s[5] - m[10] * s[2] + m[11] * s[1]) * inverse_determinant,
+
+            ( m[4] * c[4] - m[5] * c[2] + m[7] * c[0]) * inverse_determinant,
+            (-m[0] * c[4] + m[1] * c[2] - m[3] * c[0]) * inverse_determinant,
+            ( m[12] * s[4] - m[13] * s[2] + m[15] * s[0]) * inverse_determinant,
+            (-m[8] * s[4] + m[9] * s[2] - m[11] * s[0]) * inverse_determinant,
+
+            (-m[4] * c[3] + m[5] * c[1] - m[6] * c[0]) * inverse_determinant,
+            ( m[0] * c[3] - m[1] * c[1] + m[2] * c[0]) * inverse_determinant,
+            (-m[12] * s[3] + m[13] * s[1] - m[14] * s[0]) * inverse_determinant,
+            ( m[8] * s[3] - m[9] * s[1] + m[10] * s[0]) * inverse_determinant
+        ]);
+
+        Some(inverse)
+    }
+
+    /*
+        Inverse of an affine transform, meaning anything built from scales, rotations, shears and translations,
+        where the last column is (0, 0, 0, 1). Projection matrices are not affine.
+
+        With row vectors such a matrix is [A 0; t 1], where A is the 3x3 linear part and t the translation.
+        Its inverse is [A^-1 0; -t * A^-1 1], so only the 3x3 part needs a real inverse.
+    */
+    pub fn inverse_affine(&self) -> Option<Mat4> {
+        let m = &self.matrix;
+
+        // Cofactors of the 3x3 part, which are already transposed here
+        let c00 = m[5] * m[10] - m[6] * m[9];
+        let c01 = m[2] * m[9] - m[1] * m[10];
+        let c02 = m[1] * m[6] - m[2] * m[5];
+        let c10 = m[6] * m[8] - m[4] * m[10];
+        let c11 = m[0] * m[10] - m[2] * m[8];
+        let c12 = m[2] * m[4] - m[0] * m[6];
+        let c20 = m[4] * m[9] - m[5] * m[8];
+        let c21 = m[1] * m[8] - m[0] * m[9];
+        let c22 = m[0] * m[5] - m[1] * m[4];
+
+        let determinant = m[0] * c00 + m[1] * c10 + m[2] * c20;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let linear = [
+            c00 * inverse_determinant, c01 * inverse_determinant, c02 * inverse_determinant,
+            c10 * inverse_determinant, c11 * inverse_determinant, c12 * inverse_determinant,
+            c20 * inverse_determinant, c21 * inverse_determinant, c22 * inverse_determinant
+        ];
+
+        Some(Mat4::with_linear_part_and_inverted_translation(&linear, &Vector3::new(m[12], m[13], m[14])))
+    }
+
+    /*
+        Inverse of a rigid transform, which only rotates and translates, like a camera's view matrix.
+        The 3x3 part of a rotation is orthonormal, and the inverse of an orthonormal matrix is its transpose,
+        so nothing has to be divided, and it can't fail.
+
+        Gives wrong results for anything that scales or shears. Use inverse_affine for those.
+    */
+    pub fn inverse_rigid(&self) -> Mat4 {
+        let m = &self.matrix;
+        let linear = [
+            m[0], m[4], m[8],
+            m[1], m[5], m[9],
+            m[2], m[6], m[10]
+        ];
+
+        Mat4::with_linear_part_and_inverted_translation(&linear, &Vector3::new(m[12], m[13], m[14]))
+    }
+
+    // [linear 0; -translation * linear 1]
+    fn with_linear_part_and_inverted_translation(linear: &[f32; 9], translation: &Vector3) -> Mat4 {
+        let t = translation;
+
+        Mat4::new([
+            linear[0], linear[1], linear[2], 0.0,
+            linear[3], linear[4], linear[5], 0.0,
+            linear[6], linear[7], linear[8], 0.0,
+            -(t.x * linear[0] + t.y * linear[3] + t.z * linear[6]),
+            -(t.x * linear[1] + t.y * linear[4] + t.z * linear[7]),
+            -(t.x * linear[2] + t.y * linear[5] + t.z * linear[8]),
+            1.0
+        ])
+    }
+
+    /*
+        The matrix to transform normals with, for a model matrix that is affine.
+
+        Normals can't be transformed with the model matrix itself once it scales unevenly: stretching a sphere along x
+        makes its surface flatter along x, so the normals there have to lean away from x, not towards it.
+        The transpose of the inverse does exactly that. For rotations it's the matrix itself again.
+
+        Normals are directions, so the translation is left out. None if the matrix squashes space flat.
+    */
+    pub fn inverse_transpose(&self) -> Option<Mat4> {
+        let mut normal_matrix = self.inverse_affine()?.get_transposed();
+
+        normal_matrix.matrix[3] = 0.0;
+        normal_matrix.matrix[7] = 0.0;
+        normal_matrix.matrix[11] = 0.0;
+
+        Some(normal_matrix)
     }
 
     pub fn identity() -> Mat4 {
@@ -364,67 +859,37 @@ impl Mat4
         and returns a matrix containing the translation of the parent coordinate space expressed in the nested coordinate space.
     */
     pub fn parent_to_local(vec: &Vector3, orien: &Mat4) -> Mat4 {
-        let m11 = orien.get(0, 0);
-        let m12 = orien.get(0, 1);
-        let m13 = orien.get(0, 2);
-
-        let m21 = orien.get(1, 0);
-        let m22 = orien.get(1, 1);
-        let m23 = orien.get(1, 2);
-
-        let m31 = orien.get(2, 0);
-        let m32 = orien.get(2, 1);
-        let m33 = orien.get(2, 2);
-
-        let mut rotation_matrix = Mat4::new([
-            m11, m12, m13, 0.0,
-            m21, m22, m23, 0.0,
-            m31, m32, m33, 0.0,
-            0.0, 0.0, 0.0, 1.0
-        ]);
-
+        // Only the rotation part of the orientation is used, its translation is dropped
+        let rotation_matrix = Mat3::from_mat4(orien).to_mat4();
         let translation = Mat4::translate(&vec.mul(-1.0));
 
-        let res = translation.mul(&rotation_matrix);
-
-        res
-
-        // TODO: This calculation is basically the same as I'm doing above... just shortened.
-        /*
-        let m11 = orien.get(0, 0);
-        let m12 = orien.get(0, 1);
-        let m13 = orien.get(0, 2);
-
-        let m21 = orien.get(1, 0);
-        let m22 = orien.get(1, 1);
-        let m23 = orien.get(1, 2);
+        translation.mul(&rotation_matrix)
+    }
 
-        let m31 = orien.get(2, 0);
-        let m32 = orien.get(2, 1);
-        let m33 = orien.get(2, 2);
+    pub fn mul_row(&self, row: &Vector4) -> Vector4 {
+        simd::mul_row(self, row)
+    }
 
-        Mat4::new([
-            m11, m12, m13, 0.0,
-            m21, m22, m23, 0.0,
-            m31, m32, m33, 0.0,
-            -(vec.x * m11 + vec.y * m21 + vec.z * m31), -(vec.x * m12 + vec.y * m22 + vec.z * m32), -(vec.x * m13 + vec.y * m23 + vec.z * m33), 1.0
-        ])*/
+    /*
+        Transforms every point in the slice, in place, as a row vector with w = 1.
+        The resulting w is dropped, without dividing by it, so this is for affine transforms (not projections).
+        Skips working out w, which makes it cheaper than calling mul_row for every point.
+    */
+    pub fn transform_points(&self, points: &mut [Vector3]) {
+        simd::transform_points(self, points)
     }
 
-    pub fn mul_row(&self, row: &Vector4) -> Vector4 {
-        Vector4::new(
-            row.x * self.matrix[0] + row.y * self.matrix[4] + row.z * self.matrix[8] + row.w * self.matrix[12],
-            row.x * self.matrix[1] + row.y * self.matrix[5] + row.z * self.matrix[9] + row.w * self.matrix[13],
-            row.x * self.matrix[2] + row.y * self.matrix[6] + row.z * self.matrix[10] + row.w * self.matrix[14],
-            row.x * self.matrix[3] + row.y * self.matrix[7] + row.z * self.matrix[11] + row.w * self.matrix[15])
+    // Transforms every row vector in the slice, in place, the same as mul_row. With SIMD, the matrix is loaded into registers once for the whole slice.
+    pub fn transform_rows(&self, rows: &mut [Vector4]) {
+        simd::transform_rows(self, rows)
     }
 
     pub fn tranpose(&mut self) {
-        self.matrix = self.get_column_major_value()
+        *self = simd::transpose(self)
     }
 
     pub fn get_transposed(&self) -> Mat4 {
-        Mat4 { matrix: self.get_column_major_value() }
+        simd::transpose(self)
     }
 
     // For this projection matrix, I use what is sometimes referred to as the Hor+ scaling method for Field of View (https://en.wikipedia.org/wiki/Field_of_view_in_video_games).
@@ -447,165 +912,1165 @@ impl Mat4
         }
     }
 
-    pub fn mul(&self, mat: &Mat4) -> Mat4 {
-        let self_row0 = Vector4::new(self.get(0, 0), self.get(1, 0),  self.get(2, 0), self.get(3, 0));
-        let self_row1 = Vector4::new(self.get(0, 1), self.get(1, 1),  self.get(2, 1), self.get(3, 1));
-        let self_row2 = Vector4::new(self.get(0, 2), self.get(1, 2),  self.get(2, 2), self.get(3, 2));
-        let self_row3 = Vector4::new(self.get(0, 3), self.get(1, 3),  self.get(2, 3), self.get(3, 3));
-
-        let mat_column0 = Vector4::new( mat.get(0, 0), mat.get(0, 1), mat.get(0, 2), mat.get(0, 3));
-        let mat_column1 = Vector4::new( mat.get(1, 0), mat.get(1, 1), mat.get(1, 2), mat.get(1, 3));
-        let mat_column2 = Vector4::new( mat.get(2, 0), mat.get(2, 1), mat.get(2, 2), mat.get(2, 3));
-        let mat_column3 = Vector4::new( mat.get(3, 0), mat.get(3, 1), mat.get(3, 2), mat.get(3, 3));
+    /*
+        The same perspective as projection, with the far plane infinitely far away.
+        Depth gets close to 1 the further away things are, but never reaches it, so nothing is ever clipped for being too far.
+    */
+    pub fn projection_infinite(fov: f32, width: f32, height: f32, near: f32) -> Mat4 {
+        let y_scale = 1.0 / (fov * 0.5).tan();
+        let x_scale = y_scale / (width / height);
 
         Mat4 {
             matrix: [
-                self_row0.dot(&mat_column0), self_row0.dot(&mat_column1), self_row0.dot(&mat_column2), self_row0.dot(&&mat_column3),
-                self_row1.dot(&mat_column0), self_row1.dot(&mat_column1), self_row1.dot(&mat_column2), self_row1.dot(&&mat_column3),
-                self_row2.dot(&mat_column0), self_row2.dot(&mat_column1), self_row2.dot(&mat_column2), self_row2.dot(&&mat_column3),
-                self_row3.dot(&mat_column0), self_row3.dot(&mat_column1), self_row3.dot(&mat_column2), self_row3.dot(&&mat_column3),
+                x_scale, 0.0    , 0.0  , 0.0,
+                0.0    , y_scale, 0.0  , 0.0,
+                0.0    , 0.0    , 1.0  , 1.0,
+                0.0    , 0.0    , -near, 0.0
             ]
         }
     }
 
-    pub fn get_value(&self) -> [f32; 16] {
-        self.matrix
-    }
+    /*
+        The same perspective as projection, but with depth going from 1 at the near plane to 0 at the far plane.
 
-    pub fn get_column_major_value(&self) -> [f32; 16] {
-        [
-            self.matrix[0], self.matrix[4], self.matrix[8], self.matrix[12],
-            self.matrix[1], self.matrix[5], self.matrix[9], self.matrix[13],
-            self.matrix[2], self.matrix[6], self.matrix[10], self.matrix[14],
-            self.matrix[3], self.matrix[7], self.matrix[11], self.matrix[15]
-        ]
+        A floating point depth buffer has most of its precision close to 0, and a perspective projection puts most of
+        the depth range close to the near plane. Reversing the depth spreads those out over each other, so far away
+        things don't fight over the same depth values anymore.
+        It only pays off with a floating point depth buffer (DXGI_FORMAT_D32_FLOAT), which has to be cleared to 0 rather than 1,
+        and tested with D3D11_COMPARISON_GREATER rather than LESS.
+    */
+    pub fn projection_reversed_z(fov: f32, width: f32, height: f32, near: f32, far: f32) -> Mat4 {
+        let y_scale = 1.0 / (fov * 0.5).tan();
+        let x_scale = y_scale / (width / height);
+        let q = near / (near - far);
+
+        Mat4 {
+            matrix: [
+                x_scale, 0.0    , 0.0     , 0.0,
+                0.0    , y_scale, 0.0     , 0.0,
+                0.0    , 0.0    , q       , 1.0,
+                0.0    , 0.0    , -q * far, 0.0
+            ]
+        }
     }
-}
 
-// TODO: Default Quaternion should actually return a UNIT QUATERNION / IDENTITY QUATERNION
-#[derive(Default, Copy, Clone)]
-pub struct Quaternion {
-    pub w: f32,
-    pub v: Vector3
-}
+    // Reversed depth with the far plane infinitely far away, depth gets close to 0 but never reaches it
+    pub fn projection_reversed_z_infinite(fov: f32, width: f32, height: f32, near: f32) -> Mat4 {
+        let y_scale = 1.0 / (fov * 0.5).tan();
+        let x_scale = y_scale / (width / height);
 
-impl Quaternion {
-    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Quaternion {
-        Quaternion {
-            w,
-            v: Vector3::new(x, y, z)
+        Mat4 {
+            matrix: [
+                x_scale, 0.0    , 0.0 , 0.0,
+                0.0    , y_scale, 0.0 , 0.0,
+                0.0    , 0.0    , 0.0 , 1.0,
+                0.0    , 0.0    , near, 0.0
+            ]
         }
     }
 
-    pub fn from_array(array: &[f32; 4]) -> Quaternion {
-        Quaternion::new(array[3], array[0], array[1], array[2])
+    /*
+        A perspective projection where the eye isn't necessarily in the middle of the view,
+        like for one half of a stereo pair, or one tile of a screenshot that's bigger than the screen.
+        left, right, bottom and top are where the edges of the view are on the near plane, in view space.
+    */
+    pub fn projection_off_center(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+        let q = far / (far - near);
+
+        Mat4 {
+            matrix: [
+                2.0 * near / (right - left)    , 0.0                            , 0.0      , 0.0,
+                0.0                            , 2.0 * near / (top - bottom)    , 0.0      , 0.0,
+                (left + right) / (left - right), (top + bottom) / (bottom - top), q        , 1.0,
+                0.0                            , 0.0                            , -q * near, 0.0
+            ]
+        }
+    }
+
+    /*
+        A left-handed orthographic projection, centered on the view direction: things don't get smaller with distance.
+        width and height are how much of view space is visible, in view space units. Depth goes from 0 at near to 1 at far.
+        For things like a top-down map, or the view from a directional light for shadow maps.
+    */
+    pub fn orthographic(width: f32, height: f32, near: f32, far: f32) -> Mat4 {
+        let depth_scale = 1.0 / (far - near);
+
+        Mat4 {
+            matrix: [
+                2.0 / width, 0.0         , 0.0                 , 0.0,
+                0.0        , 2.0 / height, 0.0                 , 0.0,
+                0.0        , 0.0         , depth_scale         , 0.0,
+                0.0        , 0.0         , -near * depth_scale , 1.0
+            ]
+        }
+    }
+
+    /*
+        An orthographic projection of the box between left and right, bottom and top, near and far (in view space).
+        Handy for fitting a shadow map tightly around what's visible.
+    */
+    pub fn orthographic_off_center(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+        let depth_scale = 1.0 / (far - near);
+
+        Mat4 {
+            matrix: [
+                2.0 / (right - left)           , 0.0                            , 0.0                , 0.0,
+                0.0                            , 2.0 / (top - bottom)           , 0.0                , 0.0,
+                0.0                            , 0.0                            , depth_scale        , 0.0,
+                (left + right) / (left - right), (top + bottom) / (bottom - top), -near * depth_scale, 1.0
+            ]
+        }
+    }
+
+    /*
+        A view matrix for an eye at eye, looking at target. The view looks down +Z, with +Y up and +X to the right.
+        up only has to point somewhat up, the actual up of the view is made perpendicular to the view direction.
+        None if eye and target are the same point, or if the view direction is parallel to up.
+    */
+    pub fn look_at(eye: &Vector3, target: &Vector3, up: &Vector3) -> Option<Mat4> {
+        Mat4::look_to(eye, &(*target - *eye), up)
+    }
+
+    // Like look_at, with the direction to look in rather than a point to look at
+    pub fn look_to(eye: &Vector3, direction: &Vector3, up: &Vector3) -> Option<Mat4> {
+        let z = direction.try_normalize()?;
+        let x = up.cross(&z).try_normalize()?;
+        let y = z.cross(&x);
+
+        /*
+            The axes of the view are the rows of the rotation that turns the view into the world,
+            so its transpose (its inverse) turns the world into the view. The translation moves the eye to the origin first.
+        */
+        Some(Mat4 {
+            matrix: [
+                x.x        , y.x        , z.x        , 0.0,
+                x.y        , y.y        , z.y        , 0.0,
+                x.z        , y.z        , z.z        , 0.0,
+                -x.dot(eye), -y.dot(eye), -z.dot(eye), 1.0
+            ]
+        })
+    }
+
+    /*
+        Where a point ends up after the (view) projection: x and y from -1 to 1 left to right and bottom to top,
+        and the depth in z. None if the point is in the plane of the eye or behind it.
+    */
+    pub fn project(point: &Vector3, view_projection: &Mat4) -> Option<Vector3> {
+        let clip = view_projection.mul_row(&Vector4::new(point.x, point.y, point.z, 1.0));
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        Some(Vector3::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w))
+    }
+
+    /*
+        The opposite of project: from x and y (-1 to 1) and depth back to a point in the space the view projection started in
+        (world space for a view projection, view space for just a projection).
+        It works for every projection above, with their own depth ranges. The inverse is passed in as it's expensive to work out.
+        None for points infinitely far away, like depth 1 with projection_infinite.
+    */
+    pub fn unproject(normalized_device_point: &Vector3, inverse_view_projection: &Mat4) -> Option<Vector3> {
+        let p = normalized_device_point;
+        let point = inverse_view_projection.mul_row(&Vector4::new(p.x, p.y, p.z, 1.0));
+        if point.w.abs() < f32::MIN_POSITIVE {
+            return None;
+        }
+
+        Some(Vector3::new(point.x / point.w, point.y / point.w, point.z / point.w))
+    }
+
+    // From a pixel (with Y going down, as on screen) to the x and y that project and unproject use
+    pub fn screen_to_normalized_device(screen_point: &Vector2, screen_width: f32, screen_height: f32, depth: f32) -> Vector3 {
+        Vector3::new(
+            screen_point.x / screen_width * 2.0 - 1.0,
+            1.0 - screen_point.y / screen_height * 2.0,
+            depth)
+    }
+
+    pub fn mul(&self, mat: &Mat4) -> Mat4 {
+        simd::mul(self, mat)
+    }
+
+    pub fn get_value(&self) -> [f32; 16] {
+        self.matrix
+    }
+
+    pub fn get_column_major_value(&self) -> [f32; 16] {
+        simd::transpose(self).matrix
+    }
+}
+
+/*
+    Operators for Mat4.
+
+    "a * b" is the same as a.mul(&b): a's transform first, then b's, as I'm using row vectors.
+    "v * m" transforms the row vector v by m, the same as m.mul_row(&v).
+    m[row] is one row of the matrix, so m[3][0] is the x translation.
+*/
+impl ops::Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        Mat4::mul(&self, &other)
+    }
+}
+
+impl ops::MulAssign for Mat4 {
+    fn mul_assign(&mut self, other: Mat4) {
+        *self = Mat4::mul(self, &other);
+    }
+}
+
+impl ops::Mul<Mat4> for Vector4 {
+    type Output = Vector4;
+
+    fn mul(self, mat: Mat4) -> Vector4 {
+        mat.mul_row(&self)
+    }
+}
+
+impl ops::Mul<f32> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, scalar: f32) -> Mat4 {
+        Mat4 { matrix: self.matrix.map(|value| value * scalar) }
+    }
+}
+
+impl ops::Add for Mat4 {
+    type Output = Mat4;
+
+    fn add(self, other: Mat4) -> Mat4 {
+        let mut matrix = self.matrix;
+        matrix.iter_mut().zip(other.matrix.iter()).for_each(|(value, other_value)| *value += other_value);
+        Mat4 { matrix }
+    }
+}
+
+impl ops::Sub for Mat4 {
+    type Output = Mat4;
+
+    fn sub(self, other: Mat4) -> Mat4 {
+        self + -other
+    }
+}
+
+impl ops::Neg for Mat4 {
+    type Output = Mat4;
+
+    fn neg(self) -> Mat4 {
+        self * -1.0
+    }
+}
+
+impl ops::AddAssign for Mat4 {
+    fn add_assign(&mut self, other: Mat4) {
+        *self = *self + other;
+    }
+}
+
+impl ops::SubAssign for Mat4 {
+    fn sub_assign(&mut self, other: Mat4) {
+        *self = *self - other;
+    }
+}
+
+impl ops::Index<usize> for Mat4 {
+    type Output = [f32; 4];
+
+    fn index(&self, row: usize) -> &[f32; 4] {
+        // Turning the 4 values of the row into an array reference can't fail, they're always exactly 4
+        self.matrix[row * 4..row * 4 + 4].try_into().unwrap()
+    }
+}
+
+impl ops::IndexMut<usize> for Mat4 {
+    fn index_mut(&mut self, row: usize) -> &mut [f32; 4] {
+        (&mut self.matrix[row * 4..row * 4 + 4]).try_into().unwrap()
+    }
+}
+
+/*
+    3x3 matrices, for linear transforms only: rotation, scale and shear, without translation.
+    Same conventions as Mat4: row vectors, rows stored one after the other, and get(x, y) is column x of row y.
+
+    They're what's left of a Mat4 without the translation row and the projection column,
+    so they're handy for transforming directions (like normals), and for working with just the rotation of a transform.
+*/
+#[derive(Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub matrix: [f32; 9]
+}
+
+impl Default for Mat3 {
+    fn default() -> Self { Mat3::identity() }
+}
+
+impl fmt::Debug for Mat3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:?}", Vector3::new(self.get(0, 0), self.get(1, 0), self.get(2, 0)))?;
+        writeln!(f, "{:?}", Vector3::new(self.get(0, 1), self.get(1, 1), self.get(2, 1)))?;
+        writeln!(f, "{:?}", Vector3::new(self.get(0, 2), self.get(1, 2), self.get(2, 2)))
+    }
+}
+
+impl Mat3 {
+    pub fn new(matrix: [f32; 9]) -> Mat3 {
+        Mat3 { matrix }
+    }
+
+    pub fn identity() -> Mat3 {
+        Mat3::new([
+            1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            0.0, 0.0, 1.0
+        ])
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> f32 {
+        self.matrix[(x + 3 * y) as usize]
+    }
+
+    pub fn scale(scale: &Vector3) -> Mat3 {
+        Mat3::new([
+            scale.x, 0.0, 0.0,
+            0.0, scale.y, 0.0,
+            0.0, 0.0, scale.z
+        ])
+    }
+
+    // The upper left 3x3 part of the matrix, so without its translation
+    pub fn from_mat4(mat: &Mat4) -> Mat3 {
+        let m = &mat.matrix;
+        Mat3::new([
+            m[0], m[1], m[2],
+            m[4], m[5], m[6],
+            m[8], m[9], m[10]
+        ])
+    }
+
+    // Back into a Mat4 that doesn't translate
+    pub fn to_mat4(self) -> Mat4 {
+        let m = &self.matrix;
+        Mat4::new([
+            m[0], m[1], m[2], 0.0,
+            m[3], m[4], m[5], 0.0,
+            m[6], m[7], m[8], 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    // The same rotation as Quaternion::to_matrix, without the parts of the Mat4 that don't rotate
+    pub fn from_quaternion(quaternion: &Quaternion) -> Mat3 {
+        let (x, y, z, w) = (quaternion.v.x, quaternion.v.y, quaternion.v.z, quaternion.w);
+
+        Mat3::new([
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + z * w), 2.0 * (x * z - y * w),
+            2.0 * (x * y - z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + x * w),
+            2.0 * (x * z + y * w), 2.0 * (y * z - x * w), 1.0 - 2.0 * (x * x + y * y)
+        ])
+    }
+
+    /*
+        A rotation around an axis going through the origin (Rodrigues' rotation formula).
+        The axis doesn't have to be normalized, but it must have a length.
+        Positive angles turn the same way as Mat4::rotate_x/y/z do around their axes.
+    */
+    pub fn from_axis_angle(axis: &Vector3, angle_in_radians: f32) -> Mat3 {
+        let a = axis.normalized();
+        let (sin, cos) = angle_in_radians.sin_cos();
+        let t = 1.0 - cos;
+
+        Mat3::new([
+            t * a.x * a.x + cos, t * a.x * a.y + sin * a.z, t * a.x * a.z - sin * a.y,
+            t * a.x * a.y - sin * a.z, t * a.y * a.y + cos, t * a.y * a.z + sin * a.x,
+            t * a.x * a.z + sin * a.y, t * a.y * a.z - sin * a.x, t * a.z * a.z + cos
+        ])
+    }
+
+    pub fn mul(&self, mat: &Mat3) -> Mat3 {
+        let mut matrix = [0.0; 9];
+        for row in 0..3 {
+            for column in 0..3 {
+                matrix[row * 3 + column] = (0..3).map(|i| self.matrix[row * 3 + i] * mat.matrix[i * 3 + column]).sum();
+            }
+        }
+
+        Mat3::new(matrix)
+    }
+
+    pub fn mul_row(&self, row: &Vector3) -> Vector3 {
+        let m = &self.matrix;
+        Vector3::new(
+            row.x * m[0] + row.y * m[3] + row.z * m[6],
+            row.x * m[1] + row.y * m[4] + row.z * m[7],
+            row.x * m[2] + row.y * m[5] + row.z * m[8])
+    }
+
+    pub fn get_transposed(&self) -> Mat3 {
+        let m = &self.matrix;
+        Mat3::new([
+            m[0], m[3], m[6],
+            m[1], m[4], m[7],
+            m[2], m[5], m[8]
+        ])
+    }
+
+    // The triple product of the rows: how much the matrix scales volumes, negative if it mirrors
+    pub fn determinant(&self) -> f32 {
+        let row0 = Vector3::new(self.matrix[0], self.matrix[1], self.matrix[2]);
+        let row1 = Vector3::new(self.matrix[3], self.matrix[4], self.matrix[5]);
+        let row2 = Vector3::new(self.matrix[6], self.matrix[7], self.matrix[8]);
+
+        row0.dot(&row1.cross(&row2))
+    }
+
+    /*
+        The columns of the inverse are the cross products of the rows (the cofactors), divided by the determinant.
+        None for matrices that squash space flat.
+    */
+    pub fn inverse(&self) -> Option<Mat3> {
+        let row0 = Vector3::new(self.matrix[0], self.matrix[1], self.matrix[2]);
+        let row1 = Vector3::new(self.matrix[3], self.matrix[4], self.matrix[5]);
+        let row2 = Vector3::new(self.matrix[6], self.matrix[7], self.matrix[8]);
+
+        let column0 = row1.cross(&row2);
+        let column1 = row2.cross(&row0);
+        let column2 = row0.cross(&row1);
+
+        let determinant = row0.dot(&column0);
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        Some(Mat3::new([
+            column0.x * inverse_determinant, column1.x * inverse_determinant, column2.x * inverse_determinant,
+            column0.y * inverse_determinant, column1.y * inverse_determinant, column2.y * inverse_determinant,
+            column0.z * inverse_determinant, column1.z * inverse_determinant, column2.z * inverse_determinant
+        ]))
+    }
+
+    /*
+        The matrix to transform normals with, for the given model matrix: the inverse transpose of its 3x3 part
+        (see Mat4::inverse_transpose for why). The normals still need normalizing after, if the model matrix scales.
+        None if the model matrix squashes space flat.
+    */
+    pub fn normal_matrix(model: &Mat4) -> Option<Mat3> {
+        Some(Mat3::from_mat4(model).inverse()?.get_transposed())
+    }
+
+    /*
+        For uploading to a float3x3 in an HLSL constant buffer.
+        HLSL expects matrices in column major order by default (hence the transpose, as with Mat4),
+        and every column of a matrix in a constant buffer starts on a new 16 byte register,
+        so each column is padded with a zero to 4 floats.
+    */
+    pub fn get_padded_column_major_value(&self) -> [f32; 12] {
+        let m = &self.matrix;
+        [
+            m[0], m[3], m[6], 0.0,
+            m[1], m[4], m[7], 0.0,
+            m[2], m[5], m[8], 0.0
+        ]
+    }
+}
+
+impl ops::Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, other: Mat3) -> Mat3 {
+        Mat3::mul(&self, &other)
+    }
+}
+
+impl ops::Mul<Mat3> for Vector3 {
+    type Output = Vector3;
+
+    fn mul(self, mat: Mat3) -> Vector3 {
+        mat.mul_row(&self)
+    }
+}
+
+impl ops::Index<usize> for Mat3 {
+    type Output = [f32; 3];
+
+    fn index(&self, row: usize) -> &[f32; 3] {
+        self.matrix[row * 3..row * 3 + 3].try_into().unwrap()
+    }
+}
+
+impl ops::IndexMut<usize> for Mat3 {
+    fn index_mut(&mut self, row: usize) -> &mut [f32; 3] {
+        (&mut self.matrix[row * 3..row * 3 + 3]).try_into().unwrap()
+    }
+}
+
+// 2x2 matrices, for transforms in 2D (like UVs), with the same conventions as Mat3 and Mat4
+#[derive(Clone, Copy, PartialEq)]
+pub struct Mat2 {
+    pub matrix: [f32; 4]
+}
+
+impl Default for Mat2 {
+    fn default() -> Self { Mat2::identity() }
+}
+
+impl fmt::Debug for Mat2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:?}", Vector2::new(self.matrix[0], self.matrix[1]))?;
+        writeln!(f, "{:?}", Vector2::new(self.matrix[2], self.matrix[3]))
+    }
+}
+
+impl Mat2 {
+    pub fn new(matrix: [f32; 4]) -> Mat2 {
+        Mat2 { matrix }
+    }
+
+    pub fn identity() -> Mat2 {
+        Mat2::new([
+            1.0, 0.0,
+            0.0, 1.0
+        ])
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> f32 {
+        self.matrix[(x + 2 * y) as usize]
+    }
+
+    // Turns +X towards +Y, like Mat4::rotate_z does in the XY plane
+    pub fn rotate(rad: f32) -> Mat2 {
+        Mat2::new([
+            rad.cos(), rad.sin(),
+            -rad.sin(), rad.cos()
+        ])
+    }
+
+    pub fn scale(scale: &Vector2) -> Mat2 {
+        Mat2::new([
+            scale.x, 0.0,
+            0.0, scale.y
+        ])
+    }
+
+    // The upper left 2x2 part of the matrix
+    pub fn from_mat4(mat: &Mat4) -> Mat2 {
+        Mat2::new([mat.matrix[0], mat.matrix[1], mat.matrix[4], mat.matrix[5]])
+    }
+
+    // Into a Mat4 that leaves Z alone and doesn't translate
+    pub fn to_mat4(self) -> Mat4 {
+        let m = &self.matrix;
+        Mat4::new([
+            m[0], m[1], 0.0, 0.0,
+            m[2], m[3], 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    pub fn mul(&self, mat: &Mat2) -> Mat2 {
+        let (a, b) = (&self.matrix, &mat.matrix);
+        Mat2::new([
+            a[0] * b[0] + a[1] * b[2], a[0] * b[1] + a[1] * b[3],
+            a[2] * b[0] + a[3] * b[2], a[2] * b[1] + a[3] * b[3]
+        ])
+    }
+
+    pub fn mul_row(&self, row: &Vector2) -> Vector2 {
+        let m = &self.matrix;
+        Vector2::new(row.x * m[0] + row.y * m[2], row.x * m[1] + row.y * m[3])
+    }
+
+    pub fn get_transposed(&self) -> Mat2 {
+        let m = &self.matrix;
+        Mat2::new([m[0], m[2], m[1], m[3]])
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.matrix[0] * self.matrix[3] - self.matrix[1] * self.matrix[2]
+    }
+
+    // Swap the diagonal, negate the rest, divide by the determinant. None for matrices that squash the plane to a line.
+    pub fn inverse(&self) -> Option<Mat2> {
+        let determinant = self.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+
+        let m = &self.matrix;
+        let inverse_determinant = 1.0 / determinant;
+        Some(Mat2::new([
+            m[3] * inverse_determinant, -m[1] * inverse_determinant,
+            -m[2] * inverse_determinant, m[0] * inverse_determinant
+        ]))
+    }
+}
+
+impl ops::Mul for Mat2 {
+    type Output = Mat2;
+
+    fn mul(self, other: Mat2) -> Mat2 {
+        Mat2::mul(&self, &other)
+    }
+}
+
+impl ops::Mul<Mat2> for Vector2 {
+    type Output = Vector2;
+
+    fn mul(self, mat: Mat2) -> Vector2 {
+        mat.mul_row(&self)
+    }
+}
+
+impl ops::Index<usize> for Mat2 {
+    type Output = [f32; 2];
+
+    fn index(&self, row: usize) -> &[f32; 2] {
+        self.matrix[row * 2..row * 2 + 2].try_into().unwrap()
+    }
+}
+
+impl ops::IndexMut<usize> for Mat2 {
+    fn index_mut(&mut self, row: usize) -> &mut [f32; 2] {
+        (&mut self.matrix[row * 2..row * 2 + 2]).try_into().unwrap()
+    }
+}
+
+/*
+    Which axis an Euler rotation turns around first.
+    Xyz means: turn around X, then around Y, then around Z, all three around the fixed axes of the parent space.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EulerOrder {
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+impl EulerOrder {
+    // The indices of the axes, in the order they're turned around
+    fn axes(&self) -> (usize, usize, usize) {
+        match self {
+            EulerOrder::Xyz => (0, 1, 2),
+            EulerOrder::Xzy => (0, 2, 1),
+            EulerOrder::Yxz => (1, 0, 2),
+            EulerOrder::Yzx => (1, 2, 0),
+            EulerOrder::Zxy => (2, 0, 1),
+            EulerOrder::Zyx => (2, 1, 0)
+        }
+    }
+}
+
+/*
+    Rotations are unit quaternions: w = cos(angle / 2), v = sin(angle / 2) * axis.
+    Positive angles turn the same way as Mat4::rotate_x/y/z do around their axes.
+
+    Multiplying follows the usual (Hamilton) convention: a * b turns by b first, and then by a.
+    That's the other way around from Mat4, where a.mul(&b) applies a first, because Mat4s work on row vectors.
+    So (a * b).to_matrix() is the same as b.to_matrix().mul(&a.to_matrix()).
+*/
+#[derive(Copy, Clone, PartialEq)]
+pub struct Quaternion {
+    pub w: f32,
+    pub v: Vector3
+}
+
+// The identity, which doesn't rotate at all
+impl Default for Quaternion {
+    fn default() -> Self { Quaternion::identity() }
+}
+
+impl fmt::Debug for Quaternion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Quaternion(w: {}, x: {}, y: {}, z: {})", self.w, self.v.x, self.v.y, self.v.z)
+    }
+}
+
+impl Quaternion {
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Quaternion {
+        Quaternion {
+            w,
+            v: Vector3::new(x, y, z)
+        }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // [x, y, z, w], the order glTF stores them in
+    pub fn from_array(array: &[f32; 4]) -> Quaternion {
+        Quaternion::new(array[3], array[0], array[1], array[2])
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.v.x, self.v.y, self.v.z, self.w]
+    }
+
+    pub fn set_rotation(&mut self, axis: Vector3, angle_in_radians: f32)
+    {
+        let angle_in_radians = angle_in_radians / 2f32;
+
+        self.w = angle_in_radians.cos();
+        self.v = Vector3::new(
+            angle_in_radians.sin() * axis.x,
+            angle_in_radians.sin() * axis.y,
+            angle_in_radians.sin() * axis.z);
+    }
+
+    // The axis doesn't have to be normalized, but it must have a length
+    pub fn from_axis_angle(axis: &Vector3, angle_in_radians: f32) -> Quaternion {
+        let (sin, cos) = (angle_in_radians / 2.0).sin_cos();
+        Quaternion { w: cos, v: axis.normalized() * sin }
+    }
+
+    /*
+        The axis and angle (between 0 and 2 PI) the quaternion turns by.
+        The identity doesn't turn around any axis in particular, X is as good as any other.
+    */
+    pub fn to_axis_angle(self) -> (Vector3, f32) {
+        let q = self.normalized();
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+
+        match q.v.try_normalize() {
+            Some(axis) => (axis, angle),
+            None => (Vector3::new(1.0, 0.0, 0.0), 0.0)
+        }
+    }
+
+    /*
+        Angles (in radians) around X, Y and Z, turned around in the given order.
+
+        The three turns are around the fixed axes of the parent space (extrinsic). Turning around them in the
+        opposite order, around the axes as they've been turned by the turns before (intrinsic), ends up the same,
+        so from_euler(angles, EulerOrder::Zxy) is also yaw around Y, then pitch around the turned X, then roll around the turned Z.
+    */
+    pub fn from_euler(angles: &Vector3, order: EulerOrder) -> Quaternion {
+        let (first, second, third) = order.axes();
+        let turn = |axis: usize| {
+            let mut unit_axis = Vector3::zero();
+            unit_axis[axis] = 1.0;
+            Quaternion::from_axis_angle(&unit_axis, angles[axis])
+        };
+
+        turn(third) * turn(second) * turn(first)
+    }
+
+    /*
+        The opposite of from_euler. The angle around the second axis is between -PI/2 and PI/2, the others between -PI and PI.
+
+        When the second angle is (close to) +-PI/2 the first and third axes line up (gimbal lock),
+        and only their sum (or difference) matters. Then it's all put in the first angle, and the third is 0.
+    */
+    pub fn to_euler(self, order: EulerOrder) -> Vector3 {
+        let (i, j, k) = order.axes();
+        // Xyz, Yzx and Zxy are the even orders, where the signs work out like for Xyz
+        let sign = if (j + 3 - i) % 3 == 1 { 1.0 } else { -1.0 };
+
+        // m(row, column) is the matrix that works on column vectors, the transpose of the one to_matrix gives
+        let rotation = Mat3::from_quaternion(&self.normalized());
+        let m = |row: usize, column: usize| rotation.matrix[column * 3 + row];
+
+        let mut angles = Vector3::zero();
+        let sin_second = (-sign * m(k, i)).clamp(-1.0, 1.0);
+        angles[j] = sin_second.asin();
+
+        if sin_second.abs() < 0.99999 {
+            angles[i] = (sign * m(k, j)).atan2(m(k, k));
+            angles[k] = (sign * m(j, i)).atan2(m(i, i));
+        } else {
+            angles[i] = (-sign * m(j, k)).atan2(m(j, j));
+            angles[k] = 0.0;
+        }
+
+        angles
+    }
+
+    // Only the rotation of the matrix is used, it mustn't be scaled
+    pub fn from_matrix(mat: &Mat4) -> Quaternion {
+        Quaternion::from_mat3(&Mat3::from_mat4(mat))
+    }
+
+    /*
+        Every element on the diagonal gives one of the components, but taking the square root of something close to 0
+        loses a lot of precision. So the largest one is picked, and the others are worked out from the elements off the diagonal.
+    */
+    pub fn from_mat3(mat: &Mat3) -> Quaternion {
+        // m(row, column) is the matrix that works on column vectors, the transpose of mat
+        let m = |row: usize, column: usize| mat.matrix[column * 3 + row];
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+
+        let quaternion = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(0.25 * s, (m(2, 1) - m(1, 2)) / s, (m(0, 2) - m(2, 0)) / s, (m(1, 0) - m(0, 1)) / s)
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = (1.0 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2.0;
+            Quaternion::new((m(2, 1) - m(1, 2)) / s, 0.25 * s, (m(0, 1) + m(1, 0)) / s, (m(0, 2) + m(2, 0)) / s)
+        } else if m(1, 1) > m(2, 2) {
+            let s = (1.0 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2.0;
+            Quaternion::new((m(0, 2) - m(2, 0)) / s, (m(0, 1) + m(1, 0)) / s, 0.25 * s, (m(1, 2) + m(2, 1)) / s)
+        } else {
+            let s = (1.0 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2.0;
+            Quaternion::new((m(1, 0) - m(0, 1)) / s, (m(0, 2) + m(2, 0)) / s, (m(1, 2) + m(2, 1)) / s, 0.25 * s)
+        };
+
+        quaternion.normalized()
+    }
+
+    /*
+        Turns +Z (where cameras and models look) towards forward, keeping +Y as close to up as it can.
+        None if forward has no length, or points the same way as up, as then there's no telling which way is up.
+    */
+    pub fn look_rotation(forward: &Vector3, up: &Vector3) -> Option<Quaternion> {
+        let z = forward.try_normalize()?;
+        let x = up.cross(&z).try_normalize()?;
+        let y = z.cross(&x);
+
+        Some(Quaternion::from_mat3(&Mat3::new([
+            x.x, x.y, x.z,
+            y.x, y.y, y.z,
+            z.x, z.y, z.z
+        ])))
+    }
+
+    /*
+        The smallest rotation that turns the direction of from into the direction of to.
+
+        When they point in opposite directions, any axis perpendicular to them works, and one is picked.
+        None if one of them has no length.
+    */
+    pub fn shortest_arc(from: &Vector3, to: &Vector3) -> Option<Quaternion> {
+        let from = from.try_normalize()?;
+        let to = to.try_normalize()?;
+        let cos = from.dot(&to);
+
+        if cos < -0.999999 {
+            let axis = Vector3::new(1.0, 0.0, 0.0).cross(&from).try_normalize()
+                .unwrap_or_else(|| Vector3::new(0.0, 1.0, 0.0).cross(&from).normalized());
+            return Some(Quaternion::from_axis_angle(&axis, std::f32::consts::PI));
+        }
+
+        // Half way between the identity and the rotation by twice the angle, which is (cos, from x to)
+        Some(Quaternion { w: 1.0 + cos, v: from.cross(&to) }.normalized())
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f32 {
+        self.w * other.w + self.v.dot(&other.v)
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    // Rounding errors add up when multiplying a lot of rotations together, normalizing now and then keeps them rotations
+    pub fn normalized(&self) -> Quaternion {
+        let length = self.length();
+        if length < f32::MIN_POSITIVE {
+            return Quaternion::identity();
+        }
+
+        Quaternion { w: self.w / length, v: self.v / length }
+    }
+
+    // For unit quaternions the conjugate is also the inverse, the same rotation turned the other way
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion { w: self.w, v: -self.v }
+    }
+
+    // None for the zero quaternion
+    pub fn inverse(&self) -> Option<Quaternion> {
+        let length_squared = self.dot(self);
+        if length_squared < f32::MIN_POSITIVE {
+            return None;
+        }
+
+        let conjugate = self.conjugate();
+        Some(Quaternion { w: conjugate.w / length_squared, v: conjugate.v / length_squared })
+    }
+
+    // The same as q * (0, vec) * q^-1 for a unit quaternion, without working out the parts that end up 0
+    pub fn rotate(&self, vec: &Vector3) -> Vector3 {
+        let t = self.v.cross(vec) * 2.0;
+        *vec + t * self.w + self.v.cross(&t)
+    }
+
+    /*
+        Both q and -q are the same rotation, but interpolating between them is a full turn.
+        Flipping to to the same side as from makes interpolation take the short way round.
+    */
+    fn closest_to(&self, from: &Quaternion) -> Quaternion {
+        if self.dot(from) < 0.0 { -*self } else { *self }
+    }
+
+    /*
+        Straight interpolation, normalized back onto the sphere.
+        Cheap, and takes the same path as slerp, but doesn't turn at a constant speed (it's fastest in the middle).
+    */
+    pub fn nlerp(&self, to: &Quaternion, t: f32) -> Quaternion {
+        let to = to.closest_to(self);
+        Quaternion { w: self.w + (to.w - self.w) * t, v: self.v.lerp(&to.v, t) }.normalized()
+    }
+
+    // Interpolation along the sphere, turning at a constant speed
+    pub fn slerp(&self, to: &Quaternion, t: f32) -> Quaternion {
+        let to = to.closest_to(self);
+        let cos = self.dot(&to).min(1.0);
+
+        // Too close together to tell the difference, and sin(angle) below would be close to 0
+        if cos > 0.9995 {
+            return self.nlerp(&to, t);
+        }
+
+        let angle = cos.acos();
+        let from_weight = ((1.0 - t) * angle).sin() / angle.sin();
+        let to_weight = (t * angle).sin() / angle.sin();
+
+        Quaternion { w: self.w * from_weight + to.w * to_weight, v: self.v * from_weight + to.v * to_weight }.normalized()
+    }
+
+    /*
+        Smooth interpolation through a sequence of rotations (spherical cubic), between current and next.
+        current_control and next_control come from squad_control_point, for each of the rotations in the sequence.
+
+        Without control points slerp goes through the rotations too, but changes the turning speed abruptly at every one of them.
+    */
+    pub fn squad(current: &Quaternion, current_control: &Quaternion, next_control: &Quaternion, next: &Quaternion, t: f32) -> Quaternion {
+        let along_rotations = slerp_no_flip(current, next, t);
+        let along_controls = slerp_no_flip(current_control, next_control, t);
+        slerp_no_flip(&along_rotations, &along_controls, 2.0 * t * (1.0 - t))
+    }
+
+    /*
+        The control point for current, going from previous to next (use current itself for the missing neighbour at either end).
+        The rotations in the sequence should all be unit quaternions, on the same side as their neighbours (see closest_to).
+    */
+    pub fn squad_control_point(previous: &Quaternion, current: &Quaternion, next: &Quaternion) -> Quaternion {
+        let inverse = current.conjugate();
+        let to_next = (inverse * *next).ln();
+        let to_previous = (inverse * *previous).ln();
+
+        let sum = to_next.v + to_previous.v;
+        *current * Quaternion { w: 0.0, v: sum * -0.25 }.exp()
+    }
+
+    // The logarithm of a unit quaternion: (0, axis * angle / 2)
+    fn ln(&self) -> Quaternion {
+        let half_angle = self.w.clamp(-1.0, 1.0).acos();
+        match self.v.try_normalize() {
+            Some(axis) => Quaternion { w: 0.0, v: axis * half_angle },
+            None => Quaternion { w: 0.0, v: Vector3::zero() }
+        }
+    }
+
+    // The opposite of ln, for quaternions with a w of 0
+    fn exp(&self) -> Quaternion {
+        let half_angle = self.v.length();
+        match self.v.try_normalize() {
+            Some(axis) => Quaternion { w: half_angle.cos(), v: axis * half_angle.sin() },
+            None => Quaternion::identity()
+        }
+    }
+
+    pub fn to_matrix(self) -> Mat4 {
+        Mat3::from_quaternion(&self).to_mat4()
+    }
+}
+
+// squad has to interpolate between the control points as they are, flipping one of them would change the curve
+fn slerp_no_flip(from: &Quaternion, to: &Quaternion, t: f32) -> Quaternion {
+    let cos = from.dot(to).clamp(-1.0, 1.0);
+    if cos.abs() > 0.9995 {
+        return Quaternion { w: from.w + (to.w - from.w) * t, v: from.v.lerp(&to.v, t) }.normalized();
+    }
+
+    let angle = cos.acos();
+    let from_weight = ((1.0 - t) * angle).sin() / angle.sin();
+    let to_weight = (t * angle).sin() / angle.sin();
+
+    Quaternion { w: from.w * from_weight + to.w * to_weight, v: from.v * from_weight + to.v * to_weight }
+}
+
+impl ops::Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * other.w - self.v.dot(&other.v),
+            v: other.v * self.w + self.v * other.w + self.v.cross(&other.v)
+        }
+    }
+}
+
+impl ops::MulAssign for Quaternion {
+    fn mul_assign(&mut self, other: Quaternion) {
+        *self = *self * other;
+    }
+}
+
+impl ops::Neg for Quaternion {
+    type Output = Quaternion;
+
+    fn neg(self) -> Quaternion {
+        Quaternion { w: -self.w, v: -self.v }
+    }
+}
+
+/*
+    Where something is, which way it's turned and how large it is, relative to its parent.
+    Applied in that order to points: first scaled, then rotated, then translated, like the matrix
+    Mat4::scale(&scale).mul(&rotation.to_matrix()).mul(&Mat4::translate(&translation)).
+
+    Keeping the three apart rather than in a Mat4 makes it easy to change one of them,
+    and to interpolate between transforms (which doesn't work with matrices).
+
+    A transform scaled differently along its axes, under a rotation, shears its children.
+    A shear can't be expressed as a Transform, so combining and inverting transforms is only exact
+    when that doesn't happen (see mul). Mat4 is still the way to go for whole hierarchies.
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3
+}
+
+impl Default for Transform {
+    fn default() -> Self { Transform::identity() }
+}
+
+impl Transform {
+    pub fn new(translation: Vector3, rotation: Quaternion, scale: Vector3) -> Transform {
+        Transform { translation, rotation, scale }
+    }
+
+    pub fn identity() -> Transform {
+        Transform::new(Vector3::zero(), Quaternion::identity(), Vector3::new(1.0, 1.0, 1.0))
     }
 
-    pub fn set_rotation(&mut self, axis: Vector3, angle_in_radians: f32)
-    {
-        let angle_in_radians = angle_in_radians / 2f32;
+    pub fn from_translation(translation: Vector3) -> Transform {
+        Transform { translation, ..Transform::identity() }
+    }
 
-        self.w = angle_in_radians.cos();
-        self.v = Vector3::new(
-            angle_in_radians.sin() * axis.x,
-            angle_in_radians.sin() * axis.y,
-            angle_in_radians.sin() * axis.z);
+    pub fn to_matrix(self) -> Mat4 {
+        let s = self.scale;
+        let mut matrix = Mat3::from_quaternion(&self.rotation).to_mat4();
+
+        // Scaling first is the same as scaling the rows of the rotation
+        for (row, scale) in [s.x, s.y, s.z].iter().enumerate() {
+            for column in 0..3 {
+                matrix.matrix[row * 4 + column] *= scale;
+            }
+        }
+        matrix.matrix[12] = self.translation.x;
+        matrix.matrix[13] = self.translation.y;
+        matrix.matrix[14] = self.translation.z;
+
+        matrix
     }
 
-    // TODO:
-    //  Currently using standard formula for quaternion cross product.
-    //  This means that you have to remember to apply quaternions "backwards",
-    //  From "right to left".
-    //  That is, if you want to multiply two quaternions q1 * q2,
-    //  This function call would have to look like: q2.cross(&q1)
-    pub fn cross(&self, other: &Quaternion) -> Quaternion {
-        let v1 = self.v;
-        let v2 = other.v;
-
-        let mut v3 = v2.mul(self.w);
-        v3 = v3.add(&v1.mul(other.w));
-        v3 = v3.add(&v2.cross(&v1));
-
-        Quaternion::new(
-            self.w * other.w - v1.dot(&v2),
-            v3.x,
-            v3.y,
-            v3.z)
-    }
-
-    pub fn to_matrix(&self) -> Mat4 {
-        let q = Vector4::new(self.v.x, self.v.y, self.v.z, self.w);
-
-        let m11 = 1.0 - 2.0 * q.y.powf(2.0) - 2.0 * q.z.powf(2.0);
-        let m12 = 2.0 * q.x * q.y + 2.0 * q.z * q.w;
-        let m13 = 2.0 * q.x * q.z - 2.0 * q.y * q.w;
-        let m14 = 0.0;
-
-        let m21 = 2.0 * q.x * q.y - 2.0 * q.z * q.w;
-        let m22 = 1.0 - 2.0 * q.x.powf(2.0) - 2.0 * q.z.powf(2.0);
-        let m23 = 2.0 * q.y * q.z + 2.0 * q.x * q.w;
-        let m24 = 0.0;
-
-        let m31 = 2.0 * q.x * q.z + 2.0 * q.y * q.w;
-        let m32 = 2.0 * q.y * q.z - 2.0 * q.x * q.w;
-        let m33 = 1.0 - 2.0 * q.x.powf(2.0) - 2.0 * q.y.powf(2.0);
-        let m34 = 0.0;
-
-        let m41 = 0.0;
-        let m42 = 0.0;
-        let m43 = 0.0;
-        let m44 = 1.0;
-
-        let mut m = Mat4::new([
-            m11, m12, m13, m14,
-            m21, m22, m23, m24,
-            m31, m32, m33, m34,
-            m41, m42, m43, m44
-        ]);
+    /*
+        Splits a matrix back into translation, rotation and scale.
 
-        m
+        The scale along each axis is the length of the matching row. A matrix that mirrors (a negative determinant)
+        gets a negative X scale. Mirroring along another axis is the same as mirroring along X and turning half way around,
+        so the rotation makes up for it, and the result is the same matrix.
+        Shear can't be expressed, and is lost. None if the matrix squashes space flat along an axis.
+    */
+    pub fn from_matrix(mat: &Mat4) -> Option<Transform> {
+        let mut rows = [
+            Vector3::new(mat.get(0, 0), mat.get(1, 0), mat.get(2, 0)),
+            Vector3::new(mat.get(0, 1), mat.get(1, 1), mat.get(2, 1)),
+            Vector3::new(mat.get(0, 2), mat.get(1, 2), mat.get(2, 2))
+        ];
+
+        let mut scale = Vector3::new(rows[0].length(), rows[1].length(), rows[2].length());
+        if scale.x < f32::MIN_POSITIVE || scale.y < f32::MIN_POSITIVE || scale.z < f32::MIN_POSITIVE {
+            return None;
+        }
+
+        if rows[0].dot(&rows[1].cross(&rows[2])) < 0.0 {
+            scale.x = -scale.x;
+        }
+
+        for (index, row) in rows.iter_mut().enumerate() {
+            *row /= scale[index];
+        }
+
+        let rotation = Quaternion::from_mat3(&Mat3::new([
+            rows[0].x, rows[0].y, rows[0].z,
+            rows[1].x, rows[1].y, rows[1].z,
+            rows[2].x, rows[2].y, rows[2].z
+        ]));
+
+        Some(Transform::new(Vector3::new(mat.get(0, 3), mat.get(1, 3), mat.get(2, 3)), rotation, scale))
     }
 
-    pub fn Rotation(axis: Vector3, angle_in_radians: f32) -> Mat4 {
-        let angle = angle_in_radians / 2.0;
+    /*
+        self first, then other, like Mat4::mul. For a node in a hierarchy: local.mul(&parent) is where the node is in the parent's parent.
+
+        Exact as long as other is scaled the same along all axes, or self isn't rotated relative to it.
+        Otherwise the result would need a shear, and it's only close.
+    */
+    pub fn mul(&self, other: &Transform) -> Transform {
+        Transform {
+            translation: other.transform_point(&self.translation),
+            rotation: other.rotation * self.rotation,
+            scale: self.scale * other.scale
+        }
+    }
+
+    /*
+        The transform that undoes this one. Exact for transforms scaled the same along all axes (see mul).
+        None if the scale is 0 along an axis.
+    */
+    pub fn inverse(&self) -> Option<Transform> {
+        if self.scale.x == 0.0 || self.scale.y == 0.0 || self.scale.z == 0.0 {
+            return None;
+        }
 
-        let mut q = Vector4::new(
-            angle.sin() * axis.x,
-            angle.sin() * axis.y,
-            angle.sin() * axis.z,
-            angle.cos()
-        );
+        let rotation = self.rotation.conjugate();
+        let scale = Vector3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        let translation = rotation.rotate(&-self.translation) * scale;
 
-        q = q.normalize();
+        Some(Transform { translation, rotation, scale })
+    }
 
-        let m11 = 1.0 - 2.0 * q.y.powf(2.0) - 2.0 * q.z.powf(2.0);
-        let m12 = 2.0 * q.x * q.y + 2.0 * q.z * q.w;
-        let m13 = 2.0 * q.x * q.z - 2.0 * q.y * q.w;
-        let m14 = 0.0;
+    pub fn transform_point(&self, point: &Vector3) -> Vector3 {
+        self.rotation.rotate(&(*point * self.scale)) + self.translation
+    }
 
-        let m21 = 2.0 * q.x * q.y - 2.0 * q.z * q.w;
-        let m22 = 1.0 - 2.0 * q.x.powf(2.0) - 2.0 * q.z.powf(2.0);
-        let m23 = 2.0 * q.y * q.z + 2.0 * q.x * q.w;
-        let m24 = 0.0;
+    // Directions and offsets aren't moved by the translation
+    pub fn transform_vector(&self, vector: &Vector3) -> Vector3 {
+        self.rotation.rotate(&(*vector * self.scale))
+    }
 
-        let m31 = 2.0 * q.x * q.z + 2.0 * q.y * q.w;
-        let m32 = 2.0 * q.y * q.z - 2.0 * q.x * q.w;
-        let m33 = 1.0 - 2.0 * q.x.powf(2.0) - 2.0 * q.y.powf(2.0);
-        let m34 = 0.0;
+    // The opposite of transform_point. Unlike inverse, this is exact whatever the scale is.
+    pub fn inverse_transform_point(&self, point: &Vector3) -> Vector3 {
+        let unrotated = self.rotation.conjugate().rotate(&(*point - self.translation));
+        Vector3::new(unrotated.x / self.scale.x, unrotated.y / self.scale.y, unrotated.z / self.scale.z)
+    }
 
-        let m41 = 0.0;
-        let m42 = 0.0;
-        let m43 = 0.0;
-        let m44 = 1.0;
+    // Translation and scale in a straight line, rotation along the shortest way round at a constant speed
+    pub fn lerp(&self, to: &Transform, t: f32) -> Transform {
+        Transform {
+            translation: self.translation.lerp(&to.translation, t),
+            rotation: self.rotation.slerp(&to.rotation, t),
+            scale: self.scale.lerp(&to.scale, t)
+        }
+    }
+}
 
-        let m = Mat4::new([
-            m11, m12, m13, m14,
-            m21, m22, m23, m24,
-            m31, m32, m33, m34,
-            m41, m42, m43, m44
-        ]);
+impl ops::Mul for Transform {
+    type Output = Transform;
 
-        m
+    fn mul(self, other: Transform) -> Transform {
+        Transform::mul(&self, &other)
     }
 }
 
@@ -757,14 +2222,13 @@ mod tests {
     }
 
     #[test]
-    fn should_calculate_quat_crossproduct_correctly() {
+    fn should_calculate_quat_product_correctly() {
         let q1 = Quaternion::new(1.0, 2.0, 1.0, 4.0);
         let q2 = Quaternion::new(2.0, 6.0, 3.0, 2.0);
 
-        let cross = q2.cross(&q1);
-
-        println!("Quat is:");
-        println!("{}, {}, {}, {}", cross.w, cross.v.x, cross.v.y, cross.v.z);
+        assert_eq!(q1 * q2, Quaternion::new(-21.0, 0.0, 25.0, 10.0));
+        assert_eq!(Quaternion::default(), Quaternion::identity());
+        assert_eq!(Quaternion::identity() * q1, q1);
     }
 
     #[test]
@@ -778,7 +2242,703 @@ mod tests {
 
         let det = Mat4::determinant(&mat);
 
-        println!("det {}", det);
-        //assert!(det.eq(&25.0f32));
+        assert!(det.eq(&25.0f32));
+        assert_eq!(Mat4::identity().determinant(), 1.0);
+        assert_eq!(Mat4::uniform_scale(2.0).determinant(), 8.0);
+    }
+
+    #[test]
+    fn should_apply_vector_operators_component_wise() {
+        let a = Vector3::new(1.0, 2.0, 3.0);
+        let b = Vector3::new(4.0, -5.0, 6.0);
+
+        assert_eq!(a + b, Vector3::new(5.0, -3.0, 9.0));
+        assert_eq!(b - a, Vector3::new(3.0, -7.0, 3.0));
+        assert_eq!(a * 2.0, 2.0 * a);
+        assert_eq!(a * b, Vector3::new(4.0, -10.0, 18.0));
+        assert_eq!(b / 2.0, Vector3::new(2.0, -2.5, 3.0));
+        assert_eq!(-a, Vector3::new(-1.0, -2.0, -3.0));
+
+        let mut c = a;
+        c += b;
+        c -= a;
+        c *= 3.0;
+        c /= 3.0;
+        assert_eq!(c, b);
+
+        c[1] = 7.0;
+        assert_eq!((c[0], c[1], c[2]), (4.0, 7.0, 6.0));
+        assert_eq!(Vector2::new(1.0, 2.0) + Vector2::new(3.0, 4.0), Vector2::new(4.0, 6.0));
+        assert_eq!(Vector4::new(1.0, 2.0, 3.0, 4.0)[3], 4.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_panic_when_indexing_past_last_component() {
+        let _ = Vector2::new(1.0, 2.0)[2];
+    }
+
+    #[test]
+    fn should_interpolate_and_limit_vectors() {
+        let a = Vector3::new(0.0, 10.0, -4.0);
+        let b = Vector3::new(10.0, 0.0, 4.0);
+
+        assert_eq!(a.lerp(&b, 0.25), Vector3::new(2.5, 7.5, -2.0));
+        assert_eq!(a.min(&b), Vector3::new(0.0, 0.0, -4.0));
+        assert_eq!(a.max(&b), Vector3::new(10.0, 10.0, 4.0));
+        assert_eq!(Vector3::new(-5.0, 5.0, 0.5).clamp(&Vector3::zero(), &Vector3::new(1.0, 1.0, 1.0)), Vector3::new(0.0, 1.0, 0.5));
+        assert_eq!(a.abs(), Vector3::new(0.0, 10.0, 4.0));
+        assert_eq!(Vector2::new(1.0, 1.0).distance(&Vector2::new(4.0, 5.0)), 5.0);
+    }
+
+    #[test]
+    fn should_not_divide_by_zero_when_normalizing_zero_vector() {
+        assert_eq!(Vector3::zero().try_normalize(), None);
+        assert_eq!(Vector3::new(0.0, f32::MIN_POSITIVE / 2.0, 0.0).try_normalize(), None);
+        assert_eq!(Vector3::new(f32::INFINITY, 0.0, 0.0).try_normalize(), None);
+        assert_eq!(Vector3::new(0.0, 3.0, 4.0).try_normalize(), Some(Vector3::new(0.0, 0.6, 0.8)));
+    }
+
+    #[test]
+    fn should_split_vector_into_projection_and_rejection() {
+        let v = Vector3::new(3.0, 4.0, 5.0);
+        let onto = Vector3::new(0.0, 2.0, 0.0);
+
+        assert_eq!(v.project(&onto), Vector3::new(0.0, 4.0, 0.0));
+        assert_eq!(v.reject(&onto), Vector3::new(3.0, 0.0, 5.0));
+        assert_eq!(v.project(&Vector3::zero()), Vector3::zero());
+    }
+
+    #[test]
+    fn should_reflect_and_refract_off_surface() {
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let incoming = Vector3::new(1.0, -1.0, 0.0).normalized();
+
+        let reflected = incoming.reflect(&normal);
+        assert!((reflected - Vector3::new(1.0, 1.0, 0.0).normalized()).length() < 1e-6);
+
+        // Going straight through doesn't bend, whatever the ratio
+        assert_eq!(Vector3::new(0.0, -1.0, 0.0).refract(&normal, 1.0 / 1.33), Some(Vector3::new(0.0, -1.0, 0.0)));
+
+        // Into a denser medium, the direction bends towards the normal: sin(refracted) = eta * sin(incoming)
+        let refracted = incoming.refract(&normal, 1.0 / 1.33).unwrap();
+        assert!((refracted.length() - 1.0).abs() < 1e-6);
+        assert!((refracted.x - (1.0 / 1.33) * incoming.x).abs() < 1e-6);
+
+        // Out of water at a shallow angle, it can't get out at all
+        assert_eq!(Vector3::new(1.0, -0.2, 0.0).normalized().refract(&normal, 1.33), None);
+    }
+
+    #[test]
+    fn should_apply_matrix_operators() {
+        let translation = Mat4::translate(&Vector3::new(1.0, 2.0, 3.0));
+        let scale = Mat4::uniform_scale(2.0);
+
+        // Copy, so both can still be used after multiplying
+        let scale_then_translate = scale * translation;
+        assert_eq!(scale_then_translate, scale.mul(&translation));
+        assert_eq!(Vector4::new(1.0, 1.0, 1.0, 1.0) * scale_then_translate, Vector4::new(3.0, 4.0, 5.0, 1.0));
+
+        assert_eq!(scale_then_translate[3], [1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(scale_then_translate[0][0], 2.0);
+
+        let mut m = Mat4::identity();
+        m[3][1] = 5.0;
+        assert_eq!(m.get(1, 3), 5.0);
+
+        m *= scale;
+        assert_eq!(m, Mat4::identity() * Mat4::translate(&Vector3::new(0.0, 5.0, 0.0)) * scale);
+        assert_eq!(m - m, Mat4::identity() * 0.0);
+        assert_eq!(m + m, m * 2.0);
+        assert_eq!(-m + m, Mat4::identity() * 0.0);
+    }
+
+    /*
+        Property based tests: instead of checking a handful of hand picked matrices, I check that properties that should hold
+        for any matrix ("a matrix times its inverse is the identity") hold for lots of random ones.
+        Xorshift is a tiny pseudo random generator, seeded with a constant, so a failing test fails the same way every time.
+    */
+    struct Xorshift(u32);
+
+    impl Xorshift {
+        fn next_f32(&mut self, min: f32, max: f32) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            min + (self.0 as f32 / u32::MAX as f32) * (max - min)
+        }
+
+        fn next_matrix(&mut self) -> Mat4 {
+            let mut matrix = [0.0; 16];
+            matrix.iter_mut().for_each(|value| *value = self.next_f32(-10.0, 10.0));
+            Mat4::new(matrix)
+        }
+
+        fn next_rigid_transform(&mut self) -> Mat4 {
+            Mat4::rotate_x(self.next_f32(-std::f32::consts::PI, std::f32::consts::PI))
+                .mul(&Mat4::rotate_y(self.next_f32(-std::f32::consts::PI, std::f32::consts::PI)))
+                .mul(&Mat4::rotate_z(self.next_f32(-std::f32::consts::PI, std::f32::consts::PI)))
+                .mul(&Mat4::translate(&Vector3::new(self.next_f32(-100.0, 100.0), self.next_f32(-100.0, 100.0), self.next_f32(-100.0, 100.0))))
+        }
+
+        fn next_affine_transform(&mut self) -> Mat4 {
+            let scale = Vector3::new(self.next_f32(0.1, 5.0), self.next_f32(0.1, 5.0), self.next_f32(-5.0, -0.1));
+            Mat4::scale(&scale).mul(&self.next_rigid_transform())
+        }
+    }
+
+    fn assert_matrices_near(actual: &Mat4, expected: &Mat4, tolerance: f32) {
+        for (actual_value, expected_value) in actual.matrix.iter().zip(expected.matrix.iter()) {
+            assert!((actual_value - expected_value).abs() <= tolerance, "Expected\n{:?}but got\n{:?}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn should_give_identity_when_multiplying_matrix_by_its_inverse() {
+        let mut random = Xorshift(0x9E3779B9);
+        let mut tested = 0;
+
+        while tested < 500 {
+            let matrix = random.next_matrix();
+            // Nearly singular matrices lose too much precision in f32 for a fixed tolerance
+            if matrix.determinant().abs() < 1.0 {
+                continue;
+            }
+
+            let inverse = matrix.inverse().unwrap();
+            assert_matrices_near(&matrix.mul(&inverse), &Mat4::identity(), 1e-3);
+            assert_matrices_near(&inverse.mul(&matrix), &Mat4::identity(), 1e-3);
+            tested += 1;
+        }
+    }
+
+    #[test]
+    fn should_multiply_determinants_when_multiplying_matrices() {
+        let mut random = Xorshift(12345);
+
+        for _ in 0..200 {
+            let a = random.next_matrix();
+            let b = random.next_matrix();
+            let expected = a.determinant() * b.determinant();
+
+            assert!((a.mul(&b).determinant() - expected).abs() <= expected.abs() * 1e-3 + 1e-2);
+        }
+    }
+
+    #[test]
+    fn should_not_invert_singular_matrix() {
+        let flattened = Mat4::scale(&Vector3::new(1.0, 0.0, 1.0));
+
+        assert_eq!(flattened.determinant(), 0.0);
+        assert!(flattened.inverse().is_none());
+        assert!(flattened.inverse_affine().is_none());
+        assert!(flattened.inverse_transpose().is_none());
+    }
+
+    #[test]
+    fn should_match_general_inverse_when_inverting_affine_and_rigid_transforms() {
+        let mut random = Xorshift(0xDEADBEEF);
+
+        for _ in 0..200 {
+            let affine = random.next_affine_transform();
+            assert_matrices_near(&affine.inverse_affine().unwrap(), &affine.inverse().unwrap(), 1e-3);
+            assert_matrices_near(&affine.mul(&affine.inverse_affine().unwrap()), &Mat4::identity(), 1e-3);
+
+            let rigid = random.next_rigid_transform();
+            assert_matrices_near(&rigid.inverse_rigid(), &rigid.inverse().unwrap(), 1e-3);
+        }
+    }
+
+    #[test]
+    fn should_keep_normals_perpendicular_when_using_inverse_transpose() {
+        let mut random = Xorshift(777);
+
+        for _ in 0..200 {
+            let model = random.next_affine_transform();
+            let normal_matrix = model.inverse_transpose().unwrap();
+
+            // A tangent lying in the surface, and the normal of that surface
+            let tangent = Vector4::new(1.0, -1.0, 0.0, 0.0);
+            let normal = Vector4::new(1.0, 1.0, 2.0, 0.0);
+
+            let transformed_tangent = model.mul_row(&tangent);
+            let transformed_normal = normal_matrix.mul_row(&normal);
+
+            let cosine = transformed_tangent.dot(&transformed_normal) / (transformed_tangent.magnitude() * transformed_normal.magnitude());
+            assert!(cosine.abs() < 1e-4);
+            assert_eq!(transformed_normal.w, 0.0);
+        }
+    }
+
+    #[test]
+    fn should_convert_between_mat3_and_mat4() {
+        let transform = Mat4::rotate_y(0.7).mul(&Mat4::translate(&Vector3::new(1.0, 2.0, 3.0)));
+        let rotation = Mat3::from_mat4(&transform);
+
+        assert_eq!(rotation.to_mat4(), Mat4::rotate_y(0.7));
+        assert_eq!(Mat3::from_mat4(&rotation.to_mat4()), rotation);
+        assert_eq!(Mat2::from_mat4(&Mat4::rotate_z(0.3)), Mat2::rotate(0.3));
+        assert_eq!(Mat2::rotate(0.3).to_mat4(), Mat4::rotate_z(0.3));
+    }
+
+    #[test]
+    fn should_build_same_rotation_from_quaternion_axis_angle_and_mat4() {
+        let mut random = Xorshift(4242);
+
+        for _ in 0..100 {
+            let axis = Vector3::new(random.next_f32(-1.0, 1.0), random.next_f32(-1.0, 1.0), random.next_f32(-1.0, 1.0));
+            if axis.try_normalize().is_none() {
+                continue;
+            }
+            let angle = random.next_f32(-std::f32::consts::PI, std::f32::consts::PI);
+
+            let mut quaternion = Quaternion::default();
+            quaternion.set_rotation(axis.normalized(), angle);
+
+            let from_axis_angle = Mat3::from_axis_angle(&axis, angle);
+            assert_matrices_near(&from_axis_angle.to_mat4(), &quaternion.to_matrix(), 1e-5);
+            assert_matrices_near(&Mat3::from_quaternion(&quaternion).to_mat4(), &quaternion.to_matrix(), 1e-5);
+        }
+
+        assert_matrices_near(&Mat3::from_axis_angle(&Vector3::new(0.0, 2.0, 0.0), 0.5).to_mat4(), &Mat4::rotate_y(0.5), 1e-6);
+    }
+
+    #[test]
+    fn should_multiply_transpose_and_invert_mat3() {
+        let mut random = Xorshift(99);
+
+        for _ in 0..200 {
+            let a = Mat3::from_mat4(&random.next_affine_transform());
+            let b = Mat3::from_mat4(&random.next_affine_transform());
+
+            // Same results as going through Mat4
+            assert_matrices_near(&(a * b).to_mat4(), &a.to_mat4().mul(&b.to_mat4()), 1e-3);
+            assert_matrices_near(&a.get_transposed().to_mat4(), &a.to_mat4().get_transposed(), 0.0);
+            assert!((a.determinant() - a.to_mat4().determinant()).abs() <= a.determinant().abs() * 1e-4);
+            assert_matrices_near(&(a * a.inverse().unwrap()).to_mat4(), &Mat4::identity(), 1e-4);
+        }
+
+        let v = Vector3::new(1.0, 2.0, 3.0);
+        assert_eq!(v * Mat3::scale(&Vector3::new(2.0, 3.0, 4.0)), Vector3::new(2.0, 6.0, 12.0));
+        assert!(Mat3::scale(&Vector3::new(1.0, 1.0, 0.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn should_multiply_transpose_and_invert_mat2() {
+        let m = Mat2::new([1.0, 2.0, 3.0, 4.0]);
+
+        assert_eq!(m.determinant(), -2.0);
+        assert_eq!(m * m.inverse().unwrap(), Mat2::identity());
+        assert_eq!(m.get_transposed(), Mat2::new([1.0, 3.0, 2.0, 4.0]));
+        assert_eq!(m[1], [3.0, 4.0]);
+        assert_eq!(Vector2::new(1.0, 1.0) * m, Vector2::new(4.0, 6.0));
+        assert!(Mat2::new([1.0, 2.0, 2.0, 4.0]).inverse().is_none());
+
+        let quarter_turn = Vector2::new(1.0, 0.0) * Mat2::rotate(std::f32::consts::FRAC_PI_2);
+        assert!((quarter_turn - Vector2::new(0.0, 1.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn should_match_mat4_inverse_transpose_when_building_normal_matrix() {
+        let model = Mat4::scale(&Vector3::new(1.0, 4.0, 0.5)).mul(&Mat4::rotate_x(0.4)).mul(&Mat4::translate(&Vector3::new(5.0, 0.0, 1.0)));
+
+        let normal_matrix = Mat3::normal_matrix(&model).unwrap();
+
+        assert_matrices_near(&normal_matrix.to_mat4(), &model.inverse_transpose().unwrap(), 1e-5);
+        assert_eq!(Mat3::identity().get_padded_column_major_value(), [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        assert!(Mat3::normal_matrix(&Mat4::uniform_scale(0.0)).is_none());
+    }
+
+    impl Xorshift {
+        fn next_rotation(&mut self) -> Quaternion {
+            let axis = Vector3::new(self.next_f32(-1.0, 1.0), self.next_f32(-1.0, 1.0), self.next_f32(-1.0, 1.0));
+            let axis = axis.try_normalize().unwrap_or(Vector3::new(0.0, 1.0, 0.0));
+            Quaternion::from_axis_angle(&axis, self.next_f32(-std::f32::consts::PI, std::f32::consts::PI))
+        }
+
+        fn next_direction(&mut self) -> Vector3 {
+            Vector3::new(self.next_f32(-1.0, 1.0), self.next_f32(-1.0, 1.0), self.next_f32(-1.0, 1.0))
+                .try_normalize()
+                .unwrap_or(Vector3::new(0.0, 0.0, 1.0))
+        }
+    }
+
+    fn assert_same_rotation(actual: &Quaternion, expected: &Quaternion, tolerance: f32) {
+        // q and -q are the same rotation
+        assert!(actual.dot(expected).abs() >= 1.0 - tolerance, "Expected {:?} but got {:?}", expected, actual);
+    }
+
+    fn assert_vectors_near(actual: &Vector3, expected: &Vector3, tolerance: f32) {
+        assert!((*actual - *expected).length() <= tolerance, "Expected {:?} but got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn should_rotate_like_matrices_when_multiplying_quaternions() {
+        let mut random = Xorshift(7);
+
+        for _ in 0..200 {
+            let a = random.next_rotation();
+            let b = random.next_rotation();
+            let vec = Vector3::new(random.next_f32(-5.0, 5.0), random.next_f32(-5.0, 5.0), random.next_f32(-5.0, 5.0));
+
+            // b first, then a
+            assert_matrices_near(&(a * b).to_matrix(), &b.to_matrix().mul(&a.to_matrix()), 1e-5);
+
+            let rotated = a.to_matrix().mul_row(&Vector4::new(vec.x, vec.y, vec.z, 1.0));
+            assert_vectors_near(&a.rotate(&vec), &Vector3::new(rotated.x, rotated.y, rotated.z), 1e-4);
+
+            assert_same_rotation(&(a * a.conjugate()), &Quaternion::identity(), 1e-6);
+            // Not a unit quaternion, so the conjugate isn't the inverse
+            let scaled = Quaternion { w: a.w * 3.0, v: a.v * 3.0 };
+            assert_same_rotation(&(scaled * scaled.inverse().unwrap()), &Quaternion::identity(), 1e-5);
+        }
+
+        assert!(Quaternion::new(0.0, 0.0, 0.0, 0.0).inverse().is_none());
+        assert_vectors_near(&Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), std::f32::consts::FRAC_PI_2).rotate(&Vector3::new(0.0, 0.0, 1.0)), &Vector3::new(1.0, 0.0, 0.0), 1e-6);
+    }
+
+    #[test]
+    fn should_interpolate_along_shortest_path_when_slerping() {
+        let from = Quaternion::identity();
+        let to = Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 2.0);
+
+        assert_same_rotation(&from.slerp(&to, 0.0), &from, 1e-6);
+        assert_same_rotation(&from.slerp(&to, 1.0), &to, 1e-6);
+        assert_same_rotation(&from.slerp(&to, 0.25), &Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 0.5), 1e-6);
+        // -to is the same rotation, slerp still takes the short way
+        assert_same_rotation(&from.slerp(&-to, 0.25), &Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 0.5), 1e-6);
+
+        let halfway = from.nlerp(&to, 0.5);
+        assert_same_rotation(&halfway, &Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 1.0), 1e-6);
+        assert!((halfway.length() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn should_pass_through_rotations_when_squading() {
+        let mut random = Xorshift(1234);
+        let mut rotations: Vec<Quaternion> = (0..4).map(|_| random.next_rotation()).collect();
+        for i in 1..rotations.len() {
+            rotations[i] = rotations[i].closest_to(&rotations[i - 1]);
+        }
+
+        let controls: Vec<Quaternion> = (0..rotations.len())
+            .map(|i| Quaternion::squad_control_point(&rotations[i.saturating_sub(1)], &rotations[i], &rotations[(i + 1).min(rotations.len() - 1)]))
+            .collect();
+
+        for i in 0..rotations.len() - 1 {
+            let start = Quaternion::squad(&rotations[i], &controls[i], &controls[i + 1], &rotations[i + 1], 0.0);
+            let end = Quaternion::squad(&rotations[i], &controls[i], &controls[i + 1], &rotations[i + 1], 1.0);
+            assert_same_rotation(&start, &rotations[i], 1e-5);
+            assert_same_rotation(&end, &rotations[i + 1], 1e-5);
+
+            let middle = Quaternion::squad(&rotations[i], &controls[i], &controls[i + 1], &rotations[i + 1], 0.5);
+            assert!((middle.length() - 1.0).abs() < 1e-4);
+        }
+
+        // Along a single axis, at a steady speed, squad is the same as slerp
+        let steady: Vec<Quaternion> = (0..3).map(|i| Quaternion::from_axis_angle(&Vector3::new(1.0, 0.0, 0.0), i as f32 * 0.5)).collect();
+        let control = Quaternion::squad_control_point(&steady[0], &steady[1], &steady[2]);
+        assert_same_rotation(&control, &steady[1], 1e-6);
+    }
+
+    #[test]
+    fn should_convert_to_and_from_axis_angle() {
+        let axis = Vector3::new(1.0, 2.0, -2.0);
+        let (back_axis, angle) = Quaternion::from_axis_angle(&axis, 1.25).to_axis_angle();
+
+        assert_vectors_near(&back_axis, &axis.normalized(), 1e-6);
+        assert!((angle - 1.25).abs() < 1e-5);
+        assert_eq!(Quaternion::identity().to_axis_angle().1, 0.0);
+    }
+
+    #[test]
+    fn should_convert_to_and_from_euler_angles_in_every_order() {
+        let mut random = Xorshift(555);
+        let orders = [EulerOrder::Xyz, EulerOrder::Xzy, EulerOrder::Yxz, EulerOrder::Yzx, EulerOrder::Zxy, EulerOrder::Zyx];
+
+        for order in orders {
+            let (first, second, third) = order.axes();
+            for _ in 0..50 {
+                let mut angles = Vector3::zero();
+                angles[first] = random.next_f32(-3.1, 3.1);
+                angles[second] = random.next_f32(-1.5, 1.5);
+                angles[third] = random.next_f32(-3.1, 3.1);
+
+                let rotation = Quaternion::from_euler(&angles, order);
+                assert_vectors_near(&rotation.to_euler(order), &angles, 1e-3);
+            }
+
+            // Gimbal lock, the angles can't be told apart but the rotation still has to come out the same
+            let mut locked = Vector3::new(0.3, 0.3, 0.3);
+            locked[second] = std::f32::consts::FRAC_PI_2;
+            let rotation = Quaternion::from_euler(&locked, order);
+            assert_same_rotation(&Quaternion::from_euler(&rotation.to_euler(order), order), &rotation, 1e-5);
+        }
+
+        // Xyz turns around X first
+        let angles = Vector3::new(0.4, 0.5, 0.6);
+        let expected = Quaternion::from_axis_angle(&Vector3::new(0.0, 0.0, 1.0), 0.6)
+            * Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 0.5)
+            * Quaternion::from_axis_angle(&Vector3::new(1.0, 0.0, 0.0), 0.4);
+        assert_same_rotation(&Quaternion::from_euler(&angles, EulerOrder::Xyz), &expected, 1e-6);
+    }
+
+    #[test]
+    fn should_give_back_rotation_when_converting_from_matrix() {
+        let mut random = Xorshift(31337);
+
+        for _ in 0..200 {
+            let rotation = random.next_rotation();
+            assert_same_rotation(&Quaternion::from_matrix(&rotation.to_matrix()), &rotation, 1e-5);
+        }
+
+        // Half turns, where the trace is -1
+        for axis in [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)] {
+            let rotation = Quaternion::from_axis_angle(&axis, std::f32::consts::PI);
+            assert_same_rotation(&Quaternion::from_matrix(&rotation.to_matrix()), &rotation, 1e-6);
+        }
+
+        assert_same_rotation(&Quaternion::from_matrix(&Mat4::rotate_y(0.8)), &Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 0.8), 1e-6);
+    }
+
+    #[test]
+    fn should_turn_z_towards_forward_when_looking() {
+        let mut random = Xorshift(2024);
+        let up = Vector3::new(0.0, 1.0, 0.0);
+
+        for _ in 0..100 {
+            let forward = random.next_direction();
+            let rotation = Quaternion::look_rotation(&forward, &up).unwrap();
+
+            assert_vectors_near(&rotation.rotate(&Vector3::new(0.0, 0.0, 1.0)), &forward, 1e-5);
+            // The turned X stays level, so nothing rolls
+            assert!(rotation.rotate(&Vector3::new(1.0, 0.0, 0.0)).y.abs() < 1e-5);
+        }
+
+        assert!(Quaternion::look_rotation(&up, &up).is_none());
+        assert!(Quaternion::look_rotation(&Vector3::zero(), &up).is_none());
+    }
+
+    #[test]
+    fn should_turn_from_into_to_when_building_shortest_arc() {
+        let mut random = Xorshift(4711);
+
+        for _ in 0..100 {
+            let from = random.next_direction();
+            let to = random.next_direction();
+            let arc = Quaternion::shortest_arc(&(from * 3.0), &to).unwrap();
+
+            assert_vectors_near(&arc.rotate(&from), &to, 1e-4);
+            // The axis is perpendicular to both, so it's the smallest turn there is
+            assert!((arc.to_axis_angle().1 - from.dot(&to).clamp(-1.0, 1.0).acos()).abs() < 1e-3);
+
+            let opposite = Quaternion::shortest_arc(&from, &-from).unwrap();
+            assert_vectors_near(&opposite.rotate(&from), &-from, 1e-4);
+        }
+
+        assert!(Quaternion::shortest_arc(&Vector3::zero(), &Vector3::new(1.0, 0.0, 0.0)).is_none());
+    }
+
+    fn assert_projects_to(view_projection: &Mat4, point: Vector3, expected: Vector3) {
+        let projected = Mat4::project(&point, view_projection).unwrap();
+        assert_vectors_near(&projected, &expected, 1e-4);
+    }
+
+    #[test]
+    fn should_map_near_and_far_planes_to_depth_range_when_projecting() {
+        let (near, far) = (0.5, 5000.0);
+        // Square, so the edges of the view at distance d are at +-d * tan(fov / 2) = +-d
+        let fov = std::f32::consts::FRAC_PI_2;
+
+        let perspective = Mat4::projection(fov, 1.0, 1.0, near, far);
+        assert_projects_to(&perspective, Vector3::new(near, -near, near), Vector3::new(1.0, -1.0, 0.0));
+        assert_projects_to(&perspective, Vector3::new(0.0, far, far), Vector3::new(0.0, 1.0, 1.0));
+
+        let reversed = Mat4::projection_reversed_z(fov, 1.0, 1.0, near, far);
+        assert_projects_to(&reversed, Vector3::new(near, -near, near), Vector3::new(1.0, -1.0, 1.0));
+        assert_projects_to(&reversed, Vector3::new(0.0, far, far), Vector3::new(0.0, 1.0, 0.0));
+
+        let infinite = Mat4::projection_infinite(fov, 1.0, 1.0, near);
+        assert_projects_to(&infinite, Vector3::new(0.0, 0.0, near), Vector3::new(0.0, 0.0, 0.0));
+        let far_away = Mat4::project(&Vector3::new(0.0, 0.0, 1.0e4), &infinite).unwrap();
+        assert!(far_away.z < 1.0 && far_away.z > 0.999);
+
+        let reversed_infinite = Mat4::projection_reversed_z_infinite(fov, 1.0, 1.0, near);
+        assert_projects_to(&reversed_infinite, Vector3::new(0.0, 0.0, near), Vector3::new(0.0, 0.0, 1.0));
+        let far_away = Mat4::project(&Vector3::new(0.0, 0.0, 1.0e4), &reversed_infinite).unwrap();
+        assert!(far_away.z > 0.0 && far_away.z < 0.001);
+
+        // Behind the eye
+        assert!(Mat4::project(&Vector3::new(0.0, 0.0, -1.0), &perspective).is_none());
+    }
+
+    #[test]
+    fn should_map_view_box_to_normalized_device_box_when_projecting_off_center() {
+        let off_center = Mat4::projection_off_center(-1.0, 3.0, -2.0, 1.0, 1.0, 10.0);
+        assert_projects_to(&off_center, Vector3::new(-1.0, -2.0, 1.0), Vector3::new(-1.0, -1.0, 0.0));
+        assert_projects_to(&off_center, Vector3::new(30.0, 10.0, 10.0), Vector3::new(1.0, 1.0, 1.0));
+        // Centered, it's the same as the regular perspective
+        assert_matrices_near(&Mat4::projection_off_center(-1.0, 1.0, -1.0, 1.0, 1.0, 10.0), &Mat4::projection(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 1.0, 10.0), 1e-6);
+
+        let orthographic = Mat4::orthographic(20.0, 10.0, 1.0, 101.0);
+        assert_projects_to(&orthographic, Vector3::new(-10.0, 5.0, 1.0), Vector3::new(-1.0, 1.0, 0.0));
+        assert_projects_to(&orthographic, Vector3::new(10.0, -5.0, 101.0), Vector3::new(1.0, -1.0, 1.0));
+        assert_projects_to(&orthographic, Vector3::new(0.0, 0.0, 51.0), Vector3::new(0.0, 0.0, 0.5));
+
+        let orthographic_off_center = Mat4::orthographic_off_center(0.0, 100.0, -50.0, 0.0, -10.0, 10.0);
+        assert_projects_to(&orthographic_off_center, Vector3::new(0.0, -50.0, -10.0), Vector3::new(-1.0, -1.0, 0.0));
+        assert_projects_to(&orthographic_off_center, Vector3::new(100.0, 0.0, 10.0), Vector3::new(1.0, 1.0, 1.0));
+        assert_matrices_near(&Mat4::orthographic_off_center(-10.0, 10.0, -5.0, 5.0, 1.0, 101.0), &orthographic, 1e-6);
+    }
+
+    #[test]
+    fn should_put_target_in_front_of_eye_when_looking_at_it() {
+        let mut random = Xorshift(808);
+        let up = Vector3::new(0.0, 1.0, 0.0);
+
+        for _ in 0..100 {
+            let eye = random.next_direction() * 20.0;
+            let target = random.next_direction() * 5.0;
+            let view = Mat4::look_at(&eye, &target, &up).unwrap();
+
+            let target_in_view = view.mul_row(&Vector4::new(target.x, target.y, target.z, 1.0));
+            assert_vectors_near(&Vector3::new(target_in_view.x, target_in_view.y, target_in_view.z), &Vector3::new(0.0, 0.0, (target - eye).length()), 1e-3);
+            assert_vectors_near(&Vector3::new(view.inverse_rigid().get(0, 3), view.inverse_rigid().get(1, 3), view.inverse_rigid().get(2, 3)), &eye, 1e-3);
+
+            // The same orientation as turning +Z towards the target
+            let look = Quaternion::look_rotation(&(target - eye), &up).unwrap();
+            assert_matrices_near(&Mat3::from_mat4(&view).to_mat4(), &look.to_matrix().get_transposed(), 1e-4);
+        }
+
+        assert!(Mat4::look_at(&up, &up, &up).is_none());
+        assert!(Mat4::look_at(&Vector3::zero(), &up, &up).is_none());
+    }
+
+    #[test]
+    fn should_give_back_point_when_unprojecting_projected_point() {
+        let mut random = Xorshift(6060);
+        let view = Mat4::look_at(&Vector3::new(10.0, 20.0, -30.0), &Vector3::zero(), &Vector3::new(0.0, 1.0, 0.0)).unwrap();
+        let projections = [
+            Mat4::projection(1.0, 16.0, 9.0, 0.1, 5000.0),
+            Mat4::projection_reversed_z(1.0, 16.0, 9.0, 0.1, 5000.0),
+            Mat4::projection_infinite(1.0, 16.0, 9.0, 0.1),
+            Mat4::projection_reversed_z_infinite(1.0, 16.0, 9.0, 0.1),
+            Mat4::projection_off_center(-0.1, 0.05, -0.02, 0.08, 0.1, 5000.0),
+            Mat4::orthographic(100.0, 50.0, 0.1, 200.0),
+            Mat4::orthographic_off_center(-10.0, 60.0, -20.0, 30.0, 0.1, 200.0)
+        ];
+
+        for projection in projections {
+            let view_projection = view.mul(&projection);
+            let inverse = view_projection.inverse().unwrap();
+
+            for _ in 0..20 {
+                let point = random.next_direction() * random.next_f32(0.0, 10.0);
+                let projected = Mat4::project(&point, &view_projection).unwrap();
+                let unprojected = Mat4::unproject(&projected, &inverse).unwrap();
+                assert!((unprojected - point).length() < 1e-2, "{:?} came back as {:?}", point, unprojected);
+            }
+        }
+
+        let center = Mat4::screen_to_normalized_device(&Vector2::new(400.0, 300.0), 800.0, 600.0, 0.5);
+        assert_eq!(center, Vector3::new(0.0, 0.0, 0.5));
+        let top_left = Mat4::screen_to_normalized_device(&Vector2::new(0.0, 0.0), 800.0, 600.0, 1.0);
+        assert_eq!(top_left, Vector3::new(-1.0, 1.0, 1.0));
+    }
+
+    impl Xorshift {
+        fn next_transform(&mut self, uniform_scale: bool) -> Transform {
+            let translation = self.next_direction() * self.next_f32(0.0, 50.0);
+            let scale = if uniform_scale {
+                let scale = self.next_f32(0.2, 4.0);
+                Vector3::new(scale, scale, scale)
+            } else {
+                Vector3::new(self.next_f32(0.2, 4.0), self.next_f32(0.2, 4.0), self.next_f32(0.2, 4.0))
+            };
+
+            Transform::new(translation, self.next_rotation(), scale)
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_match_matrix_when_transforming_points_and_vectors() {
+        let mut random = Xorshift(9001);
+
+        for _ in 0..100 {
+            let transform = random.next_transform(false);
+            let point = random.next_direction() * 3.0;
+
+            let expected_matrix = Mat4::scale(&transform.scale).mul(&transform.rotation.to_matrix()).mul(&Mat4::translate(&transform.translation));
+            assert_matrices_near(&transform.to_matrix(), &expected_matrix, 1e-4);
+
+            let expected_point = expected_matrix.mul_row(&Vector4::new(point.x, point.y, point.z, 1.0));
+            assert_vectors_near(&transform.transform_point(&point), &Vector3::new(expected_point.x, expected_point.y, expected_point.z), 1e-3);
+            let expected_vector = expected_matrix.mul_row(&Vector4::new(point.x, point.y, point.z, 0.0));
+            assert_vectors_near(&transform.transform_vector(&point), &Vector3::new(expected_vector.x, expected_vector.y, expected_vector.z), 1e-3);
+
+            assert_vectors_near(&transform.inverse_transform_point(&transform.transform_point(&point)), &point, 1e-3);
+        }
+
+        assert_eq!(Transform::default().to_matrix(), Mat4::identity());
+    }
+
+    #[test]
+    fn should_match_matrix_product_when_combining_transforms() {
+        let mut random = Xorshift(12);
+
+        for _ in 0..100 {
+            // Exact, as the parent is scaled the same along all its axes
+            let child = random.next_transform(false);
+            let parent = random.next_transform(true);
+
+            assert_matrices_near(&(child * parent).to_matrix(), &child.to_matrix().mul(&parent.to_matrix()), 1e-3);
+
+            let inverse = parent.inverse().unwrap();
+            assert_matrices_near(&(parent * inverse).to_matrix(), &Mat4::identity(), 1e-4);
+            assert_matrices_near(&inverse.to_matrix(), &parent.to_matrix().inverse().unwrap(), 1e-4);
+        }
+
+        assert!(Transform::new(Vector3::zero(), Quaternion::identity(), Vector3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn should_give_back_transform_when_decomposing_matrix() {
+        let mut random = Xorshift(77);
+
+        for _ in 0..100 {
+            let transform = random.next_transform(false);
+            let decomposed = Transform::from_matrix(&transform.to_matrix()).unwrap();
+
+            assert_vectors_near(&decomposed.translation, &transform.translation, 1e-4);
+            assert_vectors_near(&decomposed.scale, &transform.scale, 1e-4);
+            assert_same_rotation(&decomposed.rotation, &transform.rotation, 1e-5);
+        }
+
+        // Mirrored along X, which comes back as it was
+        let mirrored = Transform::new(Vector3::new(1.0, 2.0, 3.0), Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 0.5), Vector3::new(-2.0, 3.0, 4.0));
+        let decomposed = Transform::from_matrix(&mirrored.to_matrix()).unwrap();
+        assert_vectors_near(&decomposed.scale, &mirrored.scale, 1e-5);
+        assert_same_rotation(&decomposed.rotation, &mirrored.rotation, 1e-6);
+
+        // Mirrored along Y, which comes back as another transform with the same matrix
+        let mirrored = Transform::new(Vector3::zero(), Quaternion::identity(), Vector3::new(2.0, -3.0, 4.0));
+        let decomposed = Transform::from_matrix(&mirrored.to_matrix()).unwrap();
+        assert!(decomposed.scale.x < 0.0);
+        assert_matrices_near(&decomposed.to_matrix(), &mirrored.to_matrix(), 1e-5);
+
+        assert!(Transform::from_matrix(&Mat4::scale(&Vector3::new(1.0, 0.0, 1.0))).is_none());
+    }
+
+    #[test]
+    fn should_interpolate_each_part_when_lerping_transforms() {
+        let from = Transform::new(Vector3::zero(), Quaternion::identity(), Vector3::new(1.0, 1.0, 1.0));
+        let to = Transform::new(Vector3::new(10.0, 0.0, 0.0), Quaternion::from_axis_angle(&Vector3::new(0.0, 0.0, 1.0), 2.0), Vector3::new(3.0, 3.0, 3.0));
+
+        let halfway = from.lerp(&to, 0.5);
+
+        assert_vectors_near(&halfway.translation, &Vector3::new(5.0, 0.0, 0.0), 1e-6);
+        assert_vectors_near(&halfway.scale, &Vector3::new(2.0, 2.0, 2.0), 1e-6);
+        assert_same_rotation(&halfway.rotation, &Quaternion::from_axis_angle(&Vector3::new(0.0, 0.0, 1.0), 1.0), 1e-6);
+        assert_eq!(from.lerp(&to, 1.0).translation, to.translation);
+    }
+}
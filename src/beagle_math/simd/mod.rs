@@ -0,0 +1,469 @@
+/*
+    SIMD (single instruction, multiple data) versions of the Mat4 and Vector4 operations that run the most.
+
+    A Vector4 is four f32s, which is exactly one 128 bit SIMD register, and a Mat4 is four of them, one for each row.
+    Multiplying a row vector by a matrix then becomes: x times the first row, plus y times the second row, and so on,
+    four multiplications and three additions of whole registers, instead of sixteen and twelve of single floats.
+
+    Which instructions are used is decided when compiling, with cfg:
+        - SSE2 on x86 and x86_64. Every x86_64 CPU has it, and Rust turns it on for x86_64 by default.
+        - NEON on aarch64, which every aarch64 CPU has.
+        - Plain Rust (the scalar module) on anything else.
+    The scalar versions are always compiled, as the tests and benchmarks compare the SIMD versions against them.
+
+    benches/beagle_math.rs compares them against the code from before this module. On x86_64, transforming slices of points
+    is about 1.6x as fast, transposing and the dot product about 1.2x, and mul_row and transforming slices of rows about 1.1x.
+    Mat4::mul and the single Vector4 operations are as fast as the old code, which the compiler had already turned into
+    SIMD instructions on its own.
+
+    The additions are done in the same order as in the scalar versions, so matrix products come out exactly the same.
+    Only dot products are summed in a different order, and can differ in the last bit.
+
+    Mat4 and Vector4 are aligned to 16 bytes, so they can be loaded into registers with aligned loads.
+    That's also how HLSL lays out a float4 or float4x4 in a constant buffer, so they can be copied straight into one.
+*/
+
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), target_feature = "sse2"))]
+pub use sse2::*;
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+pub use neon::*;
+
+#[cfg(not(any(
+    all(any(target_arch = "x86", target_arch = "x86_64"), target_feature = "sse2"),
+    all(target_arch = "aarch64", target_feature = "neon"))))]
+pub use scalar::*;
+
+// Only used on CPUs without SSE2 or NEON, and by the tests and benchmarks
+#[allow(dead_code)]
+pub mod scalar {
+    use crate::beagle_math::{Mat4, Vector3, Vector4};
+
+    pub fn mul(a: &Mat4, b: &Mat4) -> Mat4 {
+        let mut matrix = [0.0; 16];
+        for row in 0..4 {
+            let result = mul_row(b, &Vector4::new(a.matrix[row * 4], a.matrix[row * 4 + 1], a.matrix[row * 4 + 2], a.matrix[row * 4 + 3]));
+            matrix[row * 4..row * 4 + 4].copy_from_slice(&result.as_array());
+        }
+
+        Mat4 { matrix }
+    }
+
+    pub fn mul_row(mat: &Mat4, row: &Vector4) -> Vector4 {
+        let m = &mat.matrix;
+        Vector4::new(
+            row.x * m[0] + row.y * m[4] + row.z * m[8] + row.w * m[12],
+            row.x * m[1] + row.y * m[5] + row.z * m[9] + row.w * m[13],
+            row.x * m[2] + row.y * m[6] + row.z * m[10] + row.w * m[14],
+            row.x * m[3] + row.y * m[7] + row.z * m[11] + row.w * m[15])
+    }
+
+    pub fn transpose(mat: &Mat4) -> Mat4 {
+        let m = &mat.matrix;
+        Mat4 {
+            matrix: [
+                m[0], m[4], m[8], m[12],
+                m[1], m[5], m[9], m[13],
+                m[2], m[6], m[10], m[14],
+                m[3], m[7], m[11], m[15]
+            ]
+        }
+    }
+
+    pub fn transform_points(mat: &Mat4, points: &mut [Vector3]) {
+        let m = &mat.matrix;
+        for point in points.iter_mut() {
+            let (x, y, z) = (point.x, point.y, point.z);
+            point.x = x * m[0] + y * m[4] + z * m[8] + m[12];
+            point.y = x * m[1] + y * m[5] + z * m[9] + m[13];
+            point.z = x * m[2] + y * m[6] + z * m[10] + m[14];
+        }
+    }
+
+    pub fn transform_rows(mat: &Mat4, rows: &mut [Vector4]) {
+        for row in rows.iter_mut() {
+            *row = mul_row(mat, row);
+        }
+    }
+
+    pub fn add(a: &Vector4, b: &Vector4) -> Vector4 {
+        Vector4::new(a.x + b.x, a.y + b.y, a.z + b.z, a.w + b.w)
+    }
+
+    pub fn sub(a: &Vector4, b: &Vector4) -> Vector4 {
+        Vector4::new(a.x - b.x, a.y - b.y, a.z - b.z, a.w - b.w)
+    }
+
+    // Component by component
+    pub fn mul_components(a: &Vector4, b: &Vector4) -> Vector4 {
+        Vector4::new(a.x * b.x, a.y * b.y, a.z * b.z, a.w * b.w)
+    }
+
+    pub fn mul_scalar(a: &Vector4, scalar: f32) -> Vector4 {
+        Vector4::new(a.x * scalar, a.y * scalar, a.z * scalar, a.w * scalar)
+    }
+
+    pub fn div_scalar(a: &Vector4, scalar: f32) -> Vector4 {
+        Vector4::new(a.x / scalar, a.y / scalar, a.z / scalar, a.w / scalar)
+    }
+
+    pub fn neg(a: &Vector4) -> Vector4 {
+        Vector4::new(-a.x, -a.y, -a.z, -a.w)
+    }
+
+    pub fn dot(a: &Vector4, b: &Vector4) -> f32 {
+        a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w
+    }
+}
+
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), target_feature = "sse2"))]
+mod sse2 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use crate::beagle_math::{Mat4, Vector3, Vector4};
+
+    // The intrinsics are all unsafe functions. They're safe to call here though: the instructions are always there,
+    // as this module is only compiled with SSE2 enabled, and the loads and stores go through 16 byte aligned Mat4s and Vector4s.
+
+    fn load(vector: &Vector4) -> __m128 {
+        unsafe { _mm_load_ps(&vector.x) }
+    }
+
+    fn store(register: __m128) -> Vector4 {
+        let mut vector = Vector4::default();
+        unsafe { _mm_store_ps(&mut vector.x, register) };
+        vector
+    }
+
+    fn load_rows(mat: &Mat4) -> [__m128; 4] {
+        let matrix = mat.matrix.as_ptr();
+        unsafe { [_mm_load_ps(matrix), _mm_load_ps(matrix.add(4)), _mm_load_ps(matrix.add(8)), _mm_load_ps(matrix.add(12))] }
+    }
+
+    fn store_rows(rows: [__m128; 4]) -> Mat4 {
+        let mut mat = Mat4 { matrix: [0.0; 16] };
+        let matrix = mat.matrix.as_mut_ptr();
+        unsafe {
+            for (index, row) in rows.iter().enumerate() {
+                _mm_store_ps(matrix.add(index * 4), *row);
+            }
+        }
+        mat
+    }
+
+    // x * rows[0] + y * rows[1] + z * rows[2] + w * rows[3], with every component of the row copied into all four lanes
+    fn mul_row_register(rows: &[__m128; 4], row: __m128) -> __m128 {
+        unsafe {
+            let x = _mm_shuffle_ps(row, row, 0b00_00_00_00);
+            let y = _mm_shuffle_ps(row, row, 0b01_01_01_01);
+            let z = _mm_shuffle_ps(row, row, 0b10_10_10_10);
+            let w = _mm_shuffle_ps(row, row, 0b11_11_11_11);
+
+            let mut result = _mm_mul_ps(x, rows[0]);
+            result = _mm_add_ps(result, _mm_mul_ps(y, rows[1]));
+            result = _mm_add_ps(result, _mm_mul_ps(z, rows[2]));
+            _mm_add_ps(result, _mm_mul_ps(w, rows[3]))
+        }
+    }
+
+    // Every row of a times b is a row of the product
+    pub fn mul(a: &Mat4, b: &Mat4) -> Mat4 {
+        let a_rows = load_rows(a);
+        let b_rows = load_rows(b);
+
+        store_rows(a_rows.map(|row| mul_row_register(&b_rows, row)))
+    }
+
+    pub fn mul_row(mat: &Mat4, row: &Vector4) -> Vector4 {
+        store(mul_row_register(&load_rows(mat), load(row)))
+    }
+
+    /*
+        Two rounds of shuffles. The first puts the x and y (or z and w) of two rows next to each other:
+            xy01 = (r0.x, r0.y, r1.x, r1.y)
+        and the second picks the even (or odd) lanes of two of those, which is a column.
+    */
+    pub fn transpose(mat: &Mat4) -> Mat4 {
+        let [row0, row1, row2, row3] = load_rows(mat);
+
+        unsafe {
+            let xy01 = _mm_shuffle_ps(row0, row1, 0b01_00_01_00);
+            let zw01 = _mm_shuffle_ps(row0, row1, 0b11_10_11_10);
+            let xy23 = _mm_shuffle_ps(row2, row3, 0b01_00_01_00);
+            let zw23 = _mm_shuffle_ps(row2, row3, 0b11_10_11_10);
+
+            store_rows([
+                _mm_shuffle_ps(xy01, xy23, 0b10_00_10_00),
+                _mm_shuffle_ps(xy01, xy23, 0b11_01_11_01),
+                _mm_shuffle_ps(zw01, zw23, 0b10_00_10_00),
+                _mm_shuffle_ps(zw01, zw23, 0b11_01_11_01)
+            ])
+        }
+    }
+
+    // A Vector3 is only 12 bytes, so it's put together from three broadcasts instead of a load.
+    // w is 1, and 1 * rows[3] is just rows[3].
+    pub fn transform_points(mat: &Mat4, points: &mut [Vector3]) {
+        let rows = load_rows(mat);
+
+        for point in points.iter_mut() {
+            let transformed = unsafe {
+                let mut result = _mm_mul_ps(_mm_set1_ps(point.x), rows[0]);
+                result = _mm_add_ps(result, _mm_mul_ps(_mm_set1_ps(point.y), rows[1]));
+                result = _mm_add_ps(result, _mm_mul_ps(_mm_set1_ps(point.z), rows[2]));
+                store(_mm_add_ps(result, rows[3]))
+            };
+
+            *point = Vector3::new(transformed.x, transformed.y, transformed.z);
+        }
+    }
+
+    pub fn transform_rows(mat: &Mat4, rows: &mut [Vector4]) {
+        let mat_rows = load_rows(mat);
+
+        for row in rows.iter_mut() {
+            *row = store(mul_row_register(&mat_rows, load(row)));
+        }
+    }
+
+    pub fn add(a: &Vector4, b: &Vector4) -> Vector4 {
+        store(unsafe { _mm_add_ps(load(a), load(b)) })
+    }
+
+    pub fn sub(a: &Vector4, b: &Vector4) -> Vector4 {
+        store(unsafe { _mm_sub_ps(load(a), load(b)) })
+    }
+
+    pub fn mul_components(a: &Vector4, b: &Vector4) -> Vector4 {
+        store(unsafe { _mm_mul_ps(load(a), load(b)) })
+    }
+
+    pub fn mul_scalar(a: &Vector4, scalar: f32) -> Vector4 {
+        store(unsafe { _mm_mul_ps(load(a), _mm_set1_ps(scalar)) })
+    }
+
+    pub fn div_scalar(a: &Vector4, scalar: f32) -> Vector4 {
+        store(unsafe { _mm_div_ps(load(a), _mm_set1_ps(scalar)) })
+    }
+
+    // Flipping the sign bit of every lane, so negative zero stays the same as in the scalar version
+    pub fn neg(a: &Vector4) -> Vector4 {
+        store(unsafe { _mm_xor_ps(load(a), _mm_set1_ps(-0.0)) })
+    }
+
+    // (x + z) + (y + w), by adding the high half onto the low half, and then the second lane onto the first
+    pub fn dot(a: &Vector4, b: &Vector4) -> f32 {
+        unsafe {
+            let products = _mm_mul_ps(load(a), load(b));
+            let halves = _mm_add_ps(products, _mm_movehl_ps(products, products));
+            _mm_cvtss_f32(_mm_add_ss(halves, _mm_shuffle_ps(halves, halves, 0b01_01_01_01)))
+        }
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use crate::beagle_math::{Mat4, Vector3, Vector4};
+
+    // Same as the SSE2 module, but with NEON instructions. NEON doesn't need aligned loads, but they don't hurt either.
+
+    fn load(vector: &Vector4) -> float32x4_t {
+        unsafe { vld1q_f32(&vector.x) }
+    }
+
+    fn store(register: float32x4_t) -> Vector4 {
+        let mut vector = Vector4::default();
+        unsafe { vst1q_f32(&mut vector.x, register) };
+        vector
+    }
+
+    fn load_rows(mat: &Mat4) -> [float32x4_t; 4] {
+        let matrix = mat.matrix.as_ptr();
+        unsafe { [vld1q_f32(matrix), vld1q_f32(matrix.add(4)), vld1q_f32(matrix.add(8)), vld1q_f32(matrix.add(12))] }
+    }
+
+    fn store_rows(rows: [float32x4_t; 4]) -> Mat4 {
+        let mut mat = Mat4 { matrix: [0.0; 16] };
+        let matrix = mat.matrix.as_mut_ptr();
+        unsafe {
+            for (index, row) in rows.iter().enumerate() {
+                vst1q_f32(matrix.add(index * 4), *row);
+            }
+        }
+        mat
+    }
+
+    // Separate multiplies and adds rather than fused multiply-adds, so the results are the same as the scalar versions
+    fn mul_row_register(rows: &[float32x4_t; 4], row: float32x4_t) -> float32x4_t {
+        unsafe {
+            let mut result = vmulq_laneq_f32::<0>(rows[0], row);
+            result = vaddq_f32(result, vmulq_laneq_f32::<1>(rows[1], row));
+            result = vaddq_f32(result, vmulq_laneq_f32::<2>(rows[2], row));
+            vaddq_f32(result, vmulq_laneq_f32::<3>(rows[3], row))
+        }
+    }
+
+    pub fn mul(a: &Mat4, b: &Mat4) -> Mat4 {
+        let a_rows = load_rows(a);
+        let b_rows = load_rows(b);
+
+        store_rows(a_rows.map(|row| mul_row_register(&b_rows, row)))
+    }
+
+    pub fn mul_row(mat: &Mat4, row: &Vector4) -> Vector4 {
+        store(mul_row_register(&load_rows(mat), load(row)))
+    }
+
+    /*
+        zip1(a, b) = (a.x, b.x, a.y, b.y), and zip2 does the same with z and w.
+        Zipping row 0 with row 2 and row 1 with row 3, and then zipping those, gives the columns.
+    */
+    pub fn transpose(mat: &Mat4) -> Mat4 {
+        let [row0, row1, row2, row3] = load_rows(mat);
+
+        unsafe {
+            let xy02 = vzip1q_f32(row0, row2);
+            let zw02 = vzip2q_f32(row0, row2);
+            let xy13 = vzip1q_f32(row1, row3);
+            let zw13 = vzip2q_f32(row1, row3);
+
+            store_rows([
+                vzip1q_f32(xy02, xy13),
+                vzip2q_f32(xy02, xy13),
+                vzip1q_f32(zw02, zw13),
+                vzip2q_f32(zw02, zw13)
+            ])
+        }
+    }
+
+    pub fn transform_points(mat: &Mat4, points: &mut [Vector3]) {
+        let rows = load_rows(mat);
+
+        for point in points.iter_mut() {
+            let transformed = unsafe {
+                let mut result = vmulq_n_f32(rows[0], point.x);
+                result = vaddq_f32(result, vmulq_n_f32(rows[1], point.y));
+                result = vaddq_f32(result, vmulq_n_f32(rows[2], point.z));
+                store(vaddq_f32(result, rows[3]))
+            };
+
+            *point = Vector3::new(transformed.x, transformed.y, transformed.z);
+        }
+    }
+
+    pub fn transform_rows(mat: &Mat4, rows: &mut [Vector4]) {
+        let mat_rows = load_rows(mat);
+
+        for row in rows.iter_mut() {
+            *row = store(mul_row_register(&mat_rows, load(row)));
+        }
+    }
+
+    pub fn add(a: &Vector4, b: &Vector4) -> Vector4 {
+        store(unsafe { vaddq_f32(load(a), load(b)) })
+    }
+
+    pub fn sub(a: &Vector4, b: &Vector4) -> Vector4 {
+        store(unsafe { vsubq_f32(load(a), load(b)) })
+    }
+
+    pub fn mul_components(a: &Vector4, b: &Vector4) -> Vector4 {
+        store(unsafe { vmulq_f32(load(a), load(b)) })
+    }
+
+    pub fn mul_scalar(a: &Vector4, scalar: f32) -> Vector4 {
+        store(unsafe { vmulq_n_f32(load(a), scalar) })
+    }
+
+    pub fn div_scalar(a: &Vector4, scalar: f32) -> Vector4 {
+        store(unsafe { vdivq_f32(load(a), vdupq_n_f32(scalar)) })
+    }
+
+    pub fn neg(a: &Vector4) -> Vector4 {
+        store(unsafe { vnegq_f32(load(a)) })
+    }
+
+    // Adds up all four lanes pairwise: (x + y) + (z + w)
+    pub fn dot(a: &Vector4, b: &Vector4) -> f32 {
+        unsafe { vaddvq_f32(vmulq_f32(load(a), load(b))) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math::*;
+
+    fn test_matrix(seed: f32) -> Mat4 {
+        Mat4::new(std::array::from_fn(|index| ((index as f32 + seed) * 1.37).sin() * 10.0))
+    }
+
+    #[test]
+    fn should_match_scalar_when_multiplying_matrices() {
+        for seed in 0..20 {
+            let a = test_matrix(seed as f32);
+            let b = test_matrix(seed as f32 + 100.0);
+            let row = Vector4::new(seed as f32, -2.5, 0.125, 1.0);
+
+            assert_eq!(simd::mul(&a, &b), simd::scalar::mul(&a, &b));
+            let product = simd::scalar::mul(&a, &b);
+            for (index, value) in product.matrix.iter().enumerate() {
+                let (x, y) = ((index % 4) as i32, (index / 4) as i32);
+                let expected: f32 = (0..4).map(|k| a.get(k, y) * b.get(x, k)).sum();
+                assert!((value - expected).abs() < 1.0e-3, "{:?}", product);
+            }
+
+            assert_eq!(simd::mul_row(&a, &row), simd::scalar::mul_row(&a, &row));
+            assert_eq!(simd::transpose(&a), simd::scalar::transpose(&a));
+            assert_eq!(simd::transpose(&a).get(1, 2), a.get(2, 1));
+        }
+    }
+
+    #[test]
+    fn should_match_scalar_when_transforming_slices() {
+        let mat = Mat4::rotate_y(0.7).mul(&Mat4::translate(&Vector3::new(1.0, -2.0, 3.0)));
+        let points: Vec<Vector3> = (0..37).map(|index| Vector3::new(index as f32, (index as f32).sqrt(), -0.5 * index as f32)).collect();
+        let rows: Vec<Vector4> = points.iter().map(|point| Vector4::new(point.x, point.y, point.z, 0.5)).collect();
+
+        let (mut simd_points, mut scalar_points) = (points.clone(), points.clone());
+        simd::transform_points(&mat, &mut simd_points);
+        simd::scalar::transform_points(&mat, &mut scalar_points);
+        assert_eq!(simd_points, scalar_points);
+
+        let (mut simd_rows, mut scalar_rows) = (rows.clone(), rows.clone());
+        simd::transform_rows(&mat, &mut simd_rows);
+        simd::scalar::transform_rows(&mat, &mut scalar_rows);
+        assert_eq!(simd_rows, scalar_rows);
+
+        // Transforming a point is the same as transforming it as a row vector with w = 1
+        let expected = mat.mul_row(&Vector4::new(points[5].x, points[5].y, points[5].z, 1.0));
+        assert_eq!(simd_points[5], Vector3::new(expected.x, expected.y, expected.z));
+    }
+
+    #[test]
+    fn should_match_scalar_for_vector_operations() {
+        let a = Vector4::new(1.5, -2.0, 3.25, -0.0);
+        let b = Vector4::new(-4.0, 0.5, 8.0, 2.0);
+
+        assert_eq!(simd::add(&a, &b), simd::scalar::add(&a, &b));
+        assert_eq!(simd::sub(&a, &b), simd::scalar::sub(&a, &b));
+        assert_eq!(simd::mul_components(&a, &b), simd::scalar::mul_components(&a, &b));
+        assert_eq!(simd::mul_scalar(&a, 3.0), simd::scalar::mul_scalar(&a, 3.0));
+        assert_eq!(simd::div_scalar(&a, 3.0), simd::scalar::div_scalar(&a, 3.0));
+        assert_eq!(simd::neg(&a), simd::scalar::neg(&a));
+        assert!(simd::neg(&a).w.is_sign_positive());
+        assert!((simd::dot(&a, &b) - simd::scalar::dot(&a, &b)).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn should_align_types_for_constant_buffers() {
+        assert_eq!(std::mem::align_of::<Vector4>(), 16);
+        assert_eq!(std::mem::size_of::<Vector4>(), 16);
+        assert_eq!(std::mem::align_of::<Mat4>(), 16);
+        assert_eq!(std::mem::size_of::<Mat4>(), 64);
+    }
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use std::process;
+
+/*
+    Command line front end for the asset pipeline (see asset::pipeline), so assets can be cooked on any machine,
+    without PowerShell or Windows.
+
+        cargo run --bin asset_pipeline -- [--source <directory>] [--output <directory>] [--force]
+
+    The engine itself is Windows only, so instead of depending on it, the platform independent modules the pipeline
+    needs are compiled into this binary straight from their files.
+*/
+
+#[allow(dead_code)]
+#[path = "../asset/mod.rs"]
+mod asset;
+#[allow(dead_code)]
+#[path = "../beagle_math/mod.rs"]
+mod beagle_math;
+#[allow(dead_code)]
+#[path = "../gltf2/mod.rs"]
+mod gltf2;
+#[allow(dead_code)]
+#[path = "../shared/mod.rs"]
+mod shared;
+
+const USAGE: &str = "Usage: asset_pipeline [--source <directory>] [--output <directory>] [--force]
+
+    --source    Directory with the source assets (default: resources)
+    --output    Directory to write the cooked assets and manifest to (default: target/debug/resources)
+    --force     Rebuild every asset, even if it didn't change";
+
+fn main() {
+    let mut options = asset::pipeline::PipelineOptions {
+        source_directory: PathBuf::from("resources"),
+        output_directory: PathBuf::from("target").join("debug").join("resources"),
+        force: false
+    };
+
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--source" => options.source_directory = PathBuf::from(expect_value(&argument, arguments.next())),
+            "--output" => options.output_directory = PathBuf::from(expect_value(&argument, arguments.next())),
+            "--force" => options.force = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ => {
+                eprintln!("Unknown argument '{}'.\n\n{}", argument, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    println!("Cooking assets from {} into {}", options.source_directory.display(), options.output_directory.display());
+
+    match asset::pipeline::run(&options) {
+        Ok(report) => {
+            print!("{}", asset::pipeline::summarize(&report));
+            if !report.is_success() {
+                process::exit(1);
+            }
+        },
+        Err(err) => {
+            eprintln!("Asset pipeline failed: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+fn expect_value(argument: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| {
+        eprintln!("{} needs a directory.\n\n{}", argument, USAGE);
+        process::exit(2);
+    })
+}
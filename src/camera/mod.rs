@@ -19,13 +19,11 @@ impl Fps {
         let pitch_axis = beagle_math::Vector3::new(1.0, 0.0, 0.0);
         let yaw_axis = beagle_math::Vector3::new(0.0, 1.0, 0.0);
 
-        let mut pitch = beagle_math::Quaternion::default();
-        let mut yaw = beagle_math::Quaternion::default();
+        let pitch = beagle_math::Quaternion::from_axis_angle(&pitch_axis, self.delta_pitch);
+        let yaw = beagle_math::Quaternion::from_axis_angle(&yaw_axis, self.delta_yaw);
 
-        pitch.set_rotation(pitch_axis, self.delta_pitch);
-        yaw.set_rotation(yaw_axis, self.delta_yaw);
-
-        let rotation = pitch.cross(&yaw).to_matrix().get_transposed();
+        // Pitch first, then yaw
+        let rotation = (yaw * pitch).to_matrix().get_transposed();
         let translation_matrix = beagle_math::Mat4::translate(&self.delta_translation.mul(-1.0));
 
         self.current_view_matrix = translation_matrix.mul(&rotation);
@@ -77,29 +75,19 @@ impl FreeFlight {
     }
 
     pub fn get_position(&self) -> beagle_math::Vector3 {
-        let x = self.current_view_matrix.get(0, 3);
-        let y = self.current_view_matrix.get(1, 3);
-        let z = self.current_view_matrix.get(2, 3);
-
-        let v = beagle_math::Vector4::new(x, y, z, 1.0);
-
         /*
-            Here I take the translation part of the current view matrix, which expresses the displacement, in world coordinates, that all objects in the scene
-            should displace by RELATIVE TO THE CAMERA AS ORIGIN, in order to simulate the current orientation of the FreeFlight camera. 
-
-            I then take that world coordinate displacement, and convert it into a displacement relative to the coordinate space of the current view, that is,
-            with the current view matrix as the origin.
-
-            The resulting position is essentially the absolute world position of the FreeFlight camera.
+            The view matrix moves the whole world so that the camera ends up at the origin, looking down +Z.
+            Undoing that (the inverse of the view matrix) moves the camera from the origin back to where it is in the world,
+            so the translation of the inverse is the absolute world position of the FreeFlight camera.
 
             I need an absolute position like this when calculating things like specular light in the vertex shader, as this requires a position of the eye in world coordinates.
             And the base self.current_view_matrix stores no such information.
-        */
-        let loc = beagle_math::Mat4::parent_to_local(&beagle_math::Vector3::new(v.x, v.y, v.z), &self.current_view_matrix);
 
-        let res = beagle_math::Vector3::new(loc.get(0, 3), loc.get(1, 3), loc.get(2, 3));
+            The view matrix only ever rotates and translates, so the cheap rigid inverse is enough.
+        */
+        let camera_to_world = self.current_view_matrix.inverse_rigid();
 
-        res
+        beagle_math::Vector3::new(camera_to_world.get(0, 3), camera_to_world.get(1, 3), camera_to_world.get(2, 3))
     }
 
     pub fn view_matrix(&mut self) -> beagle_math::Mat4 {
@@ -107,15 +95,12 @@ impl FreeFlight {
         let pitch_axis = beagle_math::Vector3::new(1.0, 0.0, 0.0);
         let roll_axis = beagle_math::Vector3::new(0.0, 0.0, 1.0);
 
-        let mut yaw = beagle_math::Quaternion::default();
-        let mut pitch = beagle_math::Quaternion::default();
-        let mut roll = beagle_math::Quaternion::default();
-
-        yaw.set_rotation(yaw_axis, self.delta_yaw);
-        pitch.set_rotation(pitch_axis, self.delta_pitch);
-        roll.set_rotation(roll_axis, self.delta_roll);
+        let yaw = beagle_math::Quaternion::from_axis_angle(&yaw_axis, self.delta_yaw);
+        let pitch = beagle_math::Quaternion::from_axis_angle(&pitch_axis, self.delta_pitch);
+        let roll = beagle_math::Quaternion::from_axis_angle(&roll_axis, self.delta_roll);
 
-        let rotation = yaw.cross(&pitch).cross(&roll).to_matrix().get_transposed();
+        // Yaw first, then pitch, then roll
+        let rotation = (roll * pitch * yaw).to_matrix().get_transposed();
         let translation_matrix = beagle_math::Mat4::translate(&self.delta_translate.mul(-1.0));
 
         self.current_trans = self.current_trans.mul(&translation_matrix);
@@ -124,4 +109,29 @@ impl FreeFlight {
         
         beagle_math::Mat4::new(self.current_view_matrix.matrix)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::camera::*;
+
+    #[test]
+    fn should_return_world_position_when_free_flight_camera_moved_and_turned() {
+        let mut camera = FreeFlight::default();
+
+        camera.apply_move(0.0, 0.0, 0.0, beagle_math::Vector3::new(0.0, 0.0, 2.0));
+        camera.view_matrix();
+        // Turning in place doesn't change where the camera is
+        camera.apply_move(0.0, std::f32::consts::FRAC_PI_2, 0.0, beagle_math::Vector3::zero());
+        camera.view_matrix();
+
+        let position = camera.get_position();
+        assert!((position - beagle_math::Vector3::new(0.0, 0.0, 2.0)).length() < 1e-5, "{:?}", position);
+
+        // After turning a quarter to the right, moving forward moves along +X
+        camera.apply_move(0.0, 0.0, 0.0, beagle_math::Vector3::new(0.0, 0.0, 1.0));
+        camera.view_matrix();
+
+        let position = camera.get_position();
+        assert!((position - beagle_math::Vector3::new(1.0, 0.0, 2.0)).length() < 1e-5, "{:?}", position);
+    }
+}
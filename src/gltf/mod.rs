@@ -72,17 +72,8 @@ impl GLTF {
             let vert2 = vertex_positions[vertex_position_index + 1];
             let vert3 = vertex_positions[vertex_position_index + 2];
 
-            let edge1 = beagle_math::Vector3::new(
-                vert2.x - vert1.x,
-                vert2.y - vert1.y,
-                vert2.z - vert1.z,
-            );
-
-            let edge2 = beagle_math::Vector3::new(
-                vert3.x - vert1.x,
-                vert3.y - vert1.y,
-                vert3.z - vert1.z,
-            );
+            let edge1 = vert2 - vert1;
+            let edge2 = vert3 - vert1;
 
             let mut vertex_normal = edge1.cross(&edge2).normalized();
 
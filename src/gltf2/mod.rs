@@ -59,7 +59,11 @@ pub struct Node {
     pub scale: [f32; 3],
 
     #[serde(default)]
-    pub rotation: [f32; 4]
+    pub rotation: [f32; 4],
+
+    // Instead of translation, rotation and scale
+    #[serde(default)]
+    pub matrix: Option<[f32; 16]>
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -121,6 +125,10 @@ pub struct Accessor {
         #[serde(default)]
         pub buffer_view: u32,
 
+        // The start offset in bytes of the first element, relative to the start of the buffer view
+        #[serde(default)]
+        pub byte_offset: u32,
+
         // The data type of each individual value (component)
         // 5123 = Unsigned Short, 16 bits, 2 bytes
         // 5126 = float, 32 bits, 4 bytes
@@ -151,7 +159,12 @@ pub struct BufferView {
 
     // The start offset in bytes for this buffer view.
     #[serde(default)]
-    pub byte_offset: u32
+    pub byte_offset: u32,
+
+    // The distance in bytes from the start of one element to the start of the next.
+    // 0 when the file leaves it out, which means the elements are tightly packed.
+    #[serde(default)]
+    pub byte_stride: u32
 }
 
 #[derive(Serialize, Deserialize, Debug)]
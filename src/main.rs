@@ -11,7 +11,7 @@ use windows::{
     }, core::{Interface}  
 };
 
-use std::{mem::{size_of, self}, os::windows::prelude::OsStrExt, env, fs, ops::Mul};
+use std::{mem::{size_of, self}, os::windows::prelude::OsStrExt, env};
 use std::ptr;
 use std::ffi::*;
 use std::collections::{HashMap};
@@ -27,11 +27,16 @@ mod window;
 mod camera;
 mod shared;
 mod renderable;
+mod terrain;
 
 // Remember, constant buffers byte width must be multiple of 16
+// repr(C) so the fields stay in the order the cbuffer in vertex.hlsl expects them
+#[repr(C)]
 struct VertexConstantBuffer {
     worldViewProjection: beagle_math::Mat4,
     modelMatrix: beagle_math::Mat4,
+    // A float3x3 in HLSL, each column takes up a whole float4 (see Mat3::get_padded_column_major_value)
+    normalMatrix: [f32; 12],
     cameraPosition: beagle_math::Vector4,
     diffuseColor: beagle_math::Vector4,
     ambientColor: beagle_math::Vector4,
@@ -175,8 +180,9 @@ fn main() {
         depth_buffer_texture_description.ArraySize = 1;
 
         // The format of the texture.
-        // DXGI_FORMAT_D24_UNORM_S8_UINT = 32-bit-z-buffer format supporting 24 bits for depth and 8 bits for stencil.
-        depth_buffer_texture_description.Format = DXGI_FORMAT_D24_UNORM_S8_UINT;
+        // DXGI_FORMAT_D32_FLOAT_S8X24_UINT = 32-bit floating point depth, 8 bits for stencil and 24 unused bits.
+        // The depth has to be floating point for the reversed depth of the projection to help (see Mat4::projection_reversed_z).
+        depth_buffer_texture_description.Format = DXGI_FORMAT_D32_FLOAT_S8X24_UINT;
 
         // We simply use no MSAA right now, as I'm not checking for the supported quality level of my hardware.
         depth_buffer_texture_description.SampleDesc.Count = 1;
@@ -213,23 +219,38 @@ fn main() {
         dx_device_context.OMSetRenderTargets(
             1, &back_buffer_render_target_view, &depth_buffer_view);
 
-        // GLTF2 Testing
-        let path_to_mill = current_executable_path.parent().unwrap().join("resources\\mill\\mill.gltf");
+        // Models are cooked ahead of time by the asset pipeline (src/bin/asset_pipeline.rs), which also validates them and builds their LODs.
+        // The asset manager finds the cooked file through the manifest the pipeline writes next to it.
+        let mut assets = asset::manager::AssetManager::new(current_executable_path.parent().unwrap().join("resources"));
 
-        let gltf_file = match gltf2::File::from(path_to_mill) {
-            Ok(gltf_file) => gltf_file,
-            Err(err) => panic!("{}", err)
-        };
+        // In debug builds, changed models and shaders are reloaded while the game runs.
+        // The executable is in target/debug, so the source assets are two directories up.
+        if cfg!(debug_assertions) {
+            let source_directory = current_executable_path.parent().unwrap().join("..").join("..").join("resources");
+            assets.watch_for_changes(asset::watcher::create_watcher(), Some(source_directory).filter(|directory| directory.exists()));
+        }
 
-        let model = asset::mesh::parse_model(&gltf_file);
-        let renderable_data = renderable::flat_shaded::RenderData::from_model(&model);
-        let renderable = renderable::flat_shaded::Renderable::from_render_data(renderable_data);
+        // The mill is loaded in the background, so the window shows up right away.
+        // The renderable is only created once the game loop sees the model is done.
+        let mill = assets.load_model_async("mill/mill");
+        let mut renderable: Option<renderable::flat_shaded::Renderable> = None;
 
         // TODO: Exercise - Enumerate through the available outputs (monitors) for an adapter. Use IDXGIAdapter::EnumOutputs.
         // TODO: Exercise - Each output has a lit of supported display modes. For each of them, list width, height, refresh rate, pixel format, etc...
-        let path_to_vertex_shader = current_executable_path.parent().unwrap().join("resources\\shaders\\shaders\\compiled-vertex.shader");
-
-        let compiled_vertex_shader_code = fs::read(path_to_vertex_shader).unwrap();
+        // Shaders are loaded through the asset manager as well, so recompiling one with fxc while the game runs reloads it
+        let vertex_shader_code = match assets.load_shader("shaders/shaders/compiled-vertex.shader") {
+            Ok(shader) => shader,
+            Err(err) => panic!("{}", err)
+        };
+        let pixel_shader_code = match assets.load_shader("shaders/shaders/compiled-pixel.shader") {
+            Ok(shader) => shader,
+            Err(err) => panic!("{}", err)
+        };
+        let vertex_normals_shader_code = match assets.load_shader("shaders/shaders/compiled-vertex-normals.shader") {
+            Ok(shader) => shader,
+            Err(err) => panic!("{}", err)
+        };
+        let compiled_vertex_shader_code = &assets.get(vertex_shader_code).unwrap().bytes;
 
         // TODO: Read up on this whole layout object thing again...
         let semantic_name_position = CString::new("POSITION").unwrap();
@@ -280,8 +301,7 @@ fn main() {
         dx_device_context.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
 
         // Create vertex shader and pixel shader
-        let path_to_pixel_shader = current_executable_path.parent().unwrap().join("resources\\shaders\\shaders\\compiled-pixel.shader");
-        let compiled_pixel_shader_code = fs::read(path_to_pixel_shader).unwrap();
+        let compiled_pixel_shader_code = &assets.get(pixel_shader_code).unwrap().bytes;
 
         vertex_shader = match dx_device.CreateVertexShader(
             compiled_vertex_shader_code.as_ptr() as *const c_void, compiled_vertex_shader_code.len(), None) {
@@ -289,7 +309,7 @@ fn main() {
                 Err(err) => panic!("Failed to create vertex shader: {}", err)
             };
 
-        let pixel_shader = match dx_device.CreatePixelShader(
+        let mut pixel_shader = match dx_device.CreatePixelShader(
             compiled_pixel_shader_code.as_ptr() as *const c_void, compiled_pixel_shader_code.len(), None) {
                 Ok(ps) => ps,
                 Err(err) => panic!("Failed to create pixel shader: {}", err)
@@ -317,12 +337,24 @@ fn main() {
 
         dx_device_context.RSSetState(rasterizer_state);
 
+        // The projection reverses depth, so closer things have a greater depth, and the depth buffer is cleared to 0 rather than 1
+        let depth_stencil_description = D3D11_DEPTH_STENCIL_DESC {
+            DepthEnable: BOOL(1),
+            DepthWriteMask: D3D11_DEPTH_WRITE_MASK_ALL,
+            DepthFunc: D3D11_COMPARISON_GREATER,
+            StencilEnable: BOOL(0),
+            ..Default::default()
+        };
+
+        let depth_stencil_state = dx_device.CreateDepthStencilState(&depth_stencil_description).unwrap();
+
+        dx_device_context.OMSetDepthStencilState(depth_stencil_state, 0);
+
         // Prepare shaders for vertex normal rendering
-        let path_to_vertex_normals_shader = current_executable_path.parent().unwrap().join("resources\\shaders\\shaders\\compiled-vertex-normals.shader");
-        let compiled_vertex_normals_shader_code = fs::read(path_to_vertex_normals_shader).unwrap();
+        let compiled_vertex_normals_shader_code = &assets.get(vertex_normals_shader_code).unwrap().bytes;
 
-        vertex_normal_shader_input_layout = Some(prepare_vertex_normals_input_layout(&dx_device, &compiled_vertex_normals_shader_code));
-        vertex_normal_shader = Some(prepare_vertex_normals_shader(&dx_device, &compiled_vertex_normals_shader_code));
+        vertex_normal_shader_input_layout = Some(prepare_vertex_normals_input_layout(&dx_device, compiled_vertex_normals_shader_code));
+        vertex_normal_shader = Some(prepare_vertex_normals_shader(&dx_device, compiled_vertex_normals_shader_code));
 
         // The viewport is used by DirectX in the Rasterizer stage, in order to map Normalizerd Device Coordinates Into
         // a 2D surface render target.
@@ -356,6 +388,7 @@ fn main() {
         let mut world_view_projection_matrix = VertexConstantBuffer {
             worldViewProjection: beagle_math::Mat4::projection((45.0f32).to_radians(), window::WINDOW_WIDTH as f32, window::WINDOW_HEIGHT as f32, 0.1, 100.0),
             modelMatrix: beagle_math::Mat4::identity(),
+            normalMatrix: beagle_math::Mat3::identity().get_padded_column_major_value(),
             cameraPosition: beagle_math::Vector4::default(),
             diffuseColor: beagle_math::Vector4::new(1.0, 0.0, 0.0, 0.0),
             ambientColor: beagle_math::Vector4::new(0.15, 0.15, 0.15, 0.0),
@@ -386,6 +419,10 @@ fn main() {
 
         let mut object_position = beagle_math::Vector3::default();
 
+        // Filled again every frame, kept around so it doesn't have to be allocated every frame
+        let mut visible_meshes: Vec<renderable::culling::VisibleMesh> = vec!();
+        let mut last_culling_stats = renderable::culling::CullingStats::default();
+
         while !should_quit {
             // PROCESS INPUT
             // PeekMessage will retrieve messages associated with the main window and the thread.
@@ -460,6 +497,54 @@ fn main() {
                 drone_camera.apply_move(-drone_delta_pitch, drone_delta_yaw, drone_delta_roll, drone_position_delta);
                 fps_camera.apply_move(-drone_delta_pitch, drone_delta_yaw, drone_position_delta);
 
+                // STREAMING AND HOT RELOADING
+                // Picks up whatever finished loading in the background since the last frame.
+                // A reload that fails is only reported, the game keeps going with the old version.
+                for event in assets.poll() {
+                    match event {
+                        asset::manager::LoadEvent::Loaded { .. } => {},
+                        asset::manager::LoadEvent::Reloaded { logical_path } => {
+                            println!("Reloaded {}", logical_path);
+
+                            if assets.logical_path(mill) == Some(logical_path.as_str()) {
+                                if let (Some(renderable), Some(model)) = (renderable.as_mut(), assets.get(mill)) {
+                                    renderable.rebuild(model);
+                                }
+                            } else if assets.logical_path(vertex_shader_code) == Some(logical_path.as_str()) {
+                                // The input layout is kept, so only changes that keep the vertex shader's inputs can be reloaded
+                                let code = &assets.get(vertex_shader_code).unwrap().bytes;
+                                match dx_device.CreateVertexShader(code.as_ptr() as *const c_void, code.len(), None) {
+                                    Ok(vs) => vertex_shader = Some(vs),
+                                    Err(err) => println!("Failed to create reloaded vertex shader: {}", err)
+                                }
+                            } else if assets.logical_path(vertex_normals_shader_code) == Some(logical_path.as_str()) {
+                                let code = &assets.get(vertex_normals_shader_code).unwrap().bytes;
+                                match dx_device.CreateVertexShader(code.as_ptr() as *const c_void, code.len(), None) {
+                                    Ok(vs) => vertex_normal_shader = Some(vs),
+                                    Err(err) => println!("Failed to create reloaded vertex normals shader: {}", err)
+                                }
+                            } else if assets.logical_path(pixel_shader_code) == Some(logical_path.as_str()) {
+                                let code = &assets.get(pixel_shader_code).unwrap().bytes;
+                                match dx_device.CreatePixelShader(code.as_ptr() as *const c_void, code.len(), None) {
+                                    Ok(ps) => {
+                                        pixel_shader = ps;
+                                        dx_device_context.PSSetShader(&pixel_shader, ptr::null(), 0);
+                                    },
+                                    Err(err) => println!("Failed to create reloaded pixel shader: {}", err)
+                                }
+                            }
+                        },
+                        asset::manager::LoadEvent::Failed { error, .. } => println!("{}", error)
+                    }
+                }
+
+                if renderable.is_none() {
+                    if let Some(model) = assets.get(mill) {
+                        let renderable_data = renderable::flat_shaded::RenderData::from_model(model);
+                        renderable = Some(renderable::flat_shaded::Renderable::from_render_data(renderable_data));
+                    }
+                }
+
                 // RENDER
                 let clear_color = beagle_math::Vector4::new(0.45, 0.6, 0.95, 1.0);
 
@@ -469,41 +554,41 @@ fn main() {
                 dx_device_context.ClearDepthStencilView(
                     &depth_buffer_view,
                     (D3D11_CLEAR_DEPTH | D3D11_CLEAR_STENCIL) as u32, 
-                    1.0, 
+                    // Reversed depth, 0 is infinitely far away
+                    0.0, 
                     0);
 
-                // Doing something not so pretty here...
-                // In my memory arena of renderable meshes, I find the ones that have no parents.
-                // These meshes are "root" meshes, meaning they have to be traversed in order to render themselves and their potential children.
-                // All other meshes are in some way referenced by others, and will eventually be rendered when traversing the tree
-                let mut root_mesh_indices : Vec<u32> = vec!();
-                for (index, renderable_mesh) in renderable.renderables.iter().enumerate() {
-                    let mut is_child_of_other_mesh = false;
-
-                    // Is this mesh referred to as a child of any other meshes?
-                    for (inner_index, inner_renderable_mesh) in renderable.renderables.iter().enumerate() {
-                        if inner_index != index 
-                            && inner_renderable_mesh.renderable_mesh_data.children.contains(&(index as u16)) {
-                                is_child_of_other_mesh = true;
-                        }
+                // Nothing to draw but the sky until the mill is loaded
+                if let Some(renderable) = &renderable {
+                    // The camera moves once per frame, and everything is culled and drawn from where it ended up
+                    let view_matrix = drone_camera.view_matrix();
+                    let projection_matrix = beagle_math::Mat4::projection_reversed_z((60.0f32).to_radians(), window::WINDOW_WIDTH as f32, window::WINDOW_HEIGHT as f32, 0.1, 5000.0);
+
+                    // Only the meshes that are (at least partly) inside the view frustum are drawn.
+                    // The culling goes through the mesh hierarchy, and gives back each visible mesh with its world matrix.
+                    let frustum = beagle_math::geometry::Frustum::from_view_projection(&view_matrix.mul(&projection_matrix));
+                    let culling_stats = renderable.hierarchy.cull(&frustum, &mut visible_meshes);
+                    // Shown in the window title instead of the console, and only updated when it changes
+                    if culling_stats != last_culling_stats {
+                        let title = format!("Alouette One - drawing {} of {} meshes ({} frustum tests)", culling_stats.visible, culling_stats.mesh_count(), culling_stats.frustum_tests);
+                        let mut window_title : Vec<u16> = OsStr::new(&title).encode_wide().chain( once(0) ).collect();
+                        SetWindowTextW(main_window, PWSTR(window_title.as_mut_ptr()));
+                        last_culling_stats = culling_stats;
                     }
 
-                    if !is_child_of_other_mesh {
-                        root_mesh_indices.push(index as u32);
+                    for visible_mesh in &visible_meshes {
+                        red(
+                            visible_mesh,
+                            &view_matrix,
+                            &projection_matrix,
+                            &renderable.renderables,
+                            &dx_device_context,
+                            vertex_constant_buffer.as_ref().unwrap(),
+                            &drone_camera
+                        );
                     }
                 }
 
-                for root_mesh_index in root_mesh_indices {
-                    red(
-                        root_mesh_index as u32,
-                        &beagle_math::Mat4::identity(),
-                        &renderable.renderables,
-                        &dx_device_context,
-                        vertex_constant_buffer.as_ref().unwrap(),
-                        &mut drone_camera
-                    );
-                }
-
                 if swap_chain.Present(1, 0).is_err() {
                     panic!("Failed to present!");
                 }
@@ -513,21 +598,18 @@ fn main() {
 }
 
 unsafe fn red(
-    index: u32,
-    mat: &beagle_math::Mat4,
+    visible_mesh: &renderable::culling::VisibleMesh,
+    view_matrix: &beagle_math::Mat4,
+    projection_matrix: &beagle_math::Mat4,
     renderable_meshes: &Vec<RenderableMesh>,
     dx_device_context: &ID3D11DeviceContext,
     constant_buffer: &ID3D11Buffer,
-    camera: &mut camera::FreeFlight) -> () {
-    let current_renderable_mesh = &renderable_meshes[index as usize];
+    camera: &camera::FreeFlight) -> () {
+    let current_renderable_mesh = &renderable_meshes[visible_mesh.index];
 
     // Model Matrix
-    // TODO: Need to read up on matrix multiplication order AGAIN... still some detail I'm missing. This is not the order I was expecting myself...
-    let model_matrix = beagle_math::Mat4::scale(&current_renderable_mesh.renderable_mesh_data.scale)
-        .mul(&current_renderable_mesh.renderable_mesh_data.rotation.to_matrix())
-        .mul(&beagle_math::Mat4::translate(&current_renderable_mesh.renderable_mesh_data.translation));
-
-    let combined_matrix = model_matrix.mul(&mat);
+    // The mesh's own transform combined with all of its parents', as worked out while culling
+    let combined_matrix = visible_mesh.world_matrix;
 
     let mapped_resource = dx_device_context.Map(constant_buffer, 0, D3D11_MAP_WRITE_DISCARD, 0);
     if mapped_resource.is_err() {
@@ -536,18 +618,41 @@ unsafe fn red(
 
     let constant_vertex_buffer = mapped_resource.unwrap().pData as *mut VertexConstantBuffer;
 
-    let view_matrix = camera.view_matrix();
-
     let camera_position = camera.get_position();
+
+    // Select the LOD to draw, based on how large the mesh's bounding sphere is on the screen.
+    let renderable_mesh_data = &current_renderable_mesh.renderable_mesh_data;
+    let bounding_sphere_center = renderable_mesh_data.bounding_sphere_center;
+    let world_bounding_sphere_center = combined_matrix.mul_row(&beagle_math::Vector4::new(bounding_sphere_center.x, bounding_sphere_center.y, bounding_sphere_center.z, 1.0));
+
+    // The largest scale along any axis of the world matrix, which is the length of the longest of its three first rows.
+    let world_scale = (0..3)
+        .map(|row| beagle_math::Vector3::new(combined_matrix.get(0, row), combined_matrix.get(1, row), combined_matrix.get(2, row)).length())
+        .fold(0.0, f32::max);
+
+    let distance_to_bounding_sphere = beagle_math::Vector3::new(
+        world_bounding_sphere_center.x - camera_position.x,
+        world_bounding_sphere_center.y - camera_position.y,
+        world_bounding_sphere_center.z - camera_position.z).length() - renderable_mesh_data.bounding_sphere_radius * world_scale;
+
+    let lod_index = renderable_mesh_data.select_lod(world_scale, distance_to_bounding_sphere, (60.0f32).to_radians(), window::WINDOW_HEIGHT as f32, 1.0);
+    let lod_data = &renderable_mesh_data.lods[lod_index];
+    let lod_buffers = &current_renderable_mesh.lods[lod_index];
+
     (*constant_vertex_buffer).cameraPosition = beagle_math::Vector4::new(camera_position.x, camera_position.y, camera_position.z, 0.0);
 
-    (*constant_vertex_buffer).worldViewProjection = combined_matrix.mul(&view_matrix.mul(&beagle_math::Mat4::projection((60.0f32).to_radians(), window::WINDOW_WIDTH as f32, window::WINDOW_HEIGHT as f32, 0.1, 5000.0)));
+    (*constant_vertex_buffer).worldViewProjection = combined_matrix.mul(&view_matrix.mul(projection_matrix));
     (*constant_vertex_buffer).worldViewProjection.tranpose();
 
-    // TODO: Mat4 should implement the Copy/Clone trait!
-    (*constant_vertex_buffer).modelMatrix = beagle_math::Mat4::new(combined_matrix.matrix);
+    (*constant_vertex_buffer).modelMatrix = combined_matrix;
     (*constant_vertex_buffer).modelMatrix.tranpose();
 
+    // Normals have to stay perpendicular to their surfaces, even when the model is scaled unevenly.
+    // A model matrix that squashes the model flat has no normal matrix, nothing of it would be visible anyway.
+    (*constant_vertex_buffer).normalMatrix = beagle_math::Mat3::normal_matrix(&combined_matrix)
+        .unwrap_or_default()
+        .get_padded_column_major_value();
+
     (*constant_vertex_buffer).diffuseColor = beagle_math::Vector4::new(
         current_renderable_mesh.renderable_mesh_data.material.diffuse_color.x,
         current_renderable_mesh.renderable_mesh_data.material.diffuse_color.y,
@@ -575,8 +680,8 @@ unsafe fn red(
         0,
         2,
         ([
-            Some(current_renderable_mesh.vertex_buffer.clone()),
-            Some(current_renderable_mesh.normals_buffer.clone())
+            Some(lod_buffers.vertex_buffer.clone()),
+            Some(lod_buffers.normals_buffer.clone())
         ]).as_ptr(),
         ([
             (mem::size_of::<beagle_math::Vector3>()) as u32,
@@ -588,36 +693,28 @@ unsafe fn red(
         ]).as_ptr()
     );
 
+    dx_device_context.IASetIndexBuffer(
+        &lod_buffers.index_buffer,
+        lod_data.indices.format(),
+        0);
+
     dx_device_context.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
     dx_device_context.VSSetShader(&vertex_shader, ptr::null(), 0);
     dx_device_context.IASetInputLayout(&vertex_shader_input_layout);
-    dx_device_context.Draw(current_renderable_mesh.renderable_mesh_data.vertex_positions.len() as u32, 0);
+    dx_device_context.DrawIndexed(lod_data.indices.len() as u32, 0, 0);
 
     dx_device_context.IASetVertexBuffers(
         0, 
         1,
        ([
-           Some(current_renderable_mesh.debug_vertex_normals_buffer.clone())
+           Some(lod_buffers.debug_vertex_normals_buffer.clone())
            ]).as_ptr(),
         [(mem::size_of::<beagle_math::Vector3>() as u32)].as_ptr(),
         [0].as_ptr());
     dx_device_context.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_LINELIST);
     dx_device_context.VSSetShader(&vertex_normal_shader, ptr::null(), 0);
     dx_device_context.IASetInputLayout(&vertex_normal_shader_input_layout);
-    dx_device_context.Draw(current_renderable_mesh.renderable_mesh_data.debug_vertex_normals.len() as u32, 0);
-
-    if current_renderable_mesh.renderable_mesh_data.children.len() > 0 {
-        for child_index in &current_renderable_mesh.renderable_mesh_data.children {
-            red(
-                *child_index as u32,
-                &combined_matrix,
-                renderable_meshes,
-                dx_device_context,
-                constant_buffer,
-                camera
-            )
-        }
-    }
+    dx_device_context.Draw(lod_data.debug_vertex_normals.len() as u32, 0);
 }
 
 fn prepare_vertex_normals_shader(dx_device: &ID3D11Device, compiled_shader_code: &Vec<u8>) -> ID3D11VertexShader {
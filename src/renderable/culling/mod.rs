@@ -0,0 +1,309 @@
+use crate::asset;
+use crate::beagle_math;
+use crate::beagle_math::geometry::{Aabb, Containment, Frustum};
+
+/*
+    Finds out which meshes of a model are inside the view frustum, so only those get drawn.
+
+    Every mesh gets a bounding box around its own vertices, and one around itself and everything below it in the hierarchy
+    (its subtree), both in the mesh's own space. Going down the hierarchy:
+        - A subtree that is completely outside the frustum is skipped, children and all, without testing them.
+        - A subtree that is completely inside is drawn, children and all, without testing them either.
+        - Otherwise the mesh's own box decides whether it's drawn, and its children are tested one by one.
+
+    Everything here is on the CPU, nothing needs a GPU to run.
+*/
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CullingStats {
+    pub visible: u32,
+    pub culled: u32,
+    // How many boxes were tested against the frustum, which is less than the number of meshes when whole subtrees are decided at once
+    pub frustum_tests: u32
+}
+
+impl CullingStats {
+    pub fn mesh_count(&self) -> u32 {
+        self.visible + self.culled
+    }
+}
+
+pub struct VisibleMesh {
+    // The index of the mesh in the model
+    pub index: usize,
+    // The mesh's transform combined with all of its parents'
+    pub world_matrix: beagle_math::Mat4
+}
+
+struct Node {
+    local_matrix: beagle_math::Mat4,
+    // None for meshes without vertices, which have nothing to draw
+    bounds: Option<Aabb>,
+    subtree_bounds: Option<Aabb>,
+    subtree_size: u32,
+    children: Vec<usize>
+}
+
+pub struct Hierarchy {
+    nodes: Vec<Node>,
+    roots: Vec<usize>
+}
+
+impl Hierarchy {
+    pub fn from_model(model: &asset::mesh::Model) -> Hierarchy {
+        let mut nodes: Vec<Node> = model.meshes
+            .iter()
+            .map(|mesh| Node {
+                local_matrix: mesh.transform.to_matrix(),
+                bounds: Aabb::from_points(&mesh.vertex_positions),
+                subtree_bounds: None,
+                subtree_size: 0,
+                children: mesh.children.iter().map(|child| *child as usize).filter(|child| *child < model.meshes.len()).collect()
+            })
+            .collect();
+
+        // The meshes that aren't the child of any other mesh
+        let mut is_child = vec![false; nodes.len()];
+        for node in &nodes {
+            for child in &node.children {
+                is_child[*child] = true;
+            }
+        }
+
+        /*
+            Every mesh is reached exactly once going down from the roots. A link to a mesh that was already reached
+            (a second parent, or a cycle back up the hierarchy) is dropped, so a broken file still gives a tree,
+            and walking it always ends. Meshes only reachable through a cycle become roots themselves.
+            The walk uses a stack of its own rather than recursion, so a very deep hierarchy can't overflow the thread's stack.
+        */
+        let mut reached = vec![false; nodes.len()];
+        let mut roots: Vec<usize> = vec!();
+        // Parents before their children
+        let mut order: Vec<usize> = Vec::with_capacity(nodes.len());
+
+        let starts = (0..nodes.len()).filter(|index| !is_child[*index]).chain(0..nodes.len());
+        for start in starts {
+            if reached[start] {
+                continue;
+            }
+
+            reached[start] = true;
+            roots.push(start);
+
+            let mut stack = vec![start];
+            while let Some(index) = stack.pop() {
+                order.push(index);
+
+                nodes[index].children.retain(|child| !std::mem::replace(&mut reached[*child], true));
+                stack.extend(nodes[index].children.iter().rev());
+            }
+        }
+
+        for index in order.iter().rev() {
+            calculate_subtree_bounds(&mut nodes, *index);
+        }
+
+        Hierarchy { nodes, roots }
+    }
+
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    // Fills visible with the meshes that are (at least partly) inside the frustum, parents before their children
+    pub fn cull(&self, frustum: &Frustum, visible: &mut Vec<VisibleMesh>) -> CullingStats {
+        visible.clear();
+        let mut stats = CullingStats::default();
+
+        // The mesh, its parent's world matrix, and whether the parent's subtree is known to be completely inside.
+        // Popped in the same order a recursive walk would go through the meshes.
+        let mut stack: Vec<(usize, beagle_math::Mat4, bool)> = self.roots.iter().rev().map(|root| (*root, beagle_math::Mat4::identity(), false)).collect();
+
+        while let Some((index, parent_matrix, parent_inside)) = stack.pop() {
+            let node = &self.nodes[index];
+            let world_matrix = node.local_matrix.mul(&parent_matrix);
+
+            let mut inside = parent_inside;
+            if !inside {
+                let subtree_containment = match &node.subtree_bounds {
+                    Some(bounds) => {
+                        stats.frustum_tests += 1;
+                        frustum.classify_aabb(&bounds.transformed(&world_matrix))
+                    },
+                    None => Containment::Outside
+                };
+
+                match subtree_containment {
+                    Containment::Outside => {
+                        stats.culled += node.subtree_size;
+                        continue;
+                    },
+                    Containment::Inside => inside = true,
+                    Containment::Intersecting => {}
+                }
+            }
+
+            // Without children, the subtree's box is the mesh's own box, and it was just tested
+            let is_visible = match &node.bounds {
+                Some(_) if inside || node.children.is_empty() => true,
+                Some(bounds) => {
+                    stats.frustum_tests += 1;
+                    frustum.intersects_aabb(&bounds.transformed(&world_matrix))
+                },
+                None => false
+            };
+
+            if is_visible {
+                stats.visible += 1;
+                visible.push(VisibleMesh { index, world_matrix });
+            } else {
+                stats.culled += 1;
+            }
+
+            for child in node.children.iter().rev() {
+                stack.push((*child, world_matrix, inside));
+            }
+        }
+
+        stats
+    }
+}
+
+// The children's subtrees have to be done already
+fn calculate_subtree_bounds(nodes: &mut [Node], index: usize) {
+    let mut subtree_bounds = nodes[index].bounds;
+    let mut subtree_size = 1;
+
+    for child in &nodes[index].children {
+        let child_node = &nodes[*child];
+        subtree_size += child_node.subtree_size;
+
+        // The child's box is in the child's space, so it has to be moved into this mesh's space
+        if let Some(child_bounds) = child_node.subtree_bounds.map(|bounds| bounds.transformed(&child_node.local_matrix)) {
+            subtree_bounds = Some(subtree_bounds.map_or(child_bounds, |bounds| bounds.union(&child_bounds)));
+        }
+    }
+
+    nodes[index].subtree_bounds = subtree_bounds;
+    nodes[index].subtree_size = subtree_size;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::mesh::primitives;
+    use crate::beagle_math::geometry::Plane;
+    use crate::renderable::culling::*;
+
+    // The box from -10 to 10 along every axis, as a frustum
+    fn box_frustum() -> Frustum {
+        let plane = |x: f32, y: f32, z: f32| Plane::new(beagle_math::Vector3::new(x, y, z), 10.0);
+        Frustum::new([
+            plane(1.0, 0.0, 0.0), plane(-1.0, 0.0, 0.0),
+            plane(0.0, 1.0, 0.0), plane(0.0, -1.0, 0.0),
+            plane(0.0, 0.0, 1.0), plane(0.0, 0.0, -1.0)
+        ])
+    }
+
+    fn cube_at(name: &str, x: f32, children: Vec<u16>) -> asset::mesh::Mesh {
+        let mut mesh = primitives::create_box(&beagle_math::Vector3::new(1.0, 1.0, 1.0), 1);
+        mesh.name = String::from(name);
+        mesh.transform.translation = beagle_math::Vector3::new(x, 0.0, 0.0);
+        mesh.children = children;
+        mesh
+    }
+
+    fn visible_names(model: &asset::mesh::Model, visible: &[VisibleMesh]) -> Vec<String> {
+        visible.iter().map(|mesh| model.meshes[mesh.index].name.clone()).collect()
+    }
+
+    #[test]
+    fn should_only_keep_meshes_inside_frustum() {
+        // Children are placed relative to their parents: far_child ends up at 4 + 20 = 24, outside
+        let model = asset::mesh::Model { meshes: vec![
+            cube_at("root", 4.0, vec![1, 2]),
+            cube_at("near_child", 2.0, vec![]),
+            cube_at("far_child", 20.0, vec![]),
+            cube_at("outside", -30.0, vec![]),
+            cube_at("straddling", 10.0, vec![])
+        ]};
+        let hierarchy = Hierarchy::from_model(&model);
+        let mut visible: Vec<VisibleMesh> = vec!();
+
+        let stats = hierarchy.cull(&box_frustum(), &mut visible);
+
+        assert_eq!(hierarchy.roots(), &[0, 3, 4]);
+        assert_eq!(visible_names(&model, &visible), vec!["root", "near_child", "straddling"]);
+        assert_eq!(stats, CullingStats { visible: 3, culled: 2, frustum_tests: 6 });
+        assert_eq!(stats.mesh_count(), 5);
+
+        let near_child_position = visible[1].world_matrix.get(0, 3);
+        assert_eq!(near_child_position, 6.0);
+    }
+
+    #[test]
+    fn should_decide_whole_subtree_at_once_when_it_is_outside_or_inside() {
+        let model = asset::mesh::Model { meshes: vec![
+            cube_at("outside_root", 50.0, vec![1]),
+            // Would be inside on its own, but it's moved along with its parent
+            cube_at("outside_child", -48.0, vec![2]),
+            cube_at("outside_grandchild", -1.0, vec![]),
+            cube_at("inside_root", 0.0, vec![4, 5]),
+            cube_at("inside_child", 2.0, vec![]),
+            cube_at("inside_child", -2.0, vec![])
+        ]};
+        let hierarchy = Hierarchy::from_model(&model);
+        let mut visible: Vec<VisibleMesh> = vec!();
+
+        let stats = hierarchy.cull(&box_frustum(), &mut visible);
+
+        // The outside subtree's box includes its children, which reach back into the frustum, so only the root is culled on its own
+        assert_eq!(visible_names(&model, &visible), vec!["outside_child", "outside_grandchild", "inside_root", "inside_child", "inside_child"]);
+        assert_eq!(stats.visible, 5);
+        assert_eq!(stats.culled, 1);
+
+        // Moved far away, the whole model is culled with a single test per root
+        let far_away = asset::mesh::Model { meshes: model.meshes.into_iter().map(|mut mesh| {
+            mesh.transform.translation.y += 100.0;
+            mesh
+        }).collect() };
+        let stats = Hierarchy::from_model(&far_away).cull(&box_frustum(), &mut visible);
+
+        assert!(visible.is_empty());
+        assert_eq!(stats, CullingStats { visible: 0, culled: 6, frustum_tests: 2 });
+    }
+
+    #[test]
+    fn should_break_cycles_and_shared_children_in_hierarchy() {
+        // 0 -> 1 -> 2 -> 1 is a cycle, 3 -> 4 <- 0 gives 4 two parents, and 5 <-> 6 is a cycle nothing else points into
+        let model = asset::mesh::Model { meshes: vec![
+            cube_at("root", 0.0, vec![1, 4]),
+            cube_at("cycle_a", 1.0, vec![2]),
+            cube_at("cycle_b", 1.0, vec![1]),
+            cube_at("other_root", 0.0, vec![4]),
+            cube_at("shared", 1.0, vec![]),
+            cube_at("loop_a", 0.0, vec![6]),
+            cube_at("loop_b", 0.0, vec![5])
+        ]};
+        let hierarchy = Hierarchy::from_model(&model);
+        let mut visible: Vec<VisibleMesh> = vec!();
+
+        let stats = hierarchy.cull(&box_frustum(), &mut visible);
+
+        assert_eq!(hierarchy.roots(), &[0, 3, 5]);
+        assert_eq!(visible_names(&model, &visible), vec!["root", "cycle_a", "cycle_b", "shared", "other_root", "loop_a", "loop_b"]);
+        assert_eq!(stats.mesh_count(), 7);
+    }
+
+    #[test]
+    fn should_cull_meshes_without_vertices() {
+        let mut empty = cube_at("empty", 0.0, vec![1]);
+        empty.vertex_positions.clear();
+        let model = asset::mesh::Model { meshes: vec![empty, cube_at("child", 1.0, vec![])] };
+        let mut visible: Vec<VisibleMesh> = vec!();
+
+        let stats = Hierarchy::from_model(&model).cull(&box_frustum(), &mut visible);
+
+        assert_eq!(visible_names(&model, &visible), vec!["child"]);
+        assert_eq!(stats.culled, 1);
+    }
+}
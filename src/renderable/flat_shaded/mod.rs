@@ -16,6 +16,7 @@ use windows::{
 use crate::beagle_math;
 use crate::asset;
 use crate::dx;
+use crate::renderable::culling;
 
 enum Usage {
     GpuReadWrite = D3D11_USAGE_DEFAULT as isize,
@@ -35,7 +36,9 @@ enum CpuAccess {
 }
 
 pub struct Renderable {
-    pub renderables: Vec<RenderableMesh>
+    pub renderables: Vec<RenderableMesh>,
+    // For finding out which of the renderables are visible, in the same order
+    pub hierarchy: culling::Hierarchy
 }
 
 impl Renderable {
@@ -43,21 +46,41 @@ impl Renderable {
         let mut renderables: Vec<RenderableMesh> = vec!();
 
         for renderable_mesh_data in render_data.renderable_mesh_data {
+            let lods: Vec<RenderableLod> = renderable_mesh_data.lods
+                .iter()
+                .map(|lod_data| {
+                    let index_buffer = match &lod_data.indices {
+                        IndexBuffer::U16(indices) => Renderable::create_buffer::<u16>(BufferType::Index, Usage::GpuReadWrite, CpuAccess::None, indices),
+                        IndexBuffer::U32(indices) => Renderable::create_buffer::<u32>(BufferType::Index, Usage::GpuReadWrite, CpuAccess::None, indices)
+                    };
+
+                    RenderableLod {
+                        vertex_buffer: Renderable::create_buffer::<beagle_math::Vector3>(BufferType::Vertex, Usage::GpuReadWrite, CpuAccess::None, &lod_data.vertex_positions),
+                        normals_buffer: Renderable::create_buffer::<beagle_math::Vector3>(BufferType::Vertex, Usage::GpuReadWrite, CpuAccess::None, &lod_data.vertex_normals),
+                        index_buffer,
+                        debug_vertex_normals_buffer: Renderable::create_buffer::<beagle_math::Vector3>(BufferType::Vertex, Usage::GpuReadWrite, CpuAccess::None, &lod_data.debug_vertex_normals)
+                    }
+                }).collect();
+
             renderables.push(
                 RenderableMesh {
-                    vertex_buffer: Renderable::create_buffer::<beagle_math::Vector3>(BufferType::Vertex, Usage::GpuReadWrite, CpuAccess::None, &renderable_mesh_data.vertex_positions),
-                    normals_buffer: Renderable::create_buffer::<beagle_math::Vector3>(BufferType::Vertex, Usage::GpuReadWrite, CpuAccess::None, &renderable_mesh_data.vertex_normals),
-                    debug_vertex_normals_buffer: Renderable::create_buffer::<beagle_math::Vector3>(BufferType::Vertex, Usage::GpuReadWrite, CpuAccess::None, &renderable_mesh_data.debug_vertex_normals),
+                    lods,
                     renderable_mesh_data: renderable_mesh_data
                 }
             )
         }
 
         Renderable {
-            renderables
+            renderables,
+            hierarchy: render_data.hierarchy
         }
     }
 
+    // For when the model was hot reloaded. All buffers are created again, the old ones are released when they're dropped.
+    pub fn rebuild(&mut self, model: &asset::mesh::Model) {
+        *self = Renderable::from_render_data(RenderData::from_model(model));
+    }
+
     fn create_buffer<T>(bufferType: BufferType, usage: Usage, cpu_access: CpuAccess, initial_data: &[T]) -> ID3D11Buffer {
         unsafe {
             let buffer_description = D3D11_BUFFER_DESC {
@@ -87,13 +110,20 @@ impl Renderable {
 
 pub struct RenderableMesh {
     pub renderable_mesh_data: RenderableMeshData,
+    // GPU buffers for each LOD, matching the order of renderable_mesh_data.lods
+    pub lods: Vec<RenderableLod>
+}
+
+pub struct RenderableLod {
     pub vertex_buffer: ID3D11Buffer,
     pub normals_buffer: ID3D11Buffer,
+    pub index_buffer: ID3D11Buffer,
     pub debug_vertex_normals_buffer: ID3D11Buffer
 }
 
 pub struct RenderData {
-    pub renderable_mesh_data: Vec<RenderableMeshData>
+    pub renderable_mesh_data: Vec<RenderableMeshData>,
+    pub hierarchy: culling::Hierarchy
 }
 
 impl RenderData {
@@ -103,39 +133,97 @@ impl RenderData {
             .map(|mesh| {
                 let name = mesh.name.clone();
                 let children = mesh.children.clone();
-                let translation = mesh.translation;
-                let scale = mesh.scale;
-                let rotation = mesh.rotation;
+                let transform = mesh.transform;
                 let material = Material {
                     diffuse_color: mesh.material.diffuse_color,
                     ambient_color: mesh.material.ambient_color,
                     specular_color: mesh.material.specular_color,
                     shininess_factor: mesh.material.shininess_factor
                 };
-                let vertex_positions= RenderData::expand_vertex_buffer_by_indices(&mesh.indices, &mesh.vertex_positions);
-                let vertex_normals = RenderData::calculate_vertex_normals(&vertex_positions);
-                let debug_vertex_normals = RenderData::create_vertex_normal_debug_buffer(&vertex_positions, &vertex_normals);
+
+                // The full detail mesh is always the first LOD, followed by the simplified LODs of the mesh, if any.
+                let mut lods: Vec<RenderableLodData> = vec!(RenderData::create_lod_data(mesh, &mesh.indices, 0.0));
+                for lod in &mesh.lods {
+                    lods.push(RenderData::create_lod_data(mesh, &lod.indices, lod.error));
+                }
+
+                let (bounding_sphere_center, bounding_sphere_radius) = RenderData::calculate_bounding_sphere(&mesh.vertex_positions);
 
                 RenderableMeshData {
                     name,
                     children,
                     material,
-                    translation,
-                    scale,
-                    rotation,
-                    vertex_positions,
-                    vertex_normals,
-                    debug_vertex_normals
+                    transform,
+                    lods,
+                    bounding_sphere_center,
+                    bounding_sphere_radius
                 }
             }).collect();
 
         RenderData { 
-            renderable_mesh_data: renderable_meshes 
+            renderable_mesh_data: renderable_meshes,
+            hierarchy: culling::Hierarchy::from_model(model)
         }
     }
 
-    fn expand_vertex_buffer_by_indices<T: Copy>(indices: &Vec<u16>, vertex_buffer: &Vec<T>) -> Vec<T> {
-        indices.iter().map(|x| vertex_buffer.get((*x) as usize).unwrap().clone()).collect()
+    fn create_lod_data(mesh: &asset::mesh::Mesh, indices: &Vec<u32>, error: f32) -> RenderableLodData {
+        // Flat shading needs a vertex normal per triangle corner, but corners only need to become separate vertices
+        // when their normals actually differ. Welding merges all the corners that ended up identical.
+        let flat_shaded_vertices = RenderData::create_flat_shaded_vertices(mesh, indices);
+        let (welded_vertices, welded_indices) = asset::mesh::weld_vertices(&flat_shaded_vertices);
+
+        let vertex_positions: Vec<beagle_math::Vector3> = welded_vertices.iter().map(|vertex| vertex.position).collect();
+        let vertex_normals: Vec<beagle_math::Vector3> = welded_vertices.iter().map(|vertex| vertex.normal).collect();
+        let indices = IndexBuffer::from_indices(&welded_indices, welded_vertices.len());
+        let debug_vertex_normals = RenderData::create_vertex_normal_debug_buffer(&vertex_positions, &vertex_normals);
+
+        RenderableLodData {
+            vertex_positions,
+            vertex_normals,
+            indices,
+            debug_vertex_normals,
+            error
+        }
+    }
+
+    // A simple bounding sphere, centered on the middle of the mesh's bounding box.
+    // It isn't the tightest possible sphere, but it's good enough for LOD selection.
+    fn calculate_bounding_sphere(vertex_positions: &Vec<beagle_math::Vector3>) -> (beagle_math::Vector3, f32) {
+        if vertex_positions.is_empty() {
+            return (beagle_math::Vector3::zero(), 0.0);
+        }
+
+        let mut min = vertex_positions[0];
+        let mut max = vertex_positions[0];
+        for position in vertex_positions {
+            min = min.min(position);
+            max = max.max(position);
+        }
+
+        let center = (min + max) * 0.5;
+        let radius = vertex_positions
+            .iter()
+            .map(|position| (*position - center).length())
+            .fold(0.0, f32::max);
+
+        (center, radius)
+    }
+
+    // Creates three vertices per triangle of the mesh, each carrying the normal of the triangle it belongs to.
+    fn create_flat_shaded_vertices(mesh: &asset::mesh::Mesh, indices: &Vec<u32>) -> Vec<asset::mesh::Vertex> {
+        let vertex_normals = RenderData::calculate_vertex_normals(&mesh.vertex_positions, indices);
+
+        indices
+            .iter()
+            .zip(vertex_normals.iter())
+            .map(|(index, vertex_normal)| {
+                asset::mesh::Vertex {
+                    position: mesh.vertex_positions[*index as usize],
+                    normal: *vertex_normal,
+                    uv: mesh.vertex_uvs.get(*index as usize).copied().unwrap_or_default(),
+                    color: mesh.vertex_colors.get(*index as usize).copied().unwrap_or_default()
+                }
+            }).collect()
     }
 
     // This buffer is a list of point pairs, each pair being the position of a vertex coupled with a point representing the end of its vertex normal.
@@ -148,7 +236,9 @@ impl RenderData {
         for (index, vertex_normal) in vertex_normals.iter().enumerate() {
             // When working with vectors, it's important to realize if you're working on something representing a position or a direction.
             // Because it makes no sense to normalize a vector representing a position. That will screw with your positions, obviously.
-            let scaled_vertex_normal = vertex_normal.mul(0.2 / vertex_normal.length());
+            // Zero normals (from degenerate triangles) have no direction to draw, so they are drawn with no length instead of dividing by zero.
+            let length = vertex_normal.length();
+            let scaled_vertex_normal = if length > 0.0 { vertex_normal.mul(0.2 / length) } else { beagle_math::Vector3::zero() };
 
             let vertex_normal_end_relative_to_vertex_position = beagle_math::Vector3::new(
                 vertex_positions[index].x + scaled_vertex_normal.x,
@@ -164,36 +254,30 @@ impl RenderData {
     }
 
     /*
-        This method assumes a list of vertices, with 3 vertices making up a triangle.
-        Each 3 vertice making up one triangle will get the same vertex normal, representing perpedicularity to the same surface.
+        This method assumes a list of indices, with 3 indices making up a triangle.
+        Each 3 indices making up one triangle will get the same vertex normal, representing perpedicularity to the same surface.
+        The result has one vertex normal per index.
 
         Trying out this technique: https://computergraphics.stackexchange.com/questions/4031/programmatically-generating-vertex-normals
     */
-    fn calculate_vertex_normals(vertex_positions: &Vec<beagle_math::Vector3>) -> Vec<beagle_math::Vector3> {
-        if vertex_positions.len() % 3 != 0 {
-            panic!("The list of vertex positions is not divisible by 3, which is required as this method assumes a primitive topology of triangles.")
+    fn calculate_vertex_normals(vertex_positions: &Vec<beagle_math::Vector3>, indices: &Vec<u32>) -> Vec<beagle_math::Vector3> {
+        if indices.len() % 3 != 0 {
+            panic!("The list of indices is not divisible by 3, which is required as this method assumes a primitive topology of triangles.")
         }
 
         let mut vertex_normals: Vec<beagle_math::Vector3> = vec!();
 
-        for vertex_position_index in (0..vertex_positions.len()).step_by(3) {
-            let vert1 = vertex_positions[vertex_position_index];
-            let vert2 = vertex_positions[vertex_position_index + 1];
-            let vert3 = vertex_positions[vertex_position_index + 2];
-
-            let edge1 = beagle_math::Vector3::new(
-                vert2.x - vert1.x,
-                vert2.y - vert1.y,
-                vert2.z - vert1.z,
-            );
+        for triangle in indices.chunks(3) {
+            let vert1 = vertex_positions[triangle[0] as usize];
+            let vert2 = vertex_positions[triangle[1] as usize];
+            let vert3 = vertex_positions[triangle[2] as usize];
 
-            let edge2 = beagle_math::Vector3::new(
-                vert3.x - vert1.x,
-                vert3.y - vert1.y,
-                vert3.z - vert1.z,
-            );
+            let edge1 = vert2 - vert1;
+            let edge2 = vert3 - vert1;
 
-            let vertex_normal = edge1.cross(&edge2).normalized();
+            // A degenerate triangle (zero area) has no surface to be perpendicular to, and normalizing its zero length cross product
+            // divides by zero, giving NaN normals which then spread into the lighting. Those triangles get a zero normal instead.
+            let vertex_normal = edge1.cross(&edge2).try_normalize().unwrap_or_else(beagle_math::Vector3::zero);
 
             vertex_normals.push(vertex_normal);
             vertex_normals.push(vertex_normal);
@@ -208,12 +292,86 @@ pub struct RenderableMeshData {
     pub name: String,
     pub children: Vec<u16>,
     pub material: Material,
-    pub translation: beagle_math::Vector3,
-    pub scale: beagle_math::Vector3,
-    pub rotation: beagle_math::Quaternion,
+    pub transform: beagle_math::Transform,
+    // The first LOD is the full detail mesh, and every following LOD is coarser than the one before it.
+    pub lods: Vec<RenderableLodData>,
+    pub bounding_sphere_center: beagle_math::Vector3,
+    pub bounding_sphere_radius: f32
+}
+
+impl RenderableMeshData {
+    /*
+        Selects the coarsest LOD whose error, projected onto the screen, stays below the given number of pixels.
+
+        An error of "e" units seen at "distance" units from the camera covers e / (distance * tan(fov / 2)) of half the screen height.
+        So the further away the mesh is, the more error we can accept without anyone noticing.
+
+        - world_scale: How much the mesh is scaled by its world matrix, as errors are measured in the mesh's own units.
+        - distance: Distance from the camera to the closest point of the mesh's bounding sphere.
+    */
+    pub fn select_lod(&self, world_scale: f32, distance: f32, vertical_fov: f32, screen_height: f32, max_pixel_error: f32) -> usize {
+        // Inside or touching the bounding sphere, full detail is always used.
+        if distance <= 0.0 {
+            return 0;
+        }
+
+        let pixels_per_unit = (screen_height * 0.5) / (distance * (vertical_fov * 0.5).tan());
+
+        let mut selected_lod = 0;
+        for (lod_index, lod) in self.lods.iter().enumerate() {
+            if lod.error * world_scale * pixels_per_unit <= max_pixel_error {
+                selected_lod = lod_index;
+            } else {
+                break;
+            }
+        }
+
+        selected_lod
+    }
+}
+
+pub struct RenderableLodData {
     pub vertex_positions: Vec<beagle_math::Vector3>,
     pub vertex_normals: Vec<beagle_math::Vector3>,
-    pub debug_vertex_normals: Vec<beagle_math::Vector3>
+    pub indices: IndexBuffer,
+    pub debug_vertex_normals: Vec<beagle_math::Vector3>,
+    // See asset::mesh::Lod for what the error means. It is 0 for the full detail LOD.
+    pub error: f32
+}
+
+/*
+    Index buffers are either 16 or 32 bits per index.
+    16 bit indices use half the memory, so they are used whenever the vertex count of a mesh allows it.
+*/
+pub enum IndexBuffer {
+    U16(Vec<u16>),
+    U32(Vec<u32>)
+}
+
+impl IndexBuffer {
+    pub fn from_indices(indices: &[u32], vertex_count: usize) -> IndexBuffer {
+        // The largest u16 value (0xFFFF) is reserved as the "strip cut" value for strip topologies,
+        // so to be on the safe side I never use it as an actual index.
+        if vertex_count <= u16::MAX as usize {
+            IndexBuffer::U16(indices.iter().map(|x| *x as u16).collect())
+        } else {
+            IndexBuffer::U32(indices.to_vec())
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            IndexBuffer::U16(indices) => indices.len(),
+            IndexBuffer::U32(indices) => indices.len()
+        }
+    }
+
+    pub fn format(&self) -> DXGI_FORMAT {
+        match self {
+            IndexBuffer::U16(_) => DXGI_FORMAT_R16_UINT,
+            IndexBuffer::U32(_) => DXGI_FORMAT_R32_UINT
+        }
+    }
 }
 
 pub struct Material {
@@ -221,4 +379,37 @@ pub struct Material {
     pub ambient_color: beagle_math::Vector3,
     pub specular_color: beagle_math::Vector3,
     pub shininess_factor: f32
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderable::flat_shaded::*;
+
+    #[test]
+    fn should_use_16_bit_indices_up_to_65535_vertices() {
+        let indices: Vec<u32> = vec!(0, 1, 65534);
+
+        let index_buffer = IndexBuffer::from_indices(&indices, 65535);
+
+        assert_eq!(index_buffer.format(), DXGI_FORMAT_R16_UINT);
+        match index_buffer {
+            IndexBuffer::U16(converted) => assert_eq!(converted, vec!(0, 1, 65534)),
+            IndexBuffer::U32(_) => panic!("Expected 16 bit indices")
+        }
+    }
+
+    #[test]
+    fn should_use_32_bit_indices_from_65536_vertices() {
+        // With 65536 vertices index 65535 is needed, which is the strip cut value for 16 bit indices
+        let indices: Vec<u32> = vec!(0, 1, 65535);
+
+        let index_buffer = IndexBuffer::from_indices(&indices, 65536);
+
+        assert_eq!(index_buffer.format(), DXGI_FORMAT_R32_UINT);
+        assert_eq!(index_buffer.len(), 3);
+        match index_buffer {
+            IndexBuffer::U32(converted) => assert_eq!(converted, indices),
+            IndexBuffer::U16(_) => panic!("Expected 32 bit indices")
+        }
+    }
+}
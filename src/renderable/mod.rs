@@ -1 +1,2 @@
-pub mod flat_shaded;
\ No newline at end of file
+pub mod culling;
+pub mod flat_shaded;
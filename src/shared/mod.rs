@@ -7,6 +7,20 @@ pub trait FromBinary: Sized {
     fn from_binary_collection(binary: &[u8]) -> Vec<Self>;
 }
 
+impl FromBinary for u8 {
+    fn from_binary(binary: &[u8]) -> Self {
+        if binary.len() != size_of::<u8>() {
+            panic!("Length of binary buffer is not the byte length of u8, which is {}", size_of::<u8>());
+        }
+
+        binary[0]
+    }
+
+    fn from_binary_collection(binary: &[u8]) -> Vec<Self> {
+        binary.to_vec()
+    }
+}
+
 impl FromBinary for u16 {
     fn from_binary(binary: &[u8]) -> Self {
         if binary.len() % size_of::<u16>() != 0 {
@@ -61,4 +75,26 @@ impl FromBinary for u32 {
 
         result
     }
-}
\ No newline at end of file
+}
+
+impl FromBinary for f32 {
+    fn from_binary(binary: &[u8]) -> Self {
+        if binary.len() != size_of::<f32>() {
+            panic!("Length of binary buffer is not the byte length of f32, which is {}", size_of::<f32>());
+        }
+
+        LittleEndian::read_f32(binary)
+    }
+
+    fn from_binary_collection(binary: &[u8]) -> Vec<Self> {
+        let size_of_f32_in_bytes = size_of::<f32>();
+        if binary.len() % size_of_f32_in_bytes != 0 {
+            panic!("Length of binary buffer is not divisible by byte length of f32, which is {}", size_of_f32_in_bytes);
+        }
+
+        binary
+            .chunks(size_of_f32_in_bytes)
+            .map(LittleEndian::read_f32)
+            .collect()
+    }
+}
@@ -0,0 +1,648 @@
+use std::fs;
+use std::path::Path;
+
+use byteorder::{LittleEndian, ByteOrder};
+
+use crate::beagle_math;
+use crate::asset::mesh::Mesh;
+
+/*
+    Terrain built from a heightmap: a grid of height samples, evenly spaced over the XZ plane.
+
+    The heightmap can come from a 16 bit raw file, a 8 or 16 bit grayscale PNG, or from a noise function.
+    Heights are stored normalized between 0 and 1, and multiplied by the height scale of the terrain.
+
+    The terrain starts at the origin and grows along +X and +Z, with heightmap sample (column, row) at
+    (column * cell_size, height, row * cell_size). Row 0 of a heightmap image is its top line, which ends up at Z = 0.
+
+    Big terrains can't be one mesh, so the terrain is split into square chunks of chunk_size x chunk_size cells.
+    Each chunk can be built at a different level of detail (LOD). LOD n only uses every 2^n-th sample, so far away chunks
+    take a lot fewer triangles.
+
+    Where a detailed chunk meets a coarser one, the detailed chunk has vertices along the shared edge that the coarse chunk doesn't,
+    which would leave small cracks in the ground. Those vertices are "stitched": their height is moved onto the straight line
+    between the coarse chunk's vertices, so both chunks have exactly the same edge.
+    On top of that, every chunk can get a skirt: a strip of triangles hanging down from its edges, hiding any gap that's left
+    (for example while neighbouring chunks are being rebuilt).
+*/
+
+pub struct Heightmap {
+    pub width: u32,
+    pub depth: u32,
+    // Row by row, normalized between 0 and 1
+    pub heights: Vec<f32>
+}
+
+#[derive(Debug, Clone)]
+pub struct NoiseSettings {
+    pub seed: u32,
+    // Number of noise waves per heightmap sample. Small values give big, smooth hills.
+    pub frequency: f32,
+    // Number of layers of noise added together, each one with finer details than the last
+    pub octaves: u32,
+    // How much weaker each octave is than the one before it
+    pub persistence: f32,
+    // How much finer each octave is than the one before it
+    pub lacunarity: f32
+}
+
+#[derive(Debug, Clone)]
+pub struct TerrainSettings {
+    // Distance between two neighbouring heightmap samples
+    pub cell_size: f32,
+    // Height of a heightmap value of 1
+    pub height_scale: f32,
+    // Number of cells along each side of a chunk. Has to be a power of 2.
+    pub chunk_size: u32,
+    // Number of levels of detail. The coarsest LOD still needs at least 1 cell per chunk.
+    pub lod_count: u32,
+    // How far skirts hang down from the chunk edges. 0 disables skirts.
+    pub skirt_depth: f32
+}
+
+pub struct Terrain {
+    pub heightmap: Heightmap,
+    pub settings: TerrainSettings,
+    pub chunks_x: u32,
+    pub chunks_z: u32
+}
+
+// width * depth, or an error if that doesn't fit in memory's address range
+fn sample_count(width: u32, depth: u32) -> Result<usize, String> {
+    (width as usize).checked_mul(depth as usize).ok_or_else(|| format!("A heightmap of {}x{} is too large.", width, depth))
+}
+
+impl Heightmap {
+    // Raw files are just the samples, row by row, as little endian unsigned 16 bit values
+    pub fn from_raw16(binary_data: &[u8], width: u32, depth: u32) -> Result<Heightmap, String> {
+        let expected_length = sample_count(width, depth)?
+            .checked_mul(2)
+            .ok_or_else(|| format!("A raw heightmap of {}x{} is too large.", width, depth))?;
+        if binary_data.len() != expected_length {
+            return Err(format!("Raw heightmap of {}x{} should be {} bytes, but is {} bytes.", width, depth, expected_length, binary_data.len()));
+        }
+
+        let heights = binary_data
+            .chunks_exact(2)
+            .map(|sample| LittleEndian::read_u16(sample) as f32 / u16::MAX as f32)
+            .collect();
+
+        Heightmap::new(width, depth, heights)
+    }
+
+    pub fn from_raw16_file<P: AsRef<Path>>(path: P, width: u32, depth: u32) -> Result<Heightmap, String> {
+        let binary_data = fs::read(&path).map_err(|err| format!("Could not read heightmap {}: {}", path.as_ref().display(), err))?;
+        Heightmap::from_raw16(&binary_data, width, depth)
+    }
+
+    /*
+        Grayscale PNGs with 8 or 16 bits per sample are supported. 8 bit heightmaps only have 256 different heights,
+        which shows up as terraces, so 16 bit is preferred. For color images, only the first (red) channel is used.
+    */
+    pub fn from_png(binary_data: &[u8]) -> Result<Heightmap, String> {
+        let mut decoder = png::Decoder::new(binary_data);
+        // Without this, the decoder could turn 16 bit samples into 8 bit ones
+        decoder.set_transformations(png::Transformations::IDENTITY);
+
+        let mut reader = decoder.read_info().map_err(|err| format!("Could not read PNG heightmap: {}", err))?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buffer).map_err(|err| format!("Could not decode PNG heightmap: {}", err))?;
+
+        let channels = match frame.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::Rgb => 3,
+            png::ColorType::Rgba => 4,
+            png::ColorType::Indexed => return Err(String::from("Indexed PNGs can't be used as heightmaps."))
+        };
+
+        // PNG stores 16 bit samples as big endian
+        let heights: Vec<f32> = match frame.bit_depth {
+            png::BitDepth::Eight => buffer[..frame.buffer_size()]
+                .chunks_exact(frame.line_size)
+                .flat_map(|line| line.chunks_exact(channels).map(|pixel| pixel[0] as f32 / u8::MAX as f32))
+                .collect(),
+            png::BitDepth::Sixteen => buffer[..frame.buffer_size()]
+                .chunks_exact(frame.line_size)
+                .flat_map(|line| line.chunks_exact(channels * 2).map(|pixel| u16::from_be_bytes([pixel[0], pixel[1]]) as f32 / u16::MAX as f32))
+                .collect(),
+            bit_depth => return Err(format!("PNG heightmaps need 8 or 16 bits per sample, not {:?}.", bit_depth))
+        };
+
+        Heightmap::new(frame.width, frame.height, heights)
+    }
+
+    pub fn from_png_file<P: AsRef<Path>>(path: P) -> Result<Heightmap, String> {
+        let binary_data = fs::read(&path).map_err(|err| format!("Could not read heightmap {}: {}", path.as_ref().display(), err))?;
+        Heightmap::from_png(&binary_data)
+    }
+
+    /*
+        Fractal noise: several layers ("octaves") of gradient noise added together, each one finer and weaker than the one before.
+        The result is rescaled so the lowest point is 0 and the highest 1.
+    */
+    pub fn from_noise(width: u32, depth: u32, settings: &NoiseSettings) -> Result<Heightmap, String> {
+        let mut heights: Vec<f32> = Vec::with_capacity(sample_count(width, depth)?);
+
+        for row in 0..depth {
+            for column in 0..width {
+                let mut frequency = settings.frequency;
+                let mut amplitude = 1.0;
+                let mut height = 0.0;
+
+                for octave in 0..settings.octaves {
+                    height += gradient_noise(column as f32 * frequency, row as f32 * frequency, settings.seed.wrapping_add(octave)) * amplitude;
+                    frequency *= settings.lacunarity;
+                    amplitude *= settings.persistence;
+                }
+
+                heights.push(height);
+            }
+        }
+
+        let min = heights.iter().cloned().fold(f32::MAX, f32::min);
+        let max = heights.iter().cloned().fold(f32::MIN, f32::max);
+        let range = if max > min { max - min } else { 1.0 };
+        for height in heights.iter_mut() {
+            *height = (*height - min) / range;
+        }
+
+        Heightmap::new(width, depth, heights)
+    }
+
+    fn new(width: u32, depth: u32, heights: Vec<f32>) -> Result<Heightmap, String> {
+        if width < 2 || depth < 2 {
+            return Err(format!("A heightmap needs at least 2x2 samples, but has {}x{}.", width, depth));
+        }
+
+        if heights.len() != sample_count(width, depth)? {
+            return Err(format!("A heightmap of {}x{} needs {} samples, but has {}.", width, depth, sample_count(width, depth)?, heights.len()));
+        }
+
+        Ok(Heightmap { width, depth, heights })
+    }
+
+    // The sample at (column, row), with coordinates outside the heightmap clamped to its edge
+    pub fn get(&self, column: i64, row: i64) -> f32 {
+        let column = column.clamp(0, self.width as i64 - 1) as usize;
+        let row = row.clamp(0, self.depth as i64 - 1) as usize;
+        self.heights[row * self.width as usize + column]
+    }
+}
+
+impl Terrain {
+    pub fn new(heightmap: Heightmap, settings: TerrainSettings) -> Result<Terrain, String> {
+        // The fields are public, so the heightmap might not have come through Heightmap::new
+        let heightmap = Heightmap::new(heightmap.width, heightmap.depth, heightmap.heights)?;
+
+        if !settings.chunk_size.is_power_of_two() {
+            return Err(format!("The chunk size has to be a power of 2, but is {}.", settings.chunk_size));
+        }
+
+        if settings.lod_count == 0 || settings.lod_count > 32 || (1 << (settings.lod_count - 1)) > settings.chunk_size {
+            return Err(format!("A chunk of {} cells can't have {} LODs.", settings.chunk_size, settings.lod_count));
+        }
+
+        let cells_x = heightmap.width - 1;
+        let cells_z = heightmap.depth - 1;
+        if cells_x % settings.chunk_size != 0 || cells_z % settings.chunk_size != 0 {
+            return Err(format!(
+                "A heightmap of {}x{} samples can't be split in chunks of {} cells. The number of samples minus 1 has to be a multiple of the chunk size.",
+                heightmap.width, heightmap.depth, settings.chunk_size));
+        }
+
+        Ok(Terrain {
+            chunks_x: cells_x / settings.chunk_size,
+            chunks_z: cells_z / settings.chunk_size,
+            heightmap,
+            settings
+        })
+    }
+
+    pub fn chunk_index(&self, chunk_x: u32, chunk_z: u32) -> usize {
+        (chunk_z * self.chunks_x + chunk_x) as usize
+    }
+
+    /*
+        Picks a LOD for every chunk, based on the distance from the camera to the closest point of the chunk (ignoring height).
+        Chunks closer than lod_distance get full detail, and every doubling of the distance after that drops one level.
+    */
+    pub fn select_lods(&self, camera_position: &beagle_math::Vector3, lod_distance: f32) -> Vec<u32> {
+        let chunk_length = self.settings.chunk_size as f32 * self.settings.cell_size;
+        let mut lods: Vec<u32> = Vec::with_capacity((self.chunks_x * self.chunks_z) as usize);
+
+        for chunk_z in 0..self.chunks_z {
+            for chunk_x in 0..self.chunks_x {
+                let min_x = chunk_x as f32 * chunk_length;
+                let min_z = chunk_z as f32 * chunk_length;
+                let dx = (min_x - camera_position.x).max(camera_position.x - (min_x + chunk_length)).max(0.0);
+                let dz = (min_z - camera_position.z).max(camera_position.z - (min_z + chunk_length)).max(0.0);
+                let distance = (dx * dx + dz * dz).sqrt();
+
+                let lod = if distance < lod_distance { 0 } else { (distance / lod_distance).log2() as u32 + 1 };
+                lods.push(lod.min(self.settings.lod_count - 1));
+            }
+        }
+
+        lods
+    }
+
+    // Builds the meshes of all chunks, with "lods" holding the LOD of every chunk as returned by "select_lods"
+    pub fn build_chunk_meshes(&self, lods: &[u32]) -> Vec<Mesh> {
+        let mut meshes: Vec<Mesh> = vec!();
+
+        for chunk_z in 0..self.chunks_z {
+            for chunk_x in 0..self.chunks_x {
+                meshes.push(self.build_chunk_mesh(chunk_x, chunk_z, lods));
+            }
+        }
+
+        meshes
+    }
+
+    /*
+        Builds a single chunk at the LOD given for it in "lods".
+        The LODs of the neighbouring chunks are needed as well, to stitch the edges shared with coarser neighbours.
+    */
+    pub fn build_chunk_mesh(&self, chunk_x: u32, chunk_z: u32, lods: &[u32]) -> Mesh {
+        let lod = lods[self.chunk_index(chunk_x, chunk_z)];
+        let step = 1 << lod;
+        let cells = self.settings.chunk_size / step;
+
+        // West (-X), east (+X), south (-Z), north (+Z). Chunks outside the terrain count as having the same LOD.
+        let neighbour_lod = |x: i64, z: i64| -> u32 {
+            if x < 0 || z < 0 || x >= self.chunks_x as i64 || z >= self.chunks_z as i64 {
+                lod
+            } else {
+                lods[self.chunk_index(x as u32, z as u32)]
+            }
+        };
+        let west_step = 1 << neighbour_lod(chunk_x as i64 - 1, chunk_z as i64).max(lod);
+        let east_step = 1 << neighbour_lod(chunk_x as i64 + 1, chunk_z as i64).max(lod);
+        let south_step = 1 << neighbour_lod(chunk_x as i64, chunk_z as i64 - 1).max(lod);
+        let north_step = 1 << neighbour_lod(chunk_x as i64, chunk_z as i64 + 1).max(lod);
+
+        // The grid has the same triangles as the full detail terrain, just with bigger cells for higher LODs
+        let mut mesh = crate::asset::mesh::primitives::create_grid(cells, cells, step as f32 * self.settings.cell_size);
+        mesh.name = format!("terrain_chunk_{}_{}", chunk_x, chunk_z);
+
+        let first_column = (chunk_x * self.settings.chunk_size) as i64;
+        let first_row = (chunk_z * self.settings.chunk_size) as i64;
+
+        for local_row in 0..=cells {
+            for local_column in 0..=cells {
+                let vertex = (local_row * (cells + 1) + local_column) as usize;
+                let column = first_column + (local_column * step) as i64;
+                let row = first_row + (local_row * step) as i64;
+
+                let height = if local_column == 0 && west_step > step {
+                    self.stitched_height(column, row, west_step, false)
+                } else if local_column == cells && east_step > step {
+                    self.stitched_height(column, row, east_step, false)
+                } else if local_row == 0 && south_step > step {
+                    self.stitched_height(column, row, south_step, true)
+                } else if local_row == cells && north_step > step {
+                    self.stitched_height(column, row, north_step, true)
+                } else {
+                    self.sample_height(column, row)
+                };
+
+                mesh.vertex_positions[vertex] = beagle_math::Vector3::new(
+                    column as f32 * self.settings.cell_size,
+                    height,
+                    row as f32 * self.settings.cell_size);
+                mesh.vertex_normals[vertex] = self.sample_normal(column, row);
+                mesh.vertex_uvs[vertex] = beagle_math::Vector2::new(
+                    column as f32 / (self.heightmap.width - 1) as f32,
+                    row as f32 / (self.heightmap.depth - 1) as f32);
+            }
+        }
+
+        if self.settings.skirt_depth > 0.0 {
+            let vertex_at = |local_column: u32, local_row: u32| local_row * (cells + 1) + local_column;
+
+            // Every edge is listed going right, as seen from outside the chunk, so the skirts face outwards
+            let south: Vec<u32> = (0..=cells).map(|column| vertex_at(column, 0)).collect();
+            let east: Vec<u32> = (0..=cells).map(|row| vertex_at(cells, row)).collect();
+            let north: Vec<u32> = (0..=cells).rev().map(|column| vertex_at(column, cells)).collect();
+            let west: Vec<u32> = (0..=cells).rev().map(|row| vertex_at(0, row)).collect();
+
+            for edge in [south, east, north, west] {
+                add_skirt(&mut mesh, &edge, self.settings.skirt_depth);
+            }
+        }
+
+        mesh
+    }
+
+    /*
+        The height of the terrain at any (x, z), following the triangles of the full detail mesh exactly,
+        so things placed on the terrain don't float above or sink into it. None outside of the terrain.
+    */
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let (column, row, fraction_x, fraction_z) = self.find_cell(x, z)?;
+
+        let near_left = self.sample_height(column, row);
+        let near_right = self.sample_height(column + 1, row);
+        let far_left = self.sample_height(column, row + 1);
+        let far_right = self.sample_height(column + 1, row + 1);
+
+        // Cells are split along the diagonal from near left to far right, see "primitives::create_grid"
+        let height = if fraction_z >= fraction_x {
+            near_left + fraction_x * (far_right - far_left) + fraction_z * (far_left - near_left)
+        } else {
+            near_left + fraction_x * (near_right - near_left) + fraction_z * (far_right - near_right)
+        };
+
+        Some(height)
+    }
+
+    // The smooth surface normal at any (x, z), blended from the normals at the four surrounding samples. None outside of the terrain.
+    pub fn normal_at(&self, x: f32, z: f32) -> Option<beagle_math::Vector3> {
+        let (column, row, fraction_x, fraction_z) = self.find_cell(x, z)?;
+
+        let near = self.sample_normal(column, row).mul(1.0 - fraction_x).add(&self.sample_normal(column + 1, row).mul(fraction_x));
+        let far = self.sample_normal(column, row + 1).mul(1.0 - fraction_x).add(&self.sample_normal(column + 1, row + 1).mul(fraction_x));
+
+        Some(near.mul(1.0 - fraction_z).add(&far.mul(fraction_z)).normalized())
+    }
+
+    // The cell containing (x, z), and how far into the cell the point is along X and Z (0 to 1)
+    fn find_cell(&self, x: f32, z: f32) -> Option<(i64, i64, f32, f32)> {
+        let grid_x = x / self.settings.cell_size;
+        let grid_z = z / self.settings.cell_size;
+        let cells_x = (self.heightmap.width - 1) as f32;
+        let cells_z = (self.heightmap.depth - 1) as f32;
+
+        if !(0.0..=cells_x).contains(&grid_x) || !(0.0..=cells_z).contains(&grid_z) {
+            return None;
+        }
+
+        // Points on the far edges belong to the last cell
+        let column = (grid_x.floor() as i64).min(self.heightmap.width as i64 - 2);
+        let row = (grid_z.floor() as i64).min(self.heightmap.depth as i64 - 2);
+
+        Some((column, row, grid_x - column as f32, grid_z - row as f32))
+    }
+
+    fn sample_height(&self, column: i64, row: i64) -> f32 {
+        self.heightmap.get(column, row) * self.settings.height_scale
+    }
+
+    // The height of a vertex on an edge shared with a coarser chunk, which only has a vertex every "coarse_step" samples
+    fn stitched_height(&self, column: i64, row: i64, coarse_step: u32, along_x: bool) -> f32 {
+        let position = if along_x { column } else { row };
+        let start = position - position.rem_euclid(coarse_step as i64);
+        let amount = (position - start) as f32 / coarse_step as f32;
+
+        let (start_height, end_height) = if along_x {
+            (self.sample_height(start, row), self.sample_height(start + coarse_step as i64, row))
+        } else {
+            (self.sample_height(column, start), self.sample_height(column, start + coarse_step as i64))
+        };
+
+        start_height + (end_height - start_height) * amount
+    }
+
+    /*
+        The normal at a heightmap sample, from the slope between its neighbours on both sides (central differences).
+        Normals come from the heightmap rather than the chunk's triangles, so neighbouring chunks (even at different LODs)
+        get the same normals along their shared edges, and no lighting seams show up.
+    */
+    fn sample_normal(&self, column: i64, row: i64) -> beagle_math::Vector3 {
+        let left = (column - 1).max(0);
+        let right = (column + 1).min(self.heightmap.width as i64 - 1);
+        let near = (row - 1).max(0);
+        let far = (row + 1).min(self.heightmap.depth as i64 - 1);
+
+        let slope_x = (self.sample_height(right, row) - self.sample_height(left, row)) / ((right - left) as f32 * self.settings.cell_size);
+        let slope_z = (self.sample_height(column, far) - self.sample_height(column, near)) / ((far - near) as f32 * self.settings.cell_size);
+
+        beagle_math::Vector3::new(-slope_x, 1.0, -slope_z).normalized()
+    }
+}
+
+// Hangs a strip of triangles below the given edge vertices, which must be ordered going right as seen from outside
+fn add_skirt(mesh: &mut Mesh, edge: &[u32], depth: f32) {
+    let first_skirt_vertex = mesh.vertex_positions.len() as u32;
+
+    for vertex in edge {
+        let position = mesh.vertex_positions[*vertex as usize];
+        mesh.vertex_positions.push(beagle_math::Vector3::new(position.x, position.y - depth, position.z));
+        // The skirt uses the normal of the edge above it, so it blends in instead of showing up as a dark line
+        mesh.vertex_normals.push(mesh.vertex_normals[*vertex as usize]);
+        mesh.vertex_uvs.push(mesh.vertex_uvs[*vertex as usize]);
+    }
+
+    for index in 0..edge.len() - 1 {
+        let top_left = edge[index];
+        let top_right = edge[index + 1];
+        let bottom_left = first_skirt_vertex + index as u32;
+        let bottom_right = bottom_left + 1;
+
+        mesh.indices.extend_from_slice(&[top_left, top_right, bottom_left]);
+        mesh.indices.extend_from_slice(&[top_right, bottom_right, bottom_left]);
+    }
+}
+
+/*
+    2D gradient noise (Perlin noise). Every whole grid point gets a pseudo random direction, and the noise at a point
+    blends how far the point lies along the directions of the four grid points around it. The result is between -1 and 1.
+*/
+fn gradient_noise(x: f32, y: f32, seed: u32) -> f32 {
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fraction_x = x - x0;
+    let fraction_y = y - y0;
+
+    let corner = |offset_x: f32, offset_y: f32| -> f32 {
+        let hash = hash_grid_point(x0 as i32 + offset_x as i32, y0 as i32 + offset_y as i32, seed);
+        let angle = (hash & 0xFFFF) as f32 / 65536.0 * std::f32::consts::PI * 2.0;
+        angle.cos() * (fraction_x - offset_x) + angle.sin() * (fraction_y - offset_y)
+    };
+
+    // The "smootherstep" curve, so the noise has no visible creases along the grid lines
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let blend_x = fade(fraction_x);
+    let blend_y = fade(fraction_y);
+
+    let near = corner(0.0, 0.0) + (corner(1.0, 0.0) - corner(0.0, 0.0)) * blend_x;
+    let far = corner(0.0, 1.0) + (corner(1.0, 1.0) - corner(0.0, 1.0)) * blend_x;
+
+    // The largest possible value is sqrt(0.5), so this scales the result to -1..1
+    (near + (far - near) * blend_y) * std::f32::consts::SQRT_2
+}
+
+// Scrambles the coordinates and seed into a number that looks random, but is always the same for the same input
+fn hash_grid_point(x: i32, y: i32, seed: u32) -> u32 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2c1b_3c6d);
+    hash ^= hash >> 12;
+    hash = hash.wrapping_mul(0x297a_2d39);
+    hash ^= hash >> 15;
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beagle_math;
+    use crate::terrain::*;
+
+    fn settings(chunk_size: u32, lod_count: u32, skirt_depth: f32) -> TerrainSettings {
+        TerrainSettings { cell_size: 2.0, height_scale: 10.0, chunk_size, lod_count, skirt_depth }
+    }
+
+    fn noise_terrain(size: u32, settings: TerrainSettings) -> Terrain {
+        let noise = NoiseSettings { seed: 7, frequency: 0.1, octaves: 4, persistence: 0.5, lacunarity: 2.0 };
+        Terrain::new(Heightmap::from_noise(size, size, &noise).unwrap(), settings).unwrap()
+    }
+
+    #[test]
+    fn should_read_raw16_heightmap() {
+        let samples: [u16; 4] = [0, u16::MAX, 32768, 16384];
+        let binary_data: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+
+        let heightmap = Heightmap::from_raw16(&binary_data, 2, 2).unwrap();
+
+        assert_eq!(heightmap.get(0, 0), 0.0);
+        assert_eq!(heightmap.get(1, 0), 1.0);
+        assert!((heightmap.get(0, 1) - 0.5).abs() < 0.0001);
+        assert!(Heightmap::from_raw16(&binary_data, 3, 2).is_err());
+        assert!(Heightmap::from_raw16(&binary_data, u32::MAX, u32::MAX).is_err());
+        assert!(Heightmap::from_raw16(&binary_data, 65536, 32768).is_err());
+    }
+
+    #[test]
+    fn should_read_16_bit_png_heightmap() {
+        let samples: [u16; 6] = [0, 1000, 2000, 30000, 60000, u16::MAX];
+        let mut png_data: Vec<u8> = vec!();
+        {
+            let mut encoder = png::Encoder::new(&mut png_data, 3, 2);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Sixteen);
+            let mut writer = encoder.write_header().unwrap();
+            let image_data: Vec<u8> = samples.iter().flat_map(|sample| sample.to_be_bytes()).collect();
+            writer.write_image_data(&image_data).unwrap();
+        }
+
+        let heightmap = Heightmap::from_png(&png_data).unwrap();
+
+        assert_eq!((heightmap.width, heightmap.depth), (3, 2));
+        for (height, sample) in heightmap.heights.iter().zip(samples.iter()) {
+            assert_eq!(*height, *sample as f32 / u16::MAX as f32);
+        }
+    }
+
+    #[test]
+    fn should_generate_repeatable_noise_between_0_and_1() {
+        let noise = NoiseSettings { seed: 1, frequency: 0.05, octaves: 5, persistence: 0.5, lacunarity: 2.0 };
+
+        let first = Heightmap::from_noise(33, 33, &noise).unwrap();
+        let second = Heightmap::from_noise(33, 33, &noise).unwrap();
+        let other_seed = Heightmap::from_noise(33, 33, &NoiseSettings { seed: 2, ..noise.clone() }).unwrap();
+
+        assert_eq!(first.heights, second.heights);
+        assert_ne!(first.heights, other_seed.heights);
+        assert!(first.heights.iter().all(|height| (0.0..=1.0).contains(height)));
+        assert!(Heightmap::from_noise(1, 33, &noise).is_err());
+        assert!(Heightmap::from_noise(0, 0, &noise).is_err());
+    }
+
+    #[test]
+    fn should_reject_heightmap_not_fitting_chunks() {
+        let heightmap = Heightmap { width: 20, depth: 17, heights: vec![0.0; 20 * 17] };
+
+        assert!(Terrain::new(heightmap, settings(16, 1, 0.0)).is_err());
+        assert!(Terrain::new(Heightmap { width: 17, depth: 17, heights: vec![0.0; 17 * 17] }, settings(16, 6, 0.0)).is_err());
+    }
+
+    #[test]
+    fn should_build_chunks_facing_up_with_skirts() {
+        let terrain = noise_terrain(33, settings(16, 3, 1.0));
+        let lods = vec![0, 1, 2, 0];
+
+        let meshes = terrain.build_chunk_meshes(&lods);
+
+        assert_eq!(meshes.len(), 4);
+        for (mesh, lod) in meshes.iter().zip(lods.iter()) {
+            let cells = 16 >> lod;
+            let grid_triangles = (cells * cells * 2) as usize;
+            let skirt_triangles = (cells * 2 * 4) as usize;
+            assert_eq!(mesh.indices.len(), (grid_triangles + skirt_triangles) * 3);
+
+            let report = crate::asset::mesh::validation::validate(mesh);
+            assert!(report.is_valid(), "{:?}", report.issues);
+
+            for triangle in mesh.indices[..grid_triangles * 3].chunks_exact(3) {
+                let vert1 = mesh.vertex_positions[triangle[0] as usize];
+                let vert2 = mesh.vertex_positions[triangle[1] as usize];
+                let vert3 = mesh.vertex_positions[triangle[2] as usize];
+                let normal = vert2.add(&vert1.mul(-1.0)).cross(&vert3.add(&vert1.mul(-1.0)));
+                assert!(normal.y > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn should_stitch_edges_between_chunks_of_different_lod() {
+        let terrain = noise_terrain(33, settings(16, 3, 0.0));
+        let lods = vec![0, 2, 0, 0];
+
+        let fine = terrain.build_chunk_mesh(0, 0, &lods);
+        let coarse = terrain.build_chunk_mesh(1, 0, &lods);
+
+        // The shared edge is at x = 16 cells
+        let edge_x = 16.0 * terrain.settings.cell_size;
+        let mut coarse_edge: Vec<beagle_math::Vector3> = coarse.vertex_positions.iter().filter(|position| position.x == edge_x).cloned().collect();
+        coarse_edge.sort_by(|a, b| a.z.partial_cmp(&b.z).unwrap());
+
+        let fine_edge: Vec<&beagle_math::Vector3> = fine.vertex_positions.iter().filter(|position| position.x == edge_x).collect();
+        assert_eq!(fine_edge.len(), 17);
+        assert_eq!(coarse_edge.len(), 5);
+
+        // Every vertex of the detailed chunk lies on the straight edge of the coarse chunk
+        for position in fine_edge {
+            let segment = coarse_edge.windows(2).find(|pair| pair[0].z <= position.z && position.z <= pair[1].z).unwrap();
+            let amount = (position.z - segment[0].z) / (segment[1].z - segment[0].z);
+            let expected = segment[0].y + (segment[1].y - segment[0].y) * amount;
+
+            assert!((position.y - expected).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn should_query_height_and_normal_on_terrain_surface() {
+        let mut heightmap = Heightmap { width: 3, depth: 3, heights: vec![0.0; 9] };
+        // A single raised sample in the middle
+        heightmap.heights[4] = 1.0;
+        let terrain = Terrain::new(heightmap, settings(2, 1, 0.0)).unwrap();
+
+        assert_eq!(terrain.height_at(2.0, 2.0), Some(10.0));
+        assert_eq!(terrain.height_at(0.0, 0.0), Some(0.0));
+        // Halfway along the diagonal of the first cell, which runs from (0, 0) to the raised middle
+        assert_eq!(terrain.height_at(1.0, 1.0), Some(5.0));
+        // Halfway between the middle of the west edge and the raised middle
+        assert_eq!(terrain.height_at(1.0, 2.0), Some(5.0));
+        assert_eq!(terrain.height_at(1.0, 0.0), Some(0.0));
+        assert_eq!(terrain.height_at(-1.0, 0.0), None);
+        assert_eq!(terrain.height_at(0.0, 4.5), None);
+
+        // At the top the terrain is flat, on the near side it slopes down towards -Z
+        let top = terrain.normal_at(2.0, 2.0).unwrap();
+        assert!((top.y - 1.0).abs() < 0.0001);
+        let slope = terrain.normal_at(2.0, 0.0).unwrap();
+        assert!(slope.z < 0.0 && slope.y > 0.0);
+    }
+
+    #[test]
+    fn should_pick_lower_detail_for_distant_chunks() {
+        let terrain = noise_terrain(65, settings(16, 4, 0.0));
+
+        let lods = terrain.select_lods(&beagle_math::Vector3::new(1.0, 50.0, 1.0), 40.0);
+
+        assert_eq!(lods[terrain.chunk_index(0, 0)], 0);
+        assert!(lods[terrain.chunk_index(3, 3)] > 0);
+        assert!(lods.iter().all(|lod| *lod < 4));
+    }
+}